@@ -0,0 +1 @@
+DROP TABLE scripts;
\ No newline at end of file
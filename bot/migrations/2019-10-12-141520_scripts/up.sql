@@ -0,0 +1,10 @@
+CREATE TABLE scripts (
+    channel VARCHAR NOT NULL,
+    name VARCHAR NOT NULL,
+    source TEXT NOT NULL,
+    disabled BOOLEAN NOT NULL DEFAULT false,
+    "group" TEXT,
+    PRIMARY KEY (channel, name)
+);
+
+CREATE INDEX idx_scripts_group ON scripts("group");
\ No newline at end of file
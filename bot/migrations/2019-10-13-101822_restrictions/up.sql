@@ -0,0 +1,9 @@
+ALTER TABLE commands ADD COLUMN cooldown INTEGER DEFAULT NULL;
+ALTER TABLE commands ADD COLUMN user_cooldown INTEGER DEFAULT NULL;
+ALTER TABLE commands ADD COLUMN scope VARCHAR DEFAULT NULL;
+ALTER TABLE commands ADD COLUMN price BIGINT DEFAULT NULL;
+
+ALTER TABLE aliases ADD COLUMN cooldown INTEGER DEFAULT NULL;
+ALTER TABLE aliases ADD COLUMN user_cooldown INTEGER DEFAULT NULL;
+ALTER TABLE aliases ADD COLUMN scope VARCHAR DEFAULT NULL;
+ALTER TABLE aliases ADD COLUMN price BIGINT DEFAULT NULL;
\ No newline at end of file
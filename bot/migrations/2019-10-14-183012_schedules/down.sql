@@ -0,0 +1 @@
+DROP TABLE schedules;
\ No newline at end of file
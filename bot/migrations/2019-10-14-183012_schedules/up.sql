@@ -0,0 +1,13 @@
+CREATE TABLE schedules (
+    channel VARCHAR NOT NULL,
+    name VARCHAR NOT NULL,
+    trigger VARCHAR NOT NULL,
+    action TEXT NOT NULL,
+    only_live BOOLEAN NOT NULL DEFAULT false,
+    ran_at TIMESTAMP,
+    disabled BOOLEAN NOT NULL DEFAULT false,
+    "group" TEXT,
+    PRIMARY KEY (channel, name)
+);
+
+CREATE INDEX idx_schedules_group ON schedules("group");
\ No newline at end of file
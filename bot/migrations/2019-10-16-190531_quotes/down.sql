@@ -0,0 +1 @@
+DROP TABLE quotes;
\ No newline at end of file
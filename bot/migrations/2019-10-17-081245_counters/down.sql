@@ -0,0 +1 @@
+DROP TABLE counters;
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE command_invocations;
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE command_invocations (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    channel VARCHAR NOT NULL,
+    command VARCHAR NOT NULL,
+    user VARCHAR,
+    outcome VARCHAR NOT NULL,
+    invoked_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    stream_started_at TIMESTAMP
+);
+
+CREATE INDEX idx_command_invocations_channel_invoked_at ON command_invocations(channel, invoked_at);
\ No newline at end of file
@@ -0,0 +1,7 @@
+CREATE TABLE strikes (
+    channel VARCHAR NOT NULL,
+    user VARCHAR NOT NULL,
+    count INTEGER NOT NULL DEFAULT 0,
+    struck_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (channel, user)
+);
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE audit_log (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    channel VARCHAR NOT NULL,
+    actor VARCHAR,
+    action VARCHAR NOT NULL,
+    target VARCHAR,
+    reason VARCHAR,
+    logged_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_audit_log_channel_logged_at ON audit_log(channel, logged_at);
\ No newline at end of file
@@ -0,0 +1,8 @@
+CREATE TABLE bad_words2 (
+    word VARCHAR NOT NULL PRIMARY KEY,
+    why VARCHAR
+);
+
+INSERT INTO bad_words2 (word, why) SELECT word, message FROM bad_words WHERE NOT regex;
+DROP TABLE bad_words;
+ALTER TABLE bad_words2 RENAME TO bad_words;
\ No newline at end of file
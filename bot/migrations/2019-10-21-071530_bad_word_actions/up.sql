@@ -0,0 +1,10 @@
+CREATE TABLE bad_words2 (
+    word VARCHAR NOT NULL PRIMARY KEY,
+    regex BOOLEAN NOT NULL DEFAULT FALSE,
+    action VARCHAR NOT NULL DEFAULT 'delete',
+    message VARCHAR
+);
+
+INSERT INTO bad_words2 (word, message) SELECT word, why FROM bad_words;
+DROP TABLE bad_words;
+ALTER TABLE bad_words2 RENAME TO bad_words;
\ No newline at end of file
@@ -0,0 +1,5 @@
+DROP TRIGGER messages_au;
+DROP TRIGGER messages_ad;
+DROP TRIGGER messages_ai;
+DROP TABLE messages_fts;
+DROP TABLE messages;
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE seen_users;
\ No newline at end of file
@@ -0,0 +1,6 @@
+CREATE TABLE seen_users (
+    channel VARCHAR NOT NULL,
+    user VARCHAR NOT NULL,
+    first_seen_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (channel, user)
+);
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE ban_list;
\ No newline at end of file
@@ -0,0 +1,9 @@
+CREATE TABLE ban_list (
+    user VARCHAR NOT NULL PRIMARY KEY,
+    action VARCHAR NOT NULL DEFAULT 'ban',
+    reason VARCHAR,
+    source VARCHAR,
+    added_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_ban_list_source ON ban_list (source);
\ No newline at end of file
@@ -0,0 +1 @@
+DROP TABLE ledger;
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE ledger (
+    id INTEGER NOT NULL PRIMARY KEY AUTOINCREMENT,
+    channel VARCHAR NOT NULL,
+    user VARCHAR NOT NULL,
+    amount BIGINT NOT NULL,
+    reason VARCHAR NOT NULL,
+    counterparty VARCHAR,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_ledger_user_created_at ON ledger(user, created_at);
\ No newline at end of file
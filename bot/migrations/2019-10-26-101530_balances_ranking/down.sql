@@ -0,0 +1,2 @@
+DROP INDEX idx_balances_channel_amount;
+DROP INDEX idx_balances_channel_watch_time;
\ No newline at end of file
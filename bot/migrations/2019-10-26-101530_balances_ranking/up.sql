@@ -0,0 +1,2 @@
+CREATE INDEX idx_balances_channel_amount ON balances (channel, amount);
+CREATE INDEX idx_balances_channel_watch_time ON balances (channel, watch_time);
\ No newline at end of file
@@ -0,0 +1 @@
+DELETE FROM seen_users WHERE first_seen_at = '1970-01-01 00:00:00';
\ No newline at end of file
@@ -0,0 +1,4 @@
+-- Users who already have a balance are regulars, and shouldn't count as
+-- first-time chatters once lockdown starts tracking them.
+INSERT OR IGNORE INTO seen_users (channel, user, first_seen_at)
+SELECT channel, user, '1970-01-01 00:00:00' FROM balances;
\ No newline at end of file
@@ -0,0 +1,2 @@
+DROP INDEX idx_ledger_channel_user_created_at;
+DROP INDEX idx_ledger_created_at;
\ No newline at end of file
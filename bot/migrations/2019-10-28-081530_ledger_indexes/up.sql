@@ -0,0 +1,2 @@
+CREATE INDEX idx_ledger_channel_user_created_at ON ledger (channel, user, created_at);
+CREATE INDEX idx_ledger_created_at ON ledger (created_at);
\ No newline at end of file
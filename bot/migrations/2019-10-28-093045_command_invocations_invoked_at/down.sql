@@ -0,0 +1 @@
+DROP INDEX idx_command_invocations_invoked_at;
\ No newline at end of file
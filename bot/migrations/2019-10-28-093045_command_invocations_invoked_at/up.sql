@@ -0,0 +1 @@
+CREATE INDEX idx_command_invocations_invoked_at ON command_invocations (invoked_at);
\ No newline at end of file
@@ -42,6 +42,20 @@ impl Twitch {
         })
     }
 
+    /// Create a new API integration against the given base URL instead of
+    /// Twitch, like a mocked API in tests.
+    #[cfg(test)]
+    pub fn with_base_url(token: oauth2::SyncToken, base: Url) -> Result<Twitch, Error> {
+        Ok(Twitch {
+            client: Client::new(),
+            gql_url: base.join("gql")?,
+            api_url: base.clone(),
+            id_url: base.clone(),
+            badges_url: base,
+            token,
+        })
+    }
+
     /// Get request against API.
     fn new_api(&self, method: Method, path: &[&str]) -> RequestBuilder {
         let mut url = self.api_url.clone();
@@ -374,6 +388,32 @@ pub struct NewUser {
     pub email: Option<String>,
 }
 
+impl From<NewUser> for User {
+    /// Convert a user from the new API into a user.
+    ///
+    /// Information which is only available for the authenticated user is left
+    /// empty.
+    fn from(user: NewUser) -> User {
+        let now = Utc::now();
+
+        User {
+            id: user.id,
+            name: user.login,
+            display_name: user.display_name,
+            created_at: now,
+            updated_at: now,
+            bio: Some(user.description),
+            email: user.email.unwrap_or_default(),
+            email_verified: false,
+            logo: Some(user.profile_image_url),
+            notifications: HashMap::new(),
+            partnered: user.broadcaster_type == "partner",
+            twitter_connected: false,
+            ty: user.ty,
+        }
+    }
+}
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct User {
     #[serde(rename = "_id")]
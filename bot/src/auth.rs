@@ -53,6 +53,59 @@ impl std::str::FromStr for RoleOrUser {
     }
 }
 
+/// A requirement on a user, either being part of a role or having a scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoleOrScope {
+    Role(Role),
+    Scope(Scope),
+}
+
+impl fmt::Display for RoleOrScope {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RoleOrScope::Role(role) => role.fmt(fmt),
+            RoleOrScope::Scope(scope) => scope.fmt(fmt),
+        }
+    }
+}
+
+impl std::str::FromStr for RoleOrScope {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with('@') {
+            return match Role::from_str(s)? {
+                Role::Unknown => failure::bail!("no such role: {}", s),
+                role => Ok(RoleOrScope::Role(role)),
+            };
+        }
+
+        match Scope::from_str(s)? {
+            Scope::Unknown => failure::bail!("no such scope: {}", s),
+            scope => Ok(RoleOrScope::Scope(scope)),
+        }
+    }
+}
+
+impl serde::Serialize for RoleOrScope {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for RoleOrScope {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        str::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
 /// A grant that has been temporarily given.
 struct TemporaryGrant {
     pub scope: Scope,
@@ -569,6 +622,23 @@ scopes! {
     (Time, "time"),
     (Poll, "poll"),
     (Weather, "weather"),
+    (Script, "script"),
+    (ScriptEdit, "script/edit"),
+    (Schedule, "schedule"),
+    (ScheduleEdit, "schedule/edit"),
+    (ScheduleEditRaw, "schedule/edit-raw"),
+    (Quote, "quote"),
+    (QuoteEdit, "quote/edit"),
+    (Counter, "counter"),
+    (CounterEdit, "counter/edit"),
+    (Strikes, "strikes"),
+    (Audit, "audit"),
+    (Permit, "permit"),
+    (BanList, "ban-list"),
+    (Gamble, "gamble"),
+    (Duel, "duel"),
+    (Heist, "heist"),
+    (Rank, "rank"),
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
@@ -601,6 +671,27 @@ roles! {
     (Subscriber, "@subscriber"),
     (Vip, "@vip"),
     (Everyone, "@everyone"),
+    (Rank1, "@rank/1"),
+    (Rank2, "@rank/2"),
+    (Rank3, "@rank/3"),
+    (Rank4, "@rank/4"),
+    (Rank5, "@rank/5"),
+}
+
+/// Roles which can be bound to watch time ranks.
+pub static RANK_ROLES: [Role; 5] = [
+    Role::Rank1,
+    Role::Rank2,
+    Role::Rank3,
+    Role::Rank4,
+    Role::Rank5,
+];
+
+impl Role {
+    /// Test if the role is held by users who have reached a watch time rank.
+    pub fn is_rank(self) -> bool {
+        RANK_ROLES.contains(&self)
+    }
 }
 
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
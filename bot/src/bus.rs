@@ -168,6 +168,14 @@ pub enum Global {
     },
     #[serde(rename = "song/modified")]
     SongModified,
+    /// A counter has been modified. A count of `None` means that the counter
+    /// was deleted.
+    #[serde(rename = "counter")]
+    Counter {
+        channel: String,
+        name: String,
+        count: Option<i64>,
+    },
 }
 
 impl Message for Global {
@@ -236,8 +244,14 @@ impl Global {
 #[serde(tag = "type")]
 pub enum Command {
     /// Run a raw command.
+    ///
+    /// The command runs in the given channel, or the streamer's channel if
+    /// none is specified.
     #[serde(rename = "raw")]
-    Raw { command: String },
+    Raw {
+        command: String,
+        channel: Option<String>,
+    },
 }
 
 impl Message for Command {
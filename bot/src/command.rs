@@ -5,6 +5,10 @@ use failure::Error;
 use hashbrown::HashMap;
 use std::{fmt, time::Instant};
 
+pub mod args;
+
+pub use self::args::{Arg, Choice, Param, Spec, Sub, Toggle, User};
+
 #[async_trait]
 /// The handler trait for a given command.
 pub trait Handler {
@@ -23,9 +27,22 @@ pub trait MessageHook: std::any::Any + Send + Sync {
     fn peek(&mut self, user: &irc::User, m: &str) -> Result<(), Error>;
 }
 
+/// Error raised when the user is not permitted to use a scope.
+#[derive(Debug, err_derive::Error)]
+pub enum ScopeError {
+    /// The scope is not associated with the user.
+    #[error(display = "Scope `{}` not associated with user {:?}", _0, _1)]
+    Denied(Scope, Option<String>),
+    /// The scope is in cooldown.
+    #[error(display = "Scope `{}` is in cooldown", _0)]
+    Cooldown(Scope),
+}
+
 /// Context for a single command invocation.
 pub struct Context<'a> {
     pub api_url: Option<&'a str>,
+    /// The name of the command being invoked, without the leading `!`.
+    pub command: &'a str,
     /// Sender associated with the command.
     pub sender: &'a irc::Sender,
     pub user: irc::User,
@@ -33,6 +50,8 @@ pub struct Context<'a> {
     pub shutdown: &'a utils::Shutdown,
     pub scope_cooldowns: &'a mut HashMap<Scope, utils::Cooldown>,
     pub message_hooks: &'a mut HashMap<String, Box<dyn MessageHook>>,
+    /// The subcommand currently being parsed, if any.
+    pub current: Option<args::Current>,
 }
 
 impl<'a> Context<'a> {
@@ -71,11 +90,7 @@ impl<'a> Context<'a> {
                 ));
             }
 
-            failure::bail!(
-                "Scope `{}` not associated with user {:?}",
-                scope,
-                self.user.name()
-            );
+            return Err(ScopeError::Denied(scope, self.user.name().map(String::from)).into());
         }
 
         if self.user.has_scope(Scope::BypassCooldowns) {
@@ -91,7 +106,7 @@ impl<'a> Context<'a> {
                     utils::compact_duration(&duration),
                 ));
 
-                failure::bail!("Scope `{}` is in cooldown", scope);
+                return Err(ScopeError::Cooldown(scope).into());
             }
 
             cooldown.poke(now);
@@ -176,6 +191,115 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Take the next subcommand, as described by the given spec.
+    ///
+    /// Responds with the usage of the command and returns `None` if the
+    /// subcommand is missing or unknown.
+    pub fn subcommand(&mut self, spec: &'static Spec) -> Result<Option<&'static str>, Error> {
+        let sub = match self.next() {
+            Some(name) => spec.find(&name),
+            None => None,
+        };
+
+        let sub = match sub {
+            Some(sub) => sub,
+            None => {
+                self.respond(format!(
+                    "Expected: {}.",
+                    spec.usage(self.command, &self.user)
+                ));
+                return Ok(None);
+            }
+        };
+
+        if let Some(scope) = sub.scope {
+            self.check_scope(scope)?;
+        }
+
+        self.current = Some(args::Current::new(sub));
+        Ok(Some(sub.name))
+    }
+
+    /// Take the next argument of the current subcommand and parse it as the
+    /// given type.
+    ///
+    /// Responds with the usage of the subcommand and returns `None` if the
+    /// argument is missing or malformed.
+    pub fn arg<T>(&mut self) -> Option<T>
+    where
+        T: Arg,
+    {
+        match self.optional_arg()? {
+            Some(value) => Some(value),
+            None => {
+                self.respond(format!("Expected: {}", self.usage()));
+                None
+            }
+        }
+    }
+
+    /// Take the next argument of the current subcommand if present, and
+    /// parse it as the given type.
+    ///
+    /// Responds with the usage of the subcommand and returns `None` if the
+    /// argument is malformed.
+    pub fn optional_arg<T>(&mut self) -> Option<Option<T>>
+    where
+        T: Arg,
+    {
+        let param = self.current.as_mut().and_then(args::Current::next_param);
+
+        let s = match self.next() {
+            Some(s) => s,
+            None => return Some(None),
+        };
+
+        match T::parse(&s) {
+            Some(value) => Some(Some(value)),
+            None => {
+                let name = match param {
+                    Some(param) => param.to_string(),
+                    None => String::from("argument"),
+                };
+
+                self.respond(format!(
+                    "Bad {}, expected {}: {}",
+                    name,
+                    T::expected(),
+                    self.usage()
+                ));
+
+                None
+            }
+        }
+    }
+
+    /// Take the rest of the current subcommand as an argument.
+    ///
+    /// Responds with the usage of the subcommand and returns `None` if it is
+    /// empty.
+    pub fn rest_arg(&mut self) -> Option<&'a str> {
+        if let Some(current) = self.current.as_mut() {
+            current.next_param();
+        }
+
+        match self.rest().trim() {
+            "" => {
+                self.respond(format!("Expected: {}", self.usage()));
+                None
+            }
+            rest => Some(rest),
+        }
+    }
+
+    /// Usage of the current subcommand.
+    fn usage(&self) -> String {
+        match self.current.as_ref() {
+            Some(current) => format!("!{} {}", self.command, current.sub()),
+            None => format!("!{} <argument>", self.command),
+        }
+    }
+
     /// Take the next parameter.
     pub fn next_str<M>(&mut self, m: M) -> Option<String>
     where
@@ -0,0 +1,416 @@
+//! Declarative, typed arguments for command handlers.
+//!
+//! A command describes its subcommands and their arguments with a [Spec].
+//! The same description is used to parse arguments through
+//! [Context::subcommand](super::Context::subcommand) and
+//! [Context::arg](super::Context::arg), and to generate usage text and error
+//! replies, so that they are consistent across commands.
+
+use crate::{auth::Scope, db, irc, track_id::TrackId, utils};
+use std::{fmt, str::FromStr};
+
+/// Declarative description of the subcommands of a command.
+pub struct Spec {
+    pub subs: &'static [Sub],
+}
+
+impl Spec {
+    /// Find the subcommand with the given name.
+    pub fn find(&self, name: &str) -> Option<&'static Sub> {
+        self.subs.iter().find(|s| s.name == name)
+    }
+
+    /// Usage for all subcommands of the given command available to the
+    /// given user.
+    pub fn usage(&'static self, command: &str, user: &irc::User) -> Usage {
+        Usage {
+            command: command.to_string(),
+            subs: self
+                .subs
+                .iter()
+                .filter(|s| s.scope.map(|scope| user.has_scope(scope)).unwrap_or(true))
+                .collect(),
+        }
+    }
+}
+
+/// Usage for a collection of subcommands.
+pub struct Usage {
+    /// The name of the command, without the leading `!`.
+    command: String,
+    subs: Vec<&'static Sub>,
+}
+
+impl fmt::Display for Usage {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut it = self.subs.iter().peekable();
+
+        while let Some(sub) = it.next() {
+            write!(fmt, "!{} {}", self.command, sub)?;
+
+            if it.peek().is_some() {
+                fmt.write_str(", ")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A single subcommand, like `give <user> <amount>`.
+pub struct Sub {
+    pub name: &'static str,
+    /// Scope required to run the subcommand.
+    pub scope: Option<Scope>,
+    /// Arguments to the subcommand, in order.
+    pub params: &'static [Param],
+}
+
+impl Sub {
+    /// Construct a subcommand which anyone permitted to use the command can
+    /// run.
+    pub const fn new(name: &'static str, params: &'static [Param]) -> Sub {
+        Sub {
+            name,
+            scope: None,
+            params,
+        }
+    }
+
+    /// Construct a subcommand which requires the given scope.
+    pub const fn scoped(name: &'static str, scope: Scope, params: &'static [Param]) -> Sub {
+        Sub {
+            name,
+            scope: Some(scope),
+            params,
+        }
+    }
+}
+
+impl fmt::Display for Sub {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.write_str(self.name)?;
+
+        for param in self.params {
+            write!(fmt, " {}", param)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single argument to a subcommand.
+pub struct Param {
+    pub name: &'static str,
+    pub kind: Kind,
+    /// The words accepted by the argument, if it is a [Choice].
+    pub choices: &'static [&'static str],
+}
+
+impl Param {
+    /// A required argument, like `<user>`.
+    pub const fn required(name: &'static str) -> Param {
+        Param {
+            name,
+            kind: Kind::Required,
+            choices: &[],
+        }
+    }
+
+    /// An optional argument, like `[user]`.
+    pub const fn optional(name: &'static str) -> Param {
+        Param {
+            name,
+            kind: Kind::Optional,
+            choices: &[],
+        }
+    }
+
+    /// An argument which consumes the rest of the command, like `<query..>`.
+    pub const fn rest(name: &'static str) -> Param {
+        Param {
+            name,
+            kind: Kind::Rest,
+            choices: &[],
+        }
+    }
+
+    /// An optional argument which consumes the rest of the command, like
+    /// `[reason..]`.
+    pub const fn optional_rest(name: &'static str) -> Param {
+        Param {
+            name,
+            kind: Kind::OptionalRest,
+            choices: &[],
+        }
+    }
+
+    /// List the words accepted by the argument in place of its name, like
+    /// `[on|off]`.
+    ///
+    /// Intended to be used with [Choice::CHOICES].
+    pub const fn choices(self, choices: &'static [&'static str]) -> Param {
+        Param {
+            name: self.name,
+            kind: self.kind,
+            choices,
+        }
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.choices {
+            [] => self.name.to_string(),
+            choices => choices.join("|"),
+        };
+
+        match self.kind {
+            Kind::Required => write!(fmt, "<{}>", name),
+            Kind::Optional => write!(fmt, "[{}]", name),
+            Kind::Rest => write!(fmt, "<{}..>", name),
+            Kind::OptionalRest => write!(fmt, "[{}..]", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Required,
+    Optional,
+    Rest,
+    OptionalRest,
+}
+
+/// The subcommand currently being parsed.
+pub struct Current {
+    sub: &'static Sub,
+    index: usize,
+}
+
+impl Current {
+    /// Start parsing the arguments of the given subcommand.
+    pub fn new(sub: &'static Sub) -> Current {
+        Current { sub, index: 0 }
+    }
+
+    /// The subcommand being parsed.
+    pub fn sub(&self) -> &'static Sub {
+        self.sub
+    }
+
+    /// Take the next parameter, if it has been declared.
+    pub fn next_param(&mut self) -> Option<&'static Param> {
+        let param = self.sub.params.get(self.index)?;
+        self.index += 1;
+        Some(param)
+    }
+}
+
+/// A typed argument which can be parsed from a single word.
+pub trait Arg: Sized {
+    /// What is expected, used in error replies. Like `a whole number`.
+    fn expected() -> &'static str;
+
+    /// Parse the argument.
+    fn parse(s: &str) -> Option<Self>;
+}
+
+/// A typed argument which is one of a fixed set of words, like an enum.
+///
+/// Choices are parsed through their `FromStr` implementation. Declare the
+/// parameter with [Param::choices] so that the usage text lists them.
+pub trait Choice: FromStr {
+    /// The words which are accepted, in the order they are listed.
+    const CHOICES: &'static [&'static str];
+}
+
+impl<T> Arg for T
+where
+    T: Choice,
+{
+    fn expected() -> &'static str {
+        "one of the listed choices"
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        str::parse(s).ok()
+    }
+}
+
+/// An argument turning something on or off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Toggle {
+    On,
+    Off,
+}
+
+impl FromStr for Toggle {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "on" => Ok(Toggle::On),
+            "off" => Ok(Toggle::Off),
+            other => Err(failure::format_err!("bad toggle: {}", other)),
+        }
+    }
+}
+
+impl Choice for Toggle {
+    const CHOICES: &'static [&'static str] = &["on", "off"];
+}
+
+impl Arg for String {
+    fn expected() -> &'static str {
+        "a word"
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Some(s.to_string())
+    }
+}
+
+macro_rules! number_arg {
+    ($($ty:ty => $expected:expr,)*) => {
+        $(
+            impl Arg for $ty {
+                fn expected() -> &'static str {
+                    $expected
+                }
+
+                fn parse(s: &str) -> Option<Self> {
+                    str::parse(s).ok()
+                }
+            }
+        )*
+    };
+}
+
+number_arg! {
+    i32 => "a whole number",
+    i64 => "a whole number",
+    u32 => "a positive whole number",
+    u64 => "a positive whole number",
+    usize => "a positive whole number",
+}
+
+impl Arg for bool {
+    fn expected() -> &'static str {
+        "`true` or `false`"
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        str::parse(s).ok()
+    }
+}
+
+impl Arg for utils::Duration {
+    fn expected() -> &'static str {
+        "a duration, like `30s` or `1h5m`"
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        str::parse(s).ok()
+    }
+}
+
+impl Arg for TrackId {
+    fn expected() -> &'static str {
+        "a Spotify or YouTube track"
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        TrackId::parse_with_urls(s).ok()
+    }
+}
+
+/// A user argument, normalized to a user id without a leading `@`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct User(pub String);
+
+impl Arg for User {
+    fn expected() -> &'static str {
+        "a user"
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match db::user_id(s) {
+            ref user if user.is_empty() => None,
+            user => Some(User(user)),
+        }
+    }
+}
+
+impl fmt::Display for User {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(fmt)
+    }
+}
+
+impl AsRef<str> for User {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Arg, Choice, Param, Spec, Sub, Toggle, Usage, User};
+    use crate::auth::Scope;
+
+    static SPEC: Spec = Spec {
+        subs: &[
+            Sub::new(
+                "give",
+                &[Param::required("user"), Param::required("amount")],
+            ),
+            Sub::scoped("close", Scope::SongEditQueue, &[Param::rest("reason")]),
+            Sub::new("when", &[Param::optional("user")]),
+            Sub::new(
+                "lockdown",
+                &[Param::optional("mode").choices(Toggle::CHOICES)],
+            ),
+        ],
+    };
+
+    #[test]
+    fn test_usage() {
+        let usage = SPEC.subs.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                "give <user> <amount>",
+                "close <reason..>",
+                "when [user]",
+                "lockdown [on|off]"
+            ],
+            usage
+        );
+
+        let usage = Usage {
+            command: String::from("currency"),
+            subs: SPEC.subs[..2].iter().collect(),
+        };
+
+        assert_eq!(
+            "!currency give <user> <amount>, !currency close <reason..>",
+            usage.to_string()
+        );
+    }
+
+    #[test]
+    fn test_args() {
+        assert_eq!(
+            Some(User(String::from("setbac"))),
+            <User as Arg>::parse("@SetBac")
+        );
+        assert_eq!(None, <User as Arg>::parse("@"));
+        assert_eq!(Some(42), <i64 as Arg>::parse("42"));
+        assert_eq!(None, <u32 as Arg>::parse("-1"));
+        assert_eq!(Some(Toggle::Off), <Toggle as Arg>::parse("off"));
+        assert_eq!(None, <Toggle as Arg>::parse("maybe"));
+        assert!(SPEC.find("give").is_some());
+        assert!(SPEC.find("take").is_none());
+    }
+}
@@ -1,12 +1,19 @@
 //! Stream currency configuration.
-use crate::{api, db::Database};
-pub use crate::{db::models::Balance, utils::Duration};
+use crate::{
+    api,
+    db::{self, Database},
+};
+pub use crate::{
+    db::{models::Balance, LedgerEntry, LedgerReason, LedgerRecord},
+    utils::Duration,
+};
 use failure::Error;
 use hashbrown::HashSet;
 use std::sync::Arc;
 
 mod builtin;
 mod mysql;
+mod postgres;
 
 /// Balance of a single user.
 #[derive(Default)]
@@ -18,14 +25,50 @@ pub struct BalanceOf {
 impl BalanceOf {
     /// Get the current watch time for the specified balance as a duration.
     pub fn watch_time(&self) -> Duration {
-        if self.watch_time < 0 {
-            return Duration::default();
-        }
+        watch_time(self.watch_time)
+    }
+}
+
+/// What users are ranked by on a leaderboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RankBy {
+    Balance,
+    WatchTime,
+}
+
+impl Default for RankBy {
+    fn default() -> Self {
+        RankBy::Balance
+    }
+}
+
+/// A user on a leaderboard.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Ranked {
+    /// Rank of the user, starting at 1. Tied users share the same rank.
+    pub rank: u64,
+    pub user: String,
+    pub balance: i64,
+    pub watch_time: i64,
+}
 
-        Duration::seconds(self.watch_time as u64)
+impl Ranked {
+    /// Get the watch time of the user as a duration.
+    pub fn watch_time(&self) -> Duration {
+        watch_time(self.watch_time)
     }
 }
 
+/// The rank of a single user.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Rank {
+    /// Rank of the user, starting at 1.
+    pub rank: u64,
+    /// Number of users ranked.
+    pub total: u64,
+}
+
 /// Helper struct to construct a currency.
 pub struct CurrencyBuilder {
     pub ty: BackendType,
@@ -33,9 +76,14 @@ pub struct CurrencyBuilder {
     pub command_enabled: bool,
     pub name: Option<Arc<String>>,
     pub db: Option<Database>,
+    pub ledger: Option<db::Ledger>,
+    /// Users which are left out of leaderboards, like the streamer and bots.
+    pub leaderboard_exclude: HashSet<String>,
     pub twitch: api::Twitch,
     pub mysql_url: Option<String>,
     pub mysql_schema: mysql::Schema,
+    pub postgres_url: Option<String>,
+    pub postgres_schema: postgres::Schema,
 }
 
 impl CurrencyBuilder {
@@ -47,9 +95,13 @@ impl CurrencyBuilder {
             command_enabled: Default::default(),
             name: Default::default(),
             db: None,
+            ledger: None,
+            leaderboard_exclude: HashSet::new(),
             twitch,
             mysql_url: None,
             mysql_schema,
+            postgres_url: None,
+            postgres_schema: Default::default(),
         }
     }
 
@@ -101,16 +153,37 @@ impl CurrencyBuilder {
 
                 Backend::MySql(backend)
             }
+            BackendType::Postgres => {
+                let url = self.postgres_url.clone()?;
+                let schema = self.postgres_schema.clone();
+
+                let backend = match self::postgres::Backend::connect(url, schema) {
+                    Ok(backend) => backend,
+                    Err(e) => {
+                        log_err!(e, "failed to establish connection");
+                        return None;
+                    }
+                };
+
+                Backend::Postgres(backend)
+            }
         };
 
         let name = Arc::new(self.name.as_ref()?.to_string());
         let twitch = self.twitch.clone();
+        let ledger = self.ledger.clone();
+        let leaderboard_exclude = self.leaderboard_exclude.clone();
         let command_enabled = self.command_enabled;
 
         Some(Currency {
             name,
             command_enabled,
-            inner: Arc::new(Inner { backend, twitch }),
+            inner: Arc::new(Inner {
+                backend,
+                twitch,
+                ledger,
+                leaderboard_exclude,
+            }),
         })
     }
 }
@@ -123,6 +196,8 @@ pub enum BackendType {
     Mysql,
     #[serde(rename = "honkos")]
     Honkos,
+    #[serde(rename = "postgres")]
+    Postgres,
 }
 
 impl Default for BackendType {
@@ -134,10 +209,14 @@ impl Default for BackendType {
 enum Backend {
     BuiltIn(self::builtin::Backend),
     MySql(self::mysql::Backend),
+    Postgres(self::postgres::Backend),
 }
 
 impl Backend {
     /// Add (or subtract) from the balance for a single user.
+    ///
+    /// The `reason` is only used by backends which record changes in the
+    /// ledger themselves, see `records_ledger`.
     pub async fn balance_transfer(
         &self,
         channel: &str,
@@ -145,13 +224,14 @@ impl Backend {
         taker: &str,
         amount: i64,
         override_balance: bool,
+        reason: Option<LedgerReason>,
     ) -> Result<(), BalanceTransferError> {
         use self::Backend::*;
 
         match *self {
             BuiltIn(ref backend) => {
                 backend
-                    .balance_transfer(channel, giver, taker, amount, override_balance)
+                    .balance_transfer(channel, giver, taker, amount, override_balance, reason)
                     .await
             }
             MySql(ref backend) => {
@@ -159,6 +239,11 @@ impl Backend {
                     .balance_transfer(channel, giver, taker, amount, override_balance)
                     .await
             }
+            Postgres(ref backend) => {
+                backend
+                    .balance_transfer(channel, giver, taker, amount, override_balance)
+                    .await
+            }
         }
     }
 
@@ -169,6 +254,7 @@ impl Backend {
         match *self {
             BuiltIn(ref backend) => backend.export_balances().await,
             MySql(ref backend) => backend.export_balances().await,
+            Postgres(ref backend) => backend.export_balances().await,
         }
     }
 
@@ -179,6 +265,7 @@ impl Backend {
         match *self {
             BuiltIn(ref backend) => backend.import_balances(balances).await,
             MySql(ref backend) => backend.import_balances(balances).await,
+            Postgres(ref backend) => backend.import_balances(balances).await,
         }
     }
 
@@ -189,26 +276,41 @@ impl Backend {
         match *self {
             BuiltIn(ref backend) => backend.balance_of(channel, user).await,
             MySql(ref backend) => backend.balance_of(channel, user).await,
+            Postgres(ref backend) => backend.balance_of(channel, user).await,
         }
     }
 
     /// Add (or subtract) from the balance for a single user.
-    pub async fn balance_add(&self, channel: &str, user: &str, amount: i64) -> Result<(), Error> {
+    ///
+    /// The `record` is only used by backends which record changes in the
+    /// ledger themselves, see `records_ledger`.
+    pub async fn balance_add(
+        &self,
+        channel: &str,
+        user: &str,
+        amount: i64,
+        record: Option<LedgerRecord>,
+    ) -> Result<(), Error> {
         use self::Backend::*;
 
         match *self {
-            BuiltIn(ref backend) => backend.balance_add(channel, user, amount).await,
+            BuiltIn(ref backend) => backend.balance_add(channel, user, amount, record).await,
             MySql(ref backend) => backend.balance_add(channel, user, amount).await,
+            Postgres(ref backend) => backend.balance_add(channel, user, amount).await,
         }
     }
 
     /// Add balance to users.
+    ///
+    /// The `record` is only used by backends which record changes in the
+    /// ledger themselves, see `records_ledger`.
     pub async fn balances_increment<I>(
         &self,
         channel: &str,
         users: I,
         amount: i64,
         watch_time: i64,
+        record: Option<LedgerRecord>,
     ) -> Result<(), Error>
     where
         I: IntoIterator<Item = String> + Send + 'static,
@@ -219,10 +321,68 @@ impl Backend {
         match *self {
             BuiltIn(ref backend) => {
                 backend
-                    .balances_increment(channel, users, amount, watch_time)
+                    .balances_increment(channel, users, amount, watch_time, record)
                     .await
             }
             MySql(ref backend) => backend.balances_increment(channel, users, amount).await,
+            Postgres(ref backend) => {
+                backend
+                    .balances_increment(channel, users, amount, watch_time)
+                    .await
+            }
+        }
+    }
+
+    /// Get the top balances, excluding the given users.
+    pub async fn leaderboard(
+        &self,
+        channel: Option<&str>,
+        by: RankBy,
+        exclude: Vec<String>,
+        limit: u32,
+    ) -> Result<Vec<Ranked>, Error> {
+        use self::Backend::*;
+
+        match *self {
+            BuiltIn(ref backend) => backend.leaderboard(channel, by, exclude, limit).await,
+            MySql(ref backend) => backend.leaderboard(channel, by, exclude, limit).await,
+            Postgres(ref backend) => backend.leaderboard(channel, by, exclude, limit).await,
+        }
+    }
+
+    /// Get the rank of the given user, excluding the given users.
+    pub async fn rank_of(
+        &self,
+        channel: &str,
+        by: RankBy,
+        user: &str,
+        exclude: Vec<String>,
+    ) -> Result<Option<Rank>, Error> {
+        use self::Backend::*;
+
+        match *self {
+            BuiltIn(ref backend) => backend.rank_of(channel, by, user, exclude).await,
+            MySql(ref backend) => backend.rank_of(channel, by, user, exclude).await,
+            Postgres(ref backend) => backend.rank_of(channel, by, user, exclude).await,
+        }
+    }
+
+    /// Test if the backend records changes in the ledger itself, in the same
+    /// transaction as the change to the balances.
+    pub fn records_ledger(&self) -> bool {
+        match *self {
+            Backend::BuiltIn(..) => true,
+            Backend::MySql(..) => false,
+            Backend::Postgres(..) => false,
+        }
+    }
+
+    /// Test if the backend keeps track of watch time.
+    pub fn tracks_watch_time(&self) -> bool {
+        match *self {
+            Backend::BuiltIn(..) => true,
+            Backend::MySql(..) => false,
+            Backend::Postgres(ref backend) => backend.tracks_watch_time(),
         }
     }
 }
@@ -230,6 +390,9 @@ impl Backend {
 struct Inner {
     backend: Backend,
     twitch: api::Twitch,
+    /// Ledger where every change to a balance is recorded.
+    ledger: Option<db::Ledger>,
+    leaderboard_exclude: HashSet<String>,
 }
 
 /// The currency being used.
@@ -241,13 +404,15 @@ pub struct Currency {
 }
 
 impl Currency {
-    /// Reward all users.
+    /// Reward all users, returning the users who were rewarded.
     pub async fn add_channel_all(
         &self,
         channel: &str,
         reward: i64,
         watch_time: i64,
-    ) -> Result<usize, failure::Error> {
+        reason: LedgerReason,
+        counterparty: Option<&str>,
+    ) -> Result<HashSet<String>, failure::Error> {
         let chatters = self.inner.twitch.chatters(channel).await?;
 
         let mut users = HashSet::new();
@@ -255,17 +420,23 @@ impl Currency {
         users.extend(chatters.moderators);
         users.extend(chatters.broadcaster);
 
-        let len = users.len();
-
-        self.inner
-            .backend
-            .balances_increment(channel, users, reward, watch_time)
-            .await?;
-
-        Ok(len)
+        self.balances_increment(
+            channel,
+            users.clone(),
+            reward,
+            watch_time,
+            reason,
+            counterparty,
+        )
+        .await?;
+
+        Ok(users)
     }
 
     /// Add (or subtract) from the balance for a single user.
+    ///
+    /// The transfer is recorded in the ledger of both users with the given
+    /// reason.
     pub async fn balance_transfer(
         &self,
         channel: &str,
@@ -273,11 +444,23 @@ impl Currency {
         taker: &str,
         amount: i64,
         override_balance: bool,
+        reason: LedgerReason,
     ) -> Result<(), BalanceTransferError> {
+        let recorded = Some(reason).filter(|_| self.backend_records(amount));
+
         self.inner
             .backend
-            .balance_transfer(channel, giver, taker, amount, override_balance)
-            .await
+            .balance_transfer(channel, giver, taker, amount, override_balance, recorded)
+            .await?;
+
+        let giver = db::user_id(giver);
+        let taker = db::user_id(taker);
+
+        self.record(channel, Some(taker.as_str()), amount, reason, Some(&giver));
+
+        self.record(channel, Some(giver.as_str()), -amount, reason, Some(&taker));
+
+        Ok(())
     }
 
     /// Get balances for all users.
@@ -286,6 +469,9 @@ impl Currency {
     }
 
     /// Import balances for all users.
+    ///
+    /// Imported balances replace existing ones, and are not recorded in the
+    /// ledger.
     pub async fn import_balances(&self, balances: Vec<Balance>) -> Result<(), Error> {
         self.inner.backend.import_balances(balances).await
     }
@@ -296,8 +482,27 @@ impl Currency {
     }
 
     /// Add (or subtract) from the balance for a single user.
-    pub async fn balance_add(&self, channel: &str, user: &str, amount: i64) -> Result<(), Error> {
-        self.inner.backend.balance_add(channel, user, amount).await
+    ///
+    /// `counterparty` is the other user involved in the change, like the
+    /// moderator who made it.
+    pub async fn balance_add(
+        &self,
+        channel: &str,
+        user: &str,
+        amount: i64,
+        reason: LedgerReason,
+        counterparty: Option<&str>,
+    ) -> Result<(), Error> {
+        let record = self.backend_record(amount, reason, counterparty);
+
+        self.inner
+            .backend
+            .balance_add(channel, user, amount, record)
+            .await?;
+
+        let user = db::user_id(user);
+        self.record(channel, Some(user.as_str()), amount, reason, counterparty);
+        Ok(())
     }
 
     /// Add balance to users.
@@ -307,16 +512,212 @@ impl Currency {
         users: I,
         amount: i64,
         watch_time: i64,
+        reason: LedgerReason,
+        counterparty: Option<&str>,
     ) -> Result<(), Error>
     where
         I: IntoIterator<Item = String> + Send + 'static,
         I::IntoIter: Send + 'static,
     {
+        let users = users
+            .into_iter()
+            .map(|u| db::user_id(&u))
+            .collect::<Vec<_>>();
+
+        let record = self.backend_record(amount, reason, counterparty);
+
+        self.inner
+            .backend
+            .balances_increment(channel, users.clone(), amount, watch_time, record)
+            .await?;
+
+        self.record(
+            channel,
+            users.iter().map(String::as_str),
+            amount,
+            reason,
+            counterparty,
+        );
+
+        Ok(())
+    }
+
+    /// List the most recent changes to the balance of the given user.
+    pub fn history(
+        &self,
+        channel: &str,
+        user: &str,
+        limit: i64,
+    ) -> Result<Vec<LedgerEntry>, Error> {
+        match self.inner.ledger.as_ref() {
+            Some(ledger) => ledger.history(
+                Some(channel_id(channel).as_str()),
+                &db::user_id(user),
+                limit,
+            ),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Get the top users in the given channel, or in all channels if none is
+    /// specified.
+    pub async fn leaderboard(
+        &self,
+        channel: Option<&str>,
+        by: RankBy,
+        limit: u32,
+    ) -> Result<Vec<Ranked>, Error> {
+        let channel = channel.map(channel_id);
+
         self.inner
             .backend
-            .balances_increment(channel, users, amount, watch_time)
+            .leaderboard(
+                channel.as_ref().map(String::as_str),
+                by,
+                self.leaderboard_exclude(),
+                limit,
+            )
             .await
     }
+
+    /// Get the rank of the given user.
+    ///
+    /// Returns `None` if the user doesn't have a balance, or is left out of
+    /// leaderboards.
+    pub async fn rank_of(
+        &self,
+        channel: &str,
+        by: RankBy,
+        user: &str,
+    ) -> Result<Option<Rank>, Error> {
+        let user = db::user_id(user);
+
+        if self.inner.leaderboard_exclude.contains(&user) {
+            return Ok(None);
+        }
+
+        self.inner
+            .backend
+            .rank_of(&channel_id(channel), by, &user, self.leaderboard_exclude())
+            .await
+    }
+
+    /// Test if the currency keeps track of watch time.
+    pub fn tracks_watch_time(&self) -> bool {
+        self.inner.backend.tracks_watch_time()
+    }
+
+    /// Users to leave out of leaderboards.
+    fn leaderboard_exclude(&self) -> Vec<String> {
+        let mut exclude = self
+            .inner
+            .leaderboard_exclude
+            .iter()
+            .cloned()
+            .collect::<Vec<_>>();
+        exclude.sort();
+        exclude
+    }
+
+    /// Test if a change of the given amount is recorded in the ledger by the
+    /// backend.
+    fn backend_records(&self, amount: i64) -> bool {
+        self.inner.ledger.is_some() && amount != 0 && self.inner.backend.records_ledger()
+    }
+
+    /// How the backend should record a change of the given amount, if it
+    /// records it.
+    fn backend_record(
+        &self,
+        amount: i64,
+        reason: LedgerReason,
+        counterparty: Option<&str>,
+    ) -> Option<LedgerRecord> {
+        if !self.backend_records(amount) {
+            return None;
+        }
+
+        Some(LedgerRecord {
+            reason,
+            counterparty: counterparty.map(db::user_id),
+        })
+    }
+
+    /// Record a change to the balance of the given users in the ledger.
+    ///
+    /// Changes which are recorded by the backend are skipped. Failing to
+    /// record is logged, but doesn't fail the change since it has already
+    /// been made.
+    fn record<'a>(
+        &self,
+        channel: &str,
+        users: impl IntoIterator<Item = &'a str>,
+        amount: i64,
+        reason: LedgerReason,
+        counterparty: Option<&str>,
+    ) {
+        let ledger = match self.inner.ledger.as_ref() {
+            Some(ledger) if amount != 0 && !self.inner.backend.records_ledger() => ledger,
+            _ => return,
+        };
+
+        let counterparty = counterparty.map(db::user_id);
+        let counterparty = counterparty.as_ref().map(String::as_str);
+
+        let result = ledger.record(&channel_id(channel), users, amount, reason, counterparty);
+
+        if let Err(e) = result {
+            log_err!(e, "failed to record balance change in ledger");
+        }
+    }
+}
+
+/// Normalize the channel, which is stored with a leading hash.
+fn channel_id(channel: &str) -> String {
+    format!("#{}", channel.trim_start_matches('#'))
+}
+
+/// Convert watch time in seconds into a duration.
+fn watch_time(seconds: i64) -> Duration {
+    if seconds < 0 {
+        return Duration::default();
+    }
+
+    Duration::seconds(seconds as u64)
+}
+
+/// Rank balances which are sorted in descending order by the given value.
+///
+/// Users which are tied share the same rank, and the rank after them is
+/// skipped.
+fn rank_balances(balances: impl IntoIterator<Item = Balance>, by: RankBy) -> Vec<Ranked> {
+    let value = |balance: i64, watch_time: i64| match by {
+        RankBy::Balance => balance,
+        RankBy::WatchTime => watch_time,
+    };
+
+    let mut out = Vec::<Ranked>::new();
+
+    for (index, balance) in balances.into_iter().enumerate() {
+        let rank = match out.last() {
+            Some(last)
+                if value(last.balance, last.watch_time)
+                    == value(balance.amount, balance.watch_time) =>
+            {
+                last.rank
+            }
+            _ => index as u64 + 1,
+        };
+
+        out.push(Ranked {
+            rank,
+            user: balance.user,
+            balance: balance.amount,
+            watch_time: balance.watch_time,
+        });
+    }
+
+    out
 }
 
 #[derive(Debug, err_derive::Error)]
@@ -350,3 +751,62 @@ impl From<mysql_async::error::Error> for BalanceTransferError {
         BalanceTransferError::Other(value.into())
     }
 }
+
+impl From<tokio_postgres::Error> for BalanceTransferError {
+    fn from(value: tokio_postgres::Error) -> Self {
+        BalanceTransferError::Other(value.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rank_balances, Balance, RankBy};
+
+    fn balance(user: &str, amount: i64, watch_time: i64) -> Balance {
+        Balance {
+            channel: String::from("#setbac"),
+            user: user.to_string(),
+            amount,
+            watch_time,
+        }
+    }
+
+    #[test]
+    fn test_rank_balances() {
+        let balances = vec![
+            balance("a", 100, 10),
+            balance("b", 50, 30),
+            balance("c", 50, 20),
+            balance("d", 10, 20),
+        ];
+
+        let ranks = rank_balances(balances, RankBy::Balance)
+            .into_iter()
+            .map(|r| (r.user, r.rank))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                (String::from("a"), 1),
+                (String::from("b"), 2),
+                (String::from("c"), 2),
+                (String::from("d"), 4),
+            ],
+            ranks
+        );
+
+        let balances = vec![
+            balance("b", 50, 30),
+            balance("c", 50, 20),
+            balance("d", 10, 20),
+            balance("a", 100, 10),
+        ];
+
+        let ranks = rank_balances(balances, RankBy::WatchTime)
+            .into_iter()
+            .map(|r| r.rank)
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![1, 2, 2, 4], ranks);
+    }
+}
@@ -1,8 +1,8 @@
 //! Module for the built-in currency which uses the regular databse support.
 
 use crate::{
-    currency::{BalanceOf, BalanceTransferError},
-    db::{models, schema, user_id, Database},
+    currency::{rank_balances, BalanceOf, BalanceTransferError, Rank, RankBy, Ranked},
+    db::{models, schema, user_id, Database, LedgerReason, LedgerRecord},
     prelude::*,
 };
 
@@ -20,6 +20,9 @@ impl Backend {
     }
 
     /// Add (or subtract) from the balance for a single user.
+    ///
+    /// If a `reason` is specified, the transfer is recorded in the ledger of
+    /// both users in the same transaction.
     pub async fn balance_transfer(
         &self,
         channel: &str,
@@ -27,6 +30,7 @@ impl Backend {
         taker: &str,
         amount: i64,
         override_balance: bool,
+        reason: Option<LedgerReason>,
     ) -> Result<(), BalanceTransferError> {
         use self::schema::balances::dsl;
 
@@ -57,6 +61,17 @@ impl Backend {
 
                 modify_balance(c, &channel, &taker, amount)?;
                 modify_balance(c, &channel, &giver, -amount)?;
+
+                if let Some(reason) = reason {
+                    let record = |counterparty: &str| LedgerRecord {
+                        reason,
+                        counterparty: Some(counterparty.to_string()),
+                    };
+
+                    record(&giver).insert(c, &channel, Some(taker.as_str()), amount)?;
+                    record(&taker).insert(c, &channel, Some(giver.as_str()), -amount)?;
+                }
+
                 Ok(())
             })
         };
@@ -162,15 +177,127 @@ impl Backend {
         handle.await
     }
 
+    /// Get the top balances, excluding the given users.
+    pub async fn leaderboard(
+        &self,
+        channel: Option<&str>,
+        by: RankBy,
+        exclude: Vec<String>,
+        limit: u32,
+    ) -> Result<Vec<Ranked>, Error> {
+        use self::schema::balances::dsl;
+
+        let channel = channel.map(String::from);
+        let pool = self.db.pool.clone();
+
+        let future = async move {
+            let c = pool.lock();
+
+            let mut query = dsl::balances
+                .filter(dsl::user.ne_all(&exclude))
+                .into_boxed();
+
+            if let Some(channel) = channel.as_ref() {
+                query = query.filter(dsl::channel.eq(channel));
+            }
+
+            let query = match by {
+                RankBy::Balance => query.order((dsl::amount.desc(), dsl::user.asc())),
+                RankBy::WatchTime => query.order((dsl::watch_time.desc(), dsl::user.asc())),
+            };
+
+            let balances = query.limit(i64::from(limit)).load::<models::Balance>(&*c)?;
+
+            Ok(rank_balances(balances, by))
+        };
+
+        let (future, handle) = future.remote_handle();
+        tokio::spawn(future);
+        handle.await
+    }
+
+    /// Get the rank of the given user, excluding the given users.
+    pub async fn rank_of(
+        &self,
+        channel: &str,
+        by: RankBy,
+        user: &str,
+        exclude: Vec<String>,
+    ) -> Result<Option<Rank>, Error> {
+        use self::schema::balances::dsl;
+
+        let channel = channel_id(channel);
+        let user = user_id(user);
+        let pool = self.db.pool.clone();
+
+        let future = async move {
+            let c = pool.lock();
+
+            let balance = dsl::balances
+                .select((dsl::amount, dsl::watch_time))
+                .filter(dsl::channel.eq(&channel).and(dsl::user.eq(&user)))
+                .first::<(i64, i64)>(&*c)
+                .optional()?;
+
+            let (amount, watch_time) = match balance {
+                Some(balance) => balance,
+                None => return Ok(None),
+            };
+
+            let ranked = || {
+                dsl::balances
+                    .filter(dsl::channel.eq(&channel))
+                    .filter(dsl::user.ne_all(&exclude))
+                    .into_boxed()
+            };
+
+            let ahead = match by {
+                RankBy::Balance => ranked().filter(dsl::amount.gt(amount)),
+                RankBy::WatchTime => ranked().filter(dsl::watch_time.gt(watch_time)),
+            };
+
+            let ahead = ahead.count().get_result::<i64>(&*c)?;
+            let total = ranked().count().get_result::<i64>(&*c)?;
+
+            Ok(Some(Rank {
+                rank: ahead as u64 + 1,
+                total: total as u64,
+            }))
+        };
+
+        let (future, handle) = future.remote_handle();
+        tokio::spawn(future);
+        handle.await
+    }
+
     /// Add (or subtract) from the balance for a single user.
-    pub async fn balance_add(&self, channel: &str, user: &str, amount: i64) -> Result<(), Error> {
+    ///
+    /// The change is recorded in the ledger in the same transaction, if a
+    /// `record` is specified.
+    pub async fn balance_add(
+        &self,
+        channel: &str,
+        user: &str,
+        amount: i64,
+        record: Option<LedgerRecord>,
+    ) -> Result<(), Error> {
         let channel = channel_id(channel);
         let user = user_id(user);
         let pool = self.db.pool.clone();
 
         let future = async move {
             let c = pool.lock();
-            modify_balance(&*c, &channel, &user, amount)
+            let c = &*c;
+
+            c.transaction::<_, Error, _>(|| {
+                modify_balance(c, &channel, &user, amount)?;
+
+                if let Some(record) = record {
+                    record.insert(c, &channel, Some(user.as_str()), amount)?;
+                }
+
+                Ok(())
+            })
         };
 
         let (future, handle) = future.remote_handle();
@@ -179,55 +306,36 @@ impl Backend {
     }
 
     /// Add balance to users.
+    ///
+    /// The change is recorded in the ledger in the same transaction, if a
+    /// `record` is specified.
     pub async fn balances_increment(
         &self,
         channel: &str,
         users: impl IntoIterator<Item = String> + Send + 'static,
         amount: i64,
         watch_time: i64,
+        record: Option<LedgerRecord>,
     ) -> Result<(), Error> {
-        use self::schema::balances::dsl;
-
         // NB: for legacy reasons, channel is stored with a hash.
         let channel = format!("#{}", channel);
         let pool = self.db.pool.clone();
 
         let future = async move {
             let c = pool.lock();
+            let c = &*c;
 
-            for user in users {
-                let user = user_id(&user);
-
-                let filter = dsl::balances
-                    .filter(dsl::channel.eq(channel.as_str()).and(dsl::user.eq(&user)));
-
-                let b = filter.clone().first::<models::Balance>(&*c).optional()?;
+            c.transaction::<_, Error, _>(|| {
+                let users = users.into_iter().map(|u| user_id(&u)).collect::<Vec<_>>();
 
-                match b {
-                    None => {
-                        let balance = models::Balance {
-                            channel: channel.to_string(),
-                            user: user.clone(),
-                            amount,
-                            watch_time,
-                        };
+                increment_balances(c, &channel, &users, amount, watch_time)?;
 
-                        diesel::insert_into(dsl::balances)
-                            .values(&balance)
-                            .execute(&*c)?;
-                    }
-                    Some(b) => {
-                        let value = b.amount.saturating_add(amount);
-                        let watch_time = b.watch_time.saturating_add(watch_time);
-
-                        diesel::update(filter)
-                            .set((dsl::amount.eq(value), dsl::watch_time.eq(watch_time)))
-                            .execute(&*c)?;
-                    }
+                if let Some(record) = record {
+                    record.insert(c, &channel, users.iter().map(String::as_str), amount)?;
                 }
-            }
 
-            Ok(())
+                Ok(())
+            })
         };
 
         let (future, handle) = future.remote_handle();
@@ -236,6 +344,46 @@ impl Backend {
     }
 }
 
+/// Add to the balance and watch time of the given users.
+fn increment_balances(
+    c: &SqliteConnection,
+    channel: &str,
+    users: &[String],
+    amount: i64,
+    watch_time: i64,
+) -> Result<(), Error> {
+    use self::schema::balances::dsl;
+
+    for user in users {
+        let filter = dsl::balances.filter(dsl::channel.eq(channel).and(dsl::user.eq(user)));
+
+        match filter.clone().first::<models::Balance>(c).optional()? {
+            None => {
+                let balance = models::Balance {
+                    channel: channel.to_string(),
+                    user: user.clone(),
+                    amount,
+                    watch_time,
+                };
+
+                diesel::insert_into(dsl::balances)
+                    .values(&balance)
+                    .execute(c)?;
+            }
+            Some(b) => {
+                let amount = b.amount.saturating_add(amount);
+                let watch_time = b.watch_time.saturating_add(watch_time);
+
+                diesel::update(filter)
+                    .set((dsl::amount.eq(amount), dsl::watch_time.eq(watch_time)))
+                    .execute(c)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Common function to modify the balance for the given user.
 fn modify_balance(
     c: &SqliteConnection,
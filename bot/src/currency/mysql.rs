@@ -8,11 +8,11 @@
 //! 2) Name the fields holding channel, user, and amount.
 
 use crate::{
-    currency::{BalanceOf, BalanceTransferError},
+    currency::{rank_balances, BalanceOf, BalanceTransferError, Rank, RankBy, Ranked},
     db::{models::Balance, user_id},
 };
 
-use failure::Error;
+use failure::{bail, Error};
 use mysql_async as mysql;
 use std::{convert::TryInto as _, sync::Arc};
 
@@ -63,6 +63,113 @@ impl Queries {
         Ok((tx, result))
     }
 
+    /// Select the top balances, excluding the given users.
+    async fn select_top_balances<Tx>(
+        &self,
+        tx: Tx,
+        exclude: &[String],
+        limit: u32,
+    ) -> Result<(Tx, Vec<(String, i32)>), Error>
+    where
+        Tx: Queryable,
+    {
+        let (exclude, mut params) = self.exclude_users(exclude);
+
+        let query = format!(
+            "SELECT `{user_column}`, `{balance_column}` \
+             FROM `{table}` \
+             WHERE {exclude} \
+             ORDER BY `{balance_column}` DESC, `{user_column}` ASC \
+             LIMIT :limit",
+            table = self.schema.table,
+            balance_column = self.schema.balance_column,
+            user_column = self.schema.user_column,
+            exclude = exclude,
+        );
+
+        params.push((String::from("limit"), mysql::Value::from(limit)));
+
+        log::trace!("select_top_balances: {} {:?}", query, params);
+        let rows = tx.prep_exec(query, params).await?;
+
+        let (tx, result) = rows
+            .map_and_drop(|row| mysql::from_row::<(String, i32)>(row))
+            .await?;
+
+        Ok((tx, result))
+    }
+
+    /// Count the balances greater than the given balance, excluding the given
+    /// users.
+    ///
+    /// Counts all balances if `balance` is `None`.
+    async fn count_balances<Tx>(
+        &self,
+        tx: Tx,
+        exclude: &[String],
+        balance: Option<i32>,
+    ) -> Result<(Tx, i64), Error>
+    where
+        Tx: Queryable,
+    {
+        let (exclude, mut params) = self.exclude_users(exclude);
+
+        let greater = match balance {
+            Some(balance) => {
+                params.push((String::from("balance"), mysql::Value::from(balance)));
+                format!("`{}` > :balance", self.schema.balance_column)
+            }
+            None => String::from("TRUE"),
+        };
+
+        let query = format!(
+            "SELECT COUNT(*) FROM `{table}` WHERE {greater} AND {exclude}",
+            table = self.schema.table,
+            greater = greater,
+            exclude = exclude,
+        );
+
+        log::trace!("count_balances: {} {:?}", query, params);
+        let result = tx.prep_exec(query, params).await?;
+
+        let (tx, results) = result
+            .map_and_drop(|row| mysql::from_row::<(i64,)>(row))
+            .await?;
+
+        Ok((
+            tx,
+            results
+                .into_iter()
+                .map(|(c, ..)| c)
+                .next()
+                .unwrap_or_default(),
+        ))
+    }
+
+    /// Build a condition which excludes the given users, and its parameters.
+    fn exclude_users(&self, exclude: &[String]) -> (String, Vec<(String, mysql::Value)>) {
+        if exclude.is_empty() {
+            return (String::from("TRUE"), Vec::new());
+        }
+
+        let mut names = Vec::new();
+        let mut params = Vec::new();
+
+        for (index, user) in exclude.iter().enumerate() {
+            let name = format!("exclude{}", index);
+            names.push(format!(":{}", name));
+            params.push((name, mysql::Value::from(user.as_str())));
+        }
+
+        let condition = format!(
+            "`{user_column}` NOT IN ({names})",
+            user_column = self.schema.user_column,
+            names = names.join(", "),
+        );
+
+        (condition, params)
+    }
+
     /// Select the given balance.
     async fn select_balance<Tx>(&self, tx: Tx, user: &str) -> Result<(Tx, Option<i32>), Error>
     where
@@ -286,6 +393,75 @@ impl Backend {
         }))
     }
 
+    /// Get the top balances, excluding the given users.
+    ///
+    /// The schema doesn't have channels or watch time, so all balances are
+    /// ranked and they can't be ranked by watch time.
+    pub async fn leaderboard(
+        &self,
+        _channel: Option<&str>,
+        by: RankBy,
+        exclude: Vec<String>,
+        limit: u32,
+    ) -> Result<Vec<Ranked>, Error> {
+        if by == RankBy::WatchTime {
+            bail!("watch time is not tracked by the mysql currency backend");
+        }
+
+        let channel = self.channel.to_string();
+        let opts = mysql::TransactionOptions::new();
+        let tx = self.pool.start_transaction(opts).await?;
+
+        let (_, balances) = self
+            .queries
+            .select_top_balances(tx, &exclude, limit)
+            .await?;
+
+        let balances = balances.into_iter().map(|(user, balance)| Balance {
+            channel: channel.clone(),
+            user,
+            amount: balance as i64,
+            watch_time: 0,
+        });
+
+        Ok(rank_balances(balances, by))
+    }
+
+    /// Get the rank of the given user, excluding the given users.
+    pub async fn rank_of(
+        &self,
+        _channel: &str,
+        by: RankBy,
+        user: &str,
+        exclude: Vec<String>,
+    ) -> Result<Option<Rank>, Error> {
+        if by == RankBy::WatchTime {
+            bail!("watch time is not tracked by the mysql currency backend");
+        }
+
+        let user = user_id(user);
+        let opts = mysql::TransactionOptions::new();
+        let tx = self.pool.start_transaction(opts).await?;
+
+        let (tx, balance) = self.queries.select_balance(tx, &user).await?;
+
+        let balance = match balance {
+            Some(balance) => balance,
+            None => return Ok(None),
+        };
+
+        let (tx, ahead) = self
+            .queries
+            .count_balances(tx, &exclude, Some(balance))
+            .await?;
+        let (_, total) = self.queries.count_balances(tx, &exclude, None).await?;
+
+        Ok(Some(Rank {
+            rank: ahead as u64 + 1,
+            total: total as u64,
+        }))
+    }
+
     /// Add (or subtract) from the balance for a single user.
     pub async fn balance_add(&self, _channel: &str, user: &str, amount: i64) -> Result<(), Error> {
         let user = user_id(&user);
@@ -0,0 +1,842 @@
+//! Module for using a currency stored in a PostgreSQL database.
+//!
+//! The table is described by a [Schema], which names the table and the
+//! columns holding the user, the balance and optionally the watch time.
+//!
+//! The user column must have a unique constraint, since balances are upserted
+//! with `ON CONFLICT`.
+//!
+//! Connections are pooled, and use TLS as configured by the `sslmode` of the
+//! url, like `postgres://user@host/db?sslmode=require`.
+
+use crate::{
+    currency::{rank_balances, BalanceOf, BalanceTransferError, Rank, RankBy, Ranked},
+    db::{models::Balance, user_id},
+    prelude::*,
+};
+
+use failure::{bail, Error};
+use parking_lot::Mutex;
+use postgres_native_tls::MakeTlsConnector;
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+use tokio_postgres as postgres;
+
+/// The maximum number of idle connections kept around for reuse.
+const MAX_IDLE_CONNECTIONS: usize = 4;
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Schema {
+    pub table: String,
+    pub balance_column: String,
+    pub user_column: String,
+    /// Column holding the watch time of a user in seconds, if any.
+    #[serde(default)]
+    pub watch_time_column: Option<String>,
+}
+
+impl Default for Schema {
+    fn default() -> Self {
+        Self {
+            table: String::from("balances"),
+            balance_column: String::from("balance"),
+            user_column: String::from("user"),
+            watch_time_column: None,
+        }
+    }
+}
+
+/// Quote the given identifier so that it can be used in a query.
+fn ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+struct Queries {
+    schema: Schema,
+}
+
+impl Queries {
+    /// Expression selecting the watch time of a user, or zero if watch time
+    /// isn't tracked.
+    fn watch_time(&self) -> String {
+        match self.schema.watch_time_column {
+            Some(ref column) => format!("COALESCE({}::BIGINT, 0)", ident(column)),
+            None => String::from("0::BIGINT"),
+        }
+    }
+
+    /// Expression selecting the value to rank by.
+    fn rank_value(&self, by: RankBy) -> Result<String, Error> {
+        match by {
+            RankBy::Balance => Ok(format!("{}::BIGINT", ident(&self.schema.balance_column))),
+            RankBy::WatchTime => match self.schema.watch_time_column {
+                Some(ref column) => Ok(format!("COALESCE({}::BIGINT, 0)", ident(column))),
+                None => bail!("watch time is not tracked by the postgres currency backend"),
+            },
+        }
+    }
+
+    /// Query selecting all balances.
+    fn select_balances_query(&self) -> String {
+        format!(
+            "SELECT {user_column}, {balance_column}::BIGINT, {watch_time} FROM {table}",
+            table = ident(&self.schema.table),
+            user_column = ident(&self.schema.user_column),
+            balance_column = ident(&self.schema.balance_column),
+            watch_time = self.watch_time(),
+        )
+    }
+
+    /// Query selecting the balance of a single user.
+    fn select_balance_query(&self) -> String {
+        format!(
+            "SELECT {balance_column}::BIGINT, {watch_time} \
+             FROM {table} \
+             WHERE {user_column} = $1 \
+             LIMIT 1",
+            table = ident(&self.schema.table),
+            user_column = ident(&self.schema.user_column),
+            balance_column = ident(&self.schema.balance_column),
+            watch_time = self.watch_time(),
+        )
+    }
+
+    /// Query selecting the top balances, excluding the given users.
+    fn select_top_balances_query(&self, by: RankBy) -> Result<String, Error> {
+        Ok(format!(
+            "SELECT {user_column}, {balance_column}::BIGINT, {watch_time} \
+             FROM {table} \
+             WHERE NOT ({user_column} = ANY($1)) \
+             ORDER BY {value} DESC, {user_column} ASC \
+             LIMIT $2",
+            table = ident(&self.schema.table),
+            user_column = ident(&self.schema.user_column),
+            balance_column = ident(&self.schema.balance_column),
+            watch_time = self.watch_time(),
+            value = self.rank_value(by)?,
+        ))
+    }
+
+    /// Query counting the balances, excluding the given users.
+    ///
+    /// If `greater` is set, only counts the balances which are greater than
+    /// the given value.
+    fn count_balances_query(&self, by: RankBy, greater: bool) -> Result<String, Error> {
+        let greater = if greater {
+            format!("{} > $2", self.rank_value(by)?)
+        } else {
+            String::from("TRUE")
+        };
+
+        Ok(format!(
+            "SELECT COUNT(*) FROM {table} WHERE {greater} AND NOT ({user_column} = ANY($1))",
+            table = ident(&self.schema.table),
+            user_column = ident(&self.schema.user_column),
+            greater = greater,
+        ))
+    }
+
+    /// Query adding to the balance (and watch time) of a single user,
+    /// inserting it if it doesn't exist.
+    fn upsert_balance_query(&self) -> String {
+        let table = ident(&self.schema.table);
+        let user_column = ident(&self.schema.user_column);
+        let balance_column = ident(&self.schema.balance_column);
+
+        let mut columns = vec![user_column.clone(), balance_column.clone()];
+        let mut values = vec![String::from("$1"), String::from("$2::BIGINT")];
+
+        let mut updates = vec![format!(
+            "{column} = {table}.{column} + EXCLUDED.{column}",
+            table = table,
+            column = balance_column,
+        )];
+
+        if let Some(ref column) = self.schema.watch_time_column {
+            let column = ident(column);
+
+            updates.push(format!(
+                "{column} = COALESCE({table}.{column}, 0) + EXCLUDED.{column}",
+                table = table,
+                column = column,
+            ));
+
+            columns.push(column);
+            values.push(String::from("$3::BIGINT"));
+        }
+
+        format!(
+            "INSERT INTO {table} ({columns}) VALUES ({values}) \
+             ON CONFLICT ({user_column}) DO UPDATE SET {updates}",
+            table = table,
+            user_column = user_column,
+            columns = columns.join(", "),
+            values = values.join(", "),
+            updates = updates.join(", "),
+        )
+    }
+
+    /// Query subtracting from the balance of a single user, only if the
+    /// balance covers the amount.
+    ///
+    /// The check and the update happen in a single statement, so concurrent
+    /// debits can't overdraw the balance.
+    fn debit_balance_query(&self) -> String {
+        format!(
+            "UPDATE {table} SET {balance_column} = {balance_column} - $2::BIGINT \
+             WHERE {user_column} = $1 AND {balance_column}::BIGINT >= $2::BIGINT",
+            table = ident(&self.schema.table),
+            user_column = ident(&self.schema.user_column),
+            balance_column = ident(&self.schema.balance_column),
+        )
+    }
+
+    /// Query setting the balance (and watch time) of a single user, inserting
+    /// it if it doesn't exist.
+    fn replace_balance_query(&self) -> String {
+        let table = ident(&self.schema.table);
+        let user_column = ident(&self.schema.user_column);
+        let balance_column = ident(&self.schema.balance_column);
+
+        let mut columns = vec![user_column.clone(), balance_column.clone()];
+        let mut values = vec![String::from("$1"), String::from("$2::BIGINT")];
+        let mut updates = vec![format!(
+            "{column} = EXCLUDED.{column}",
+            column = balance_column
+        )];
+
+        if let Some(ref column) = self.schema.watch_time_column {
+            let column = ident(column);
+            updates.push(format!("{column} = EXCLUDED.{column}", column = column));
+            columns.push(column);
+            values.push(String::from("$3::BIGINT"));
+        }
+
+        format!(
+            "INSERT INTO {table} ({columns}) VALUES ({values}) \
+             ON CONFLICT ({user_column}) DO UPDATE SET {updates}",
+            table = table,
+            user_column = user_column,
+            columns = columns.join(", "),
+            values = values.join(", "),
+            updates = updates.join(", "),
+        )
+    }
+
+    /// Select all balances.
+    async fn select_balances(
+        &self,
+        tx: &mut postgres::Transaction<'_>,
+    ) -> Result<Vec<(String, i64, i64)>, Error> {
+        let query = self.select_balances_query();
+        log::trace!("select_balances: {}", query);
+
+        let stmt = tx.prepare(&query).await?;
+        let rows = tx.query(&stmt, &[]);
+        let rows = rows.try_collect::<Vec<_>>().await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect())
+    }
+
+    /// Select the given balance and watch time.
+    async fn select_balance(
+        &self,
+        tx: &mut postgres::Transaction<'_>,
+        user: &str,
+    ) -> Result<Option<(i64, i64)>, Error> {
+        let query = self.select_balance_query();
+        log::trace!("select_balance: {} {:?}", query, user);
+
+        let stmt = tx.prepare(&query).await?;
+        let rows = tx.query(&stmt, &[&user]);
+        let rows = rows.try_collect::<Vec<_>>().await?;
+
+        Ok(rows.into_iter().map(|row| (row.get(0), row.get(1))).next())
+    }
+
+    /// Select the top balances, excluding the given users.
+    async fn select_top_balances(
+        &self,
+        tx: &mut postgres::Transaction<'_>,
+        by: RankBy,
+        exclude: &[String],
+        limit: u32,
+    ) -> Result<Vec<(String, i64, i64)>, Error> {
+        let query = self.select_top_balances_query(by)?;
+        let limit = i64::from(limit);
+        log::trace!("select_top_balances: {} {:?} {}", query, exclude, limit);
+
+        let stmt = tx.prepare(&query).await?;
+        let rows = tx.query(&stmt, &[&exclude, &limit]);
+        let rows = rows.try_collect::<Vec<_>>().await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get(0), row.get(1), row.get(2)))
+            .collect())
+    }
+
+    /// Count the balances greater than the given value, excluding the given
+    /// users.
+    ///
+    /// Counts all balances if `value` is `None`.
+    async fn count_balances(
+        &self,
+        tx: &mut postgres::Transaction<'_>,
+        by: RankBy,
+        exclude: &[String],
+        value: Option<i64>,
+    ) -> Result<i64, Error> {
+        let query = self.count_balances_query(by, value.is_some())?;
+        log::trace!("count_balances: {} {:?} {:?}", query, exclude, value);
+
+        let stmt = tx.prepare(&query).await?;
+
+        let rows = match value {
+            Some(ref value) => tx.query(&stmt, &[&exclude, value]),
+            None => tx.query(&stmt, &[&exclude]),
+        };
+
+        let rows = rows.try_collect::<Vec<_>>().await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| row.get(0))
+            .next()
+            .unwrap_or_default())
+    }
+
+    /// Add to the balance and watch time of the given users.
+    async fn upsert_balances<I>(
+        &self,
+        tx: &mut postgres::Transaction<'_>,
+        users: I,
+        amount: i64,
+        watch_time: i64,
+    ) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let query = self.upsert_balance_query();
+        log::trace!("upsert_balances: {}", query);
+
+        let stmt = tx.prepare(&query).await?;
+
+        for user in users {
+            if self.schema.watch_time_column.is_some() {
+                tx.execute(&stmt, &[&user, &amount, &watch_time]).await?;
+            } else {
+                tx.execute(&stmt, &[&user, &amount]).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Subtract from the balance of the given user if it covers the amount.
+    ///
+    /// Returns `false` if the balance doesn't cover the amount, or if the user
+    /// has no balance.
+    async fn debit_balance(
+        &self,
+        tx: &mut postgres::Transaction<'_>,
+        user: &str,
+        amount: i64,
+    ) -> Result<bool, Error> {
+        let query = self.debit_balance_query();
+        log::trace!("debit_balance: {} {:?} {}", query, user, amount);
+
+        let stmt = tx.prepare(&query).await?;
+        Ok(tx.execute(&stmt, &[&user, &amount]).await? == 1)
+    }
+
+    /// Set the balance and watch time of the given user.
+    async fn replace_balance(
+        &self,
+        tx: &mut postgres::Transaction<'_>,
+        user: &str,
+        amount: i64,
+        watch_time: i64,
+    ) -> Result<(), Error> {
+        let query = self.replace_balance_query();
+        log::trace!("replace_balance: {} {:?}", query, user);
+
+        let stmt = tx.prepare(&query).await?;
+
+        if self.schema.watch_time_column.is_some() {
+            tx.execute(&stmt, &[&user, &amount, &watch_time]).await?;
+        } else {
+            tx.execute(&stmt, &[&user, &amount]).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A pool of connections to the database.
+struct Pool {
+    config: postgres::Config,
+    tls: MakeTlsConnector,
+    idle: Mutex<Vec<postgres::Client>>,
+}
+
+impl Pool {
+    /// Get an idle connection, or establish a new one if there are none.
+    async fn get(self: Arc<Self>) -> Result<Connection, Error> {
+        loop {
+            let client = match self.idle.lock().pop() {
+                Some(client) => client,
+                None => break,
+            };
+
+            if !client.is_closed() {
+                return Ok(Connection {
+                    pool: self,
+                    client: Some(client),
+                });
+            }
+        }
+
+        let (client, connection) = self.config.connect(self.tls.clone()).await?;
+
+        tokio::spawn(async move {
+            if let Err(e) = connection.await {
+                log_err!(e, "postgres connection failed");
+            }
+        });
+
+        Ok(Connection {
+            pool: self,
+            client: Some(client),
+        })
+    }
+}
+
+/// A connection borrowed from the pool, which is returned to it when
+/// dropped.
+struct Connection {
+    pool: Arc<Pool>,
+    client: Option<postgres::Client>,
+}
+
+impl Deref for Connection {
+    type Target = postgres::Client;
+
+    fn deref(&self) -> &Self::Target {
+        self.client.as_ref().expect("connection taken")
+    }
+}
+
+impl DerefMut for Connection {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.client.as_mut().expect("connection taken")
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let client = match self.client.take() {
+            Some(client) if !client.is_closed() => client,
+            _ => return,
+        };
+
+        let mut idle = self.pool.idle.lock();
+
+        if idle.len() < MAX_IDLE_CONNECTIONS {
+            idle.push(client);
+        }
+    }
+}
+
+pub struct Backend {
+    pool: Arc<Pool>,
+    queries: Arc<Queries>,
+}
+
+impl Backend {
+    /// Construct a new PostgreSQL backend.
+    ///
+    /// Connections are established lazily when needed, so this only
+    /// validates the url.
+    pub fn connect(url: String, schema: Schema) -> Result<Self, Error> {
+        let config = url.parse::<postgres::Config>()?;
+        let tls = MakeTlsConnector::new(native_tls::TlsConnector::new()?);
+
+        Ok(Backend {
+            pool: Arc::new(Pool {
+                config,
+                tls,
+                idle: Mutex::new(Vec::new()),
+            }),
+            queries: Arc::new(Queries { schema }),
+        })
+    }
+
+    /// Test if the backend keeps track of watch time.
+    pub fn tracks_watch_time(&self) -> bool {
+        self.queries.schema.watch_time_column.is_some()
+    }
+
+    /// Get a connection to the database from the pool.
+    async fn client(&self) -> Result<Connection, Error> {
+        self.pool.clone().get().await
+    }
+
+    /// Add (or subtract) from the balance for a single user.
+    pub async fn balance_transfer(
+        &self,
+        _channel: &str,
+        giver: &str,
+        taker: &str,
+        amount: i64,
+        override_balance: bool,
+    ) -> Result<(), BalanceTransferError> {
+        let taker = user_id(taker);
+        let giver = user_id(giver);
+
+        let mut client = self.client().await?;
+        let mut tx = client.transaction().await?;
+
+        if override_balance {
+            self.queries
+                .upsert_balances(&mut tx, vec![giver], -amount, 0)
+                .await?;
+        } else if !self.queries.debit_balance(&mut tx, &giver, amount).await? {
+            return Err(BalanceTransferError::NoBalance);
+        }
+
+        self.queries
+            .upsert_balances(&mut tx, vec![taker], amount, 0)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get balances for all users.
+    pub async fn export_balances(&self) -> Result<Vec<Balance>, Error> {
+        let mut client = self.client().await?;
+        let mut tx = client.transaction().await?;
+
+        let balances = self.queries.select_balances(&mut tx).await?;
+
+        Ok(balances
+            .into_iter()
+            .map(|(user, amount, watch_time)| Balance {
+                channel: String::new(),
+                user,
+                amount,
+                watch_time,
+            })
+            .collect())
+    }
+
+    /// Import balances for all users.
+    pub async fn import_balances(&self, balances: Vec<Balance>) -> Result<(), Error> {
+        let mut client = self.client().await?;
+        let mut tx = client.transaction().await?;
+
+        for balance in balances {
+            let user = user_id(&balance.user);
+
+            self.queries
+                .replace_balance(&mut tx, &user, balance.amount, balance.watch_time)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Find user balance.
+    pub async fn balance_of(&self, _channel: &str, user: &str) -> Result<Option<BalanceOf>, Error> {
+        let user = user_id(user);
+
+        let mut client = self.client().await?;
+        let mut tx = client.transaction().await?;
+
+        let balance = self.queries.select_balance(&mut tx, &user).await?;
+
+        Ok(balance.map(|(balance, watch_time)| BalanceOf {
+            balance,
+            watch_time,
+        }))
+    }
+
+    /// Get the top balances, excluding the given users.
+    ///
+    /// The schema doesn't have channels, so all balances are ranked.
+    pub async fn leaderboard(
+        &self,
+        _channel: Option<&str>,
+        by: RankBy,
+        exclude: Vec<String>,
+        limit: u32,
+    ) -> Result<Vec<Ranked>, Error> {
+        let mut client = self.client().await?;
+        let mut tx = client.transaction().await?;
+
+        let balances = self
+            .queries
+            .select_top_balances(&mut tx, by, &exclude, limit)
+            .await?;
+
+        let balances = balances
+            .into_iter()
+            .map(|(user, amount, watch_time)| Balance {
+                channel: String::new(),
+                user,
+                amount,
+                watch_time,
+            });
+
+        Ok(rank_balances(balances, by))
+    }
+
+    /// Get the rank of the given user, excluding the given users.
+    pub async fn rank_of(
+        &self,
+        _channel: &str,
+        by: RankBy,
+        user: &str,
+        exclude: Vec<String>,
+    ) -> Result<Option<Rank>, Error> {
+        let user = user_id(user);
+
+        let mut client = self.client().await?;
+        let mut tx = client.transaction().await?;
+
+        let value = match self.queries.select_balance(&mut tx, &user).await? {
+            Some((balance, watch_time)) => match by {
+                RankBy::Balance => balance,
+                RankBy::WatchTime => watch_time,
+            },
+            None => return Ok(None),
+        };
+
+        let ahead = self
+            .queries
+            .count_balances(&mut tx, by, &exclude, Some(value))
+            .await?;
+        let total = self
+            .queries
+            .count_balances(&mut tx, by, &exclude, None)
+            .await?;
+
+        Ok(Some(Rank {
+            rank: ahead as u64 + 1,
+            total: total as u64,
+        }))
+    }
+
+    /// Add (or subtract) from the balance for a single user.
+    pub async fn balance_add(&self, _channel: &str, user: &str, amount: i64) -> Result<(), Error> {
+        let user = user_id(user);
+
+        let mut client = self.client().await?;
+        let mut tx = client.transaction().await?;
+
+        self.queries
+            .upsert_balances(&mut tx, vec![user], amount, 0)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Add balance and watch time to users.
+    pub async fn balances_increment<I>(
+        &self,
+        _channel: &str,
+        users: I,
+        amount: i64,
+        watch_time: i64,
+    ) -> Result<(), Error>
+    where
+        I: IntoIterator<Item = String> + Send + 'static,
+        I::IntoIter: Send + 'static,
+    {
+        let mut client = self.client().await?;
+        let mut tx = client.transaction().await?;
+
+        let users = users.into_iter().map(|u| user_id(&u));
+
+        self.queries
+            .upsert_balances(&mut tx, users, amount, watch_time)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backend, Queries, Schema};
+    use crate::currency::{Balance, BalanceTransferError, RankBy};
+    use failure::Error;
+
+    fn queries(watch_time_column: Option<&str>) -> Queries {
+        Queries {
+            schema: Schema {
+                watch_time_column: watch_time_column.map(String::from),
+                ..Schema::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_upsert_balance_query() {
+        assert_eq!(
+            "INSERT INTO \"balances\" (\"user\", \"balance\") VALUES ($1, $2::BIGINT) \
+             ON CONFLICT (\"user\") DO UPDATE SET \
+             \"balance\" = \"balances\".\"balance\" + EXCLUDED.\"balance\"",
+            queries(None).upsert_balance_query()
+        );
+
+        assert_eq!(
+            "INSERT INTO \"balances\" (\"user\", \"balance\", \"watch_time\") \
+             VALUES ($1, $2::BIGINT, $3::BIGINT) \
+             ON CONFLICT (\"user\") DO UPDATE SET \
+             \"balance\" = \"balances\".\"balance\" + EXCLUDED.\"balance\", \
+             \"watch_time\" = COALESCE(\"balances\".\"watch_time\", 0) + EXCLUDED.\"watch_time\"",
+            queries(Some("watch_time")).upsert_balance_query()
+        );
+    }
+
+    #[test]
+    fn test_debit_balance_query() {
+        assert_eq!(
+            "UPDATE \"balances\" SET \"balance\" = \"balance\" - $2::BIGINT \
+             WHERE \"user\" = $1 AND \"balance\"::BIGINT >= $2::BIGINT",
+            queries(None).debit_balance_query()
+        );
+    }
+
+    #[test]
+    fn test_rank_queries() -> Result<(), Error> {
+        assert!(queries(None)
+            .select_top_balances_query(RankBy::WatchTime)
+            .is_err());
+
+        assert_eq!(
+            "SELECT COUNT(*) FROM \"balances\" \
+             WHERE COALESCE(\"wt\"::BIGINT, 0) > $2 AND NOT (\"user\" = ANY($1))",
+            queries(Some("wt")).count_balances_query(RankBy::WatchTime, true)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ident() {
+        assert_eq!("\"my \"\"table\"\"\"", super::ident("my \"table\""));
+    }
+
+    /// Test the backend against a local database.
+    ///
+    /// Run with a database url in `OXIDIZE_POSTGRES_URL`, like:
+    ///
+    /// ```bash
+    /// OXIDIZE_POSTGRES_URL=postgres://postgres@localhost/oxidize cargo test -- --ignored
+    /// ```
+    #[test]
+    #[ignore]
+    fn test_backend() -> Result<(), Error> {
+        let url = std::env::var("OXIDIZE_POSTGRES_URL")?;
+
+        let schema = Schema {
+            table: String::from("oxidize_test_balances"),
+            watch_time_column: Some(String::from("watch_time")),
+            ..Schema::default()
+        };
+
+        let backend = Backend::connect(url, schema)?;
+
+        let runtime = tokio::runtime::Runtime::new()?;
+
+        runtime.block_on(async move {
+            let mut client = backend.client().await?;
+
+            client
+                .batch_execute(
+                    "DROP TABLE IF EXISTS oxidize_test_balances; \
+                     CREATE TABLE oxidize_test_balances ( \
+                        \"user\" TEXT PRIMARY KEY, \
+                        balance INTEGER NOT NULL, \
+                        watch_time INTEGER \
+                     );",
+                )
+                .await?;
+
+            backend
+                .balances_increment("", vec![String::from("a"), String::from("b")], 10, 60)
+                .await?;
+            backend.balance_add("", "b", 5).await?;
+
+            match backend.balance_transfer("", "a", "b", 20, false).await {
+                Err(BalanceTransferError::NoBalance) => (),
+                _ => panic!("expected transfer to fail"),
+            }
+
+            backend
+                .balance_transfer("", "b", "a", 15, false)
+                .await
+                .map_err(|e| failure::format_err!("{}", e))?;
+
+            let a = backend.balance_of("", "a").await?.expect("balance of a");
+            assert_eq!((25, 60), (a.balance, a.watch_time));
+
+            let ranked = backend
+                .leaderboard(None, RankBy::Balance, vec![], 10)
+                .await?
+                .into_iter()
+                .map(|r| (r.user, r.rank))
+                .collect::<Vec<_>>();
+
+            assert_eq!(vec![(String::from("a"), 1), (String::from("b"), 2)], ranked);
+
+            backend
+                .import_balances(vec![Balance {
+                    channel: String::new(),
+                    user: String::from("c"),
+                    amount: 100,
+                    watch_time: 120,
+                }])
+                .await?;
+
+            let rank = backend
+                .rank_of("", RankBy::WatchTime, "c", vec![])
+                .await?
+                .expect("rank of c");
+            assert_eq!((1, 3), (rank.rank, rank.total));
+
+            let mut balances = backend
+                .export_balances()
+                .await?
+                .into_iter()
+                .map(|b| (b.user, b.amount, b.watch_time))
+                .collect::<Vec<_>>();
+            balances.sort();
+
+            assert_eq!(
+                vec![
+                    (String::from("a"), 25, 60),
+                    (String::from("b"), 0, 60),
+                    (String::from("c"), 100, 120),
+                ],
+                balances
+            );
+
+            client
+                .batch_execute("DROP TABLE oxidize_test_balances;")
+                .await?;
+
+            Ok(())
+        })
+    }
+}
@@ -2,11 +2,23 @@
 mod macros;
 mod after_streams;
 mod aliases;
+mod audit_log;
+mod ban_list;
+mod command_stats;
 pub(crate) mod commands;
+mod counters;
+mod ledger;
 mod matcher;
+mod messages;
 pub(crate) mod models;
 mod promotions;
+mod quotes;
+mod restrictions;
+mod schedules;
 pub(crate) mod schema;
+mod scripts;
+mod seen_users;
+mod strikes;
 mod themes;
 mod words;
 
@@ -15,12 +27,24 @@ use std::path::Path;
 
 pub use self::{
     after_streams::{AfterStream, AfterStreams},
-    aliases::{Alias, Aliases},
+    aliases::{Alias, AliasTemplate, Aliases},
+    audit_log::{AuditAction, AuditEntry, AuditFilter, AuditLog, AuditPage},
+    ban_list::{BanAction, BanEntry, BanList, BanListFormat},
+    command_stats::{CommandStats, Outcome, Report as CommandReport, MAX_REPORT_DAYS},
     commands::{Command, Commands},
+    counters::{Counter, Counters},
+    ledger::{Ledger, LedgerEntry, LedgerReason, LedgerRecord},
     matcher::Captures,
+    messages::{Message, MessageFilter, Messages},
     promotions::{Promotion, Promotions},
+    quotes::{Quote, Quotes},
+    restrictions::Restrictions,
+    schedules::{Action, Schedule, Schedules, Trigger},
+    scripts::{Script, Scripts},
+    seen_users::SeenUsers,
+    strikes::{Strike, Strikes},
     themes::{Theme, Themes},
-    words::{Word, Words},
+    words::{Action as WordAction, Word, Words},
 };
 
 pub use self::matcher::Key;
@@ -93,7 +117,13 @@ impl words::Backend for Database {
     }
 
     /// Insert a bad word into the database.
-    fn edit(&self, word: &str, why: Option<&str>) -> Result<(), Error> {
+    fn edit(
+        &self,
+        word: &str,
+        regex: bool,
+        action: &str,
+        message: Option<&str>,
+    ) -> Result<(), Error> {
         use self::schema::bad_words::dsl;
 
         let c = self.pool.lock();
@@ -105,7 +135,9 @@ impl words::Backend for Database {
             None => {
                 let bad_word = models::BadWord {
                     word: word.to_string(),
-                    why: why.map(|s| s.to_string()),
+                    regex,
+                    action: action.to_string(),
+                    message: message.map(|s| s.to_string()),
                 };
 
                 diesel::insert_into(dsl::bad_words)
@@ -114,7 +146,11 @@ impl words::Backend for Database {
             }
             Some(_) => {
                 diesel::update(filter)
-                    .set(why.map(|w| dsl::why.eq(w)))
+                    .set((
+                        dsl::regex.eq(regex),
+                        dsl::action.eq(action),
+                        dsl::message.eq(message),
+                    ))
                     .execute(&*c)?;
             }
         }
@@ -1,14 +1,19 @@
-use crate::{db, template, utils};
+use crate::{db, utils};
 use diesel::prelude::*;
 use parking_lot::RwLock;
 use std::{fmt, sync::Arc};
 
+mod template;
+
+pub use self::template::AliasTemplate;
+
 /// Local database wrapper.
 #[derive(Clone)]
 struct Database(db::Database);
 
 impl Database {
     private_database_group_fns!(aliases, Alias, db::Key);
+    private_database_restriction_fns!(aliases, db::Key);
 
     fn edit(&self, key: &db::Key, text: &str) -> Result<db::models::Alias, failure::Error> {
         use db::schema::aliases::dsl;
@@ -28,6 +33,10 @@ impl Database {
                     text: text.to_string(),
                     group: None,
                     disabled: false,
+                    cooldown: None,
+                    user_cooldown: None,
+                    scope: None,
+                    price: None,
                 };
 
                 diesel::insert_into(dsl::aliases)
@@ -73,6 +82,7 @@ pub struct Aliases {
 
 impl Aliases {
     database_group_fns!(Alias, db::Key);
+    database_restriction_fns!(db::Key);
 
     /// Construct a new commands store with a db.
     pub fn load(db: db::Database) -> Result<Aliases, failure::Error> {
@@ -92,7 +102,7 @@ impl Aliases {
     }
 
     /// Resolve the given command.
-    pub fn resolve(&self, channel: &str, message: &str) -> Option<(db::Key, String)> {
+    pub fn resolve(&self, channel: &str, message: &str) -> Option<(Arc<Alias>, String)> {
         let mut it = utils::Words::new(message);
         let first = it.next();
 
@@ -101,10 +111,8 @@ impl Aliases {
                 .read()
                 .resolve(channel, first.as_ref().map(String::as_str), &it)
         {
-            let key = alias.key.clone();
-
-            match alias.template.render_to_string(&captures) {
-                Ok(s) => return Some((key, s)),
+            match alias.template.render(captures, it.rest()) {
+                Ok(s) => return Some((alias.clone(), s)),
                 Err(e) => {
                     log::error!("failed to render alias: {}", e);
                 }
@@ -119,7 +127,7 @@ impl Aliases {
         &self,
         channel: &str,
         name: &str,
-        template: template::Template,
+        template: AliasTemplate,
     ) -> Result<(), failure::Error> {
         let key = db::Key::new(channel, name);
 
@@ -130,12 +138,20 @@ impl Aliases {
         } else {
             let pattern = db::Pattern::from_db(alias.pattern.as_ref())?;
 
+            let restrictions = db::Restrictions::from_db(
+                alias.cooldown,
+                alias.user_cooldown,
+                alias.scope.as_ref(),
+                alias.price,
+            )?;
+
             let alias = Alias {
                 key: key.clone(),
                 pattern,
                 template,
                 group: alias.group,
                 disabled: alias.disabled,
+                restrictions,
             };
 
             self.inner.write().insert(key, Arc::new(alias));
@@ -164,9 +180,11 @@ impl Aliases {
 pub struct Alias {
     pub key: db::Key,
     pub pattern: db::Pattern,
-    pub template: template::Template,
+    pub template: AliasTemplate,
     pub group: Option<String>,
     pub disabled: bool,
+    #[serde(flatten)]
+    pub restrictions: db::Restrictions,
 }
 
 impl db::Matchable for Alias {
@@ -186,7 +204,14 @@ impl Alias {
     pub fn from_db(alias: &db::models::Alias) -> Result<Alias, failure::Error> {
         let key = db::Key::new(&alias.channel, &alias.name);
         let pattern = db::Pattern::from_db(alias.pattern.as_ref())?;
-        let template = template::Template::compile(&alias.text)?;
+        let template = AliasTemplate::load(&alias.text)?;
+
+        let restrictions = db::Restrictions::from_db(
+            alias.cooldown,
+            alias.user_cooldown,
+            alias.scope.as_ref(),
+            alias.price,
+        )?;
 
         Ok(Alias {
             key,
@@ -194,6 +219,7 @@ impl Alias {
             template,
             group: alias.group.clone(),
             disabled: alias.disabled,
+            restrictions,
         })
     }
 }
@@ -202,11 +228,12 @@ impl fmt::Display for Alias {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             fmt,
-            "template = \"{template}\", pattern = {pattern}, group = {group}, disabled = {disabled}",
+            "template = \"{template}\", pattern = {pattern}, group = {group}, disabled = {disabled}, {restrictions}",
             template = self.template,
             pattern = self.pattern,
             group = self.group.as_ref().map(|g| g.as_str()).unwrap_or("*none*"),
             disabled = self.disabled,
+            restrictions = self.restrictions,
         )
     }
 }
@@ -0,0 +1,392 @@
+//! Templates for aliases.
+//!
+//! In addition to regular templates, aliases support positional arguments
+//! which are taken from the words following the alias:
+//!
+//! * `$1`, `$2`, ... - the argument at the given position.
+//! * `$*` - all arguments as they were written.
+//! * `${1:-default}`, `${*:-default}` - an argument with a default value,
+//!   used if the argument is missing.
+//! * `$$` - a literal `$`.
+
+use crate::{db, template, utils};
+use failure::{bail, format_err};
+use std::fmt;
+
+/// Prefix of the template variables that positional arguments are
+/// translated into.
+const ARG: &str = "alias_arg";
+/// Template variable that all arguments are translated into.
+const ARGS: &str = "alias_args";
+
+#[derive(Debug, Clone)]
+pub struct AliasTemplate {
+    /// The source of the template, as written.
+    source: String,
+    /// The template with all positional arguments translated into variables.
+    template: template::Template,
+}
+
+impl AliasTemplate {
+    /// Compile an alias template.
+    ///
+    /// Errors if any positional arguments are malformed, or if the template is
+    /// not a valid template after they've been translated.
+    pub fn compile(source: &str) -> Result<AliasTemplate, failure::Error> {
+        let translated = translate(source, true)?;
+
+        Ok(AliasTemplate {
+            source: source.to_string(),
+            template: template::Template::compile(&translated)?,
+        })
+    }
+
+    /// Compile a stored alias template.
+    ///
+    /// Aliases stored before positional arguments were supported might
+    /// contain text like `$0` or `${`, so malformed positional arguments are
+    /// kept as literal text instead of being errors.
+    pub fn load(source: &str) -> Result<AliasTemplate, failure::Error> {
+        let translated = translate(source, false)?;
+
+        Ok(AliasTemplate {
+            source: source.to_string(),
+            template: template::Template::compile(&translated)?,
+        })
+    }
+
+    /// Access the source of the template.
+    pub fn source(&self) -> &str {
+        self.source.as_str()
+    }
+
+    /// Render the alias with the given captures, and the remaining words of
+    /// the message as positional arguments.
+    pub fn render(&self, captures: db::Captures<'_>, rest: &str) -> Result<String, failure::Error> {
+        self.template.render_to_string(&Vars {
+            captures,
+            args: Args(rest),
+        })
+    }
+}
+
+impl std::str::FromStr for AliasTemplate {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::compile(s)
+    }
+}
+
+impl fmt::Display for AliasTemplate {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(fmt)
+    }
+}
+
+impl serde::Serialize for AliasTemplate {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.source.serialize(serializer)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for AliasTemplate {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        AliasTemplate::compile(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Vars<'a> {
+    #[serde(flatten)]
+    captures: db::Captures<'a>,
+    #[serde(flatten)]
+    args: Args<'a>,
+}
+
+/// Positional arguments, serialized into the variables they were translated
+/// into.
+struct Args<'a>(&'a str);
+
+impl serde::Serialize for Args<'_> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap as _;
+
+        let mut m = serializer.serialize_map(None)?;
+
+        let all = self.0.trim();
+
+        if !all.is_empty() {
+            m.serialize_entry(ARGS, all)?;
+        }
+
+        for (i, word) in utils::Words::new(self.0).enumerate() {
+            m.serialize_entry(&format!("{}{}", ARG, i + 1), &quote(&word))?;
+        }
+
+        m.end()
+    }
+}
+
+/// A single positional argument.
+enum Arg {
+    /// The argument at the given position, starting at 1.
+    At(usize),
+    /// All arguments.
+    All,
+}
+
+impl Arg {
+    /// Parse an argument, like `1` or `*`.
+    fn parse(s: &str) -> Result<Arg, failure::Error> {
+        if s == "*" {
+            return Ok(Arg::All);
+        }
+
+        let n = str::parse::<usize>(s)
+            .map_err(|_| format_err!("bad argument `{}`, expected a number or `*`", s))?;
+
+        if n == 0 {
+            bail!("arguments are numbered from 1, like `$1`");
+        }
+
+        Ok(Arg::At(n))
+    }
+
+    /// The template variable for the argument.
+    fn var(&self) -> String {
+        match *self {
+            Arg::At(n) => format!("{}{}", ARG, n),
+            Arg::All => ARGS.to_string(),
+        }
+    }
+}
+
+/// A positional argument parsed from the text following a `$`.
+struct Parsed<'a> {
+    arg: Arg,
+    default: Option<&'a str>,
+    /// Length of the text the argument was parsed from.
+    len: usize,
+}
+
+/// Parse the positional argument at the start of the text following a `$`.
+///
+/// Returns `None` if the `$` isn't followed by an argument.
+fn parse_arg(rest: &str) -> Result<Option<Parsed<'_>>, failure::Error> {
+    if rest.starts_with('*') {
+        return Ok(Some(Parsed {
+            arg: Arg::All,
+            default: None,
+            len: 1,
+        }));
+    }
+
+    if rest.starts_with('{') {
+        let end = match rest.find('}') {
+            Some(end) => end,
+            None => bail!("missing closing `}}` for argument `${}`", rest),
+        };
+
+        let inner = &rest[1..end];
+
+        let (arg, default) = match inner.find(":-") {
+            Some(i) => (Arg::parse(&inner[..i])?, Some(&inner[i + 2..])),
+            None => (Arg::parse(inner)?, None),
+        };
+
+        return Ok(Some(Parsed {
+            arg,
+            default,
+            len: end + 1,
+        }));
+    }
+
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+
+    if end == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(Parsed {
+        arg: Arg::parse(&rest[..end])?,
+        default: None,
+        len: end,
+    }))
+}
+
+/// Translate all positional arguments in the source into template variables.
+///
+/// If `strict` is set, malformed arguments are errors. Otherwise they are
+/// kept as literal text.
+fn translate(source: &str, strict: bool) -> Result<String, failure::Error> {
+    let mut out = String::with_capacity(source.len());
+    let mut rest = source;
+
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+
+        if rest.starts_with('$') {
+            out.push('$');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let parsed = match parse_arg(rest) {
+            Ok(Some(parsed)) => parsed,
+            // NB: a `$` which is not followed by an argument is kept as-is.
+            Ok(None) => {
+                out.push('$');
+                continue;
+            }
+            Err(e) if strict => return Err(e),
+            Err(..) => {
+                out.push('$');
+                continue;
+            }
+        };
+
+        rest = &rest[parsed.len..];
+        let default = parsed.default;
+        let var = parsed.arg.var();
+
+        match default {
+            Some(default) => {
+                out.push_str(&format!(
+                    "{{{{#if {var}}}}}{{{{{var}}}}}{{{{else}}}}{default}{{{{/if}}}}",
+                    var = var,
+                    default = default
+                ));
+            }
+            None => {
+                out.push_str(&format!("{{{{{}}}}}", var));
+            }
+        }
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Quote a single argument so that it is kept as one argument when the
+/// expanded alias is split into words again.
+fn quote(word: &str) -> String {
+    let needs_quotes = word.is_empty()
+        || word
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\\');
+
+    if !needs_quotes {
+        return word.to_string();
+    }
+
+    let mut out = String::with_capacity(word.len() + 2);
+    out.push('"');
+
+    for c in word.chars() {
+        match c {
+            '"' | '\\' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{translate, AliasTemplate};
+    use crate::db;
+
+    fn translate_strict(source: &str) -> Result<String, failure::Error> {
+        translate(source, true)
+    }
+
+    #[test]
+    fn test_translate() -> Result<(), failure::Error> {
+        assert_eq!(
+            "!song request {{alias_arg1}}",
+            translate_strict("!song request $1")?
+        );
+        assert_eq!(
+            "!foo {{alias_arg2}} {{alias_arg1}}",
+            translate_strict("!foo $2 $1")?
+        );
+        assert_eq!("!foo {{alias_args}}", translate_strict("!foo $*")?);
+        assert_eq!(
+            "!foo {{#if alias_arg1}}{{alias_arg1}}{{else}}bar baz{{/if}}",
+            translate_strict("!foo ${1:-bar baz}")?
+        );
+        assert_eq!("costs $5, $ or $x", translate_strict("costs $$5, $ or $x")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_translate_errors() {
+        assert!(translate_strict("!foo $0").is_err());
+        assert!(translate_strict("!foo ${1").is_err());
+        assert!(translate_strict("!foo ${bar}").is_err());
+        assert!(translate_strict("!foo ${:-bar}").is_err());
+    }
+
+    #[test]
+    fn test_translate_legacy() -> Result<(), failure::Error> {
+        assert_eq!("!foo $0", translate("!foo $0", false)?);
+        assert_eq!("!foo ${1", translate("!foo ${1", false)?);
+        assert_eq!("!foo ${bar} baz", translate("!foo ${bar} baz", false)?);
+        assert_eq!("!foo $0 {{alias_arg1}}", translate("!foo $0 $1", false)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_legacy() -> Result<(), failure::Error> {
+        let captures = || db::Captures::Prefix { rest: "" };
+
+        assert!(AliasTemplate::compile("!say costs $0 or ${").is_err());
+
+        let template = AliasTemplate::load("!say costs $0 or ${")?;
+        assert_eq!("!say costs $0 or ${", template.render(captures(), "")?);
+        assert_eq!("!say costs $0 or ${", template.to_string());
+        Ok(())
+    }
+
+    #[test]
+    fn test_render() -> Result<(), failure::Error> {
+        let template = AliasTemplate::compile("!song request spotify ${*:-never gonna}")?;
+        let captures = || db::Captures::Prefix { rest: "" };
+
+        assert_eq!(
+            "!song request spotify never gonna",
+            template.render(captures(), "")?
+        );
+        assert_eq!(
+            "!song request spotify \"give you\" up",
+            template.render(captures(), " \"give you\" up")?
+        );
+
+        let template = AliasTemplate::compile("!foo $2 ${1:-bar}")?;
+        assert_eq!(
+            "!foo baz \"a b\"",
+            template.render(captures(), "\"a b\" baz")?
+        );
+        assert_eq!("!foo  bar", template.render(captures(), "")?);
+        Ok(())
+    }
+}
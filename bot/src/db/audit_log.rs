@@ -0,0 +1,164 @@
+use crate::db::{self, models, schema};
+use diesel::{prelude::*, sqlite::Sqlite};
+use std::fmt;
+
+pub use self::models::AuditEntry;
+
+/// An action recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AuditAction {
+    /// A chat message was deleted.
+    DeleteMessage,
+    /// A user was timed out.
+    Timeout,
+    /// A user was banned.
+    Ban,
+    /// The list of moderators was refreshed.
+    RefreshMods,
+    /// The list of VIPs was refreshed.
+    RefreshVips,
+    /// A scope was temporarily granted through `!auth permit`, or links
+    /// through `!permit`.
+    Permit,
+    /// A setting was changed.
+    SettingChanged,
+    /// A group of commands, aliases, promotions and themes was enabled.
+    GroupEnabled,
+    /// A group of commands, aliases, promotions and themes was disabled.
+    GroupDisabled,
+    /// The balance of one or more users was changed.
+    BalanceChanged,
+}
+
+impl AuditAction {
+    /// Get the action as it is stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AuditAction::DeleteMessage => "delete-message",
+            AuditAction::Timeout => "timeout",
+            AuditAction::Ban => "ban",
+            AuditAction::RefreshMods => "refresh-mods",
+            AuditAction::RefreshVips => "refresh-vips",
+            AuditAction::Permit => "permit",
+            AuditAction::SettingChanged => "setting-changed",
+            AuditAction::GroupEnabled => "group-enabled",
+            AuditAction::GroupDisabled => "group-disabled",
+            AuditAction::BalanceChanged => "balance-changed",
+        }
+    }
+}
+
+impl fmt::Display for AuditAction {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(fmt)
+    }
+}
+
+/// Filter applied when listing the audit log.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter<'a> {
+    /// Only include actions taken by the given user.
+    pub actor: Option<&'a str>,
+    /// Only include actions of the given kind.
+    pub action: Option<AuditAction>,
+    /// Only include actions taken against the given target.
+    pub target: Option<&'a str>,
+}
+
+/// A single page of audit log entries.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct AuditPage {
+    /// The entries on the page, most recent first.
+    pub entries: Vec<AuditEntry>,
+    /// The total number of entries matching the filter.
+    pub total: i64,
+}
+
+#[derive(Clone)]
+pub struct AuditLog {
+    db: db::Database,
+}
+
+impl AuditLog {
+    /// Open the audit log database.
+    pub fn load(db: db::Database) -> Result<Self, failure::Error> {
+        Ok(AuditLog { db })
+    }
+
+    /// Append an entry to the audit log.
+    ///
+    /// An `actor` of `None` means that the action was taken by the bot.
+    pub fn record(
+        &self,
+        channel: &str,
+        actor: Option<&str>,
+        action: AuditAction,
+        target: Option<&str>,
+        reason: Option<&str>,
+    ) -> Result<(), failure::Error> {
+        use self::schema::audit_log::dsl;
+        let c = self.db.pool.lock();
+
+        let entry = models::InsertAuditEntry {
+            channel,
+            actor,
+            action: action.as_str(),
+            target,
+            reason,
+        };
+
+        diesel::insert_into(dsl::audit_log)
+            .values(&entry)
+            .execute(&*c)?;
+
+        Ok(())
+    }
+
+    /// List entries in the given channel matching the filter, most recent
+    /// first.
+    pub fn list(
+        &self,
+        channel: &str,
+        filter: &AuditFilter<'_>,
+        offset: i64,
+        limit: i64,
+    ) -> Result<AuditPage, failure::Error> {
+        use self::schema::audit_log::dsl;
+        let c = self.db.pool.lock();
+
+        let total = filtered(channel, filter).count().get_result(&*c)?;
+
+        let entries = filtered(channel, filter)
+            .order((dsl::logged_at.desc(), dsl::id.desc()))
+            .offset(offset)
+            .limit(limit)
+            .load::<models::AuditEntry>(&*c)?;
+
+        Ok(AuditPage { entries, total })
+    }
+}
+
+/// Build a query for all entries in the channel matching the filter.
+fn filtered<'a>(
+    channel: &'a str,
+    filter: &AuditFilter<'a>,
+) -> schema::audit_log::BoxedQuery<'a, Sqlite> {
+    use self::schema::audit_log::dsl;
+
+    let mut query = dsl::audit_log.filter(dsl::channel.eq(channel)).into_boxed();
+
+    if let Some(actor) = filter.actor {
+        query = query.filter(dsl::actor.eq(actor));
+    }
+
+    if let Some(action) = filter.action {
+        query = query.filter(dsl::action.eq(action.as_str()));
+    }
+
+    if let Some(target) = filter.target {
+        query = query.filter(dsl::target.eq(target));
+    }
+
+    query
+}
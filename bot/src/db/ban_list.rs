@@ -0,0 +1,474 @@
+//! A ban list shared between channels.
+//!
+//! Entries are either added locally, or synchronized from subscribed lists.
+//! Entries from a subscribed list are tagged with their source, so that they
+//! can be replaced when the list is read again without touching entries
+//! which were added locally.
+
+use crate::{
+    db::{self, models, schema},
+    utils,
+};
+use diesel::prelude::*;
+use failure::{format_err, Error};
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use std::{fmt, sync::Arc};
+
+/// The action to take against a user on the ban list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BanAction {
+    /// Ban the user.
+    Ban,
+    /// Time out the user for the given duration.
+    Timeout(utils::Duration),
+}
+
+impl Default for BanAction {
+    fn default() -> Self {
+        BanAction::Ban
+    }
+}
+
+impl std::str::FromStr for BanAction {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = s.trim().split_whitespace();
+
+        let action = match it.next() {
+            Some("ban") => BanAction::Ban,
+            Some("timeout") => match it.next() {
+                Some(duration) => BanAction::Timeout(str::parse(duration)?),
+                None => failure::bail!("expected `timeout <duration>`"),
+            },
+            _ => failure::bail!("expected one of: `ban`, or `timeout <duration>`"),
+        };
+
+        if it.next().is_some() {
+            failure::bail!("unexpected trailing arguments to action");
+        }
+
+        Ok(action)
+    }
+}
+
+impl fmt::Display for BanAction {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            BanAction::Ban => "ban".fmt(fmt),
+            BanAction::Timeout(ref duration) => write!(fmt, "timeout {}", duration),
+        }
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for BanAction {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        str::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+impl serde::Serialize for BanAction {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+/// A single user on the ban list.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct BanEntry {
+    pub user: String,
+    #[serde(default)]
+    pub action: BanAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl BanEntry {
+    /// Convert from a database model.
+    fn from_model(entry: models::BanListEntry) -> BanEntry {
+        let action = match str::parse(&entry.action) {
+            Ok(action) => action,
+            Err(e) => {
+                log::warn!("bad action for banned user `{}`: {}", entry.user, e);
+                BanAction::default()
+            }
+        };
+
+        BanEntry {
+            user: entry.user,
+            action,
+            reason: entry.reason,
+        }
+    }
+}
+
+/// The format of an imported or exported ban list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BanListFormat {
+    /// A JSON array of entries, or of plain user names.
+    Json,
+    /// Lines of `user,action,reason`, where action and reason are optional.
+    Csv,
+}
+
+impl BanListFormat {
+    /// Guess the format of the list at the given path or URL.
+    pub fn from_path(path: &str) -> BanListFormat {
+        if path.to_lowercase().ends_with(".csv") {
+            BanListFormat::Csv
+        } else {
+            BanListFormat::Json
+        }
+    }
+
+    /// Parse a ban list.
+    pub fn parse(self, data: &str) -> Result<Vec<BanEntry>, Error> {
+        let entries = match self {
+            BanListFormat::Json => parse_json(data)?,
+            BanListFormat::Csv => parse_csv(data)?,
+        };
+
+        Ok(entries
+            .into_iter()
+            .map(|e| BanEntry {
+                user: db::user_id(e.user.trim()),
+                ..e
+            })
+            .filter(|e| !e.user.is_empty())
+            .collect())
+    }
+
+    /// Serialize a ban list.
+    pub fn serialize(self, entries: &[BanEntry]) -> Result<String, Error> {
+        match self {
+            BanListFormat::Json => Ok(serde_json::to_string_pretty(entries)?),
+            BanListFormat::Csv => {
+                let mut out = String::from("user,action,reason\n");
+
+                for e in entries {
+                    out.push_str(&e.user);
+                    out.push(',');
+                    out.push_str(&e.action.to_string());
+
+                    if let Some(reason) = e.reason.as_ref() {
+                        out.push(',');
+                        out.push_str(&reason.replace(|c| c == '\r' || c == '\n', " "));
+                    }
+
+                    out.push('\n');
+                }
+
+                Ok(out)
+            }
+        }
+    }
+}
+
+/// Parse a JSON ban list.
+fn parse_json(data: &str) -> Result<Vec<BanEntry>, Error> {
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum Item {
+        User(String),
+        Entry(BanEntry),
+    }
+
+    let items = serde_json::from_str::<Vec<Item>>(data)?;
+
+    Ok(items
+        .into_iter()
+        .map(|item| match item {
+            Item::User(user) => BanEntry {
+                user,
+                action: BanAction::default(),
+                reason: None,
+            },
+            Item::Entry(entry) => entry,
+        })
+        .collect())
+}
+
+/// Parse a CSV ban list.
+///
+/// Empty lines, lines starting with `#`, and a leading `user,...` header are
+/// ignored.
+fn parse_csv(data: &str) -> Result<Vec<BanEntry>, Error> {
+    let mut entries = Vec::new();
+    let mut first = true;
+
+    for (n, line) in data.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let mut it = line.splitn(3, ',').map(str::trim);
+        let user = it.next().unwrap_or_default();
+
+        if std::mem::replace(&mut first, false) && user.eq_ignore_ascii_case("user") {
+            continue;
+        }
+
+        let action = match it.next().filter(|s| !s.is_empty()) {
+            Some(action) => str::parse(action).map_err(|e| format_err!("line {}: {}", n + 1, e))?,
+            None => BanAction::default(),
+        };
+
+        let reason = it
+            .next()
+            .map(|s| s.trim_matches('"'))
+            .filter(|s| !s.is_empty())
+            .map(String::from);
+
+        entries.push(BanEntry {
+            user: user.to_string(),
+            action,
+            reason,
+        });
+    }
+
+    Ok(entries)
+}
+
+#[derive(Clone)]
+pub struct BanList {
+    inner: Arc<RwLock<HashMap<String, BanEntry>>>,
+    db: db::Database,
+}
+
+impl BanList {
+    /// Load the ban list from the database.
+    pub fn load(db: db::Database) -> Result<BanList, Error> {
+        let list = BanList {
+            inner: Default::default(),
+            db,
+        };
+
+        list.reload()?;
+        Ok(list)
+    }
+
+    /// Get the entry for the given user, if they are on the ban list.
+    pub fn get(&self, user: &str) -> Option<BanEntry> {
+        self.inner.read().get(user).cloned()
+    }
+
+    /// List all entries, ordered by user.
+    pub fn list(&self) -> Vec<BanEntry> {
+        let mut entries = self.inner.read().values().cloned().collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.user.cmp(&b.user));
+        entries
+    }
+
+    /// Add or replace the given entries.
+    pub fn insert(&self, entries: &[BanEntry]) -> Result<(), Error> {
+        use self::schema::ban_list::dsl;
+
+        {
+            let c = self.db.pool.lock();
+
+            c.transaction::<_, Error, _>(|| {
+                for e in entries {
+                    let action = e.action.to_string();
+
+                    let entry = models::InsertBanListEntry {
+                        user: &e.user,
+                        action: &action,
+                        reason: e.reason.as_ref().map(String::as_str),
+                        source: None,
+                    };
+
+                    diesel::replace_into(dsl::ban_list)
+                        .values(&entry)
+                        .execute(&*c)?;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        self.reload()
+    }
+
+    /// Remove a user from the ban list.
+    pub fn delete(&self, user: &str) -> Result<bool, Error> {
+        use self::schema::ban_list::dsl;
+
+        let count = {
+            let c = self.db.pool.lock();
+            diesel::delete(dsl::ban_list.filter(dsl::user.eq(user))).execute(&*c)?
+        };
+
+        self.inner.write().remove(user);
+        Ok(count == 1)
+    }
+
+    /// Replace all entries which came from the given source.
+    ///
+    /// Users which are already on the list are kept as they are.
+    pub fn sync(&self, source: &str, entries: &[BanEntry]) -> Result<(), Error> {
+        use self::schema::ban_list::dsl;
+
+        {
+            let c = self.db.pool.lock();
+
+            c.transaction::<_, Error, _>(|| {
+                diesel::delete(dsl::ban_list.filter(dsl::source.eq(source))).execute(&*c)?;
+
+                for e in entries {
+                    let action = e.action.to_string();
+
+                    let entry = models::InsertBanListEntry {
+                        user: &e.user,
+                        action: &action,
+                        reason: e.reason.as_ref().map(String::as_str),
+                        source: Some(source),
+                    };
+
+                    diesel::insert_or_ignore_into(dsl::ban_list)
+                        .values(&entry)
+                        .execute(&*c)?;
+                }
+
+                Ok(())
+            })?;
+        }
+
+        self.reload()
+    }
+
+    /// Remove all entries from sources which are no longer subscribed to.
+    pub fn retain_sources(&self, sources: &[String]) -> Result<(), Error> {
+        use self::schema::ban_list::dsl;
+
+        let count = {
+            let c = self.db.pool.lock();
+
+            diesel::delete(
+                dsl::ban_list.filter(dsl::source.is_not_null().and(dsl::source.ne_all(sources))),
+            )
+            .execute(&*c)?
+        };
+
+        if count > 0 {
+            self.reload()?;
+        }
+
+        Ok(())
+    }
+
+    /// Reload the in-memory list from the database.
+    fn reload(&self) -> Result<(), Error> {
+        use self::schema::ban_list::dsl;
+
+        let entries = {
+            let c = self.db.pool.lock();
+            dsl::ban_list.load::<models::BanListEntry>(&*c)?
+        };
+
+        let entries = entries
+            .into_iter()
+            .map(BanEntry::from_model)
+            .map(|e| (e.user.clone(), e))
+            .collect();
+
+        *self.inner.write() = entries;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BanAction, BanEntry, BanListFormat};
+    use crate::utils::Duration;
+
+    #[test]
+    fn test_parse_csv() {
+        let data = "user,action,reason\n\
+                    # comment\n\
+                    \n\
+                    @Spammer\n\
+                    troll, timeout 10m\n\
+                    bot,ban,follow bot, part of a raid\n";
+
+        let entries = BanListFormat::Csv.parse(data).unwrap();
+
+        assert_eq!(
+            vec![
+                BanEntry {
+                    user: String::from("spammer"),
+                    action: BanAction::Ban,
+                    reason: None,
+                },
+                BanEntry {
+                    user: String::from("troll"),
+                    action: BanAction::Timeout(Duration::seconds(600)),
+                    reason: None,
+                },
+                BanEntry {
+                    user: String::from("bot"),
+                    action: BanAction::Ban,
+                    reason: Some(String::from("follow bot, part of a raid")),
+                },
+            ],
+            entries
+        );
+
+        assert!(BanListFormat::Csv.parse("troll,kick\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_json() {
+        let data =
+            r#"["Spammer", {"user": "troll", "action": "timeout 10m", "reason": "trolling"}]"#;
+        let entries = BanListFormat::Json.parse(data).unwrap();
+
+        assert_eq!(
+            vec![
+                BanEntry {
+                    user: String::from("spammer"),
+                    action: BanAction::Ban,
+                    reason: None,
+                },
+                BanEntry {
+                    user: String::from("troll"),
+                    action: BanAction::Timeout(Duration::seconds(600)),
+                    reason: Some(String::from("trolling")),
+                },
+            ],
+            entries
+        );
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let entries = vec![
+            BanEntry {
+                user: String::from("spammer"),
+                action: BanAction::Ban,
+                reason: Some(String::from("spam,\nmore spam")),
+            },
+            BanEntry {
+                user: String::from("troll"),
+                action: BanAction::Timeout(Duration::seconds(600)),
+                reason: None,
+            },
+        ];
+
+        for format in &[BanListFormat::Json, BanListFormat::Csv] {
+            let data = format.serialize(&entries).unwrap();
+            let parsed = format.parse(&data).unwrap();
+            assert_eq!(entries.len(), parsed.len());
+            assert_eq!(entries[1], parsed[1]);
+        }
+    }
+}
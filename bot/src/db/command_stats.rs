@@ -0,0 +1,344 @@
+use crate::db::{self, models, schema};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::{
+    prelude::*,
+    sql_types::{Text, Timestamp},
+};
+use std::{collections::BTreeMap, fmt};
+
+/// The largest number of days a report can cover.
+pub const MAX_REPORT_DAYS: u32 = 3650;
+
+/// Query used to count invocations for a report.
+///
+/// Invocations are aggregated in the database, so that reports over long
+/// periods don't load every invocation.
+const REPORT_QUERY: &str = "SELECT \
+     date(invoked_at) AS day, stream_started_at, command, outcome, COUNT(*) AS count \
+     FROM command_invocations \
+     WHERE channel = ?1 AND invoked_at >= ?2 \
+     GROUP BY date(invoked_at), stream_started_at, command, outcome";
+
+/// The outcome of a single command invocation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    /// The command ran successfully.
+    Ok,
+    /// The user was not permitted to run the command.
+    Denied,
+    /// The command was in cooldown.
+    Cooldown,
+    /// The command failed to run.
+    Error,
+}
+
+impl Outcome {
+    /// Get the outcome as it is stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::Denied => "denied",
+            Outcome::Cooldown => "cooldown",
+            Outcome::Error => "error",
+        }
+    }
+
+    /// Parse an outcome as it is stored in the database.
+    pub fn parse(s: &str) -> Option<Outcome> {
+        match s {
+            "ok" => Some(Outcome::Ok),
+            "denied" => Some(Outcome::Denied),
+            "cooldown" => Some(Outcome::Cooldown),
+            "error" => Some(Outcome::Error),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(fmt)
+    }
+}
+
+#[derive(Clone)]
+pub struct CommandStats {
+    db: db::Database,
+}
+
+impl CommandStats {
+    /// Open the command stats database.
+    pub fn load(db: db::Database) -> Result<Self, failure::Error> {
+        Ok(CommandStats { db })
+    }
+
+    /// Record a single command invocation.
+    pub fn record(
+        &self,
+        channel: &str,
+        command: &str,
+        user: Option<&str>,
+        outcome: Outcome,
+        stream_started_at: Option<NaiveDateTime>,
+    ) -> Result<(), failure::Error> {
+        use self::schema::command_invocations::dsl;
+        let c = self.db.pool.lock();
+
+        let invocation = models::InsertCommandInvocation {
+            channel,
+            command,
+            user,
+            outcome: outcome.as_str(),
+            stream_started_at,
+        };
+
+        diesel::insert_into(dsl::command_invocations)
+            .values(&invocation)
+            .execute(&*c)?;
+
+        Ok(())
+    }
+
+    /// Remove all invocations before the given point in time, returning the
+    /// number of invocations removed.
+    pub fn prune(&self, before: NaiveDateTime) -> Result<usize, failure::Error> {
+        use self::schema::command_invocations::dsl;
+        let c = self.db.pool.lock();
+
+        let count = diesel::delete(dsl::command_invocations.filter(dsl::invoked_at.lt(before)))
+            .execute(&*c)?;
+        Ok(count)
+    }
+
+    /// Build a report of all invocations in the given channel since the given
+    /// point in time.
+    pub fn report(&self, channel: &str, since: NaiveDateTime) -> Result<Report, failure::Error> {
+        let c = self.db.pool.lock();
+
+        let counts = diesel::sql_query(REPORT_QUERY)
+            .bind::<Text, _>(channel)
+            .bind::<Timestamp, _>(since)
+            .load::<models::CommandInvocationCount>(&*c)?;
+
+        Ok(Report::build(counts))
+    }
+}
+
+/// Number of invocations by outcome.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Counts {
+    pub total: u64,
+    pub ok: u64,
+    pub denied: u64,
+    pub cooldown: u64,
+    pub error: u64,
+}
+
+impl Counts {
+    /// Count a number of invocations with the given outcome.
+    fn add(&mut self, outcome: Outcome, count: u64) {
+        self.total += count;
+
+        match outcome {
+            Outcome::Ok => self.ok += count,
+            Outcome::Denied => self.denied += count,
+            Outcome::Cooldown => self.cooldown += count,
+            Outcome::Error => self.error += count,
+        }
+    }
+}
+
+/// Usage of a single command.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Usage {
+    pub command: String,
+    #[serde(flatten)]
+    pub counts: Counts,
+}
+
+/// Usage aggregated over a single day.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Day {
+    /// The day, in UTC.
+    pub day: NaiveDate,
+    pub counts: Counts,
+    pub commands: Vec<Usage>,
+}
+
+/// Usage aggregated over a single stream session.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Session {
+    /// When the stream started, or `None` for invocations while offline.
+    pub started_at: Option<NaiveDateTime>,
+    pub counts: Counts,
+    pub commands: Vec<Usage>,
+}
+
+/// Aggregated command usage.
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct Report {
+    /// Usage over the whole period covered by the report.
+    pub counts: Counts,
+    /// Usage by command over the whole period, most used first.
+    pub commands: Vec<Usage>,
+    /// Usage by day, in chronological order.
+    pub days: Vec<Day>,
+    /// Usage by stream session, in chronological order.
+    pub sessions: Vec<Session>,
+}
+
+impl Report {
+    /// Aggregate the given invocation counts into a report.
+    fn build(counts: impl IntoIterator<Item = models::CommandInvocationCount>) -> Report {
+        let mut total = Counts::default();
+        let mut commands = Aggregate::default();
+        let mut days = BTreeMap::<_, (Counts, Aggregate)>::new();
+        let mut sessions = BTreeMap::<_, (Counts, Aggregate)>::new();
+
+        for invocation in counts {
+            let outcome = match Outcome::parse(&invocation.outcome) {
+                Some(outcome) => outcome,
+                None => {
+                    log::warn!("ignoring unsupported outcome: {}", invocation.outcome);
+                    continue;
+                }
+            };
+
+            let count = invocation.count.max(0) as u64;
+
+            total.add(outcome, count);
+            commands.add(&invocation.command, outcome, count);
+
+            let day = days.entry(invocation.day).or_default();
+            day.0.add(outcome, count);
+            day.1.add(&invocation.command, outcome, count);
+
+            let session = sessions.entry(invocation.stream_started_at).or_default();
+            session.0.add(outcome, count);
+            session.1.add(&invocation.command, outcome, count);
+        }
+
+        Report {
+            counts: total,
+            commands: commands.into_usage(),
+            days: days
+                .into_iter()
+                .map(|(day, (counts, commands))| Day {
+                    day,
+                    counts,
+                    commands: commands.into_usage(),
+                })
+                .collect(),
+            sessions: sessions
+                .into_iter()
+                .map(|(started_at, (counts, commands))| Session {
+                    started_at,
+                    counts,
+                    commands: commands.into_usage(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Counts by command.
+#[derive(Default)]
+struct Aggregate(BTreeMap<String, Counts>);
+
+impl Aggregate {
+    /// Count a number of invocations of the given command.
+    fn add(&mut self, command: &str, outcome: Outcome, count: u64) {
+        self.0
+            .entry(command.to_string())
+            .or_default()
+            .add(outcome, count);
+    }
+
+    /// Convert into usage, with the most used commands first.
+    fn into_usage(self) -> Vec<Usage> {
+        let mut usage = self
+            .0
+            .into_iter()
+            .map(|(command, counts)| Usage { command, counts })
+            .collect::<Vec<_>>();
+
+        // NB: sort is stable, so ties stay ordered by name.
+        usage.sort_by(|a, b| b.counts.total.cmp(&a.counts.total));
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandStats, Outcome, Report};
+    use crate::db::{self, models::CommandInvocationCount};
+    use chrono::{Duration, NaiveDate, Utc};
+    use std::path::Path;
+
+    fn count(
+        command: &str,
+        outcome: Outcome,
+        day: u32,
+        stream: Option<u32>,
+        count: i64,
+    ) -> CommandInvocationCount {
+        CommandInvocationCount {
+            day: NaiveDate::from_ymd(2019, 10, day),
+            stream_started_at: stream
+                .map(|day| NaiveDate::from_ymd(2019, 10, day).and_hms(10, 0, 0)),
+            command: String::from(command),
+            outcome: outcome.as_str().to_string(),
+            count,
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let report = Report::build(vec![
+            count("song", Outcome::Ok, 1, Some(1), 2),
+            count("8ball", Outcome::Cooldown, 1, Some(1), 1),
+            count("song", Outcome::Denied, 2, None, 1),
+            count("song", Outcome::Error, 3, Some(3), 1),
+        ]);
+
+        assert_eq!(5, report.counts.total);
+        assert_eq!(1, report.counts.cooldown);
+        assert_eq!("song", report.commands[0].command);
+        assert_eq!(4, report.commands[0].counts.total);
+        assert_eq!("8ball", report.commands[1].command);
+
+        assert_eq!(3, report.days.len());
+        assert_eq!(3, report.days[0].counts.total);
+
+        assert_eq!(3, report.sessions.len());
+        assert_eq!(None, report.sessions[0].started_at);
+        assert_eq!(1, report.sessions[0].counts.denied);
+        assert_eq!(1, report.sessions[2].counts.error);
+    }
+
+    #[test]
+    fn test_report_query() -> Result<(), failure::Error> {
+        let stats = CommandStats::load(db::Database::open(Path::new(":memory:"))?)?;
+        let started_at = Some(Utc::now().naive_utc() - Duration::hours(1));
+
+        stats.record("#setbac", "song", Some("a"), Outcome::Ok, started_at)?;
+        stats.record("#setbac", "song", Some("b"), Outcome::Ok, started_at)?;
+        stats.record("#setbac", "song", Some("a"), Outcome::Denied, None)?;
+        stats.record("#other", "song", Some("a"), Outcome::Ok, None)?;
+
+        let report = stats.report("#setbac", Utc::now().naive_utc() - Duration::days(1))?;
+
+        assert_eq!(3, report.counts.total);
+        assert_eq!(2, report.counts.ok);
+        assert_eq!(1, report.counts.denied);
+        assert_eq!(2, report.sessions.len());
+
+        assert_eq!(0, stats.prune(Utc::now().naive_utc() - Duration::days(1))?);
+        assert_eq!(4, stats.prune(Utc::now().naive_utc() + Duration::days(1))?);
+
+        let report = stats.report("#setbac", Utc::now().naive_utc() - Duration::days(1))?;
+        assert_eq!(0, report.counts.total);
+        Ok(())
+    }
+}
@@ -17,6 +17,7 @@ struct Database(db::Database);
 
 impl Database {
     private_database_group_fns!(commands, Command, db::Key);
+    private_database_restriction_fns!(commands, db::Key);
 
     /// Edit the text for the given key.
     fn edit(&self, key: &db::Key, text: &str) -> Result<db::models::Command, Error> {
@@ -40,6 +41,10 @@ impl Database {
                     text: text.to_string(),
                     group: None,
                     disabled: false,
+                    cooldown: None,
+                    user_cooldown: None,
+                    scope: None,
+                    price: None,
                 };
 
                 diesel::insert_into(dsl::commands)
@@ -98,6 +103,7 @@ pub struct Commands {
 
 impl Commands {
     database_group_fns!(Command, db::Key);
+    database_restriction_fns!(db::Key);
 
     /// Construct a new commands store with a db.
     pub fn load(db: db::Database) -> Result<Commands, Error> {
@@ -132,6 +138,13 @@ impl Commands {
         } else {
             let vars = template.vars();
 
+            let restrictions = db::Restrictions::from_db(
+                command.cooldown,
+                command.user_cooldown,
+                command.scope.as_ref(),
+                command.price,
+            )?;
+
             let command = Arc::new(Command {
                 key: key.clone(),
                 pattern: db::Pattern::from_db(command.pattern.as_ref())?,
@@ -140,6 +153,7 @@ impl Commands {
                 vars,
                 group: command.group,
                 disabled: command.disabled,
+                restrictions,
             });
 
             self.inner.write().insert(key.clone(), command.clone());
@@ -197,6 +211,8 @@ pub struct Command {
     vars: HashSet<String>,
     pub group: Option<String>,
     pub disabled: bool,
+    #[serde(flatten)]
+    pub restrictions: db::Restrictions,
 }
 
 /// Serialize the atomic count.
@@ -225,6 +241,13 @@ impl Command {
 
         let pattern = db::Pattern::from_db(command.pattern.as_ref())?;
 
+        let restrictions = db::Restrictions::from_db(
+            command.cooldown,
+            command.user_cooldown,
+            command.scope.as_ref(),
+            command.price,
+        )?;
+
         Ok(Command {
             key,
             pattern,
@@ -233,6 +256,7 @@ impl Command {
             vars,
             group: command.group.clone(),
             disabled: command.disabled,
+            restrictions,
         })
     }
 
@@ -269,11 +293,12 @@ impl fmt::Display for Command {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             fmt,
-            "template = \"{template}\", pattern = {pattern}, group = {group}, disabled = {disabled}",
+            "template = \"{template}\", pattern = {pattern}, group = {group}, disabled = {disabled}, {restrictions}",
             template = self.template,
             pattern = self.pattern,
             group = self.group.as_ref().map(|g| g.as_str()).unwrap_or("*none*"),
             disabled = self.disabled,
+            restrictions = self.restrictions,
         )
     }
 }
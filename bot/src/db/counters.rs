@@ -0,0 +1,207 @@
+use crate::db;
+use diesel::prelude::*;
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// Local database wrapper.
+#[derive(Clone)]
+struct Database(db::Database);
+
+impl Database {
+    /// List all counters.
+    fn list(&self) -> Result<Vec<db::models::Counter>, failure::Error> {
+        use db::schema::counters::dsl;
+        let c = self.0.pool.lock();
+        Ok(dsl::counters.load::<db::models::Counter>(&*c)?)
+    }
+
+    /// Insert a new counter, unless it already exists.
+    fn insert(&self, key: &db::Key) -> Result<bool, failure::Error> {
+        use db::schema::counters::dsl;
+        let c = self.0.pool.lock();
+
+        let filter =
+            dsl::counters.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
+
+        if filter
+            .first::<db::models::Counter>(&*c)
+            .optional()?
+            .is_some()
+        {
+            return Ok(false);
+        }
+
+        let counter = db::models::Counter {
+            channel: key.channel.to_string(),
+            name: key.name.to_string(),
+            count: 0,
+        };
+
+        diesel::insert_into(dsl::counters)
+            .values(&counter)
+            .execute(&*c)?;
+
+        Ok(true)
+    }
+
+    /// Set the count of the given counter.
+    fn set(&self, key: &db::Key, count: i64) -> Result<bool, failure::Error> {
+        use db::schema::counters::dsl;
+        let c = self.0.pool.lock();
+
+        let count = diesel::update(
+            dsl::counters.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+        )
+        .set(dsl::count.eq(count))
+        .execute(&*c)?;
+
+        Ok(count == 1)
+    }
+
+    /// Delete the given counter.
+    fn delete(&self, key: &db::Key) -> Result<bool, failure::Error> {
+        use db::schema::counters::dsl;
+        let c = self.0.pool.lock();
+
+        let count = diesel::delete(
+            dsl::counters.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+        )
+        .execute(&*c)?;
+
+        Ok(count == 1)
+    }
+}
+
+#[derive(Clone)]
+pub struct Counters {
+    inner: Arc<RwLock<HashMap<db::Key, i64>>>,
+    db: Database,
+}
+
+impl Counters {
+    /// Construct a new counters store with a db.
+    pub fn load(db: db::Database) -> Result<Counters, failure::Error> {
+        let db = Database(db);
+
+        let mut inner = HashMap::new();
+
+        for counter in db.list()? {
+            inner.insert(db::Key::new(&counter.channel, &counter.name), counter.count);
+        }
+
+        Ok(Counters {
+            inner: Arc::new(RwLock::new(inner)),
+            db,
+        })
+    }
+
+    /// Create a new counter starting at zero.
+    ///
+    /// Returns `false` if the counter already exists.
+    pub fn create(&self, channel: &str, name: &str) -> Result<bool, failure::Error> {
+        let key = db::Key::new(channel, name);
+
+        let mut inner = self.inner.write();
+
+        if !self.db.insert(&key)? {
+            return Ok(false);
+        }
+
+        inner.insert(key, 0);
+        Ok(true)
+    }
+
+    /// Add the given amount to a counter, returning the new count.
+    ///
+    /// Returns `None` if the counter doesn't exist.
+    pub fn add(
+        &self,
+        channel: &str,
+        name: &str,
+        amount: i64,
+    ) -> Result<Option<i64>, failure::Error> {
+        let key = db::Key::new(channel, name);
+
+        let mut inner = self.inner.write();
+
+        let count = match inner.get(&key) {
+            Some(count) => count.saturating_add(amount),
+            None => return Ok(None),
+        };
+
+        self.db.set(&key, count)?;
+        inner.insert(key, count);
+        Ok(Some(count))
+    }
+
+    /// Set the count of a counter.
+    ///
+    /// Returns `false` if the counter doesn't exist.
+    pub fn set(&self, channel: &str, name: &str, count: i64) -> Result<bool, failure::Error> {
+        let key = db::Key::new(channel, name);
+
+        let mut inner = self.inner.write();
+
+        if !inner.contains_key(&key) {
+            return Ok(false);
+        }
+
+        self.db.set(&key, count)?;
+        inner.insert(key, count);
+        Ok(true)
+    }
+
+    /// Delete a counter.
+    pub fn delete(&self, channel: &str, name: &str) -> Result<bool, failure::Error> {
+        let key = db::Key::new(channel, name);
+
+        let mut inner = self.inner.write();
+
+        if !self.db.delete(&key)? {
+            return Ok(false);
+        }
+
+        inner.remove(&key);
+        Ok(true)
+    }
+
+    /// Get the current count of a counter.
+    pub fn get(&self, channel: &str, name: &str) -> Option<i64> {
+        self.inner.read().get(&db::Key::new(channel, name)).cloned()
+    }
+
+    /// List all counters in the given channel, ordered by name.
+    pub fn list(&self, channel: &str) -> Vec<Counter> {
+        let mut out = self
+            .inner
+            .read()
+            .iter()
+            .filter(|(key, _)| key.channel == channel)
+            .map(|(key, count)| Counter {
+                key: key.clone(),
+                count: *count,
+            })
+            .collect::<Vec<_>>();
+
+        out.sort_by(|a, b| a.key.name.cmp(&b.key.name));
+        out
+    }
+
+    /// Get all counters in the given channel by name, for use as template
+    /// variables.
+    pub fn vars(&self, channel: &str) -> HashMap<String, i64> {
+        self.inner
+            .read()
+            .iter()
+            .filter(|(key, _)| key.channel == channel)
+            .map(|(key, count)| (key.name.clone(), *count))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Counter {
+    pub key: db::Key,
+    pub count: i64,
+}
@@ -0,0 +1,209 @@
+use crate::db::{self, models, schema};
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use std::fmt;
+
+pub use self::models::LedgerEntry;
+
+/// Why the balance of a user changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LedgerReason {
+    /// Reward for watching the stream.
+    WatchReward,
+    /// Reward for requesting a song.
+    SongRequest,
+    /// Cost of a GTA V effect.
+    Gtav,
+    /// Reward or undo of a water reminder.
+    Water,
+    /// Payout from the swear jar.
+    Swearjar,
+    /// Balance changed by a moderator, like with `!currency boost`.
+    Admin,
+    /// Transfer between users with `!currency give`.
+    Transfer,
+    /// Wager or winnings from `!gamble`.
+    Gamble,
+    /// Stake lost or won in a `!duel`.
+    Duel,
+    /// Stake or winnings from a `!heist`.
+    Heist,
+    /// Price of running a command or an alias.
+    Command,
+    /// Balance changed by a script.
+    Script,
+}
+
+impl LedgerReason {
+    /// Get the reason as it is stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LedgerReason::WatchReward => "watch-reward",
+            LedgerReason::SongRequest => "song-request",
+            LedgerReason::Gtav => "gtav",
+            LedgerReason::Water => "water",
+            LedgerReason::Swearjar => "swearjar",
+            LedgerReason::Admin => "admin",
+            LedgerReason::Transfer => "transfer",
+            LedgerReason::Gamble => "gamble",
+            LedgerReason::Duel => "duel",
+            LedgerReason::Heist => "heist",
+            LedgerReason::Command => "command",
+            LedgerReason::Script => "script",
+        }
+    }
+}
+
+impl fmt::Display for LedgerReason {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.as_str().fmt(fmt)
+    }
+}
+
+/// How a change to balances is recorded in the ledger.
+#[derive(Debug, Clone)]
+pub struct LedgerRecord {
+    pub reason: LedgerReason,
+    /// The other user involved in the change, like the moderator who made it.
+    pub counterparty: Option<String>,
+}
+
+impl LedgerRecord {
+    /// Record that the balance of each of the given users changed by
+    /// `amount`, using an existing connection.
+    ///
+    /// This lets the builtin currency record a change in the same transaction
+    /// as the change to the balances.
+    pub fn insert<'a>(
+        &self,
+        c: &SqliteConnection,
+        channel: &str,
+        users: impl IntoIterator<Item = &'a str>,
+        amount: i64,
+    ) -> Result<(), failure::Error> {
+        use self::schema::ledger::dsl;
+
+        for user in users {
+            let entry = models::InsertLedgerEntry {
+                channel,
+                user,
+                amount,
+                reason: self.reason.as_str(),
+                counterparty: self.counterparty.as_ref().map(String::as_str),
+            };
+
+            diesel::insert_into(dsl::ledger).values(&entry).execute(c)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Append-only ledger of changes to the balances of users.
+#[derive(Clone)]
+pub struct Ledger {
+    db: db::Database,
+}
+
+impl Ledger {
+    /// Open the ledger database.
+    pub fn load(db: db::Database) -> Result<Self, failure::Error> {
+        Ok(Ledger { db })
+    }
+
+    /// Record that the balance of each of the given users changed by
+    /// `amount`.
+    pub fn record<'a>(
+        &self,
+        channel: &str,
+        users: impl IntoIterator<Item = &'a str>,
+        amount: i64,
+        reason: LedgerReason,
+        counterparty: Option<&str>,
+    ) -> Result<(), failure::Error> {
+        let c = self.db.pool.lock();
+
+        let record = LedgerRecord {
+            reason,
+            counterparty: counterparty.map(String::from),
+        };
+
+        c.transaction::<_, failure::Error, _>(|| record.insert(&*c, channel, users, amount))
+    }
+
+    /// Remove all entries created before the given point in time, returning
+    /// the number of entries removed.
+    pub fn prune(&self, before: NaiveDateTime) -> Result<usize, failure::Error> {
+        use self::schema::ledger::dsl;
+        let c = self.db.pool.lock();
+
+        let count = diesel::delete(dsl::ledger.filter(dsl::created_at.lt(before))).execute(&*c)?;
+        Ok(count)
+    }
+
+    /// List the changes to the balance of the given user, most recent first.
+    ///
+    /// If `channel` is specified, only changes to the balance in that channel
+    /// are listed.
+    pub fn history(
+        &self,
+        channel: Option<&str>,
+        user: &str,
+        limit: i64,
+    ) -> Result<Vec<LedgerEntry>, failure::Error> {
+        use self::schema::ledger::dsl;
+        let c = self.db.pool.lock();
+
+        let mut query = dsl::ledger.filter(dsl::user.eq(user)).into_boxed();
+
+        if let Some(channel) = channel {
+            query = query.filter(dsl::channel.eq(channel));
+        }
+
+        let entries = query
+            .order((dsl::created_at.desc(), dsl::id.desc()))
+            .limit(limit)
+            .load::<LedgerEntry>(&*c)?;
+
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Ledger, LedgerReason};
+    use crate::db;
+    use chrono::{Duration, Utc};
+    use diesel::prelude::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_prune() -> Result<(), failure::Error> {
+        let db = db::Database::open(Path::new(":memory:"))?;
+        let ledger = Ledger::load(db.clone())?;
+
+        ledger.record(
+            "#setbac",
+            vec!["a", "b"],
+            10,
+            LedgerReason::WatchReward,
+            None,
+        )?;
+
+        diesel::sql_query("UPDATE ledger SET created_at = datetime('now', '-2 days')")
+            .execute(&*db.pool.lock())?;
+
+        ledger.record("#setbac", vec!["a"], 5, LedgerReason::Admin, Some("mod"))?;
+
+        let before = Utc::now().naive_utc() - Duration::days(1);
+        assert_eq!(2, ledger.prune(before)?);
+
+        let history = ledger.history(Some("#setbac"), "a", 10)?;
+        assert_eq!(
+            vec![5],
+            history.into_iter().map(|e| e.amount).collect::<Vec<_>>()
+        );
+        Ok(())
+    }
+}
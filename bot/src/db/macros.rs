@@ -300,3 +300,68 @@ macro_rules! private_database_group_fns {
         }
     }
 }
+
+/// Helper macro to build database functions for managing restrictions.
+macro_rules! database_restriction_fns {
+    ($key:ty) => {
+        /// Edit the restrictions of the given thing.
+        pub fn edit_restrictions(
+            &self,
+            channel: &str,
+            name: &str,
+            restrictions: db::Restrictions,
+        ) -> Result<bool, failure::Error> {
+            let key = <$key>::new(channel, name);
+
+            if !self.db.edit_restrictions(&key, &restrictions)? {
+                return Ok(false);
+            }
+
+            self.inner.write().modify(key, |thing| {
+                thing.restrictions = restrictions;
+            });
+
+            Ok(true)
+        }
+    };
+}
+
+/// Helper macro to build private database functions related to restrictions.
+macro_rules! private_database_restriction_fns {
+    ($module:ident, $key:ty) => {
+        /// Edit the restrictions of the given thing.
+        fn edit_restrictions(
+            &self,
+            key: &$key,
+            restrictions: &db::Restrictions,
+        ) -> Result<bool, failure::Error> {
+            use db::schema::$module::dsl;
+            let c = self.0.pool.lock();
+
+            let cooldown = match restrictions.cooldown.as_ref() {
+                Some(d) => Some(db::Restrictions::cooldown_to_db(d)?),
+                None => None,
+            };
+
+            let user_cooldown = match restrictions.user_cooldown.as_ref() {
+                Some(d) => Some(db::Restrictions::cooldown_to_db(d)?),
+                None => None,
+            };
+
+            let scope = restrictions.scope.as_ref().map(|s| s.to_string());
+
+            let count = diesel::update(
+                dsl::$module.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+            )
+            .set((
+                dsl::cooldown.eq(cooldown),
+                dsl::user_cooldown.eq(user_cooldown),
+                dsl::scope.eq(scope),
+                dsl::price.eq(restrictions.price),
+            ))
+            .execute(&*c)?;
+
+            Ok(count == 1)
+        }
+    };
+}
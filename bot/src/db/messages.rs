@@ -0,0 +1,270 @@
+use crate::db::{self, models, schema};
+use chrono::NaiveDateTime;
+use diesel::{
+    prelude::*,
+    sql_types::{Bool, Integer, Nullable, Text, Timestamp},
+};
+
+/// Query used to search for messages.
+///
+/// Every filter is optional, and ignored if its parameter is `NULL`.
+const SEARCH_QUERY: &str = "SELECT * FROM messages \
+     WHERE (?1 IS NULL OR channel = ?1) \
+     AND (?2 IS NULL OR user = ?2) \
+     AND (?3 IS NULL OR sent_at >= ?3) \
+     AND (?4 OR NOT deleted) \
+     AND (?5 IS NULL OR id IN (SELECT rowid FROM messages_fts WHERE messages_fts MATCH ?5)) \
+     ORDER BY sent_at DESC, id DESC \
+     LIMIT ?6";
+
+/// A message stored in the chat history.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Message {
+    pub id: i32,
+    pub channel: String,
+    pub message_id: Option<String>,
+    pub user: String,
+    pub display_name: Option<String>,
+    pub text: String,
+    pub tags: serde_json::Value,
+    pub sent_at: NaiveDateTime,
+    pub deleted: bool,
+}
+
+impl From<models::Message> for Message {
+    fn from(m: models::Message) -> Self {
+        Message {
+            id: m.id,
+            channel: m.channel,
+            message_id: m.message_id,
+            user: m.user,
+            display_name: m.display_name,
+            text: m.text,
+            tags: serde_json::from_str(&m.tags).unwrap_or_default(),
+            sent_at: m.sent_at,
+            deleted: m.deleted,
+        }
+    }
+}
+
+/// Filter used when searching for messages.
+#[derive(Debug, Clone, Default)]
+pub struct MessageFilter<'a> {
+    /// Only include messages in the given channel.
+    pub channel: Option<&'a str>,
+    /// Only include messages sent by the given user.
+    pub user: Option<&'a str>,
+    /// Only include messages matching the given full-text search.
+    pub q: Option<&'a str>,
+    /// Only include messages sent since the given point in time.
+    pub since: Option<NaiveDateTime>,
+    /// Include deleted messages.
+    pub deleted: bool,
+}
+
+#[derive(Clone)]
+pub struct Messages {
+    db: db::Database,
+}
+
+impl Messages {
+    /// Open the messages database.
+    pub fn load(db: db::Database) -> Result<Self, failure::Error> {
+        Ok(Messages { db })
+    }
+
+    /// Store a message sent in chat.
+    pub fn push(
+        &self,
+        channel: &str,
+        message_id: Option<&str>,
+        user: &str,
+        display_name: Option<&str>,
+        text: &str,
+        tags: &serde_json::Value,
+    ) -> Result<(), failure::Error> {
+        use self::schema::messages::dsl;
+        let c = self.db.pool.lock();
+
+        let tags = serde_json::to_string(tags)?;
+
+        let message = models::InsertMessage {
+            channel,
+            message_id,
+            user,
+            display_name,
+            text,
+            tags: &tags,
+        };
+
+        diesel::insert_into(dsl::messages)
+            .values(&message)
+            .execute(&*c)?;
+
+        Ok(())
+    }
+
+    /// Mark the message with the given Twitch identifier as deleted.
+    pub fn delete_by_id(&self, channel: &str, message_id: &str) -> Result<(), failure::Error> {
+        use self::schema::messages::dsl;
+        let c = self.db.pool.lock();
+
+        diesel::update(
+            dsl::messages.filter(dsl::channel.eq(channel).and(dsl::message_id.eq(message_id))),
+        )
+        .set(dsl::deleted.eq(true))
+        .execute(&*c)?;
+
+        Ok(())
+    }
+
+    /// Mark messages by the given user sent since the given point in time as
+    /// deleted.
+    ///
+    /// Only messages which were still visible in chat should be marked, so
+    /// that older history stays searchable after a timeout or ban.
+    pub fn delete_by_user(
+        &self,
+        channel: &str,
+        user: &str,
+        since: NaiveDateTime,
+    ) -> Result<(), failure::Error> {
+        use self::schema::messages::dsl;
+        let c = self.db.pool.lock();
+
+        diesel::update(
+            dsl::messages.filter(
+                dsl::channel
+                    .eq(channel)
+                    .and(dsl::user.eq(user))
+                    .and(dsl::sent_at.ge(since)),
+            ),
+        )
+        .set(dsl::deleted.eq(true))
+        .execute(&*c)?;
+
+        Ok(())
+    }
+
+    /// Mark messages in the channel sent since the given point in time as
+    /// deleted.
+    pub fn delete_all(&self, channel: &str, since: NaiveDateTime) -> Result<(), failure::Error> {
+        use self::schema::messages::dsl;
+        let c = self.db.pool.lock();
+
+        diesel::update(dsl::messages.filter(dsl::channel.eq(channel).and(dsl::sent_at.ge(since))))
+            .set(dsl::deleted.eq(true))
+            .execute(&*c)?;
+
+        Ok(())
+    }
+
+    /// Remove all messages sent before the given point in time, returning
+    /// the number of messages removed.
+    pub fn prune(&self, before: NaiveDateTime) -> Result<usize, failure::Error> {
+        use self::schema::messages::dsl;
+        let c = self.db.pool.lock();
+
+        let count = diesel::delete(dsl::messages.filter(dsl::sent_at.lt(before))).execute(&*c)?;
+        Ok(count)
+    }
+
+    /// Search for messages matching the filter, most recent first.
+    pub fn search(
+        &self,
+        filter: &MessageFilter<'_>,
+        limit: u32,
+    ) -> Result<Vec<Message>, failure::Error> {
+        let c = self.db.pool.lock();
+
+        let q = filter.q.and_then(match_expression);
+
+        let messages = diesel::sql_query(SEARCH_QUERY)
+            .bind::<Nullable<Text>, _>(filter.channel)
+            .bind::<Nullable<Text>, _>(filter.user)
+            .bind::<Nullable<Timestamp>, _>(filter.since)
+            .bind::<Bool, _>(filter.deleted)
+            .bind::<Nullable<Text>, _>(q)
+            .bind::<Integer, _>(limit as i32)
+            .load::<models::Message>(&*c)?;
+
+        Ok(messages.into_iter().map(Message::from).collect())
+    }
+}
+
+/// Convert a search into a full-text match expression.
+///
+/// Every term is quoted, so that the search can't contain any query syntax.
+/// All terms have to be present in matching messages.
+fn match_expression(q: &str) -> Option<String> {
+    let terms = q
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+
+    if terms.is_empty() {
+        return None;
+    }
+
+    Some(terms.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{match_expression, MessageFilter, Messages};
+    use crate::db;
+    use chrono::{Duration, Utc};
+    use diesel::prelude::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_clear_keeps_older_messages() -> Result<(), failure::Error> {
+        let db = db::Database::open(Path::new(":memory:"))?;
+        let messages = Messages::load(db.clone())?;
+        let tags = serde_json::Value::Null;
+
+        messages.push("#setbac", Some("abc"), "someuser", None, "old news", &tags)?;
+
+        diesel::sql_query("UPDATE messages SET sent_at = datetime('now', '-1 hour')")
+            .execute(&*db.pool.lock())?;
+
+        messages.push(
+            "#setbac",
+            Some("def"),
+            "someuser",
+            None,
+            "fresh news",
+            &tags,
+        )?;
+
+        let since = Utc::now().naive_utc() - Duration::minutes(5);
+        messages.delete_by_user("#setbac", "someuser", since)?;
+
+        let filter = MessageFilter {
+            channel: Some("#setbac"),
+            q: Some("news"),
+            ..MessageFilter::default()
+        };
+
+        let found = messages.search(&filter, 10)?;
+        assert_eq!(1, found.len());
+        assert_eq!("old news", found[0].text);
+
+        messages.delete_all("#setbac", since)?;
+        assert_eq!(1, messages.search(&filter, 10)?.len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_match_expression() {
+        assert_eq!(None, match_expression("  "));
+        assert_eq!(
+            Some(String::from("\"hello\" \"world\"")),
+            match_expression("hello world")
+        );
+        assert_eq!(
+            Some(String::from("\"\"\"NEAR\"\"\" \"OR*\"")),
+            match_expression("\"NEAR\" OR*")
+        );
+    }
+}
@@ -1,5 +1,7 @@
 use super::schema::{
-    after_streams, aliases, bad_words, balances, commands, promotions, songs, themes,
+    after_streams, aliases, audit_log, bad_words, balances, ban_list, command_invocations,
+    commands, counters, ledger, messages, promotions, quotes, schedules, scripts, seen_users,
+    songs, strikes, themes,
 };
 use crate::track_id::TrackId;
 use chrono::NaiveDateTime;
@@ -42,6 +44,14 @@ pub struct Command {
     pub group: Option<String>,
     /// If the command is disabled.
     pub disabled: bool,
+    /// Global cooldown in seconds between invocations.
+    pub cooldown: Option<i32>,
+    /// Per-user cooldown in seconds between invocations.
+    pub user_cooldown: Option<i32>,
+    /// Role or scope required to invoke.
+    pub scope: Option<String>,
+    /// Price in stream currency to invoke.
+    pub price: Option<i64>,
 }
 
 #[derive(Debug, Clone, Default, diesel::AsChangeset)]
@@ -68,6 +78,14 @@ pub struct Alias {
     pub group: Option<String>,
     /// If the promotion is disabled.
     pub disabled: bool,
+    /// Global cooldown in seconds between invocations.
+    pub cooldown: Option<i32>,
+    /// Per-user cooldown in seconds between invocations.
+    pub user_cooldown: Option<i32>,
+    /// Role or scope required to invoke.
+    pub scope: Option<String>,
+    /// Price in stream currency to invoke.
+    pub price: Option<i64>,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Insertable)]
@@ -109,10 +127,52 @@ pub struct InsertAfterStream {
     pub text: String,
 }
 
+#[derive(Debug, Clone, diesel::Queryable, diesel::Insertable)]
+pub struct Counter {
+    /// The channel the counter belongs to.
+    pub channel: String,
+    /// The name of the counter.
+    pub name: String,
+    /// The current count.
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, diesel::Queryable)]
+pub struct Quote {
+    /// The unique identifier of the quote.
+    pub id: i32,
+    /// The channel the quote belongs to.
+    pub channel: String,
+    /// When the quote was added.
+    pub added_at: NaiveDateTime,
+    /// The user that added the quote.
+    pub user: String,
+    /// The game that was played when the quote was added.
+    pub game: Option<String>,
+    /// The text of the quote.
+    pub text: String,
+}
+
+/// Insert model for quotes.
+#[derive(diesel::Insertable)]
+#[table_name = "quotes"]
+pub struct InsertQuote {
+    pub channel: String,
+    pub user: String,
+    pub game: Option<String>,
+    pub text: String,
+}
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
 pub struct BadWord {
+    /// The word, or the pattern if this is a regular expression.
     pub word: String,
-    pub why: Option<String>,
+    /// If the word is a regular expression.
+    pub regex: bool,
+    /// The action to take when the word is matched.
+    pub action: String,
+    /// Message template to respond with when the word is matched.
+    pub message: Option<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, diesel::Queryable)]
@@ -199,3 +259,226 @@ pub struct UpdateTheme<'a> {
     pub group: Option<&'a str>,
     pub disabled: Option<bool>,
 }
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
+pub struct Script {
+    /// The channel the script belongs to.
+    pub channel: String,
+    /// The name of the script, which is also the command it is registered as.
+    pub name: String,
+    /// The source of the script.
+    pub source: String,
+    /// If the script is disabled.
+    pub disabled: bool,
+    /// The group the script is part of, if any.
+    pub group: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, diesel::AsChangeset)]
+#[table_name = "scripts"]
+pub struct UpdateScript<'a> {
+    pub source: Option<&'a str>,
+    pub group: Option<&'a str>,
+    pub disabled: Option<bool>,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, diesel::Queryable, diesel::Insertable)]
+pub struct Schedule {
+    /// The channel the schedule belongs to.
+    pub channel: String,
+    /// The name of the schedule.
+    pub name: String,
+    /// The interval or cron expression which triggers the schedule.
+    pub trigger: String,
+    /// The action to perform when triggered.
+    pub action: String,
+    /// If the schedule should only run while the stream is live.
+    pub only_live: bool,
+    /// The last time the schedule ran.
+    pub ran_at: Option<NaiveDateTime>,
+    /// If the schedule is disabled.
+    pub disabled: bool,
+    /// The group the schedule is part of, if any.
+    pub group: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, diesel::AsChangeset)]
+#[table_name = "schedules"]
+pub struct UpdateSchedule<'a> {
+    pub trigger: Option<&'a str>,
+    pub action: Option<&'a str>,
+    pub only_live: Option<bool>,
+    pub ran_at: Option<&'a NaiveDateTime>,
+    pub group: Option<&'a str>,
+    pub disabled: Option<bool>,
+}
+
+/// Number of command invocations with the same day, stream, command and
+/// outcome.
+#[derive(Debug, Clone, diesel::QueryableByName)]
+pub struct CommandInvocationCount {
+    /// The day the command was invoked, in UTC.
+    #[sql_type = "diesel::sql_types::Date"]
+    pub day: chrono::NaiveDate,
+    /// When the stream that was live during the invocations started, if any.
+    #[sql_type = "diesel::sql_types::Nullable<diesel::sql_types::Timestamp>"]
+    pub stream_started_at: Option<NaiveDateTime>,
+    /// The name of the command that was invoked.
+    #[sql_type = "diesel::sql_types::Text"]
+    pub command: String,
+    /// The outcome of the invocations.
+    #[sql_type = "diesel::sql_types::Text"]
+    pub outcome: String,
+    /// The number of invocations.
+    #[sql_type = "diesel::sql_types::BigInt"]
+    pub count: i64,
+}
+
+/// Insert model for command invocations.
+#[derive(diesel::Insertable)]
+#[table_name = "command_invocations"]
+pub struct InsertCommandInvocation<'a> {
+    pub channel: &'a str,
+    pub command: &'a str,
+    pub user: Option<&'a str>,
+    pub outcome: &'a str,
+    pub stream_started_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, diesel::Queryable, diesel::Insertable)]
+pub struct Strike {
+    /// The channel the strikes were given in.
+    pub channel: String,
+    /// The user who has the strikes.
+    pub user: String,
+    /// The number of strikes.
+    pub count: i32,
+    /// When the user was last given a strike.
+    pub struck_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, serde::Serialize, diesel::Queryable)]
+pub struct AuditEntry {
+    /// The unique identifier of the entry.
+    pub id: i32,
+    /// The channel the action was taken in.
+    pub channel: String,
+    /// The user who took the action, or `None` if it was taken by the bot.
+    pub actor: Option<String>,
+    /// The action that was taken.
+    pub action: String,
+    /// What the action was taken against, like a user or a setting.
+    pub target: Option<String>,
+    /// Why the action was taken, or details on it.
+    pub reason: Option<String>,
+    /// When the action was taken.
+    pub logged_at: NaiveDateTime,
+}
+
+/// Insert model for audit log entries.
+#[derive(diesel::Insertable)]
+#[table_name = "audit_log"]
+pub struct InsertAuditEntry<'a> {
+    pub channel: &'a str,
+    pub actor: Option<&'a str>,
+    pub action: &'a str,
+    pub target: Option<&'a str>,
+    pub reason: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, diesel::Queryable, diesel::QueryableByName)]
+#[table_name = "messages"]
+pub struct Message {
+    /// The unique identifier of the stored message.
+    pub id: i32,
+    /// The channel the message was sent in.
+    pub channel: String,
+    /// The Twitch identifier of the message, if known.
+    pub message_id: Option<String>,
+    /// The login of the user who sent the message.
+    pub user: String,
+    /// The display name of the user who sent the message.
+    pub display_name: Option<String>,
+    /// The text of the message.
+    pub text: String,
+    /// The tags of the message, serialized as JSON.
+    pub tags: String,
+    /// When the message was sent.
+    pub sent_at: NaiveDateTime,
+    /// If the message has been deleted.
+    pub deleted: bool,
+}
+
+/// Insert model for messages.
+#[derive(diesel::Insertable)]
+#[table_name = "messages"]
+pub struct InsertMessage<'a> {
+    pub channel: &'a str,
+    pub message_id: Option<&'a str>,
+    pub user: &'a str,
+    pub display_name: Option<&'a str>,
+    pub text: &'a str,
+    pub tags: &'a str,
+}
+
+#[derive(Debug, Clone, diesel::Queryable, diesel::Insertable)]
+pub struct SeenUser {
+    /// The channel the user was seen in.
+    pub channel: String,
+    /// The user who was seen.
+    pub user: String,
+    /// When the user was first seen.
+    pub first_seen_at: NaiveDateTime,
+}
+
+/// A user on the ban list.
+#[derive(Debug, Clone, diesel::Queryable)]
+pub struct BanListEntry {
+    pub user: String,
+    /// The action to take, like `ban` or `timeout 10m`.
+    pub action: String,
+    pub reason: Option<String>,
+    /// The subscribed list the entry came from, if any.
+    pub source: Option<String>,
+    pub added_at: NaiveDateTime,
+}
+
+/// Insert model for the ban list.
+#[derive(diesel::Insertable)]
+#[table_name = "ban_list"]
+pub struct InsertBanListEntry<'a> {
+    pub user: &'a str,
+    pub action: &'a str,
+    pub reason: Option<&'a str>,
+    pub source: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, serde::Serialize, diesel::Queryable)]
+pub struct LedgerEntry {
+    /// The unique identifier of the entry.
+    pub id: i32,
+    /// The channel the balance belongs to.
+    pub channel: String,
+    /// The user whose balance changed.
+    pub user: String,
+    /// How much the balance changed by.
+    pub amount: i64,
+    /// Why the balance changed, like `watch-reward` or `transfer`.
+    pub reason: String,
+    /// The other user involved in the change, like the other side of a
+    /// transfer or the moderator who changed the balance.
+    pub counterparty: Option<String>,
+    /// When the balance changed.
+    pub created_at: NaiveDateTime,
+}
+
+/// Insert model for ledger entries.
+#[derive(diesel::Insertable)]
+#[table_name = "ledger"]
+pub struct InsertLedgerEntry<'a> {
+    pub channel: &'a str,
+    pub user: &'a str,
+    pub amount: i64,
+    pub reason: &'a str,
+    pub counterparty: Option<&'a str>,
+}
@@ -0,0 +1,180 @@
+use crate::db::{self, models, schema};
+use diesel::prelude::*;
+
+pub use self::models::Quote;
+
+#[derive(Clone)]
+pub struct Quotes {
+    db: db::Database,
+}
+
+impl Quotes {
+    /// Open the quotes database.
+    pub fn load(db: db::Database) -> Result<Self, failure::Error> {
+        Ok(Quotes { db })
+    }
+
+    /// Add the given quote, returning its id.
+    pub fn push(
+        &self,
+        channel: &str,
+        user: &str,
+        game: Option<&str>,
+        text: &str,
+    ) -> Result<i32, failure::Error> {
+        use self::schema::quotes::dsl;
+        let c = self.db.pool.lock();
+
+        let quote = models::InsertQuote {
+            channel: String::from(channel),
+            user: String::from(user),
+            game: game.map(String::from),
+            text: String::from(text),
+        };
+
+        diesel::insert_into(dsl::quotes)
+            .values(&quote)
+            .execute(&*c)?;
+
+        // NB: sqlite doesn't support returning the inserted row, but we hold
+        // the lock so the last row is ours.
+        let id = dsl::quotes
+            .select(dsl::id)
+            .order(dsl::id.desc())
+            .first::<i32>(&*c)?;
+
+        Ok(id)
+    }
+
+    /// Get the quote with the given id.
+    pub fn get(&self, channel: &str, id: i32) -> Result<Option<Quote>, failure::Error> {
+        use self::schema::quotes::dsl;
+        let c = self.db.pool.lock();
+
+        Ok(dsl::quotes
+            .filter(dsl::channel.eq(channel).and(dsl::id.eq(id)))
+            .first::<models::Quote>(&*c)
+            .optional()?)
+    }
+
+    /// Pick a random quote.
+    pub fn random(&self, channel: &str) -> Result<Option<Quote>, failure::Error> {
+        use self::schema::quotes::dsl;
+        use rand::Rng as _;
+
+        let c = self.db.pool.lock();
+        let filter = dsl::quotes.filter(dsl::channel.eq(channel));
+
+        let count = filter.clone().count().get_result::<i64>(&*c)?;
+
+        if count == 0 {
+            return Ok(None);
+        }
+
+        let offset = rand::thread_rng().gen_range(0, count);
+
+        Ok(filter
+            .order(dsl::id.asc())
+            .offset(offset)
+            .first::<models::Quote>(&*c)
+            .optional()?)
+    }
+
+    /// Search for at most `limit` quotes containing the given term, ignoring
+    /// case.
+    pub fn search(
+        &self,
+        channel: &str,
+        term: &str,
+        limit: i64,
+    ) -> Result<Vec<Quote>, failure::Error> {
+        use self::schema::quotes::dsl;
+        let c = self.db.pool.lock();
+
+        Ok(dsl::quotes
+            .filter(dsl::channel.eq(channel))
+            .filter(dsl::text.like(like_pattern(term)).escape('\\'))
+            .order(dsl::id.asc())
+            .limit(limit)
+            .load::<models::Quote>(&*c)?)
+    }
+
+    /// Delete the quote with the given id.
+    pub fn delete(&self, channel: &str, id: i32) -> Result<bool, failure::Error> {
+        use self::schema::quotes::dsl;
+        let c = self.db.pool.lock();
+
+        let count =
+            diesel::delete(dsl::quotes.filter(dsl::channel.eq(channel).and(dsl::id.eq(id))))
+                .execute(&*c)?;
+
+        Ok(count == 1)
+    }
+
+    /// List all quotes in the given channel.
+    pub fn list(&self, channel: &str) -> Result<Vec<Quote>, failure::Error> {
+        use self::schema::quotes::dsl;
+        let c = self.db.pool.lock();
+
+        Ok(dsl::quotes
+            .filter(dsl::channel.eq(channel))
+            .order(dsl::id.asc())
+            .load::<models::Quote>(&*c)?)
+    }
+}
+
+/// Build a `LIKE` pattern matching text which contains the given term, with
+/// `\` as the escape character.
+fn like_pattern(term: &str) -> String {
+    let mut pattern = String::with_capacity(term.len() + 2);
+    pattern.push('%');
+
+    for c in term.chars() {
+        if let '%' | '_' | '\\' = c {
+            pattern.push('\\');
+        }
+
+        pattern.push(c);
+    }
+
+    pattern.push('%');
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{like_pattern, Quotes};
+    use crate::db;
+    use std::path::Path;
+
+    #[test]
+    fn test_like_pattern() {
+        assert_eq!("%hello%", like_pattern("hello"));
+        assert_eq!("%50\\%\\_off\\\\%", like_pattern("50%_off\\"));
+    }
+
+    #[test]
+    fn test_search() -> Result<(), failure::Error> {
+        let db = db::Database::open(Path::new(":memory:"))?;
+        let quotes = Quotes::load(db)?;
+
+        quotes.push("#setbac", "someuser", None, "Hello World")?;
+        quotes.push("#setbac", "someuser", None, "500 subs")?;
+        quotes.push("#setbac", "someuser", None, "hello again")?;
+        quotes.push("#other", "someuser", None, "hello there")?;
+
+        let search = |term, limit| -> Result<Vec<String>, failure::Error> {
+            Ok(quotes
+                .search("#setbac", term, limit)?
+                .into_iter()
+                .map(|q| q.text)
+                .collect())
+        };
+
+        assert_eq!(vec!["Hello World", "hello again"], search("HELLO", 10)?);
+        assert_eq!(vec!["Hello World"], search("hello", 1)?);
+        assert!(search("50%", 10)?.is_empty());
+        assert!(search("5_0", 10)?.is_empty());
+        Ok(())
+    }
+}
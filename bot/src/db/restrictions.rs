@@ -0,0 +1,92 @@
+use crate::{auth, utils};
+use failure::format_err;
+use std::{convert::TryFrom as _, fmt};
+
+/// Restrictions on who may invoke a custom command or an alias, and how often.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct Restrictions {
+    /// Cooldown in effect between any two invocations.
+    #[serde(default)]
+    pub cooldown: Option<utils::Duration>,
+    /// Cooldown in effect between two invocations by the same user.
+    #[serde(default)]
+    pub user_cooldown: Option<utils::Duration>,
+    /// Role or scope required to invoke.
+    #[serde(default)]
+    pub scope: Option<auth::RoleOrScope>,
+    /// Price in stream currency for a single invocation.
+    #[serde(default)]
+    pub price: Option<i64>,
+}
+
+impl Restrictions {
+    /// Load restrictions from their database columns.
+    pub fn from_db(
+        cooldown: Option<i32>,
+        user_cooldown: Option<i32>,
+        scope: Option<&String>,
+        price: Option<i64>,
+    ) -> Result<Restrictions, failure::Error> {
+        let scope = match scope {
+            Some(scope) => Some(str::parse(scope)?),
+            None => None,
+        };
+
+        Ok(Restrictions {
+            cooldown: cooldown.map(|s| utils::Duration::seconds(s.max(0) as u64)),
+            user_cooldown: user_cooldown.map(|s| utils::Duration::seconds(s.max(0) as u64)),
+            scope,
+            price,
+        })
+    }
+
+    /// Convert a cooldown into seconds, as stored in the database.
+    ///
+    /// Errors if the cooldown is too long to be stored.
+    pub fn cooldown_to_db(cooldown: &utils::Duration) -> Result<i32, failure::Error> {
+        i32::try_from(cooldown.num_seconds())
+            .map_err(|_| format_err!("cooldown is too long: {}", cooldown))
+    }
+
+    /// Test if there are no restrictions.
+    pub fn is_empty(&self) -> bool {
+        self.cooldown.is_none()
+            && self.user_cooldown.is_none()
+            && self.scope.is_none()
+            && self.price.is_none()
+    }
+}
+
+impl fmt::Display for Restrictions {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "cooldown = {cooldown}, user_cooldown = {user_cooldown}, scope = {scope}, price = {price}",
+            cooldown = display_option(self.cooldown.as_ref()),
+            user_cooldown = display_option(self.user_cooldown.as_ref()),
+            scope = display_option(self.scope.as_ref()),
+            price = display_option(self.price.as_ref()),
+        )
+    }
+}
+
+/// Helper to display an optional value.
+fn display_option(value: Option<&impl fmt::Display>) -> String {
+    match value {
+        Some(value) => value.to_string(),
+        None => String::from("*none*"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Restrictions;
+    use crate::utils::Duration;
+
+    #[test]
+    fn test_cooldown_to_db() -> Result<(), failure::Error> {
+        assert_eq!(3600, Restrictions::cooldown_to_db(&Duration::hours(1))?);
+        assert!(Restrictions::cooldown_to_db(&Duration::seconds(1 << 31)).is_err());
+        Ok(())
+    }
+}
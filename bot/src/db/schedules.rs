@@ -0,0 +1,523 @@
+use crate::{db, settings, utils};
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use failure::{format_err, ResultExt as _};
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use std::{fmt, sync::Arc};
+
+/// Local database wrapper.
+#[derive(Clone)]
+struct Database(db::Database);
+
+impl Database {
+    private_database_group_fns!(schedules, Schedule, db::Key);
+
+    fn edit(
+        &self,
+        key: &db::Key,
+        trigger: &str,
+        action: &str,
+    ) -> Result<Option<db::models::Schedule>, failure::Error> {
+        use db::schema::schedules::dsl;
+
+        let c = self.0.pool.lock();
+        let filter =
+            dsl::schedules.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
+
+        match filter
+            .clone()
+            .first::<db::models::Schedule>(&*c)
+            .optional()?
+        {
+            None => {
+                let schedule = db::models::Schedule {
+                    channel: key.channel.to_string(),
+                    name: key.name.to_string(),
+                    trigger: trigger.to_string(),
+                    action: action.to_string(),
+                    only_live: false,
+                    ran_at: None,
+                    disabled: false,
+                    group: None,
+                };
+
+                diesel::insert_into(dsl::schedules)
+                    .values(&schedule)
+                    .execute(&*c)?;
+
+                Ok(Some(schedule))
+            }
+            Some(schedule) => {
+                let mut set = db::models::UpdateSchedule::default();
+                set.trigger = Some(trigger);
+                set.action = Some(action);
+                diesel::update(filter).set(&set).execute(&*c)?;
+
+                if schedule.disabled {
+                    return Ok(None);
+                }
+
+                Ok(Some(schedule))
+            }
+        }
+    }
+
+    fn edit_only_live(&self, key: &db::Key, only_live: bool) -> Result<bool, failure::Error> {
+        use db::schema::schedules::dsl;
+
+        let c = self.0.pool.lock();
+        let count = diesel::update(
+            dsl::schedules.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+        )
+        .set(dsl::only_live.eq(only_live))
+        .execute(&*c)?;
+
+        Ok(count == 1)
+    }
+
+    fn bump_ran_at(&self, key: &db::Key, now: &DateTime<Utc>) -> Result<bool, failure::Error> {
+        use db::schema::schedules::dsl;
+
+        let c = self.0.pool.lock();
+        let count = diesel::update(
+            dsl::schedules.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name))),
+        )
+        .set(dsl::ran_at.eq(now.naive_utc()))
+        .execute(&*c)?;
+
+        Ok(count == 1)
+    }
+}
+
+#[derive(Clone)]
+pub struct Schedules {
+    inner: Arc<RwLock<HashMap<db::Key, Arc<Schedule>>>>,
+    db: Database,
+}
+
+impl Schedules {
+    database_group_fns!(Schedule, db::Key);
+
+    /// Construct a new schedules store with a db.
+    pub fn load(db: db::Database) -> Result<Schedules, failure::Error> {
+        let db = Database(db);
+
+        let mut inner = HashMap::new();
+
+        for schedule in db.list()? {
+            let schedule = Schedule::from_db(&schedule)?;
+            inner.insert(schedule.key.clone(), Arc::new(schedule));
+        }
+
+        Ok(Schedules {
+            inner: Arc::new(RwLock::new(inner)),
+            db,
+        })
+    }
+
+    /// Edit the trigger and action of a schedule.
+    pub fn edit(
+        &self,
+        channel: &str,
+        name: &str,
+        trigger: Trigger,
+        action: Action,
+    ) -> Result<(), failure::Error> {
+        let key = db::Key::new(channel, name);
+
+        let mut inner = self.inner.write();
+
+        let schedule = self
+            .db
+            .edit(&key, &trigger.to_string(), &action.to_string())?;
+
+        if let Some(schedule) = schedule {
+            inner.insert(
+                key.clone(),
+                Arc::new(Schedule {
+                    key,
+                    trigger,
+                    action,
+                    only_live: schedule.only_live,
+                    ran_at: schedule.ran_at.map(|d| DateTime::from_utc(d, Utc)),
+                    group: schedule.group,
+                    disabled: schedule.disabled,
+                }),
+            );
+        } else {
+            inner.remove(&key);
+        }
+
+        Ok(())
+    }
+
+    /// Set if the given schedule should only run while the stream is live.
+    pub fn edit_only_live(
+        &self,
+        channel: &str,
+        name: &str,
+        only_live: bool,
+    ) -> Result<bool, failure::Error> {
+        let key = db::Key::new(channel, name);
+
+        if !self.db.edit_only_live(&key, only_live)? {
+            return Ok(false);
+        }
+
+        let mut inner = self.inner.write();
+
+        if let Some(mut schedule) = inner.get(&key).map(|s| (**s).clone()) {
+            schedule.only_live = only_live;
+            inner.insert(key, Arc::new(schedule));
+        }
+
+        Ok(true)
+    }
+
+    /// Bump that the given schedule was last run at the given time.
+    pub fn bump_ran_at(
+        &self,
+        schedule: &Schedule,
+        now: &DateTime<Utc>,
+    ) -> Result<(), failure::Error> {
+        let mut inner = self.inner.write();
+
+        if !self.db.bump_ran_at(&schedule.key, now)? {
+            return Ok(());
+        }
+
+        if let Some(mut schedule) = inner.get(&schedule.key).map(|s| (**s).clone()) {
+            schedule.ran_at = Some(now.clone());
+            inner.insert(schedule.key.clone(), Arc::new(schedule));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Schedule {
+    pub key: db::Key,
+    pub trigger: Trigger,
+    pub action: Action,
+    pub only_live: bool,
+    pub ran_at: Option<DateTime<Utc>>,
+    pub group: Option<String>,
+    pub disabled: bool,
+}
+
+impl Schedule {
+    pub const NAME: &'static str = "schedule";
+
+    /// Convert a database schedule into an in-memory schedule.
+    pub fn from_db(schedule: &db::models::Schedule) -> Result<Schedule, failure::Error> {
+        let key = db::Key::new(&schedule.channel, &schedule.name);
+
+        let trigger = str::parse::<Trigger>(&schedule.trigger)
+            .with_context(|_| format_err!("bad trigger in schedule `{:?}` from db", schedule))?;
+
+        let action = str::parse::<Action>(&schedule.action)
+            .with_context(|_| format_err!("bad action in schedule `{:?}` from db", schedule))?;
+
+        Ok(Schedule {
+            key,
+            trigger,
+            action,
+            only_live: schedule.only_live,
+            ran_at: schedule.ran_at.map(|d| DateTime::from_utc(d, Utc)),
+            group: schedule.group.clone(),
+            disabled: schedule.disabled,
+        })
+    }
+
+    /// Get the next time the schedule should run, counting from `since`.
+    pub fn next_run(&self, since: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let since = self.ran_at.as_ref().unwrap_or(since);
+
+        match self.trigger {
+            Trigger::Interval(ref interval) => {
+                since.checked_add_signed(interval.checked_as_chrono()?)
+            }
+            Trigger::Cron(ref cron) => cron.next_after(since),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "trigger = \"{trigger}\", action = \"{action}\", only_live = {only_live}, group = {group}, disabled = {disabled}",
+            trigger = self.trigger,
+            action = self.action,
+            only_live = self.only_live,
+            group = self.group.as_ref().map(|g| g.as_str()).unwrap_or("*none*"),
+            disabled = self.disabled,
+        )
+    }
+}
+
+/// What triggers a schedule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Trigger {
+    /// Trigger at a fixed interval, like `5m`.
+    Interval(utils::Duration),
+    /// Trigger according to a cron expression, like `*/5 * * * *`.
+    Cron(utils::Cron),
+}
+
+impl std::str::FromStr for Trigger {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if !s.contains(char::is_whitespace) {
+            if let Ok(interval) = str::parse::<utils::Duration>(s) {
+                if interval.is_empty() {
+                    failure::bail!("interval must be non-empty");
+                }
+
+                let next = interval
+                    .checked_as_chrono()
+                    .and_then(|d| Utc::now().checked_add_signed(d));
+
+                if next.is_none() {
+                    failure::bail!("interval is too large");
+                }
+
+                return Ok(Trigger::Interval(interval));
+            }
+        }
+
+        let cron = str::parse::<utils::Cron>(s).map_err(|e| {
+            format_err!(
+                "expected an interval like `5m` or a cron expression like `*/5 * * * *`: {}",
+                e
+            )
+        })?;
+
+        Ok(Trigger::Cron(cron))
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Trigger::Interval(ref interval) => interval.fmt(fmt),
+            Trigger::Cron(ref cron) => cron.fmt(fmt),
+        }
+    }
+}
+
+impl serde::Serialize for Trigger {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Trigger {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        str::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// The action performed when a schedule triggers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Run a raw command, as if it was sent by the bot itself.
+    Raw(String),
+    /// Post a message in the channel.
+    Message(String),
+    /// Change a setting.
+    Setting { key: String, value: String },
+}
+
+impl Action {
+    /// Validate the action against the given settings, so that setting
+    /// actions refer to an existing setting and have a value of the right
+    /// type.
+    pub fn validate(&self, settings: &settings::Settings) -> Result<(), failure::Error> {
+        if let Action::Setting { ref key, ref value } = *self {
+            let schema = match settings.lookup(key) {
+                Some(schema) => schema,
+                None => failure::bail!("no setting named `{}`", key),
+            };
+
+            if let Err(e) = schema.ty.parse_as_json(value) {
+                failure::bail!("value is not a valid {} type: {}", schema.ty, e);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = utils::Words::new(s.trim());
+
+        let action = match it.next().as_ref().map(String::as_str) {
+            Some("raw") => Action::Raw(it.rest().trim().to_string()),
+            Some("say") => Action::Message(it.rest().trim().to_string()),
+            Some("set") => match it.next() {
+                Some(key) => Action::Setting {
+                    key,
+                    value: it.rest().trim().to_string(),
+                },
+                None => failure::bail!("expected `set <key> <value>`"),
+            },
+            _ => failure::bail!(
+                "expected one of: `raw <command>`, `say <message>`, or `set <key> <value>`"
+            ),
+        };
+
+        match action {
+            Action::Raw(ref rest)
+            | Action::Message(ref rest)
+            | Action::Setting {
+                value: ref rest, ..
+            } if rest.is_empty() => {
+                failure::bail!("action is missing an argument");
+            }
+            action => Ok(action),
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Action::Raw(ref command) => write!(fmt, "raw {}", command),
+            Action::Message(ref message) => write!(fmt, "say {}", message),
+            Action::Setting { ref key, ref value } => write!(fmt, "set {} {}", key, value),
+        }
+    }
+}
+
+impl serde::Serialize for Action {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Action {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        str::parse(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Schedule, Trigger};
+    use crate::{db, utils};
+    use chrono::{TimeZone as _, Utc};
+
+    fn schedule(trigger: &str) -> Schedule {
+        Schedule {
+            key: db::Key::new("#setbac", "test"),
+            trigger: str::parse(trigger).expect("trigger"),
+            action: Action::Message(String::from("hello")),
+            only_live: false,
+            ran_at: None,
+            group: None,
+            disabled: false,
+        }
+    }
+
+    #[test]
+    fn test_next_run() {
+        let since = Utc.ymd(2019, 10, 1).and_hms(12, 0, 30);
+
+        let mut interval = schedule("5m");
+        assert_eq!(
+            Some(Utc.ymd(2019, 10, 1).and_hms(12, 5, 30)),
+            interval.next_run(&since)
+        );
+
+        interval.ran_at = Some(Utc.ymd(2019, 10, 1).and_hms(13, 0, 0));
+        assert_eq!(
+            Some(Utc.ymd(2019, 10, 1).and_hms(13, 5, 0)),
+            interval.next_run(&since)
+        );
+
+        let cron = schedule("*/5 * * * *");
+        assert_eq!(
+            Some(Utc.ymd(2019, 10, 1).and_hms(12, 5, 0)),
+            cron.next_run(&since)
+        );
+
+        // NB: intervals which can't be represented never run, rather than
+        // panicking.
+        let mut huge = schedule("5m");
+        huge.trigger = Trigger::Interval(utils::Duration::seconds(u64::max_value()));
+        assert_eq!(None, huge.next_run(&since));
+
+        huge.trigger =
+            Trigger::Interval(utils::Duration::seconds((i64::max_value() / 1000) as u64));
+        assert_eq!(None, huge.next_run(&since));
+    }
+
+    #[test]
+    fn test_parse_trigger() {
+        assert_eq!(
+            Trigger::Interval(utils::Duration::seconds(300)),
+            str::parse::<Trigger>("5m").expect("interval")
+        );
+        assert!(matches(str::parse::<Trigger>("*/5 * * * *")));
+        assert!(str::parse::<Trigger>("0s").is_err());
+        assert!(str::parse::<Trigger>("100000000000000d").is_err());
+        assert!(str::parse::<Trigger>("not a trigger").is_err());
+
+        fn matches(trigger: Result<Trigger, failure::Error>) -> bool {
+            match trigger {
+                Ok(Trigger::Cron(..)) => true,
+                _ => false,
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_action() {
+        assert_eq!(
+            Action::Message(String::from("hello world")),
+            str::parse::<Action>("say  hello world ").expect("say")
+        );
+        assert_eq!(
+            Action::Raw(String::from("!song skip")),
+            str::parse::<Action>("raw !song skip").expect("raw")
+        );
+        assert_eq!(
+            Action::Setting {
+                key: String::from("song/enabled"),
+                value: String::from("false"),
+            },
+            str::parse::<Action>("set song/enabled false").expect("set")
+        );
+
+        assert!(str::parse::<Action>("say").is_err());
+        assert!(str::parse::<Action>("set song/enabled").is_err());
+        assert!(str::parse::<Action>("set").is_err());
+        assert!(str::parse::<Action>("shout hello").is_err());
+
+        let action = str::parse::<Action>("set song/enabled false").expect("set");
+        assert_eq!(action, str::parse(&action.to_string()).expect("roundtrip"));
+    }
+}
@@ -16,6 +16,10 @@ table! {
         text -> Text,
         group -> Nullable<Text>,
         disabled -> Bool,
+        cooldown -> Nullable<Integer>,
+        user_cooldown -> Nullable<Integer>,
+        scope -> Nullable<Text>,
+        price -> Nullable<BigInt>,
     }
 }
 
@@ -32,7 +36,9 @@ table! {
 table! {
     bad_words (word) {
         word -> Text,
-        why -> Nullable<Text>,
+        regex -> Bool,
+        action -> Text,
+        message -> Nullable<Text>,
     }
 }
 
@@ -63,6 +69,10 @@ table! {
         text -> Text,
         group -> Nullable<Text>,
         disabled -> Bool,
+        cooldown -> Nullable<Integer>,
+        user_cooldown -> Nullable<Integer>,
+        scope -> Nullable<Text>,
+        price -> Nullable<BigInt>,
     }
 }
 
@@ -90,6 +100,125 @@ table! {
     }
 }
 
+table! {
+    scripts (channel, name) {
+        channel -> Text,
+        name -> Text,
+        source -> Text,
+        disabled -> Bool,
+        group -> Nullable<Text>,
+    }
+}
+
+table! {
+    schedules (channel, name) {
+        channel -> Text,
+        name -> Text,
+        trigger -> Text,
+        action -> Text,
+        only_live -> Bool,
+        ran_at -> Nullable<Timestamp>,
+        disabled -> Bool,
+        group -> Nullable<Text>,
+    }
+}
+
+table! {
+    counters (channel, name) {
+        channel -> Text,
+        name -> Text,
+        count -> BigInt,
+    }
+}
+
+table! {
+    quotes (id) {
+        id -> Integer,
+        channel -> Text,
+        added_at -> Timestamp,
+        user -> Text,
+        game -> Nullable<Text>,
+        text -> Text,
+    }
+}
+
+table! {
+    command_invocations (id) {
+        id -> Integer,
+        channel -> Text,
+        command -> Text,
+        user -> Nullable<Text>,
+        outcome -> Text,
+        invoked_at -> Timestamp,
+        stream_started_at -> Nullable<Timestamp>,
+    }
+}
+
+table! {
+    strikes (channel, user) {
+        channel -> Text,
+        user -> Text,
+        count -> Integer,
+        struck_at -> Timestamp,
+    }
+}
+
+table! {
+    audit_log (id) {
+        id -> Integer,
+        channel -> Text,
+        actor -> Nullable<Text>,
+        action -> Text,
+        target -> Nullable<Text>,
+        reason -> Nullable<Text>,
+        logged_at -> Timestamp,
+    }
+}
+
+table! {
+    messages (id) {
+        id -> Integer,
+        channel -> Text,
+        message_id -> Nullable<Text>,
+        user -> Text,
+        display_name -> Nullable<Text>,
+        text -> Text,
+        tags -> Text,
+        sent_at -> Timestamp,
+        deleted -> Bool,
+    }
+}
+
+table! {
+    seen_users (channel, user) {
+        channel -> Text,
+        user -> Text,
+        first_seen_at -> Timestamp,
+    }
+}
+
+table! {
+    ban_list (user) {
+        user -> Text,
+        action -> Text,
+        reason -> Nullable<Text>,
+        source -> Nullable<Text>,
+        added_at -> Timestamp,
+    }
+}
+
+table! {
+    ledger (id) {
+        id -> Integer,
+        channel -> Text,
+        user -> Text,
+        amount -> BigInt,
+        reason -> Text,
+        counterparty -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
 // Grants that have been initialized from their default configuration.
 table! {
     initialized_grants (scope) {
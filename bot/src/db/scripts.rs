@@ -0,0 +1,156 @@
+use crate::{db, script};
+use diesel::prelude::*;
+use failure::{format_err, ResultExt as _};
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use std::{fmt, sync::Arc};
+
+/// Local database wrapper.
+#[derive(Clone)]
+struct Database(db::Database);
+
+impl Database {
+    private_database_group_fns!(scripts, Script, db::Key);
+
+    /// Edit the source of the given script.
+    fn edit(
+        &self,
+        key: &db::Key,
+        source: &str,
+    ) -> Result<Option<db::models::Script>, failure::Error> {
+        use db::schema::scripts::dsl;
+        let c = self.0.pool.lock();
+
+        let filter =
+            dsl::scripts.filter(dsl::channel.eq(&key.channel).and(dsl::name.eq(&key.name)));
+
+        match filter.clone().first::<db::models::Script>(&*c).optional()? {
+            None => {
+                let script = db::models::Script {
+                    channel: key.channel.to_string(),
+                    name: key.name.to_string(),
+                    source: source.to_string(),
+                    disabled: false,
+                    group: None,
+                };
+
+                diesel::insert_into(dsl::scripts)
+                    .values(&script)
+                    .execute(&*c)?;
+                Ok(Some(script))
+            }
+            Some(script) => {
+                let mut set = db::models::UpdateScript::default();
+                set.source = Some(source);
+                diesel::update(filter).set(&set).execute(&*c)?;
+
+                if script.disabled {
+                    return Ok(None);
+                }
+
+                Ok(Some(script))
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Scripts {
+    inner: Arc<RwLock<HashMap<db::Key, Arc<Script>>>>,
+    db: Database,
+}
+
+impl Scripts {
+    database_group_fns!(Script, db::Key);
+
+    /// Construct a new scripts store with a db.
+    pub fn load(db: db::Database) -> Result<Scripts, failure::Error> {
+        let mut inner = HashMap::new();
+
+        let db = Database(db);
+
+        for script in db.list()? {
+            let script = Script::from_db(&script)?;
+            inner.insert(script.key.clone(), Arc::new(script));
+        }
+
+        Ok(Scripts {
+            inner: Arc::new(RwLock::new(inner)),
+            db,
+        })
+    }
+
+    /// Edit the source of a script.
+    ///
+    /// The source is expected to already have been compiled into `program`.
+    pub fn edit(
+        &self,
+        channel: &str,
+        name: &str,
+        source: &str,
+        program: script::Program,
+    ) -> Result<(), failure::Error> {
+        let key = db::Key::new(channel, name);
+
+        let mut inner = self.inner.write();
+
+        if let Some(script) = self.db.edit(&key, source)? {
+            inner.insert(
+                key.clone(),
+                Arc::new(Script {
+                    key,
+                    source: source.to_string(),
+                    program: Arc::new(program),
+                    group: script.group,
+                    disabled: script.disabled,
+                }),
+            );
+        } else {
+            inner.remove(&key);
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Script {
+    pub key: db::Key,
+    pub source: String,
+    #[serde(skip)]
+    pub program: Arc<script::Program>,
+    pub group: Option<String>,
+    pub disabled: bool,
+}
+
+impl Script {
+    pub const NAME: &'static str = "script";
+
+    /// Convert a database script into an in-memory script.
+    pub fn from_db(script: &db::models::Script) -> Result<Script, failure::Error> {
+        let key = db::Key::new(&script.channel, &script.name);
+
+        let program = script::Program::parse(&script.source)
+            .with_context(|_| format_err!("failed to compile script `{:?}` from db", script))?;
+
+        Ok(Script {
+            key,
+            source: script.source.clone(),
+            program: Arc::new(program),
+            group: script.group.clone(),
+            disabled: script.disabled,
+        })
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            fmt,
+            "source = \"{source}\", group = {group}, disabled = {disabled}",
+            source = self.source,
+            group = self.group.as_ref().map(|g| g.as_str()).unwrap_or("*none*"),
+            disabled = self.disabled,
+        )
+    }
+}
@@ -0,0 +1,46 @@
+use crate::db::{self, models, schema};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+#[derive(Clone)]
+pub struct SeenUsers {
+    db: db::Database,
+}
+
+impl SeenUsers {
+    /// Open the seen users database.
+    pub fn load(db: db::Database) -> Result<Self, failure::Error> {
+        Ok(SeenUsers { db })
+    }
+
+    /// Mark a user as seen in the given channel.
+    ///
+    /// Returns when the user was first seen, and whether this is the first
+    /// time they were seen.
+    pub fn seen(&self, channel: &str, user: &str) -> Result<(NaiveDateTime, bool), failure::Error> {
+        use self::schema::seen_users::dsl;
+        let c = self.db.pool.lock();
+
+        let first_seen_at = dsl::seen_users
+            .filter(dsl::channel.eq(channel).and(dsl::user.eq(user)))
+            .select(dsl::first_seen_at)
+            .first::<NaiveDateTime>(&*c)
+            .optional()?;
+
+        if let Some(first_seen_at) = first_seen_at {
+            return Ok((first_seen_at, false));
+        }
+
+        let seen = models::SeenUser {
+            channel: channel.to_string(),
+            user: user.to_string(),
+            first_seen_at: Utc::now().naive_utc(),
+        };
+
+        diesel::insert_into(dsl::seen_users)
+            .values(&seen)
+            .execute(&*c)?;
+
+        Ok((seen.first_seen_at, true))
+    }
+}
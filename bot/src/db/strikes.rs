@@ -0,0 +1,162 @@
+use crate::{
+    db::{self, models, schema},
+    utils,
+};
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+
+pub use self::models::Strike;
+
+#[derive(Clone)]
+pub struct Strikes {
+    db: db::Database,
+}
+
+impl Strikes {
+    /// Open the strikes database.
+    pub fn load(db: db::Database) -> Result<Self, failure::Error> {
+        Ok(Strikes { db })
+    }
+
+    /// Get the strikes a user has after decay.
+    ///
+    /// One strike decays for every full `decay` period since the last strike.
+    /// Returns `None` if the user has no strikes.
+    pub fn get(
+        &self,
+        channel: &str,
+        user: &str,
+        decay: &utils::Duration,
+    ) -> Result<Option<Strike>, failure::Error> {
+        use self::schema::strikes::dsl;
+        let c = self.db.pool.lock();
+
+        let strike = dsl::strikes
+            .filter(dsl::channel.eq(channel).and(dsl::user.eq(user)))
+            .first::<models::Strike>(&*c)
+            .optional()?;
+
+        let now = Utc::now().naive_utc();
+
+        Ok(strike
+            .map(|s| Strike {
+                count: decayed(s.count, s.struck_at, now, decay),
+                ..s
+            })
+            .filter(|s| s.count > 0))
+    }
+
+    /// Give a user a strike, returning the number of strikes they have after
+    /// decay.
+    pub fn add(
+        &self,
+        channel: &str,
+        user: &str,
+        decay: &utils::Duration,
+    ) -> Result<u32, failure::Error> {
+        use self::schema::strikes::dsl;
+        let c = self.db.pool.lock();
+
+        let filter = dsl::strikes.filter(dsl::channel.eq(channel).and(dsl::user.eq(user)));
+        let now = Utc::now().naive_utc();
+
+        let strike = filter.clone().first::<models::Strike>(&*c).optional()?;
+
+        let count = match strike {
+            Some(strike) => {
+                let count = decayed(strike.count, strike.struck_at, now, decay).saturating_add(1);
+
+                diesel::update(filter)
+                    .set((dsl::count.eq(count), dsl::struck_at.eq(now)))
+                    .execute(&*c)?;
+
+                count
+            }
+            None => {
+                let strike = models::Strike {
+                    channel: channel.to_string(),
+                    user: user.to_string(),
+                    count: 1,
+                    struck_at: now,
+                };
+
+                diesel::insert_into(dsl::strikes)
+                    .values(&strike)
+                    .execute(&*c)?;
+
+                1
+            }
+        };
+
+        Ok(count as u32)
+    }
+
+    /// Clear all strikes of a user.
+    ///
+    /// Returns `false` if the user didn't have any strikes.
+    pub fn clear(&self, channel: &str, user: &str) -> Result<bool, failure::Error> {
+        use self::schema::strikes::dsl;
+        let c = self.db.pool.lock();
+
+        let count =
+            diesel::delete(dsl::strikes.filter(dsl::channel.eq(channel).and(dsl::user.eq(user))))
+                .execute(&*c)?;
+
+        Ok(count == 1)
+    }
+}
+
+/// Number of strikes left after decay.
+///
+/// A decay too large to be represented means strikes never decay.
+fn decayed(
+    count: i32,
+    struck_at: NaiveDateTime,
+    now: NaiveDateTime,
+    decay: &utils::Duration,
+) -> i32 {
+    if decay.is_empty() {
+        return count;
+    }
+
+    let decay = match decay.checked_as_chrono() {
+        Some(decay) => decay,
+        None => return count,
+    };
+
+    let elapsed = now.signed_duration_since(struck_at).num_seconds();
+    let periods = elapsed / decay.num_seconds();
+
+    if periods <= 0 {
+        return count;
+    }
+
+    if periods >= i64::from(count) {
+        return 0;
+    }
+
+    count - periods as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decayed;
+    use crate::utils::Duration;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_decayed() {
+        let at = |h| NaiveDate::from_ymd(2019, 10, 19).and_hms(h, 0, 0);
+        let decay = Duration::hours(2);
+
+        assert_eq!(3, decayed(3, at(0), at(1), &decay));
+        assert_eq!(2, decayed(3, at(0), at(2), &decay));
+        assert_eq!(1, decayed(3, at(0), at(5), &decay));
+        assert_eq!(0, decayed(3, at(0), at(23), &decay));
+        assert_eq!(3, decayed(3, at(0), at(23), &Duration::default()));
+        assert_eq!(
+            3,
+            decayed(3, at(0), at(23), &Duration::seconds(u64::max_value()))
+        );
+    }
+}
@@ -1,42 +1,328 @@
-use crate::{db, template};
+use crate::{db, template, utils};
 use hashbrown::HashMap;
 use parking_lot::{RwLock, RwLockReadGuard};
-use std::sync::Arc;
+use std::{fmt, sync::Arc};
 
-/// Tokenize the given word.
-pub fn tokenize(word: &str) -> String {
-    let word = word.to_lowercase();
-    inflector::string::singularize::to_singular(&word)
+/// Words shorter than this are never matched phonetically, since too many
+/// common short words sound alike.
+const MIN_PHONETIC_LEN: usize = 5;
+/// Runs of at least this many repeated characters are considered stretched,
+/// like in `baaadword`.
+const MIN_STRETCHED_RUN: usize = 3;
+
+/// Normalize the given word.
+///
+/// Punctuation surrounding the word is trimmed, the word is lowercased,
+/// homoglyphs and leetspeak substitutions are mapped to the letters they
+/// resemble, punctuation inserted into the word is removed, and finally the
+/// word is singularized.
+///
+/// So `B4DW0RD!` and `b.a.d.w.o.r.d` both normalize to `badword`. Digits are
+/// only substituted in words which also have letters, so numbers like `1337`
+/// are kept as they are. Repeated characters are kept, see `fold`.
+pub fn normalize(word: &str) -> String {
+    let word = word.trim_matches(|c: char| c.is_ascii_punctuation());
+    let leet = word.chars().any(char::is_alphabetic);
+
+    let out = word
+        .chars()
+        .flat_map(char::to_lowercase)
+        .map(|c| {
+            if c.is_numeric() && !leet {
+                c
+            } else {
+                substitute(c)
+            }
+        })
+        .filter(|c| c.is_alphanumeric())
+        .collect::<String>();
+
+    inflector::string::singularize::to_singular(&out)
+}
+
+/// Fold runs of repeated characters in a normalized word into one.
+///
+/// Folded words are only compared when the word being tested is stretched, so
+/// that `baaadword` matches `badword`, but `as` doesn't match `ass`.
+pub fn fold(word: &str) -> String {
+    let mut out = String::with_capacity(word.len());
+    let mut last = None;
+
+    for c in word.chars() {
+        if last != Some(c) {
+            out.push(c);
+        }
+
+        last = Some(c);
+    }
+
+    out
+}
+
+/// Test if the normalized word has a run of repeated characters long enough
+/// to be stretched on purpose.
+fn is_stretched(word: &str) -> bool {
+    let mut last = None;
+    let mut run = 0;
+
+    for c in word.chars() {
+        if last == Some(c) {
+            run += 1;
+        } else {
+            run = 1;
+        }
+
+        if run >= MIN_STRETCHED_RUN {
+            return true;
+        }
+
+        last = Some(c);
+    }
+
+    false
+}
+
+/// Map a lowercase character to the letter it is commonly used in place of.
+fn substitute(c: char) -> char {
+    match c {
+        // leetspeak
+        '4' | '@' => 'a',
+        '8' => 'b',
+        '3' => 'e',
+        '1' | '!' | '|' => 'i',
+        '0' => 'o',
+        '5' | '$' => 's',
+        '7' | '+' => 't',
+        // latin letters with diacritics
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
+        'ç' => 'c',
+        'è' | 'é' | 'ê' | 'ë' => 'e',
+        'ì' | 'í' | 'î' | 'ï' => 'i',
+        'ñ' => 'n',
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' => 'o',
+        'ù' | 'ú' | 'û' | 'ü' => 'u',
+        'ý' | 'ÿ' => 'y',
+        // cyrillic and greek homoglyphs
+        'а' | 'α' => 'a',
+        'в' | 'β' => 'b',
+        'с' => 'c',
+        'е' | 'ε' => 'e',
+        'н' | 'η' => 'h',
+        'і' | 'ι' => 'i',
+        'ј' => 'j',
+        'к' | 'κ' => 'k',
+        'м' => 'm',
+        'о' | 'ο' => 'o',
+        'р' | 'ρ' => 'p',
+        'ѕ' => 's',
+        'т' | 'τ' => 't',
+        'υ' => 'u',
+        'ν' => 'v',
+        'х' | 'χ' => 'x',
+        'у' => 'y',
+        // fullwidth forms
+        '\u{ff01}'..='\u{ff5e}' => substitute(std::char::from_u32(c as u32 - 0xfee0).unwrap_or(c)),
+        c => c,
+    }
+}
+
+/// The action to take when a bad word is matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    /// Delete the message.
+    Delete,
+    /// Only respond with the message of the word, without deleting anything.
+    Warn,
+    /// Delete the message and time out the user for the given duration.
+    Timeout(utils::Duration),
+    /// Ban the user.
+    Ban,
+}
+
+impl Default for Action {
+    fn default() -> Self {
+        Action::Delete
+    }
+}
+
+impl std::str::FromStr for Action {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut it = s.trim().split_whitespace();
+
+        let action = match it.next() {
+            Some("delete") => Action::Delete,
+            Some("warn") => Action::Warn,
+            Some("timeout") => match it.next() {
+                Some(duration) => Action::Timeout(str::parse(duration)?),
+                None => failure::bail!("expected `timeout <duration>`"),
+            },
+            Some("ban") => Action::Ban,
+            _ => {
+                failure::bail!("expected one of: `delete`, `warn`, `timeout <duration>`, or `ban`")
+            }
+        };
+
+        if it.next().is_some() {
+            failure::bail!("unexpected trailing arguments to action");
+        }
+
+        Ok(action)
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Action::Delete => "delete".fmt(fmt),
+            Action::Warn => "warn".fmt(fmt),
+            Action::Timeout(ref duration) => write!(fmt, "timeout {}", duration),
+            Action::Ban => "ban".fmt(fmt),
+        }
+    }
 }
 
 #[derive(Debug, Default)]
 struct Inner {
-    hashed: HashMap<eudex::Hash, Arc<Word>>,
+    /// Words by the phonetic hash of their folded form, for words which are
+    /// long enough to be matched phonetically.
+    hashed: HashMap<eudex::Hash, Vec<Arc<Word>>>,
+    /// Words by their folded form.
+    folded: HashMap<String, Vec<Arc<Word>>>,
     exact: HashMap<String, Arc<Word>>,
+    patterns: Vec<Arc<Word>>,
 }
 
 impl Inner {
-    /// Insert a bad word.
-    fn insert(&mut self, word: &str, why: Option<&str>) -> Result<(), failure::Error> {
-        let word = Word {
-            word: tokenize(word),
-            why: why.map(template::Template::compile).transpose()?,
-        };
+    /// Insert a bad word, replacing any existing word with the same key.
+    #[cfg(test)]
+    fn insert(
+        &mut self,
+        word: &str,
+        regex: bool,
+        action: Action,
+        message: Option<&str>,
+    ) -> Result<(), failure::Error> {
+        self.insert_word(word, Word::new(word, regex, action, message)?);
+        Ok(())
+    }
+
+    /// Insert a built bad word, replacing any existing word with the same
+    /// key.
+    fn insert_word(&mut self, key: &str, word: Word) {
+        self.remove(key);
 
         let word = Arc::new(word);
-        self.hashed
-            .insert(eudex::Hash::new(&word.word), Arc::clone(&word));
+
+        if word.pattern.is_some() {
+            self.patterns.push(word);
+            return;
+        }
+
+        let folded = fold(&word.word);
+
+        if folded.chars().count() >= MIN_PHONETIC_LEN {
+            self.hashed
+                .entry(eudex::Hash::new(&folded))
+                .or_default()
+                .push(Arc::clone(&word));
+        }
+
+        self.folded
+            .entry(folded)
+            .or_default()
+            .push(Arc::clone(&word));
         self.exact.insert(word.word.to_string(), Arc::clone(&word));
-        Ok(())
     }
 
-    /// Insert a bad word.
+    /// Remove a bad word or pattern.
     fn remove(&mut self, word: &str) {
-        let word = tokenize(word);
+        self.patterns.retain(|w| w.word != word);
+
+        let word = normalize(word);
+
+        if self.exact.remove(&word).is_none() {
+            return;
+        }
+
+        let folded = fold(&word);
+
+        // NB: different words might share the same folded form or hash, only
+        // remove this word.
+        if let Some(words) = self.folded.get_mut(&folded) {
+            words.retain(|w| w.word != word);
 
-        // TODO: there might be hash conflicts. Deal with them.
-        self.hashed.remove(&eudex::Hash::new(&word));
-        self.exact.remove(&word);
+            if words.is_empty() {
+                self.folded.remove(&folded);
+            }
+        }
+
+        let hash = eudex::Hash::new(&folded);
+
+        if let Some(words) = self.hashed.get_mut(&hash) {
+            words.retain(|w| w.word != word);
+
+            if words.is_empty() {
+                self.hashed.remove(&hash);
+            }
+        }
+    }
+
+    /// Test a single normalized word.
+    fn test_word(&self, word: &str) -> Option<&Arc<Word>> {
+        if let Some(w) = self.exact.get(word) {
+            return Some(w);
+        }
+
+        let folded = fold(word);
+
+        if is_stretched(word) {
+            if let Some(w) = self.folded.get(&folded).and_then(|w| w.first()) {
+                return Some(w);
+            }
+        }
+
+        if folded.chars().count() < MIN_PHONETIC_LEN {
+            return None;
+        }
+
+        self.hashed.get(&eudex::Hash::new(&folded))?.first()
+    }
+
+    /// Test the given message.
+    fn test(&self, message: &str) -> Option<Arc<Word>> {
+        let mut normalized = Vec::new();
+
+        for word in message.split_whitespace() {
+            let word = normalize(word);
+
+            if word.is_empty() {
+                continue;
+            }
+
+            if let Some(w) = self.test_word(&word) {
+                return Some(Arc::clone(w));
+            }
+
+            normalized.push(word);
+        }
+
+        if self.patterns.is_empty() {
+            return None;
+        }
+
+        let normalized = normalized.join(" ");
+
+        for w in &self.patterns {
+            if let Some(pattern) = w.pattern.as_ref() {
+                if pattern.is_match(message) || pattern.is_match(&normalized) {
+                    return Some(Arc::clone(w));
+                }
+            }
+        }
+
+        None
     }
 }
 
@@ -46,7 +332,13 @@ pub trait Backend: Clone + Send + Sync {
     fn list(&self) -> Result<Vec<db::models::BadWord>, failure::Error>;
 
     /// Insert or update an existing word.
-    fn edit(&self, word: &str, why: Option<&str>) -> Result<(), failure::Error>;
+    fn edit(
+        &self,
+        word: &str,
+        regex: bool,
+        action: &str,
+        message: Option<&str>,
+    ) -> Result<(), failure::Error>;
 
     /// Delete the given word from the backend.
     fn delete(&self, word: &str) -> Result<bool, failure::Error>;
@@ -64,7 +356,25 @@ impl Words {
         let mut inner = Inner::default();
 
         for word in db.list()? {
-            inner.insert(&word.word, word.why.as_ref().map(|s| s.as_str()))?;
+            let action = match str::parse(&word.action) {
+                Ok(action) => action,
+                Err(e) => {
+                    log::warn!("bad action for word `{}`: {}", word.word, e);
+                    Action::default()
+                }
+            };
+
+            let result = Word::new(
+                &word.word,
+                word.regex,
+                action,
+                word.message.as_ref().map(|s| s.as_str()),
+            );
+
+            match result {
+                Ok(parsed) => inner.insert_word(&word.word, parsed),
+                Err(e) => log::warn!("failed to load bad word `{}`: {}", word.word, e),
+            }
         }
 
         Ok(Words {
@@ -73,11 +383,20 @@ impl Words {
         })
     }
 
-    /// Insert a word into the bad words list.
-    pub fn edit(&self, word: &str, why: Option<&str>) -> Result<(), failure::Error> {
-        self.db.edit(word, why)?;
+    /// Insert a word or a regular expression into the bad words list.
+    pub fn edit(
+        &self,
+        word: &str,
+        regex: bool,
+        action: &Action,
+        message: Option<&str>,
+    ) -> Result<(), failure::Error> {
+        // NB: make sure the word is valid before storing it.
+        let parsed = Word::new(word, regex, action.clone(), message)?;
+
         let mut inner = self.inner.write();
-        inner.insert(word, why)?;
+        self.db.edit(word, regex, &action.to_string(), message)?;
+        inner.insert_word(word, parsed);
         Ok(())
     }
 
@@ -106,24 +425,168 @@ pub struct Tester<'a> {
 }
 
 impl Tester<'_> {
-    /// Test the given word.
-    pub fn test(&self, word: &str) -> Option<Arc<Word>> {
-        let word = tokenize(word);
+    /// Test the given message, returning the first bad word found in it.
+    ///
+    /// Every word in the message is normalized before it is compared.
+    /// Regular expressions are matched against both the original and the
+    /// normalized message.
+    pub fn test(&self, message: &str) -> Option<Arc<Word>> {
+        self.inner.test(message)
+    }
+}
+
+#[derive(Debug)]
+pub struct Word {
+    /// The normalized word, or the pattern if this is a regular expression.
+    pub word: String,
+    /// The compiled regular expression, if this is one.
+    pub pattern: Option<regex::Regex>,
+    /// The action to take when the word is matched.
+    pub action: Action,
+    /// Message to respond with when the word is matched.
+    pub message: Option<template::Template>,
+}
+
+impl Word {
+    /// Build a bad word, normalizing it or compiling it as a regular
+    /// expression.
+    fn new(
+        word: &str,
+        regex: bool,
+        action: Action,
+        message: Option<&str>,
+    ) -> Result<Word, failure::Error> {
+        let message = message.map(template::Template::compile).transpose()?;
+
+        if regex {
+            let pattern = regex::RegexBuilder::new(word)
+                .case_insensitive(true)
+                .build()?;
 
-        if let Some(w) = self.inner.hashed.get(&eudex::Hash::new(&word)) {
-            return Some(Arc::clone(w));
+            return Ok(Word {
+                word: word.to_string(),
+                pattern: Some(pattern),
+                action,
+                message,
+            });
         }
 
-        if let Some(w) = self.inner.exact.get(&word) {
-            return Some(Arc::clone(w));
+        let word = normalize(word);
+
+        if word.is_empty() {
+            failure::bail!("word is empty after normalization");
         }
 
-        None
+        Ok(Word {
+            word,
+            pattern: None,
+            action,
+            message,
+        })
     }
 }
 
-#[derive(Debug)]
-pub struct Word {
-    pub word: String,
-    pub why: Option<template::Template>,
+#[cfg(test)]
+mod tests {
+    use super::{fold, is_stretched, normalize, Action, Inner};
+    use crate::utils::Duration;
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!("badword", normalize("badword"));
+        assert_eq!("badword", normalize("B4DW0RD"));
+        assert_eq!("badword", normalize("b.a.d-w_o*r.d"));
+        assert_eq!("baaaddwooord", normalize("baaaddwooord"));
+        assert_eq!("badword", normalize("bаdwоrd"));
+        assert_eq!("badword", normalize("ｂａｄｗｏｒｄ"));
+        assert_eq!("badword", normalize("badwords"));
+        assert_eq!("", normalize("..."));
+        assert_eq!("1337", normalize("1337"));
+        assert_eq!("404", normalize("404!"));
+    }
+
+    #[test]
+    fn test_fold() {
+        assert_eq!("badword", fold("baaaddwooord"));
+        assert_eq!("as", fold("ass"));
+        assert!(is_stretched("baaadword"));
+        assert!(!is_stretched("ass"));
+        assert!(!is_stretched("balloon"));
+    }
+
+    #[test]
+    fn test_action() {
+        assert_eq!(Action::Delete, str::parse::<Action>("delete").unwrap());
+        assert_eq!(Action::Warn, str::parse::<Action>("warn").unwrap());
+        assert_eq!(Action::Ban, str::parse::<Action>("ban").unwrap());
+        assert_eq!(
+            Action::Timeout(Duration::seconds(600)),
+            str::parse::<Action>("timeout 10m").unwrap()
+        );
+        assert_eq!(
+            "timeout 10m",
+            Action::Timeout(Duration::seconds(600)).to_string()
+        );
+        assert!(str::parse::<Action>("timeout").is_err());
+        assert!(str::parse::<Action>("kick").is_err());
+    }
+
+    #[test]
+    fn test_words() -> Result<(), failure::Error> {
+        let mut inner = Inner::default();
+        inner.insert("frick", false, Action::Delete, None)?;
+        inner.insert(r"\bd+[a4]+r+n+\b", true, Action::Warn, None)?;
+
+        let action = |inner: &Inner, m| inner.test(m).map(|w| w.action.clone());
+
+        assert_eq!(Some(Action::Delete), action(&inner, "oh FR1CK!"));
+        assert_eq!(Some(Action::Delete), action(&inner, "oh f.r.i.c.k"));
+        assert_eq!(Some(Action::Warn), action(&inner, "daaarn it"));
+        assert_eq!(None, action(&inner, "oh hello"));
+
+        inner.remove("FRICK");
+        assert_eq!(None, action(&inner, "oh frick"));
+        assert!(inner.hashed.is_empty());
+        assert!(inner.folded.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_numbers() -> Result<(), failure::Error> {
+        let mut inner = Inner::default();
+        inner.insert("ass", false, Action::Delete, None)?;
+        inner.insert("is", false, Action::Delete, None)?;
+
+        let matches = |m| inner.test(m).is_some();
+
+        assert!(matches("4SS"));
+        assert!(matches("a55"));
+        assert!(!matches("455"));
+        assert!(!matches("call me at 455 1337"));
+        assert!(!matches("15 minutes"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_repeated_letters() -> Result<(), failure::Error> {
+        let mut inner = Inner::default();
+        inner.insert("ass", false, Action::Delete, None)?;
+        inner.insert("badword", false, Action::Delete, None)?;
+
+        let matches = |m| inner.test(m).is_some();
+
+        assert!(matches("4SS"));
+        assert!(matches("aaasss"));
+        assert!(matches("baaaddwooord"));
+
+        // NB: common words which only differ by repeated letters, or which
+        // sound alike, are not matched.
+        assert!(!matches("as"));
+        assert!(!matches("was"));
+        assert!(!matches("is"));
+        assert!(!matches("ash"));
+        assert!(!matches("class"));
+        assert!(!matches("baddword"));
+        Ok(())
+    }
 }
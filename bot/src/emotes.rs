@@ -363,43 +363,18 @@ impl Emotes {
         // is only filled if needed lazily.
         let mut message_chars = None::<Vec<char>>;
 
-        // 300354391:8-16/28087:0-6
-        for emote in emotes.split('/') {
-            let mut p = emote.split(':');
-
-            let id = match p.next() {
-                Some(id) => str::parse::<u64>(id)?,
-                None => continue,
-            };
-
-            let span = match p.next() {
-                Some(rest) => first_span(rest),
-                None => continue,
-            };
-
+        for span in twitch_spans(emotes) {
             let message = message_chars.get_or_insert_with(|| message.chars().collect());
 
-            let word: String = match span {
-                Some((s, e)) => message[s..=e].iter().collect(),
+            let word: String = match message.get(span.start..=span.end) {
+                Some(word) => word.iter().collect(),
                 None => continue,
             };
 
-            out.insert(word, Self::twitch_emote(id));
+            out.insert(word, Self::twitch_emote(span.id));
         }
 
-        return Ok(out);
-
-        fn first_span(rest: &str) -> Option<(usize, usize)> {
-            let mut it = rest.split(',').next()?.split('-');
-
-            let s = it.next()?;
-            let s = str::parse::<usize>(&s).ok()?;
-
-            let e = it.next()?;
-            let e = str::parse::<usize>(&e).ok()?;
-
-            Some((s, e))
-        }
+        Ok(out)
     }
 
     /// Get all user emotes.
@@ -838,6 +813,39 @@ impl Emotes {
     }
 }
 
+/// A single emote in a Twitch message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    /// The id of the emote.
+    pub id: u64,
+    /// The character offset where the emote starts.
+    pub start: usize,
+    /// The character offset where the emote ends, inclusive.
+    pub end: usize,
+}
+
+/// Iterate over all emote spans in the `emotes` tag of a Twitch message.
+///
+/// The tag looks like `300354391:8-16/28087:0-6,18-24`. Malformed spans are
+/// skipped.
+pub fn twitch_spans(emotes: &str) -> impl Iterator<Item = Span> + '_ {
+    emotes
+        .split('/')
+        .filter_map(|emote| {
+            let mut p = emote.splitn(2, ':');
+            let id = str::parse::<u64>(p.next()?).ok()?;
+            Some((id, p.next()?))
+        })
+        .flat_map(|(id, spans)| {
+            spans.split(',').filter_map(move |span| {
+                let mut it = span.splitn(2, '-');
+                let start = str::parse::<usize>(it.next()?).ok()?;
+                let end = str::parse::<usize>(it.next()?).ok()?;
+                Some(Span { id, start, end })
+            })
+        })
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type")]
 enum Item {
@@ -1019,7 +1027,7 @@ impl<'a> Iterator for Words<'a> {
 
 #[cfg(test)]
 mod tests {
-    use super::Words;
+    use super::{twitch_spans, Span, Words};
 
     #[test]
     pub fn test_words() {
@@ -1038,4 +1046,31 @@ mod tests {
             w.collect::<Vec<_>>()
         );
     }
+
+    #[test]
+    pub fn test_twitch_spans() {
+        let spans =
+            twitch_spans("300354391:8-16/28087:0-6,18-24/bad:1-2/1:x-3").collect::<Vec<_>>();
+
+        assert_eq!(
+            vec![
+                Span {
+                    id: 300354391,
+                    start: 8,
+                    end: 16
+                },
+                Span {
+                    id: 28087,
+                    start: 0,
+                    end: 6
+                },
+                Span {
+                    id: 28087,
+                    start: 18,
+                    end: 24
+                },
+            ],
+            spans
+        );
+    }
 }
@@ -1,9 +1,9 @@
 use crate::{
     api::{self, twitch},
-    auth::{Auth, Role, Scope},
+    auth::{Auth, Role, RoleOrScope, Scope},
     bus, command,
-    currency::{Currency, CurrencyBuilder},
-    db, idle,
+    currency::{BalanceTransferError, Currency, CurrencyBuilder, LedgerReason},
+    db, emotes, idle,
     injector::{self, Injector, Key},
     message_log::MessageLog,
     module, oauth2,
@@ -27,15 +27,34 @@ use tracing::trace_span;
 use tracing_futures::Instrument as _;
 
 // re-exports
-pub use self::sender::Sender;
+pub use self::{
+    links::LinkPermits,
+    ranks::{Rank, Ranks},
+    sender::Sender,
+};
 
 mod chat_log;
 mod currency_admin;
+mod links;
+mod lockdown;
+mod ranks;
+mod rate_limit;
 mod sender;
+mod spam;
+mod strikes;
+#[cfg(test)]
+pub(crate) mod testing;
 
 const SERVER: &'static str = "irc.chat.twitch.tv";
 const TWITCH_TAGS_CAP: &'static str = "twitch.tv/tags";
 const TWITCH_COMMANDS_CAP: &'static str = "twitch.tv/commands";
+const TWITCH_MEMBERSHIP_CAP: &'static str = "twitch.tv/membership";
+/// How many users the ban list is remembered as applied to before users who
+/// are no longer on the ban list are forgotten.
+const BAN_LIST_APPLIED_LIMIT: usize = 10_000;
+/// How far back messages are considered visible in chat when it's cleared,
+/// and marked as deleted in the chat history.
+const CLEAR_WINDOW_MINUTES: i64 = 5;
 
 struct TwitchSetup {
     streamer_stream: injector::Stream<oauth2::SyncToken>,
@@ -172,6 +191,7 @@ pub struct Irc {
     pub settings: settings::Settings,
     pub auth: Auth,
     pub global_channel: Arc<RwLock<Option<String>>>,
+    pub global_channels: Arc<RwLock<Vec<String>>>,
     pub injector: Injector,
     pub stream_state_tx: mpsc::Sender<stream_info::StreamState>,
     pub message_log: MessageLog,
@@ -188,6 +208,7 @@ impl Irc {
             settings,
             auth,
             global_channel,
+            global_channels,
             injector,
             stream_state_tx,
             message_log,
@@ -222,11 +243,61 @@ impl Irc {
             let chat_channel = format!("#{}", channel.name);
             *global_channel.write() = Some(chat_channel.clone());
 
+            let (mut channels_stream, extra_channels) = settings
+                .stream::<HashSet<String>>("chat/channels")
+                .or_default()?;
+
+            // NB: the streamer's channel is always the first one.
+            let mut joined = vec![(streamer.clone(), channel.clone(), settings.clone())];
+
+            for name in extra_channels {
+                let name = name.trim_start_matches('#').to_lowercase();
+
+                if name.is_empty() || joined.iter().any(|(_, c, _)| c.name == name) {
+                    continue;
+                }
+
+                // NB: a channel which can't be looked up is skipped, so that
+                // it doesn't prevent joining the other channels.
+                let user = match streamer_twitch.user_by_login(&name).await {
+                    Ok(Some(user)) => twitch::User::from(user),
+                    Ok(None) => {
+                        log::warn!("Not joining channel `{}`: no such user", name);
+                        continue;
+                    }
+                    Err(e) => {
+                        log_err!(
+                            e,
+                            "failed to get user for channel `{}`, not joining it",
+                            name
+                        );
+                        continue;
+                    }
+                };
+
+                let extra = match streamer_twitch.channel_by_id(&user.id).await {
+                    Ok(extra) => extra,
+                    Err(e) => {
+                        log_err!(e, "failed to get channel `{}`, not joining it", name);
+                        continue;
+                    }
+                };
+                let scope = format!("{}/{}", settings::CHANNELS_PREFIX, name);
+                joined.push((Arc::new(user), Arc::new(extra), settings.scoped(&scope)));
+            }
+
+            let chat_channels = joined
+                .iter()
+                .map(|(_, c, _)| format!("#{}", c.name))
+                .collect::<Vec<_>>();
+
+            *global_channels.write() = chat_channels.clone();
+
             let access_token = bot_twitch.token.read()?.access_token().to_string();
 
             let irc_client_config = client::data::config::Config {
                 nickname: Some(bot.name.to_string()),
-                channels: vec![chat_channel.clone()],
+                channels: chat_channels.clone(),
                 password: Some(format!("oauth:{}", access_token)),
                 server: Some(String::from(SERVER)),
                 port: Some(6697),
@@ -237,14 +308,7 @@ impl Irc {
             let mut client = Client::from_config(irc_client_config).await?;
             client.identify()?;
 
-            let chat_settings = settings.scoped("chat");
-
-            let url_whitelist_enabled = chat_settings.var("url-whitelist/enabled", true)?;
-            let bad_words_enabled = chat_settings.var("bad-words/enabled", false)?;
-            let sender_ty = chat_settings.var("sender-type", sender::Type::Chat)?;
-            let threshold = chat_settings.var("idle-detection/threshold", 5)?;
-            let idle = idle::Idle::new(threshold);
-
+            let sender_ty = settings.var("chat/sender-type", sender::Type::Chat)?;
             let nightbot = injector.var::<Arc<api::NightBot>>()?;
 
             let buckets = LeakyBuckets::new();
@@ -257,120 +321,86 @@ impl Irc {
                 &buckets,
             )?;
 
-            let mut futures = futures::stream::FuturesUnordered::new();
+            // NB: futures which are set up per channel, before the collection
+            // of futures borrowing the channels is constructed.
+            let mut pending = Vec::new();
+            let mut setups = Vec::new();
 
-            let future = async move {
-                buckets.coordinate().await?;
-                Ok(())
-            };
+            for (index, (streamer, channel, settings)) in joined.into_iter().enumerate() {
+                let chat_settings = settings.scoped("chat");
+                let threshold = chat_settings.var("idle-detection/threshold", 5)?;
 
-            futures.push(
-                future
-                    .instrument(trace_span!(target: "futures", "buckets-coordinator",))
-                    .boxed(),
-            );
+                let sender = match index {
+                    0 => sender.clone(),
+                    _ => sender.for_channel(format!("#{}", channel.name)),
+                };
 
-            let stream_info = {
                 let (stream_info, mut stream_state_rx, future) =
                     stream_info::setup(streamer.clone(), streamer_twitch.clone());
 
-                let mut stream_state_tx = stream_state_tx.clone();
-
-                let forward = async move {
-                    loop {
-                        let m = stream_state_rx.select_next_some().await;
-                        stream_state_tx
-                            .send(m)
-                            .await
-                            .map_err(|_| format_err!("failed to send"))?;
-                    }
-                };
-
-                futures.push(
-                    forward
-                        .instrument(trace_span!(target: "futures", "stream-info-forward",))
-                        .boxed(),
-                );
-                futures.push(
+                pending.push(
                     future
                         .instrument(trace_span!(target: "futures", "stream-info-refresh",))
                         .boxed(),
                 );
 
-                stream_info
-            };
-
-            futures.push(
-                refresh_mods_future(sender.clone())
-                    .instrument(trace_span!(target: "futures", "refresh-mods",))
-                    .boxed(),
-            );
-
-            let mut handlers = module::Handlers::default();
+                // Only the state of the streamer's own stream is forwarded.
+                if index == 0 {
+                    let mut stream_state_tx = stream_state_tx.clone();
+
+                    let forward = async move {
+                        loop {
+                            let m = stream_state_rx.select_next_some().await;
+                            stream_state_tx
+                                .send(m)
+                                .await
+                                .map_err(|_| format_err!("failed to send"))?;
+                        }
+                    };
 
-            for module in modules.iter() {
-                if log::log_enabled!(log::Level::Trace) {
-                    log::trace!("initializing module: {}", module.ty());
+                    pending.push(
+                        forward
+                            .instrument(trace_span!(target: "futures", "stream-info-forward",))
+                            .boxed(),
+                    );
                 }
 
-                let result = module
-                    .hook(module::HookContext {
-                        handlers: &mut handlers,
-                        futures: &mut futures,
-                        stream_info: &stream_info,
-                        idle: &idle,
-                        twitch: &bot_twitch,
-                        streamer_twitch: &streamer_twitch,
-                        sender: &sender,
-                        settings: &settings,
-                        injector: &injector,
-                        auth: &auth,
-                    })
-                    .await;
-
-                result.with_context(|_| {
-                    format_err!("failed to initialize module: {}", module.ty())
-                })?;
-            }
+                pending.push(
+                    refresh_mods_future(sender.clone())
+                        .instrument(trace_span!(target: "futures", "refresh-mods",))
+                        .boxed(),
+                );
 
-            let (future, currency_handler) = currency_admin::setup(&injector)?;
+                setups.push(ChannelSetup {
+                    streamer,
+                    channel,
+                    sender,
+                    settings,
+                    chat_settings,
+                    idle: idle::Idle::new(threshold),
+                    stream_info,
+                });
+            }
 
-            futures.push(
-                future
-                    .instrument(trace_span!(target: "futures", "currency-adminm",))
-                    .boxed(),
-            );
+            let mut futures = futures::stream::FuturesUnordered::new();
 
-            let future = currency_loop(
-                streamer_twitch.clone(),
-                channel.clone(),
-                sender.clone(),
-                idle.clone(),
-                &injector,
-                &chat_settings,
-                &settings,
-            )?;
+            let future = async move {
+                buckets.coordinate().await?;
+                Ok(())
+            };
 
             futures.push(
                 future
-                    .instrument(trace_span!(target: "futures", "currency-loop",))
+                    .instrument(trace_span!(target: "futures", "buckets-coordinator",))
                     .boxed(),
             );
 
-            let (mut whitelisted_hosts_stream, whitelisted_hosts) =
-                chat_settings.stream("whitelisted-hosts").or_default()?;
-
-            let (mut moderator_cooldown_stream, moderator_cooldown) =
-                chat_settings.stream("moderator-cooldown").optional()?;
+            for future in pending {
+                futures.push(future);
+            }
 
             let (mut api_url_stream, api_url) = settings.stream("remote/api-url").optional()?;
 
-            let join_message = chat_settings.get::<String>("join-message")?;
-
-            let leave_message = chat_settings
-                .get::<String>("leave-message")?
-                .unwrap_or_else(|| String::from("Leaving chat... VoHiYo"));
-
             let mut chat_log_builder = chat_log::Builder::new(
                 bot_twitch.clone(),
                 &injector,
@@ -380,37 +410,166 @@ impl Irc {
 
             let (mut commands_stream, commands) = injector.stream();
             let (mut aliases_stream, aliases) = injector.stream();
+            let (mut currency_stream, currency) = injector.stream::<Currency>();
+            let (mut counters_stream, counters) = injector.stream::<db::Counters>();
+            let (mut command_stats_stream, command_stats) = injector.stream::<db::CommandStats>();
+            let (mut strikes_stream, strikes) = injector.stream::<db::Strikes>();
+            let (mut audit_log_stream, audit_log) = injector.stream::<db::AuditLog>();
+            let (mut history_stream, history) = injector.stream::<db::Messages>();
+            let (mut seen_users_stream, seen_users) = injector.stream::<db::SeenUsers>();
+            let (mut link_permits_stream, link_permits) = injector.stream::<LinkPermits>();
+            let (mut ban_list_stream, ban_list) = injector.stream::<db::BanList>();
+            let (mut ranks_stream, ranks) = injector.stream::<Ranks>();
+
+            let resolver: Arc<dyn links::Resolver> = Arc::new(links::HttpResolver::new()?);
+
+            let mut handlers = Vec::new();
+            let mut leave_messages = Vec::new();
+            let (bad_links_tx, mut bad_links) = mpsc::unbounded();
+
+            for (index, setup) in setups.iter().enumerate() {
+                let mut module_handlers = module::Handlers::default();
+
+                for module in modules.iter() {
+                    // Modules which are tied to the streamer only operate in
+                    // the streamer's own channel.
+                    if index > 0 && !module.multi_channel() {
+                        continue;
+                    }
 
-            let mut pong_timeout = None;
+                    if log::log_enabled!(log::Level::Trace) {
+                        log::trace!(
+                            "initializing module: {} ({})",
+                            module.ty(),
+                            setup.sender.channel()
+                        );
+                    }
 
-            let mut handler = Handler {
-                streamer,
-                sender: sender.clone(),
-                moderators: Default::default(),
-                vips: Default::default(),
-                whitelisted_hosts,
-                commands,
-                bad_words: &bad_words,
-                global_bus: &global_bus,
-                aliases,
-                api_url,
-                moderator_cooldown,
-                handlers,
-                shutdown: &shutdown,
-                idle: &idle,
-                pong_timeout: &mut pong_timeout,
-                token: &bot_twitch.token,
-                handler_shutdown: false,
-                stream_info: &stream_info,
-                auth: &auth,
-                scope_cooldowns: auth.scope_cooldowns(),
-                currency_handler,
-                url_whitelist_enabled,
-                bad_words_enabled,
-                message_hooks: Default::default(),
-                chat_log: chat_log_builder.build()?,
-                channel,
-            };
+                    let result = module
+                        .hook(module::HookContext {
+                            handlers: &mut module_handlers,
+                            futures: &mut futures,
+                            stream_info: &setup.stream_info,
+                            idle: &setup.idle,
+                            twitch: &bot_twitch,
+                            streamer_twitch: &streamer_twitch,
+                            sender: &setup.sender,
+                            settings: &setup.settings,
+                            injector: &injector,
+                            auth: &auth,
+                            command_bus: &command_bus,
+                            global_bus: &global_bus,
+                        })
+                        .await;
+
+                    result.with_context(|_| {
+                        format_err!("failed to initialize module: {}", module.ty())
+                    })?;
+                }
+
+                let (future, currency_handler) = currency_admin::setup(&injector)?;
+
+                futures.push(
+                    future
+                        .instrument(trace_span!(target: "futures", "currency-admin",))
+                        .boxed(),
+                );
+
+                // NB: the currency is shared, so rewards are only handed out
+                // in the streamer's channel.
+                if index == 0 {
+                    let future = currency_loop(
+                        streamer_twitch.clone(),
+                        setup.channel.clone(),
+                        bot.clone(),
+                        setup.sender.clone(),
+                        setup.idle.clone(),
+                        &injector,
+                        &setup.chat_settings,
+                        &setup.settings,
+                    )?;
+
+                    futures.push(
+                        future
+                            .instrument(trace_span!(target: "futures", "currency-loop",))
+                            .boxed(),
+                    );
+                }
+
+                if let Some(join_message) = setup.chat_settings.get::<String>("join-message")? {
+                    // greeting when bot joins
+                    setup.sender.privmsg_immediate(join_message);
+                }
+
+                let leave_message = setup
+                    .chat_settings
+                    .get::<String>("leave-message")?
+                    .unwrap_or_else(|| String::from("Leaving chat... VoHiYo"));
+
+                leave_messages.push((setup.sender.clone(), leave_message));
+
+                handlers.push(Handler {
+                    streamer: setup.streamer.clone(),
+                    sender: setup.sender.clone(),
+                    moderators: Default::default(),
+                    vips: Default::default(),
+                    whitelisted_hosts: setup
+                        .chat_settings
+                        .var("whitelisted-hosts", HashSet::new())?,
+                    commands: commands.clone(),
+                    bad_words: &bad_words,
+                    global_bus: &global_bus,
+                    aliases: aliases.clone(),
+                    api_url: api_url.clone(),
+                    moderator_cooldown: setup.chat_settings.optional("moderator-cooldown")?,
+                    handlers: module_handlers,
+                    shutdown: &shutdown,
+                    idle: &setup.idle,
+                    rate_limit: rate_limit::RateLimit::new(
+                        &setup.chat_settings.scoped("rate-limit"),
+                    )?,
+                    token: &bot_twitch.token,
+                    handler_shutdown: false,
+                    stream_info: &setup.stream_info,
+                    auth: &auth,
+                    scope_cooldowns: auth.scope_cooldowns(),
+                    currency_handler,
+                    url_whitelist_enabled: setup
+                        .chat_settings
+                        .var("url-whitelist/enabled", true)?,
+                    bad_words_enabled: setup.chat_settings.var("bad-words/enabled", false)?,
+                    spam: spam::Spam::new(&setup.chat_settings.scoped("spam"))?,
+                    escalation: strikes::Escalation::new(&setup.chat_settings.scoped("strikes"))?,
+                    strikes: strikes.clone(),
+                    audit_log: audit_log.clone(),
+                    history: history.clone(),
+                    history_enabled: setup.chat_settings.var("history/enabled", false)?,
+                    lockdown: lockdown::Lockdown::new(setup.chat_settings.scoped("lockdown"))?,
+                    seen_users: seen_users.clone(),
+                    link_permits: link_permits.clone(),
+                    ranks: ranks.clone(),
+                    ban_list: ban_list.clone(),
+                    ban_list_enabled: setup.chat_settings.var("ban-list/enabled", false)?,
+                    ban_list_on_join: setup.chat_settings.var("ban-list/on-join", true)?,
+                    ban_list_applied: Default::default(),
+                    shorteners: links::Shorteners::new(
+                        &setup.chat_settings.scoped("url-whitelist"),
+                        resolver.clone(),
+                    )?,
+                    bad_links: bad_links_tx.clone(),
+                    message_hooks: Default::default(),
+                    // NB: the chat log only covers the streamer's channel.
+                    chat_log: match index {
+                        0 => chat_log_builder.build()?,
+                        _ => None,
+                    },
+                    channel: setup.channel.clone(),
+                    currency: currency.clone(),
+                    counters: counters.clone(),
+                    command_stats: command_stats.clone(),
+                    last_invoked: Default::default(),
+                });
+            }
 
             let mut outgoing = client
                 .outgoing()
@@ -421,13 +580,11 @@ impl Irc {
             let mut ping_interval =
                 tokio::timer::Interval::new_interval(time::Duration::from_secs(10));
 
-            handler.sender.cap_req(TWITCH_TAGS_CAP);
-            handler.sender.cap_req(TWITCH_COMMANDS_CAP);
+            let mut pong_timeout = None;
 
-            if let Some(join_message) = join_message.as_ref() {
-                // greeting when bot joins
-                handler.sender.privmsg_immediate(join_message);
-            }
+            sender.cap_req(TWITCH_TAGS_CAP);
+            sender.cap_req(TWITCH_COMMANDS_CAP);
+            sender.cap_req(TWITCH_MEMBERSHIP_CAP);
 
             let mut commands = command_bus.add_rx();
 
@@ -437,9 +594,24 @@ impl Irc {
                 futures::select! {
                     command = commands.select_next_some() => {
                         match command {
-                            bus::Command::Raw { command } => {
+                            bus::Command::Raw { command, channel } => {
                                 log::trace!("Raw command: {}", command);
 
+                                let handler = match channel {
+                                    Some(channel) => {
+                                        let channel = format!("#{}", channel.trim_start_matches('#').to_lowercase());
+
+                                        match handlers.iter_mut().find(|h| h.sender.channel() == channel) {
+                                            Some(handler) => handler,
+                                            None => {
+                                                log::warn!("Raw command for channel not joined: {}", channel);
+                                                continue;
+                                            }
+                                        }
+                                    }
+                                    None => &mut handlers[0],
+                                };
+
                                 if let Err(e) = handler.raw(&command).await {
                                     log::error!("Failed to handle message: {}", e);
                                 }
@@ -469,50 +641,146 @@ impl Irc {
                             leave = Some(tokio::timer::delay(time::Instant::now() + time::Duration::from_secs(1)));
                         }
                     },
+                    _ = channels_stream.select_next_some() => {
+                        log::info!("Channels changed, reconnecting");
+                        leave = Some(tokio::timer::delay(time::Instant::now() + time::Duration::from_secs(1)));
+                    },
                     update = commands_stream.select_next_some() => {
-                        handler.commands = update;
+                        for handler in &mut handlers {
+                            handler.commands = update.clone();
+                        }
                     }
                     update = aliases_stream.select_next_some() => {
-                        handler.aliases = update;
+                        for handler in &mut handlers {
+                            handler.aliases = update.clone();
+                        }
+                    }
+                    update = currency_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.currency = update.clone();
+                        }
+                    }
+                    update = counters_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.counters = update.clone();
+                        }
+                    }
+                    update = command_stats_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.command_stats = update.clone();
+                        }
+                    }
+                    update = strikes_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.strikes = update.clone();
+                        }
+                    }
+                    update = audit_log_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.audit_log = update.clone();
+                        }
+                    }
+                    update = history_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.history = update.clone();
+                        }
+                    }
+                    update = seen_users_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.seen_users = update.clone();
+                        }
+                    }
+                    update = link_permits_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.link_permits = update.clone();
+                        }
+                    }
+                    update = ranks_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.ranks = update.clone();
+                        }
+                    }
+                    update = ban_list_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.ban_list = update.clone();
+                        }
                     }
                     cache = chat_log_builder.cache_stream.select_next_some() => {
                         chat_log_builder.cache = cache;
-                        handler.chat_log = chat_log_builder.build()?;
+                        handlers[0].chat_log = chat_log_builder.build()?;
                     }
                     update = chat_log_builder.enabled_stream.select_next_some() => {
                         chat_log_builder.enabled = update;
                         chat_log_builder.message_log.enabled(update);
-                        handler.chat_log = chat_log_builder.build()?;
+                        handlers[0].chat_log = chat_log_builder.build()?;
+                    }
+                    update = chat_log_builder.path_stream.select_next_some() => {
+                        chat_log_builder.path(update);
+                        handlers[0].chat_log = chat_log_builder.build()?;
                     }
                     update = chat_log_builder.emotes_enabled_stream.select_next_some() => {
                         chat_log_builder.emotes_enabled = update;
-                        handler.chat_log = chat_log_builder.build()?;
+                        handlers[0].chat_log = chat_log_builder.build()?;
                     }
-                    update = api_url_stream.select_next_some() => {
-                        handler.api_url = update;
+                    user = bad_links.select_next_some() => {
+                        let handler = handlers
+                            .iter_mut()
+                            .find(|h| h.sender.channel() == user.channel());
+
+                        if let Some(handler) = handler {
+                            if let Err(e) = handler.moderate_bad_link(&user) {
+                                log_err!(e, "failed to moderate link");
+                            }
+                        }
                     }
-                    update = moderator_cooldown_stream.select_next_some() => {
-                        handler.moderator_cooldown = update;
+                    update = api_url_stream.select_next_some() => {
+                        for handler in &mut handlers {
+                            handler.api_url = update.clone();
+                        }
                     }
                     _ = ping_interval.select_next_some() => {
-                        handler.send_ping()?;
+                        sender.send_immediate(Command::PING(String::from(SERVER), None));
+                        pong_timeout = Some(tokio::timer::delay(time::Instant::now() + time::Duration::from_secs(5)));
                     }
-                    timeout = handler.pong_timeout.current() => {
+                    _ = pong_timeout.current() => {
                         bail!("server not responding");
                     }
-                    update = whitelisted_hosts_stream.next() => {
-                        if let Some(update) = update {
-                            handler.whitelisted_hosts = update;
-                        }
-                    },
                     message = client_stream.next() => {
                         if let Some(m) = message.transpose()? {
-                            if let Err(e) = handler.handle(m).await {
-                                log::error!("Failed to handle message: {}", e);
+                            match m.command {
+                                Command::PING(ref server, ref other) => {
+                                    log::trace!("Received PING, responding with PONG");
+                                    sender.send_immediate(Command::PONG(server.clone(), other.clone()));
+                                }
+                                Command::PONG(..) => {
+                                    log::trace!("Received PONG, clearing PING timeout");
+                                    pong_timeout = None;
+                                }
+                                // capabilities apply to every channel.
+                                Command::CAP(..) => {
+                                    for handler in &mut handlers {
+                                        if let Err(e) = handler.handle(m.clone()).await {
+                                            log::error!("Failed to handle message: {}", e);
+                                        }
+                                    }
+                                }
+                                _ => {
+                                    let handler = match message_channel(&m) {
+                                        Some(channel) => handlers
+                                            .iter()
+                                            .position(|h| h.sender.channel() == channel)
+                                            .unwrap_or(0),
+                                        None => 0,
+                                    };
+
+                                    if let Err(e) = handlers[handler].handle(m).await {
+                                        log::error!("Failed to handle message: {}", e);
+                                    }
+                                }
                             }
                         }
 
-                        if handler.handler_shutdown {
+                        if handlers.iter().any(|h| h.handler_shutdown) {
                             bail!("handler forcibly shut down");
                         }
                     }
@@ -525,7 +793,9 @@ impl Irc {
                 }
             }
 
-            handler.sender.privmsg_immediate(leave_message);
+            for (sender, leave_message) in leave_messages {
+                sender.privmsg_immediate(leave_message);
+            }
 
             loop {
                 futures::select! {
@@ -543,10 +813,60 @@ impl Irc {
     }
 }
 
+/// Everything needed to operate in a single joined channel.
+struct ChannelSetup {
+    /// The owner of the channel.
+    streamer: Arc<twitch::User>,
+    /// Information on the channel.
+    channel: Arc<twitch::Channel>,
+    /// Sender for the channel.
+    sender: Sender,
+    /// Settings for the channel.
+    settings: settings::Settings,
+    /// Chat settings for the channel.
+    chat_settings: settings::Settings,
+    /// Idle detection for the channel.
+    idle: idle::Idle,
+    /// Stream information for the channel.
+    stream_info: stream_info::StreamInfo,
+}
+
+/// Get the channel the given message is associated with, if any.
+fn message_channel(m: &Message) -> Option<&str> {
+    match m.command {
+        Command::PRIVMSG(ref target, _)
+        | Command::NOTICE(ref target, _)
+        | Command::JOIN(ref target, _, _) => Some(target.as_str()),
+        Command::Raw(ref command, ref args, _)
+            if command == "CLEARMSG" || command == "CLEARCHAT" =>
+        {
+            args.first().map(String::as_str)
+        }
+        _ => None,
+    }
+}
+
+/// Known bots which are left out of leaderboards by default.
+fn default_leaderboard_exclude() -> HashSet<String> {
+    let bots = &[
+        "nightbot",
+        "streamelements",
+        "streamlabs",
+        "moobot",
+        "fossabot",
+        "wizebot",
+        "commanderroot",
+        "anotherttvviewer",
+    ];
+
+    bots.iter().map(|b| b.to_string()).collect()
+}
+
 /// Set up a reward loop.
 fn currency_loop<'a>(
     twitch: api::Twitch,
     channel: Arc<twitch::Channel>,
+    bot: Arc<twitch::User>,
     sender: Sender,
     idle: idle::Idle,
     injector: &'a Injector,
@@ -575,16 +895,40 @@ fn currency_loop<'a>(
     let (mut mysql_url_stream, mysql_url) = settings.stream("currency/mysql/url").optional()?;
     let (mut mysql_schema_stream, mysql_schema) =
         settings.stream("currency/mysql/schema").or_default()?;
+    let (mut postgres_url_stream, postgres_url) =
+        settings.stream("currency/postgres/url").optional()?;
+    let (mut postgres_schema_stream, postgres_schema) =
+        settings.stream("currency/postgres/schema").or_default()?;
+
+    let (mut leaderboard_exclude_stream, leaderboard_exclude) = settings
+        .stream("currency/leaderboard/exclude")
+        .or_with_else(default_leaderboard_exclude)?;
 
     let (mut db_stream, db) = injector.stream::<db::Database>();
+    let (mut ledger_stream, ledger) = injector.stream::<db::Ledger>();
+    let (mut ranks_stream, mut ranks) = injector.stream::<Ranks>();
+
+    // NB: the streamer and the bot itself are never on leaderboards.
+    let streamer_id = db::user_id(&channel.name);
+    let bot_id = db::user_id(&bot.name);
+
+    let with_streamer_and_bot = move |mut exclude: HashSet<String>| {
+        exclude.insert(streamer_id.clone());
+        exclude.insert(bot_id.clone());
+        exclude
+    };
 
     let mut builder = CurrencyBuilder::new(twitch.clone(), mysql_schema);
     builder.db = db;
+    builder.ledger = ledger;
+    builder.leaderboard_exclude = with_streamer_and_bot(leaderboard_exclude);
     builder.ty = ty;
     builder.enabled = enabled;
     builder.command_enabled = command_enabled;
     builder.name = name.map(Arc::new);
     builder.mysql_url = mysql_url;
+    builder.postgres_url = postgres_url;
+    builder.postgres_schema = postgres_schema;
 
     let build = |injector: &Injector, builder: &CurrencyBuilder| match builder.build() {
         Some(currency) => {
@@ -622,6 +966,17 @@ fn currency_loop<'a>(
                     builder.db = update;
                     currency = build(injector, &builder);
                 }
+                update = ledger_stream.select_next_some() => {
+                    builder.ledger = update;
+                    currency = build(injector, &builder);
+                }
+                update = ranks_stream.select_next_some() => {
+                    ranks = update;
+                }
+                update = leaderboard_exclude_stream.select_next_some() => {
+                    builder.leaderboard_exclude = with_streamer_and_bot(update);
+                    currency = build(injector, &builder);
+                }
                 enabled = enabled_stream.select_next_some() => {
                     builder.enabled = enabled;
                     currency = build(injector, &builder);
@@ -642,6 +997,14 @@ fn currency_loop<'a>(
                     builder.mysql_schema = update;
                     currency = build(injector, &builder);
                 }
+                postgres_url = postgres_url_stream.select_next_some() => {
+                    builder.postgres_url = postgres_url;
+                    currency = build(injector, &builder);
+                }
+                update = postgres_schema_stream.select_next_some() => {
+                    builder.postgres_schema = update;
+                    currency = build(injector, &builder);
+                }
                 command_enabled = command_enabled_stream.select_next_some() => {
                     builder.command_enabled = command_enabled;
                     currency = build(injector, &builder);
@@ -660,16 +1023,29 @@ fn currency_loop<'a>(
                     log::trace!("running reward loop");
 
                     let reward = (reward * *reward_percentage.read() as i64) / 100i64;
-                    let count = currency
-                        .add_channel_all(&channel.name, reward, seconds)
+                    let users = currency
+                        .add_channel_all(&channel.name, reward, seconds, LedgerReason::WatchReward, None)
                         .await?;
 
-                    if notify_rewards && count > 0 && !idle.is_idle() {
+                    if notify_rewards && !users.is_empty() && !idle.is_idle() {
                         sender.privmsg(format!(
                             "/me has given {} {} to all viewers!",
                             reward, currency.name
                         ));
                     }
+
+                    if let Some(ranks) = ranks.as_ref() {
+                        let result = ranks.reward(currency, &channel.name, users, seconds).await;
+
+                        match result {
+                            Ok(announcements) => {
+                                for announcement in announcements {
+                                    sender.privmsg(announcement);
+                                }
+                            }
+                            Err(e) => log_err!(e, "failed to update ranks"),
+                        }
+                    }
                 }
             }
         }
@@ -687,7 +1063,7 @@ struct Handler<'a> {
     /// VIPs.
     vips: Arc<RwLock<HashSet<String>>>,
     /// Whitelisted hosts for links.
-    whitelisted_hosts: HashSet<String>,
+    whitelisted_hosts: Arc<RwLock<HashSet<String>>>,
     /// All registered commands.
     commands: Option<db::Commands>,
     /// Bad words.
@@ -699,15 +1075,15 @@ struct Handler<'a> {
     /// Configured API URL.
     api_url: Option<String>,
     /// Active moderator cooldown.
-    moderator_cooldown: Option<Cooldown>,
+    moderator_cooldown: Arc<RwLock<Option<Cooldown>>>,
     /// Handlers for specific commands like `!skip`.
     handlers: module::Handlers<'a>,
     /// Handler for shutting down the service.
     shutdown: &'a utils::Shutdown,
     /// Build idle detection.
     idle: &'a idle::Idle,
-    /// Pong timeout currently running.
-    pong_timeout: &'a mut Option<tokio::timer::Delay>,
+    /// Per-user message rate limits.
+    rate_limit: rate_limit::RateLimit,
     /// OAuth 2.0 Token used to authenticate with IRC.
     token: &'a oauth2::SyncToken,
     /// Force a shutdown.
@@ -722,12 +1098,63 @@ struct Handler<'a> {
     currency_handler: currency_admin::Handler,
     bad_words_enabled: Arc<RwLock<bool>>,
     url_whitelist_enabled: Arc<RwLock<bool>>,
+    /// Heuristic spam filters.
+    spam: spam::Spam,
+    /// How moderation actions escalate with strikes.
+    escalation: strikes::Escalation,
+    /// Strikes given to users for filtered messages.
+    strikes: Option<db::Strikes>,
+    /// Audit log of moderation actions taken by the bot.
+    audit_log: Option<db::AuditLog>,
+    /// Persistent history of chat messages.
+    history: Option<db::Messages>,
+    /// If messages should be stored in the chat history.
+    history_enabled: Arc<RwLock<bool>>,
+    /// Stricter filtering of new users during raids.
+    lockdown: lockdown::Lockdown,
+    /// When users were first seen in chat.
+    seen_users: Option<db::SeenUsers>,
+    /// Links users have been permitted to post with `!permit`.
+    link_permits: Option<LinkPermits>,
+    /// Watch time ranks of users.
+    ranks: Option<Ranks>,
+    /// Users banned across channels.
+    ban_list: Option<db::BanList>,
+    /// If the ban list is applied to users in this channel.
+    ban_list_enabled: Arc<RwLock<bool>>,
+    /// If the ban list is applied when users join, rather than when they
+    /// first chat.
+    ban_list_on_join: Arc<RwLock<bool>>,
+    /// Users the ban list has already been applied to.
+    ban_list_applied: HashSet<String>,
+    /// Expands links from URL shorteners before they are checked.
+    shorteners: links::Shorteners,
+    /// Messages found to link to hosts which aren't whitelisted once their
+    /// shortened links were expanded in the background, sent back here to be
+    /// moderated.
+    bad_links: mpsc::UnboundedSender<User>,
     /// A hook that can be installed to peek at all incoming messages.
     message_hooks: HashMap<String, Box<dyn command::MessageHook>>,
     /// Handler for chat logs.
     chat_log: Option<chat_log::ChatLog>,
     /// Information on the current channel.
     channel: Arc<twitch::Channel>,
+    /// Currency used to charge for restricted commands and aliases.
+    currency: Option<Currency>,
+    /// Counters available as template variables.
+    counters: Option<db::Counters>,
+    /// Store used to record command invocations.
+    command_stats: Option<db::CommandStats>,
+    /// When the cooldowns of restricted commands and aliases end, globally and
+    /// for specific users.
+    last_invoked: HashMap<(&'static str, db::Key, Option<String>), time::Instant>,
+}
+
+/// A restricted alias or custom command which is part of an invocation.
+struct Restricted {
+    what: &'static str,
+    key: db::Key,
+    restrictions: db::Restrictions,
 }
 
 /// Handle a command.
@@ -737,20 +1164,27 @@ pub async fn process_command<'a, 'b: 'a>(
     global_bus: &'a Arc<bus::Bus<bus::Global>>,
     currency_handler: &'a mut currency_admin::Handler,
     handlers: &'a mut module::Handlers<'b>,
+    command_stats: Option<&'a db::CommandStats>,
 ) -> Result<(), Error> {
     match command {
         "ping" => {
             ctx.user.respond("What do you want?");
             global_bus.send(bus::Global::Ping);
+            record_invocation(command_stats, &ctx.user, command, db::Outcome::Ok);
         }
         other => {
             log::trace!("Testing command: {}", other);
 
+            let mut dynamic = handlers.get_dynamic(ctx.channel(), other);
+
             let handler = match (other, currency_handler.command_name()) {
                 (other, Some(ref name)) if other == **name => {
                     Some(currency_handler as &mut (dyn command::Handler + Send))
                 }
-                (other, Some(..)) | (other, None) => handlers.get_mut(other),
+                (other, Some(..)) | (other, None) => match dynamic.as_mut() {
+                    Some(handler) => Some(&mut **handler as &mut (dyn command::Handler + Send)),
+                    None => handlers.get_mut(other),
+                },
             };
 
             if let Some(handler) = handler {
@@ -775,11 +1209,25 @@ pub async fn process_command<'a, 'b: 'a>(
                             }
                         }
 
+                        record_invocation(command_stats, &ctx.user, other, db::Outcome::Denied);
                         return Ok(());
                     }
                 }
 
-                handler.handle(ctx).await?;
+                let user = ctx.user.clone();
+                let result = handler.handle(ctx).await;
+
+                let outcome = match &result {
+                    Ok(()) => db::Outcome::Ok,
+                    Err(e) => match e.downcast_ref::<command::ScopeError>() {
+                        Some(command::ScopeError::Denied(..)) => db::Outcome::Denied,
+                        Some(command::ScopeError::Cooldown(..)) => db::Outcome::Cooldown,
+                        None => db::Outcome::Error,
+                    },
+                };
+
+                record_invocation(command_stats, &user, other, outcome);
+                result?;
                 return Ok(());
             }
         }
@@ -788,9 +1236,90 @@ pub async fn process_command<'a, 'b: 'a>(
     Ok(())
 }
 
+/// Record an invocation of the given command, if command stats are
+/// available.
+///
+/// Failing to record is logged, but doesn't fail the command.
+fn record_invocation(
+    command_stats: Option<&db::CommandStats>,
+    user: &User,
+    command: &str,
+    outcome: db::Outcome,
+) {
+    let command_stats = match command_stats {
+        Some(command_stats) => command_stats,
+        None => return,
+    };
+
+    let stream_started_at = user
+        .inner
+        .stream_info
+        .data
+        .read()
+        .stream
+        .as_ref()
+        .map(|stream| stream.started_at.naive_utc());
+
+    let result = command_stats.record(
+        user.channel(),
+        command,
+        user.name(),
+        outcome,
+        stream_started_at,
+    );
+
+    if let Err(e) = result {
+        log_err!(e, "failed to record command invocation");
+    }
+}
+
 impl<'a> Handler<'a> {
+    /// Store a message in the chat history, if enabled.
+    ///
+    /// Failing to store is logged, but doesn't stop the message from being
+    /// processed.
+    fn record_history(&self, tags: &Tags, name: &str, message: &str) {
+        let history = match self.history.as_ref() {
+            Some(history) if *self.history_enabled.read() => history,
+            _ => return,
+        };
+
+        let result = serde_json::to_value(tags)
+            .map_err(Error::from)
+            .and_then(|json| {
+                history.push(
+                    self.sender.channel(),
+                    tags.id.as_ref().map(String::as_str),
+                    name,
+                    tags.display_name.as_ref().map(String::as_str),
+                    message,
+                    &json,
+                )
+            });
+
+        if let Err(e) = result {
+            log_err!(e, "failed to store message in chat history");
+        }
+    }
+
+    /// Record a moderation action taken by the bot against the given user.
+    ///
+    /// Failing to record is logged, but doesn't stop the action.
+    fn audit(&self, channel: &str, user: Option<&str>, action: db::AuditAction, reason: &str) {
+        let audit_log = match self.audit_log.as_ref() {
+            Some(audit_log) => audit_log,
+            None => return,
+        };
+
+        let result = audit_log.record(channel, None, action, user, Some(reason));
+
+        if let Err(e) = result {
+            log_err!(e, "failed to record moderation action");
+        }
+    }
+
     /// Delete the given message.
-    fn delete_message(&self, user: &User) -> Result<(), Error> {
+    fn delete_message(&self, user: &User, reason: &str) -> Result<(), Error> {
         let id = match &user.inner.tags.id {
             Some(id) => id,
             None => return Ok(()),
@@ -798,27 +1327,104 @@ impl<'a> Handler<'a> {
 
         log::info!("Attempting to delete message: {}", id);
         user.inner.sender.delete(id);
+        self.audit(
+            user.channel(),
+            user.name(),
+            db::AuditAction::DeleteMessage,
+            reason,
+        );
         Ok(())
     }
 
-    /// Test if the message should be deleted.
-    fn should_be_deleted(&self, user: &User, message: &str) -> bool {
+    /// Test if moderated messages escalate with strikes.
+    fn escalates(&self) -> bool {
+        self.strikes.is_some() && self.escalation.is_enabled()
+    }
+
+    /// Moderate a message which was matched by a filter.
+    ///
+    /// The message is deleted, and if strikes are enabled the user is given a
+    /// strike and the action taken escalates with the number of strikes they
+    /// have.
+    fn moderate(&self, user: &User, reason: &str) -> Result<(), Error> {
+        self.delete_message(user, reason)?;
+
+        let strikes = match self.strikes.as_ref() {
+            Some(strikes) if self.escalation.is_enabled() => strikes,
+            _ => return Ok(()),
+        };
+
+        let user = match user.real() {
+            Some(user) => user,
+            None => return Ok(()),
+        };
+
+        let count = strikes.add(user.channel(), user.name(), &self.escalation.decay())?;
+        let step = self.escalation.step(count);
+
+        log::info!(
+            "Strike {} for {}: {} ({:?})",
+            count,
+            user.name(),
+            reason,
+            step
+        );
+
+        match step {
+            strikes::Step::Warn => (),
+            strikes::Step::Timeout(ref duration) => {
+                self.sender.timeout(user.name(), duration.as_std(), reason);
+                self.audit(
+                    user.channel(),
+                    Some(user.name()),
+                    db::AuditAction::Timeout,
+                    reason,
+                );
+            }
+            strikes::Step::Ban => {
+                self.sender.ban(user.name(), reason);
+                self.audit(
+                    user.channel(),
+                    Some(user.name()),
+                    db::AuditAction::Ban,
+                    reason,
+                );
+            }
+        }
+
+        let response = self
+            .escalation
+            .render(&step, user.display_name(), reason, count)?;
+        self.sender.privmsg(response);
+        Ok(())
+    }
+
+    /// Test the message against the bad words and URL filters, returning the
+    /// reason and the action to take if it matches one.
+    fn test_filters(&self, user: &User, message: &str) -> Option<(&'static str, db::WordAction)> {
         // Moderators can say whatever they want.
         if user.is_moderator() {
-            return false;
+            return None;
         }
 
         if *self.bad_words_enabled.read() {
-            if let Some(word) = self.test_bad_words(message) {
-                if let Some(why) = word.why.as_ref() {
-                    let why = why.render_to_string(&BadWordsVars {
+            if let Some(word) = self.bad_words.tester().test(message) {
+                // NB: deleted messages are moderated, which responds with the
+                // strike instead when escalation is enabled.
+                let escalated = match word.action {
+                    db::WordAction::Delete => self.escalates(),
+                    _ => false,
+                };
+
+                if let (Some(m), false) = (word.message.as_ref(), escalated) {
+                    let m = m.render_to_string(&BadWordsVars {
                         name: user.display_name(),
                         target: user.channel(),
                     });
 
-                    match why {
-                        Ok(why) => {
-                            self.sender.privmsg(&why);
+                    match m {
+                        Ok(m) => {
+                            self.sender.privmsg(&m);
                         }
                         Err(e) => {
                             log_err!(e, "failed to render response");
@@ -826,67 +1432,445 @@ impl<'a> Handler<'a> {
                     }
                 }
 
-                return true;
+                return Some(("bad word", word.action.clone()));
             }
         }
 
         if !user.has_scope(Scope::ChatBypassUrlWhitelist) && *self.url_whitelist_enabled.read() {
-            if self.has_bad_link(message) {
-                return true;
+            match self.check_links(message) {
+                LinkCheck::Bad => {
+                    if !take_link_permit(self.link_permits.as_ref(), user) {
+                        return Some(("link not permitted", db::WordAction::Delete));
+                    }
+                }
+                LinkCheck::Pending(urls) => {
+                    self.spawn_link_check(user, urls);
+                }
+                LinkCheck::Ok => (),
             }
         }
 
-        false
+        None
     }
 
-    /// Test the message for bad words.
-    fn test_bad_words(&self, message: &str) -> Option<Arc<db::Word>> {
-        let tester = self.bad_words.tester();
+    /// Act on a message which was matched by the bad words or URL filters.
+    ///
+    /// Deleted messages are moderated, which might escalate with strikes.
+    fn apply_filter(&self, user: &User, reason: &str, action: db::WordAction) -> Result<(), Error> {
+        match action {
+            db::WordAction::Delete => {
+                self.moderate(user, reason)?;
+            }
+            db::WordAction::Warn => (),
+            db::WordAction::Timeout(duration) => {
+                self.delete_message(user, reason)?;
 
-        for word in utils::TrimmedWords::new(message) {
-            if let Some(word) = tester.test(word) {
-                return Some(word);
+                if let Some(name) = user.name() {
+                    self.sender.timeout(name, duration.as_std(), reason);
+                    self.audit(user.channel(), Some(name), db::AuditAction::Timeout, reason);
+                }
+            }
+            db::WordAction::Ban => {
+                if let Some(name) = user.name() {
+                    self.sender.ban(name, reason);
+                    self.audit(user.channel(), Some(name), db::AuditAction::Ban, reason);
+                }
             }
         }
 
-        None
+        Ok(())
+    }
+
+    /// Test the message against the spam filters.
+    fn test_spam(&mut self, user: &User, message: &str) -> Option<spam::Verdict> {
+        let user = user.real()?;
+
+        let emotes = user
+            .tags
+            .emotes
+            .as_ref()
+            .map(|emotes| emotes::twitch_spans(emotes).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        self.spam.test(user.name(), &user.roles(), message, &emotes)
+    }
+
+    /// Track the message against the rate limit of the user, returning a
+    /// verdict if they are sending messages too quickly.
+    fn test_rate_limit(&mut self, user: &User) -> Option<spam::Verdict> {
+        let user = user.real()?;
+
+        self.rate_limit
+            .test(user.name(), &user.roles(), time::Instant::now())
+    }
+
+    /// Act on a message which was matched by a spam filter.
+    fn apply_spam_verdict(&self, user: &User, verdict: spam::Verdict) -> Result<(), Error> {
+        log::info!(
+            "Spam filter matched message from {:?}: {}",
+            user.name(),
+            verdict.reason
+        );
+
+        match verdict.action {
+            spam::Action::Delete => {
+                self.delete_message(user, verdict.reason)?;
+            }
+            spam::Action::Warn => {
+                self.delete_message(user, verdict.reason)?;
+                user.respond(format!("Your message was removed: {}", verdict.reason));
+            }
+            spam::Action::Timeout => {
+                if let Some(name) = user.name() {
+                    self.sender
+                        .timeout(name, verdict.timeout.as_std(), verdict.reason);
+                    self.audit(
+                        user.channel(),
+                        Some(name),
+                        db::AuditAction::Timeout,
+                        verdict.reason,
+                    );
+                }
+            }
+            spam::Action::Strike => {
+                self.moderate(user, verdict.reason)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Mark the user as seen, and test the message against the lockdown
+    /// filters if the user is new and lockdown is active.
+    fn test_lockdown(&mut self, user: &User, message: &str) -> Result<Option<&'static str>, Error> {
+        let now = time::Instant::now();
+
+        if let Some(lockdown::Transition::Disabled) = self.lockdown.update(now)? {
+            self.sender
+                .privmsg("Lockdown has ended, thank you for your patience!");
+        }
+
+        let user = match user.real() {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+
+        let seen_users = match self.seen_users.as_ref() {
+            Some(seen_users) => seen_users,
+            None => return Ok(None),
+        };
+
+        // NB: failing to look up the user shouldn't prevent the message from
+        // being processed, so they are treated as not being new.
+        let (first_seen_at, first_time) = match seen_users.seen(user.channel(), user.name()) {
+            Ok(seen) => seen,
+            Err(e) => {
+                log_err!(e, "failed to look up when user was first seen");
+                return Ok(None);
+            }
+        };
+
+        if first_time {
+            if let Some(lockdown::Transition::Enabled) = self.lockdown.observe_new_user(now)? {
+                self.sender.privmsg(format!(
+                    "Lots of new chatters, enabling lockdown for {}. New chatters can't post links, caps, or lots of emotes.",
+                    utils::compact_duration(&self.lockdown.cooldown().as_std()),
+                ));
+            }
+        }
+
+        if !self.lockdown.is_active() || user.is_moderator() || user.is_streamer() {
+            return Ok(None);
+        }
+
+        if !self
+            .lockdown
+            .is_new_user(first_seen_at, chrono::Utc::now().naive_utc())
+        {
+            return Ok(None);
+        }
+
+        let emotes = user
+            .tags
+            .emotes
+            .as_ref()
+            .map(|emotes| emotes::twitch_spans(emotes).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        Ok(self.lockdown.test(message, &emotes))
+    }
+
+    /// Apply the ban list to the given user, returning `true` if they were
+    /// banned or timed out.
+    ///
+    /// The ban list is only applied once to each user, and never to the
+    /// streamer or moderators.
+    fn apply_ban_list(&mut self, channel: &str, name: &str) -> bool {
+        if !*self.ban_list_enabled.read() {
+            return false;
+        }
+
+        let entry = match self.ban_list.as_ref().and_then(|b| b.get(name)) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        if name == self.streamer.name || self.moderators.read().contains(name) {
+            return false;
+        }
+
+        if !self.ban_list_applied.insert(name.to_string()) {
+            return false;
+        }
+
+        if self.ban_list_applied.len() > BAN_LIST_APPLIED_LIMIT {
+            if let Some(ban_list) = self.ban_list.as_ref() {
+                self.ban_list_applied
+                    .retain(|user| ban_list.get(user).is_some());
+            }
+
+            // NB: the ban list itself is larger than the limit.
+            if self.ban_list_applied.len() > BAN_LIST_APPLIED_LIMIT {
+                self.ban_list_applied.clear();
+                self.ban_list_applied.insert(name.to_string());
+            }
+        }
+
+        let reason = match entry.reason.as_ref() {
+            Some(reason) => format!("ban list: {}", reason),
+            None => String::from("ban list"),
+        };
+
+        match entry.action {
+            db::BanAction::Ban => {
+                self.sender.ban_limited(name, &reason);
+                self.audit(channel, Some(name), db::AuditAction::Ban, &reason);
+            }
+            db::BanAction::Timeout(ref duration) => {
+                self.sender
+                    .timeout_limited(name, duration.as_std(), &reason);
+                self.audit(channel, Some(name), db::AuditAction::Timeout, &reason);
+            }
+        }
+
+        true
     }
 
-    /// Check if the given iterator has URLs that need to be
-    fn has_bad_link(&self, message: &str) -> bool {
+    /// Check if the message has links to hosts which aren't whitelisted.
+    ///
+    /// Links from known URL shorteners are expanded first, if enabled. Links
+    /// which haven't been expanded before are returned as pending, so that
+    /// they can be resolved without holding up chat.
+    fn check_links(&self, message: &str) -> LinkCheck {
+        let mut pending = Vec::new();
+
         for url in utils::Urls::new(message) {
-            if let Some(host) = url.host_str() {
-                if !self.whitelisted_hosts.contains(host) {
-                    return true;
+            match self.shorteners.cached(url.clone()) {
+                Some(url) => {
+                    if !is_whitelisted(&self.whitelisted_hosts, &url) {
+                        return LinkCheck::Bad;
+                    }
                 }
+                None => pending.push(url),
             }
         }
 
-        false
+        if pending.is_empty() {
+            LinkCheck::Ok
+        } else {
+            LinkCheck::Pending(pending)
+        }
     }
 
-    /// Send a ping to the remote server.
-    fn send_ping(&mut self) -> Result<(), Error> {
-        self.sender
-            .send_immediate(Command::PING(String::from(SERVER), None));
+    /// Expand the given shortened links in the background, sending the
+    /// message back to be moderated if any of them point to a host which isn't
+    /// whitelisted.
+    fn spawn_link_check(&self, user: &User, urls: Vec<url::Url>) {
+        let user = user.clone();
+        let shorteners = self.shorteners.clone();
+        let whitelisted_hosts = self.whitelisted_hosts.clone();
+        let bad_links = self.bad_links.clone();
+
+        tokio::spawn(async move {
+            for url in urls {
+                let url = shorteners.expand(url).await;
+
+                if !is_whitelisted(&whitelisted_hosts, &url) {
+                    if let Err(e) = bad_links.unbounded_send(user) {
+                        log::warn!("failed to send link for moderation: {}", e);
+                    }
 
-        *self.pong_timeout = Some(tokio::timer::delay(
-            time::Instant::now() + time::Duration::from_secs(5),
-        ));
+                    return;
+                }
+            }
+        });
+    }
 
-        Ok(())
+    /// Moderate a message found to link to a host which isn't whitelisted once
+    /// its shortened links were expanded in the background.
+    pub fn moderate_bad_link(&self, user: &User) -> Result<(), Error> {
+        if take_link_permit(self.link_permits.as_ref(), user) {
+            return Ok(());
+        }
+
+        self.moderate(user, "link not permitted")
     }
 
-    /// Process the given command.
-    pub async fn process_message(&mut self, user: &User, mut message: &str) -> Result<(), Error> {
-        for (key, hook) in &mut self.message_hooks {
-            hook.peek(&user, message)
-                .with_context(|_| format_err!("hook `{}` failed", key))?;
+    /// Test if the user passes all restrictions of an invocation, and charge
+    /// them once for the combined price if it has one.
+    ///
+    /// An invocation is a chain of aliases, optionally ending in a custom
+    /// command. Every restriction in the chain is checked before anything is
+    /// charged, so that users never pay for an invocation which is refused.
+    ///
+    /// Returns anything but `Outcome::Ok` if the user is not allowed to invoke
+    /// the command.
+    async fn check_restrictions(
+        &mut self,
+        chain: &[Restricted],
+        user: &User,
+    ) -> Result<db::Outcome, Error> {
+        if chain.iter().all(|r| r.restrictions.is_empty()) {
+            return Ok(db::Outcome::Ok);
+        }
+
+        let name = match user.real() {
+            Some(user) => user.name().to_string(),
+            None => return Ok(db::Outcome::Ok),
+        };
+
+        let now = time::Instant::now();
+        self.last_invoked.retain(|_, until| *until > now);
+
+        let bypass_cooldowns = user.has_scope(Scope::BypassCooldowns);
+        let mut price = 0i64;
+
+        for r in chain {
+            if let Some(scope) = r.restrictions.scope {
+                if !user.has_role_or_scope(scope) {
+                    user.respond(format!("You are not allowed to run that {}", r.what));
+                    return Ok(db::Outcome::Denied);
+                }
+            }
+
+            if !bypass_cooldowns {
+                let until = self
+                    .last_invoked
+                    .get(&(r.what, r.key.clone(), None))
+                    .or_else(|| {
+                        self.last_invoked
+                            .get(&(r.what, r.key.clone(), Some(name.clone())))
+                    });
+
+                if let Some(until) = until {
+                    user.respond(format!(
+                        "Cooldown in effect for {}",
+                        utils::compact_duration(&(*until - now)),
+                    ));
+
+                    return Ok(db::Outcome::Cooldown);
+                }
+            }
+
+            if let Some(p) = r.restrictions.price.filter(|p| *p > 0) {
+                price = price.saturating_add(p);
+            }
+        }
+
+        if price > 0 {
+            let currency = match self.currency.as_ref() {
+                Some(currency) => currency.clone(),
+                None => {
+                    user.respond("No currency configured for stream, but it is required.");
+                    return Ok(db::Outcome::Error);
+                }
+            };
+
+            let streamer = user.streamer().name.clone();
+
+            let result = currency
+                .balance_transfer(
+                    user.channel(),
+                    &name,
+                    &streamer,
+                    price,
+                    false,
+                    LedgerReason::Command,
+                )
+                .await;
+
+            match result {
+                Ok(()) => (),
+                Err(BalanceTransferError::NoBalance) => {
+                    let what = chain.last().map(|r| r.what).unwrap_or("command");
+
+                    user.respond(format!(
+                        "You need {price} {currency} to run that {what}, sorry :(",
+                        price = price,
+                        currency = currency.name,
+                        what = what,
+                    ));
+
+                    return Ok(db::Outcome::Denied);
+                }
+                Err(BalanceTransferError::Other(e)) => return Err(e),
+            }
+        }
+
+        if !bypass_cooldowns {
+            for r in chain {
+                let cooldowns = vec![
+                    (None, r.restrictions.cooldown.as_ref()),
+                    (Some(name.clone()), r.restrictions.user_cooldown.as_ref()),
+                ];
+
+                for (user, cooldown) in cooldowns {
+                    // NB: cooldowns are bounded when stored, so this only
+                    // fails for instants too close to the end of time.
+                    let until = cooldown.and_then(|c| now.checked_add(c.as_std()));
+
+                    if let Some(until) = until {
+                        self.last_invoked
+                            .insert((r.what, r.key.clone(), user), until);
+                    }
+                }
+            }
+        }
+
+        Ok(db::Outcome::Ok)
+    }
+
+    /// Process the given command.
+    pub async fn process_message(&mut self, user: &User, mut message: &str) -> Result<(), Error> {
+        for (key, hook) in &mut self.message_hooks {
+            hook.peek(&user, message)
+                .with_context(|_| format_err!("hook `{}` failed", key))?;
+        }
+
+        // only non-moderators and non-streamer bumps the idle counter.
+        if !user.is_streamer() {
+            self.idle.seen();
+        }
+
+        if let Some(name) = user.name() {
+            if self.apply_ban_list(user.channel(), name) {
+                return Ok(());
+            }
+        }
+
+        if let Some(reason) = self.test_lockdown(user, message)? {
+            self.moderate(user, reason)?;
+            return Ok(());
+        }
+
+        if let Some(verdict) = self.test_rate_limit(user) {
+            self.apply_spam_verdict(user, verdict)?;
+            return Ok(());
         }
 
-        // only non-moderators and non-streamer bumps the idle counter.
-        if !user.is_streamer() {
-            self.idle.seen();
+        if let Some(verdict) = self.test_spam(user, message) {
+            self.apply_spam_verdict(user, verdict)?;
+            return Ok(());
         }
 
         // NB: declared here to be in scope.
@@ -894,17 +1878,18 @@ impl<'a> Handler<'a> {
 
         let mut seen = HashSet::new();
         let mut path = Vec::new();
+        let mut chain = Vec::new();
 
-        if let Some(aliases) = self.aliases.as_ref() {
+        if let Some(aliases) = self.aliases.clone() {
             loop {
-                let (key, next) = match aliases.resolve(user.channel(), message) {
-                    Some((key, next)) => (key, next),
+                let (alias, next) = match aliases.resolve(user.channel(), message) {
+                    Some((alias, next)) => (alias, next),
                     None => break,
                 };
 
-                path.push(key.to_string());
+                path.push(alias.key.to_string());
 
-                if !seen.insert(key.clone()) {
+                if !seen.insert(alias.key.clone()) {
                     user.respond(format!(
                         "Recursion found in alias expansion: {} :(",
                         path.join(" -> ")
@@ -912,6 +1897,12 @@ impl<'a> Handler<'a> {
                     return Ok(());
                 }
 
+                chain.push(Restricted {
+                    what: "alias",
+                    key: alias.key.clone(),
+                    restrictions: alias.restrictions.clone(),
+                });
+
                 resolved = next;
                 message = &resolved;
             }
@@ -920,24 +1911,71 @@ impl<'a> Handler<'a> {
         let mut it = utils::Words::new(message);
         let first = it.next();
 
-        if let Some(commands) = self.commands.as_ref() {
-            if let Some((command, captures)) =
-                commands.resolve(user.channel(), first.as_ref().map(String::as_str), &it)
-            {
-                if command.has_var("count") {
-                    commands.increment(&*command)?;
-                }
+        let commands = self.commands.clone();
 
-                let vars = CommandVars {
-                    name: user.display_name(),
-                    target: user.channel(),
-                    count: command.count(),
-                    captures,
-                };
+        let resolved_command = commands
+            .as_ref()
+            .and_then(|c| c.resolve(user.channel(), first.as_ref().map(String::as_str), &it));
+
+        if let Some((command, _)) = resolved_command.as_ref() {
+            chain.push(Restricted {
+                what: "command",
+                key: command.key.clone(),
+                restrictions: command.restrictions.clone(),
+            });
+        }
+
+        let outcome = self.check_restrictions(&chain, user).await?;
+
+        if outcome != db::Outcome::Ok {
+            if let Some((command, _)) = resolved_command.as_ref() {
+                record_invocation(
+                    self.command_stats.as_ref(),
+                    user,
+                    &command.key.name,
+                    outcome,
+                );
+            }
+
+            if let Some((reason, action)) = self.test_filters(&user, message) {
+                self.apply_filter(&user, reason, action)?;
+            }
+
+            return Ok(());
+        }
 
-                let response = command.render(&vars)?;
-                self.sender.privmsg(response);
+        if let (Some(commands), Some((command, captures))) = (commands.as_ref(), resolved_command) {
+            if command.has_var("count") {
+                commands.increment(&*command)?;
             }
+
+            let vars = CommandVars {
+                name: user.display_name(),
+                target: user.channel(),
+                count: command.count(),
+                counters: self
+                    .counters
+                    .as_ref()
+                    .map(|c| c.vars(user.channel()))
+                    .unwrap_or_default(),
+                captures,
+            };
+
+            let response = command.render(&vars);
+
+            let outcome = match response {
+                Ok(..) => db::Outcome::Ok,
+                Err(..) => db::Outcome::Error,
+            };
+
+            record_invocation(
+                self.command_stats.as_ref(),
+                user,
+                &command.key.name,
+                outcome,
+            );
+
+            self.sender.privmsg(response?);
         }
 
         if let Some(command) = first {
@@ -946,12 +1984,14 @@ impl<'a> Handler<'a> {
 
                 let ctx = command::Context {
                     api_url: self.api_url.as_ref().map(|s| s.as_str()),
+                    command,
                     sender: &self.sender,
                     user: user.clone(),
                     it,
                     shutdown: self.shutdown,
                     scope_cooldowns: &mut self.scope_cooldowns,
                     message_hooks: &mut self.message_hooks,
+                    current: None,
                 };
 
                 let result = process_command(
@@ -960,6 +2000,7 @@ impl<'a> Handler<'a> {
                     &self.global_bus,
                     &mut self.currency_handler,
                     &mut self.handlers,
+                    self.command_stats.as_ref(),
                 );
 
                 if let Err(e) = result.await {
@@ -968,8 +2009,8 @@ impl<'a> Handler<'a> {
             }
         }
 
-        if self.should_be_deleted(&user, message) {
-            self.delete_message(&user)?;
+        if let Some((reason, action)) = self.test_filters(&user, message) {
+            self.apply_filter(&user, reason, action)?;
         }
 
         Ok(())
@@ -989,6 +2030,7 @@ impl<'a> Handler<'a> {
                 vips: self.vips.clone(),
                 stream_info: self.stream_info.clone(),
                 auth: self.auth.clone(),
+                ranks: self.ranks.clone(),
             }),
         };
 
@@ -1011,12 +2053,23 @@ impl<'a> Handler<'a> {
                     let channel = self.channel.clone();
                     let name = name.clone();
                     let message = message.clone();
+                    let stream_started_at = self
+                        .stream_info
+                        .data
+                        .read()
+                        .stream
+                        .as_ref()
+                        .map(|s| s.started_at);
 
                     tokio::spawn(async move {
-                        chat_log.observe(&tags, &*channel, &name, &message).await;
+                        chat_log
+                            .observe(&tags, &*channel, &name, &message, stream_started_at)
+                            .await;
                     });
                 }
 
+                self.record_history(&tags, &name, message);
+
                 let user = User {
                     inner: Arc::new(UserInner {
                         tags,
@@ -1027,6 +2080,7 @@ impl<'a> Handler<'a> {
                         vips: self.vips.clone(),
                         stream_info: self.stream_info.clone(),
                         auth: self.auth.clone(),
+                        ranks: self.ranks.clone(),
                     }),
                 };
 
@@ -1052,19 +2106,14 @@ impl<'a> Handler<'a> {
             Command::JOIN(ref channel, _, _) => {
                 let user = m.source_nickname().unwrap_or("?");
                 log::trace!("{} joined {}", user, channel);
+
+                if *self.ban_list_on_join.read() {
+                    self.apply_ban_list(channel, user);
+                }
             }
             Command::Response(..) => {
                 log::trace!("Response: {}", m);
             }
-            Command::PING(ref server, ref other) => {
-                log::trace!("Received PING, responding with PONG");
-                self.sender
-                    .send_immediate(Command::PONG(server.clone(), other.clone()));
-            }
-            Command::PONG(..) => {
-                log::trace!("Received PONG, clearing PING timeout");
-                *self.pong_timeout = None;
-            }
             Command::NOTICE(_, ref message) => {
                 let tags = Tags::from_tags(m.tags.take());
 
@@ -1097,10 +2146,14 @@ impl<'a> Handler<'a> {
             }
             Command::Raw(ref command, _, ref tail) => match command.as_str() {
                 "CLEARMSG" => {
-                    if let Some(chat_log) = self.chat_log.as_ref() {
-                        if let Some(tags) = ClearMsgTags::from_tags(m.tags) {
+                    if let Some(tags) = ClearMsgTags::from_tags(m.tags) {
+                        if let Some(chat_log) = self.chat_log.as_ref() {
                             chat_log.message_log.delete_by_id(&tags.target_msg_id);
                         }
+
+                        if let Some(history) = self.history.as_ref() {
+                            history.delete_by_id(self.sender.channel(), &tags.target_msg_id)?;
+                        }
                     }
                 }
                 "CLEARCHAT" => {
@@ -1114,6 +2167,20 @@ impl<'a> Handler<'a> {
                             }
                         }
                     }
+
+                    if let Some(history) = self.history.as_ref() {
+                        let since = chrono::Utc::now().naive_utc()
+                            - chrono::Duration::minutes(CLEAR_WINDOW_MINUTES);
+
+                        match tail {
+                            Some(user) => {
+                                history.delete_by_user(self.sender.channel(), user, since)?;
+                            }
+                            None => {
+                                history.delete_all(self.sender.channel(), since)?;
+                            }
+                        }
+                    }
                 }
                 _ => {
                     log::trace!("Raw: {:?}", m);
@@ -1140,6 +2207,7 @@ pub struct RealUser<'a> {
     vips: &'a RwLock<HashSet<String>>,
     stream_info: &'a stream_info::StreamInfo,
     auth: &'a Auth,
+    ranks: Option<&'a Ranks>,
 }
 
 impl<'a> RealUser<'a> {
@@ -1214,6 +2282,11 @@ impl<'a> RealUser<'a> {
         }
 
         roles.push(Role::Everyone);
+
+        if let Some(ranks) = self.ranks {
+            roles.extend(ranks.roles(self.name));
+        }
+
         roles
     }
 
@@ -1239,6 +2312,35 @@ struct UserInner {
     vips: Arc<RwLock<HashSet<String>>>,
     stream_info: stream_info::StreamInfo,
     auth: Auth,
+    ranks: Option<Ranks>,
+}
+
+/// The result of checking the links in a message against the whitelist.
+enum LinkCheck {
+    /// All links are whitelisted.
+    Ok,
+    /// A link isn't whitelisted.
+    Bad,
+    /// Shortened links which need to be expanded before they can be checked.
+    Pending(Vec<url::Url>),
+}
+
+/// Test if the host of the given link is whitelisted.
+fn is_whitelisted(whitelisted_hosts: &RwLock<HashSet<String>>, url: &url::Url) -> bool {
+    match url.host_str() {
+        Some(host) => whitelisted_hosts.read().contains(host),
+        None => true,
+    }
+}
+
+/// Use up a link permit given to the user with `!permit`, if they have one.
+fn take_link_permit(link_permits: Option<&LinkPermits>, user: &User) -> bool {
+    match (link_permits, user.name()) {
+        (Some(link_permits), Some(name)) => {
+            link_permits.take(user.channel(), name, &chrono::Utc::now())
+        }
+        _ => false,
+    }
 }
 
 #[derive(Clone)]
@@ -1259,6 +2361,7 @@ impl User {
                 vips: &*self.inner.vips,
                 stream_info: &self.inner.stream_info,
                 auth: &self.inner.auth,
+                ranks: self.inner.ranks.as_ref(),
             }),
             Principal::Injected => None,
         }
@@ -1368,6 +2471,16 @@ impl User {
     pub fn has_scope(&self, scope: Scope) -> bool {
         self.real().map(|u| u.has_scope(scope)).unwrap_or(true)
     }
+
+    /// Test if the current user is part of the given role, or has the given scope.
+    pub fn has_role_or_scope(&self, requirement: RoleOrScope) -> bool {
+        match requirement {
+            RoleOrScope::Role(role) => {
+                self.real().is_none() || self.roles().iter().any(|r| *r == role)
+            }
+            RoleOrScope::Scope(scope) => self.has_scope(scope),
+        }
+    }
 }
 
 struct PartitionResponse<'a, I> {
@@ -1458,7 +2571,7 @@ where
 }
 
 /// Struct of tags.
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, serde::Serialize)]
 pub struct Tags {
     /// Contents of the id tag if present.
     pub id: Option<String>,
@@ -1562,6 +2675,7 @@ pub struct CommandVars<'a> {
     name: Option<&'a str>,
     target: &'a str,
     count: i32,
+    counters: HashMap<String, i64>,
     #[serde(flatten)]
     captures: db::Captures<'a>,
 }
@@ -1601,8 +2715,10 @@ fn parse_room_members(message: &str) -> HashSet<String> {
 
 #[cfg(test)]
 mod tests {
-    use super::parse_room_members;
+    use super::{parse_room_members, testing, Duration, LedgerReason, Rank, Role, Scope};
+    use crate::{auth::RoleOrScope, db, template::Template};
     use hashbrown::HashSet;
+    use irc::proto::command::Command;
 
     #[test]
     fn test_parse_room_mods() {
@@ -1625,4 +2741,1002 @@ mod tests {
             parse_room_members("The moderators of this channel are:")
         );
     }
+
+    #[test]
+    fn test_connect_requests_moderators() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            chat.connect(&mut handler).await?;
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("/mods")),
+                chat.privmsg().await?
+            );
+            assert_eq!(
+                (String::from("#setbac"), String::from("/vips")),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_respond_with_display_name() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                "@display-name=SomeUser;id=1 :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :!ping",
+            )
+            .await?;
+
+            assert_eq!(
+                (
+                    String::from("#setbac"),
+                    String::from("SomeUser -> What do you want?")
+                ),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_alias_expansion() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            fixture
+                .commands
+                .edit("#setbac", "!hello", Template::compile("Hello {{rest}}!")?)?;
+            fixture.aliases.edit(
+                "#setbac",
+                "!hi",
+                db::AliasTemplate::compile("!hello {{rest}}")?,
+            )?;
+
+            chat.push(
+                &mut handler,
+                ":someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :!hi everyone",
+            )
+            .await?;
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("Hello everyone!")),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_alias_positional_arguments() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            fixture
+                .commands
+                .edit("#setbac", "!hello", Template::compile("Hello {{rest}}!")?)?;
+            fixture.aliases.edit(
+                "#setbac",
+                "!hi",
+                db::AliasTemplate::compile("!hello $2 and ${1:-nobody}")?,
+            )?;
+
+            chat.push(
+                &mut handler,
+                ":someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :!hi \"John Doe\" Jane",
+            )
+            .await?;
+
+            assert_eq!(
+                (
+                    String::from("#setbac"),
+                    String::from("Hello Jane and \"John Doe\"!")
+                ),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_alias_is_not_charged_when_command_is_refused() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            fixture
+                .commands
+                .edit("#setbac", "!hello", Template::compile("Hello {{rest}}!")?)?;
+            fixture.commands.edit_restrictions(
+                "#setbac",
+                "!hello",
+                db::Restrictions {
+                    scope: Some(RoleOrScope::Role(Role::Moderator)),
+                    ..db::Restrictions::default()
+                },
+            )?;
+
+            fixture.aliases.edit(
+                "#setbac",
+                "!hi",
+                db::AliasTemplate::compile("!hello {{rest}}")?,
+            )?;
+            fixture.aliases.edit_restrictions(
+                "#setbac",
+                "!hi",
+                db::Restrictions {
+                    price: Some(10),
+                    ..db::Restrictions::default()
+                },
+            )?;
+
+            fixture
+                .currency
+                .balance_add("#setbac", "someuser", 100, LedgerReason::Admin, None)
+                .await?;
+
+            chat.push(
+                &mut handler,
+                ":someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :!hi everyone",
+            )
+            .await?;
+
+            assert_eq!(
+                (
+                    String::from("#setbac"),
+                    String::from("someuser -> You are not allowed to run that command")
+                ),
+                chat.privmsg().await?
+            );
+
+            let balance = fixture.currency.balance_of("#setbac", "someuser").await?;
+            assert_eq!(Some(100), balance.map(|b| b.balance));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_delete_bad_link() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                "@id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :visit https://example.com",
+            )
+            .await?;
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete abc")),
+                chat.privmsg().await?
+            );
+
+            let echo = chat.echo().await?;
+
+            assert!(match echo.command {
+                Command::Raw(ref command, ..) => command == "CLEARMSG",
+                _ => false,
+            });
+
+            // moderators can link to whatever they want.
+            chat.push(
+                &mut handler,
+                "@msg-id=room_mods :tmi.twitch.tv NOTICE #setbac :The moderators of this channel are: moduser",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@id=def;display-name=ModUser :moduser!moduser@moduser.tmi.twitch.tv PRIVMSG #setbac :visit https://example.com",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@display-name=ModUser :moduser!moduser@moduser.tmi.twitch.tv PRIVMSG #setbac :!ping",
+            )
+            .await?;
+
+            assert_eq!(
+                (
+                    String::from("#setbac"),
+                    String::from("ModUser -> What do you want?")
+                ),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_bad_word() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            fixture.bad_words.edit(
+                "frick",
+                false,
+                &db::WordAction::Delete,
+                Some("{{name}} -> Watch your language!"),
+            )?;
+
+            chat.push(
+                &mut handler,
+                "@id=abc;display-name=SomeUser :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :oh frick",
+            )
+            .await?;
+
+            // NB: the order is not deterministic since the response is rate
+            // limited, and the deletion is not.
+            let mut sent = vec![chat.privmsg().await?.1, chat.privmsg().await?.1];
+            sent.sort();
+
+            assert_eq!(
+                vec![
+                    String::from("/delete abc"),
+                    String::from("SomeUser -> Watch your language!")
+                ],
+                sent
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_bad_word_escalation_replies_once() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("chat/strikes/enabled", true)?;
+            let mut handler = fixture.handler()?;
+
+            fixture.bad_words.edit(
+                "frick",
+                false,
+                &db::WordAction::Delete,
+                Some("{{name}} -> Watch your language!"),
+            )?;
+
+            let expected = vec![
+                vec![
+                    "/delete 1",
+                    "SomeUser -> Please don't do that (bad word), this is a warning.",
+                ],
+                vec![
+                    "/delete 2",
+                    "/timeout someuser 600 bad word",
+                    "SomeUser -> Timed out for 10m (bad word).",
+                ],
+            ];
+
+            for (id, expected) in expected.into_iter().enumerate() {
+                chat.push(
+                    &mut handler,
+                    &format!("@display-name=SomeUser;id={} :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :oh frick", id + 1),
+                )
+                .await?;
+
+                for expected in expected {
+                    assert_eq!(
+                        (String::from("#setbac"), String::from(expected)),
+                        chat.privmsg().await?
+                    );
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_link_permit() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            fixture.link_permits.insert(
+                "#setbac",
+                "someuser",
+                true,
+                chrono::Utc::now() + chrono::Duration::minutes(5),
+            );
+
+            chat.push(
+                &mut handler,
+                "@id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :visit https://example.com",
+            )
+            .await?;
+
+            // NB: the permit was used up by the first link.
+            chat.push(
+                &mut handler,
+                "@id=def :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :visit https://example.com",
+            )
+            .await?;
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete def")),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_rank_role_grants_scope() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            fixture
+                .auth
+                .insert(Scope::ChatBypassUrlWhitelist, Role::Rank1)?;
+
+            fixture.ranks.set_enabled(true);
+            fixture.ranks.set_ranks(vec![Rank {
+                name: String::from("Regular"),
+                watch_time: Duration::hours(10),
+                role: Some(Role::Rank1),
+            }])?;
+            fixture.ranks.observe("someuser", 11 * 3600);
+            fixture.ranks.observe("newuser", 3600);
+
+            chat.push(
+                &mut handler,
+                "@id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :visit https://example.com",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@id=def :newuser!newuser@newuser.tmi.twitch.tv PRIVMSG #setbac :visit https://example.com",
+            )
+            .await?;
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete def")),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_slow_shortened_link_is_not_resolved() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture
+                .settings
+                .set("chat/url-whitelist/resolve-shorteners", true)?;
+            fixture
+                .redirects
+                .insert("https://bit.ly/slow", "https://example.com/video");
+            fixture.redirects.hang("https://bit.ly/slow");
+
+            let mut handler = fixture.handler()?;
+            handler
+                .whitelisted_hosts
+                .write()
+                .insert(String::from("example.com"));
+
+            chat.push(
+                &mut handler,
+                "@id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :https://bit.ly/slow",
+            )
+            .await?;
+
+            chat.bad_link(&mut handler).await?;
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete abc")),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_shortened_link_does_not_block_chat() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture
+                .settings
+                .set("chat/url-whitelist/resolve-shorteners", true)?;
+            fixture.redirects.hang("https://bit.ly/slow");
+
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                "@id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :https://bit.ly/slow",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@id=def :otheruser!otheruser@otheruser.tmi.twitch.tv PRIVMSG #setbac :https://malware.example.org/",
+            )
+            .await?;
+
+            // NB: the shortened link is deleted once it times out resolving,
+            // while the rest of chat is handled in the meantime.
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete def")),
+                chat.privmsg().await?
+            );
+            chat.bad_link(&mut handler).await?;
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete abc")),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_shortened_link_is_resolved() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture
+                .settings
+                .set("chat/url-whitelist/resolve-shorteners", true)?;
+            fixture
+                .redirects
+                .insert("https://bit.ly/good", "https://example.com/video");
+            fixture
+                .redirects
+                .insert("https://bit.ly/bad", "https://tinyurl.com/bad");
+            fixture
+                .redirects
+                .insert("https://tinyurl.com/bad", "https://malware.example.org/");
+
+            let mut handler = fixture.handler()?;
+            handler
+                .whitelisted_hosts
+                .write()
+                .insert(String::from("example.com"));
+
+            chat.push(
+                &mut handler,
+                "@id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :https://bit.ly/good",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@id=def :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :https://bit.ly/bad",
+            )
+            .await?;
+
+            chat.bad_link(&mut handler).await?;
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete def")),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_expanded_link_is_struck() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("chat/strikes/enabled", true)?;
+            fixture
+                .settings
+                .set("chat/url-whitelist/resolve-shorteners", true)?;
+            fixture
+                .redirects
+                .insert("https://bit.ly/bad", "https://malware.example.org/");
+
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                "@display-name=SomeUser;id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :https://bit.ly/bad",
+            )
+            .await?;
+
+            chat.bad_link(&mut handler).await?;
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete abc")),
+                chat.privmsg().await?
+            );
+            assert_eq!(
+                (
+                    String::from("#setbac"),
+                    String::from(
+                        "SomeUser -> Please don't do that (link not permitted), this is a warning."
+                    )
+                ),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_ban_list_on_first_message() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("chat/ban-list/enabled", true)?;
+            fixture.settings.set("chat/ban-list/on-join", false)?;
+            fixture.ban_list.insert(&[db::BanEntry {
+                user: String::from("spammer"),
+                action: db::BanAction::Ban,
+                reason: Some(String::from("spam bot")),
+            }])?;
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                ":spammer!spammer@spammer.tmi.twitch.tv JOIN #setbac",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@id=abc :spammer!spammer@spammer.tmi.twitch.tv PRIVMSG #setbac :buy followers",
+            )
+            .await?;
+
+            assert_eq!(
+                (
+                    String::from("#setbac"),
+                    String::from("/ban spammer ban list: spam bot")
+                ),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_ban_list_on_join() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("chat/ban-list/enabled", true)?;
+            fixture.ban_list.insert(&[db::BanEntry {
+                user: String::from("troll"),
+                action: db::BanAction::Timeout(Duration::seconds(600)),
+                reason: None,
+            }])?;
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                ":troll!troll@troll.tmi.twitch.tv JOIN #setbac",
+            )
+            .await?;
+
+            assert_eq!(
+                (
+                    String::from("#setbac"),
+                    String::from("/timeout troll 600 ban list")
+                ),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_obfuscated_bad_word_timeout() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            fixture.bad_words.edit(
+                "frick",
+                false,
+                &db::WordAction::Timeout(Duration::seconds(600)),
+                None,
+            )?;
+
+            chat.push(
+                &mut handler,
+                "@id=abc;display-name=SomeUser :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :oh FR1CCK!",
+            )
+            .await?;
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete abc")),
+                chat.privmsg().await?
+            );
+            assert_eq!(
+                (
+                    String::from("#setbac"),
+                    String::from("/timeout someuser 600 bad word")
+                ),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_history_honours_deletions() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                "@id=abc;display-name=SomeUser :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :hello there",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@id=def;display-name=OtherUser :otheruser!otheruser@otheruser.tmi.twitch.tv PRIVMSG #setbac :hello back",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@login=someuser;target-msg-id=abc :tmi.twitch.tv CLEARMSG #setbac :hello there",
+            )
+            .await?;
+
+            let filter = db::MessageFilter {
+                channel: Some("#setbac"),
+                q: Some("hello"),
+                ..db::MessageFilter::default()
+            };
+
+            let messages = fixture.messages.search(&filter, 10)?;
+            assert_eq!(1, messages.len());
+            assert_eq!("otheruser", messages[0].user);
+            assert_eq!(
+                Some("def"),
+                messages[0].message_id.as_ref().map(String::as_str)
+            );
+
+            let filter = db::MessageFilter {
+                user: Some("someuser"),
+                deleted: true,
+                ..filter
+            };
+
+            let messages = fixture.messages.search(&filter, 10)?;
+            assert_eq!(1, messages.len());
+            assert!(messages[0].deleted);
+            assert_eq!("hello there", messages[0].text);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_history_records_badges() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                "@id=abc;badges=subscriber/12,bits/100;display-name=SomeUser :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :hello there",
+            )
+            .await?;
+
+            let filter = db::MessageFilter {
+                channel: Some("#setbac"),
+                user: Some("someuser"),
+                ..db::MessageFilter::default()
+            };
+
+            let messages = fixture.messages.search(&filter, 10)?;
+            assert_eq!(1, messages.len());
+            assert_eq!(
+                Some("subscriber/12,bits/100"),
+                messages[0].tags["badges"].as_str()
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_history_honours_clear_chat() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                "@id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :hello there",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@id=def :otheruser!otheruser@otheruser.tmi.twitch.tv PRIVMSG #setbac :hello back",
+            )
+            .await?;
+
+            let filter = |deleted| db::MessageFilter {
+                channel: Some("#setbac"),
+                deleted,
+                ..db::MessageFilter::default()
+            };
+
+            chat.push(
+                &mut handler,
+                "@ban-duration=10 :tmi.twitch.tv CLEARCHAT #setbac :someuser",
+            )
+            .await?;
+
+            let messages = fixture.messages.search(&filter(false), 10)?;
+            assert_eq!(1, messages.len());
+            assert_eq!("otheruser", messages[0].user);
+
+            chat.push(&mut handler, ":tmi.twitch.tv CLEARCHAT #setbac")
+                .await?;
+
+            assert!(fixture.messages.search(&filter(false), 10)?.is_empty());
+            assert_eq!(2, fixture.messages.search(&filter(true), 10)?.len());
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_subscriber_bypasses_url_whitelist() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            let mut handler = fixture.handler()?;
+
+            fixture
+                .auth
+                .insert(Scope::ChatBypassUrlWhitelist, Role::Subscriber)?;
+
+            fixture.api.insert(
+                "/helix/subscriptions",
+                serde_json::json!({ "data": [testing::subscription("setbac", "someuser")] }),
+            );
+            fixture.refresh_subs().await;
+
+            chat.push(
+                &mut handler,
+                "@id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :visit https://example.com",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@id=def :newuser!newuser@newuser.tmi.twitch.tv PRIVMSG #setbac :visit https://example.com",
+            )
+            .await?;
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete def")),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_lockdown_on_new_chatter_burst() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("chat/lockdown/auto/enabled", true)?;
+            fixture.settings.set("chat/lockdown/auto/threshold", 2)?;
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                "@id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :hi",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@id=def :otheruser!otheruser@otheruser.tmi.twitch.tv PRIVMSG #setbac :hello",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@id=ghi :otheruser!otheruser@otheruser.tmi.twitch.tv PRIVMSG #setbac :LOOK AT THIS",
+            )
+            .await?;
+
+            // NB: the order is not deterministic since the announcement is
+            // rate limited, and the deletion is not.
+            let mut sent = vec![chat.privmsg().await?.1, chat.privmsg().await?.1];
+            sent.sort();
+
+            assert_eq!(String::from("/delete ghi"), sent[0]);
+            assert!(sent[1].starts_with("Lots of new chatters, enabling lockdown for 10m."));
+
+            let (first_seen_at, first_time) = fixture.seen_users.seen("#setbac", "someuser")?;
+            assert!(!first_time);
+            assert!(handler
+                .lockdown
+                .is_new_user(first_seen_at, chrono::Utc::now().naive_utc()));
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_lockdown_with_huge_new_user_age() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("chat/lockdown/active", true)?;
+            fixture.settings.set(
+                "chat/lockdown/new-user-age",
+                Duration::seconds(u64::max_value() / 2),
+            )?;
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                "@id=abc :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :LOOK AT THIS",
+            )
+            .await?;
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete abc")),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_rate_limit_deletes() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("chat/rate-limit/enabled", true)?;
+            fixture
+                .settings
+                .set("chat/rate-limit/everyone/messages", 2)?;
+            let mut handler = fixture.handler()?;
+
+            for id in 1..=3 {
+                chat.push(
+                    &mut handler,
+                    &format!(
+                        "@id={} :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :hi",
+                        id
+                    ),
+                )
+                .await?;
+            }
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete 3")),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_spam_filter_warns() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("chat/spam/caps/enabled", true)?;
+            fixture.settings.set("chat/spam/caps/action", "warn")?;
+            let mut handler = fixture.handler()?;
+
+            chat.push(
+                &mut handler,
+                "@display-name=SomeUser;id=1;emotes=25:15-19 :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :hello everyone Kappa",
+            )
+            .await?;
+
+            chat.push(
+                &mut handler,
+                "@display-name=SomeUser;id=2 :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :HELLO EVERYONE",
+            )
+            .await?;
+
+            assert_eq!(
+                (String::from("#setbac"), String::from("/delete 2")),
+                chat.privmsg().await?
+            );
+            assert_eq!(
+                (
+                    String::from("#setbac"),
+                    String::from("SomeUser -> Your message was removed: too many capital letters")
+                ),
+                chat.privmsg().await?
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_strikes_escalate() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("chat/strikes/enabled", true)?;
+            let mut handler = fixture.handler()?;
+
+            let expected = vec![
+                vec![
+                    "/delete 1",
+                    "SomeUser -> Please don't do that (link not permitted), this is a warning.",
+                ],
+                vec![
+                    "/delete 2",
+                    "/timeout someuser 600 link not permitted",
+                    "SomeUser -> Timed out for 10m (link not permitted).",
+                ],
+            ];
+
+            for (id, expected) in expected.into_iter().enumerate() {
+                chat.push(
+                    &mut handler,
+                    &format!("@display-name=SomeUser;id={} :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :see http://example.com", id + 1),
+                )
+                .await?;
+
+                for expected in expected {
+                    assert_eq!(
+                        (String::from("#setbac"), String::from(expected)),
+                        chat.privmsg().await?
+                    );
+                }
+            }
+
+            assert_eq!(
+                2,
+                fixture
+                    .strikes
+                    .get("#setbac", "someuser", &Duration::hours(24))?
+                    .map(|s| s.count)
+                    .unwrap_or_default()
+            );
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_moderation_is_audited() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("chat/strikes/enabled", true)?;
+            let mut handler = fixture.handler()?;
+
+            for id in 1..=2 {
+                chat.push(
+                    &mut handler,
+                    &format!("@display-name=SomeUser;id={} :someuser!someuser@someuser.tmi.twitch.tv PRIVMSG #setbac :see http://example.com", id),
+                )
+                .await?;
+            }
+
+            let page = fixture
+                .audit_log
+                .list("#setbac", &db::AuditFilter::default(), 0, 10)?;
+
+            assert_eq!(3, page.total);
+
+            let filter = db::AuditFilter {
+                action: Some(db::AuditAction::Timeout),
+                ..db::AuditFilter::default()
+            };
+
+            let page = fixture.audit_log.list("#setbac", &filter, 0, 10)?;
+            assert_eq!(1, page.total);
+
+            let entry = &page.entries[0];
+            assert_eq!(None, entry.actor);
+            assert_eq!(Some("someuser"), entry.target.as_ref().map(String::as_str));
+            assert_eq!(
+                Some("link not permitted"),
+                entry.reason.as_ref().map(String::as_str)
+            );
+            Ok(())
+        })
+    }
 }
@@ -2,8 +2,14 @@ use crate::{
     api::{twitch::Channel, Twitch},
     emotes, injector, irc, message_log, settings,
     storage::Cache,
+    utils,
 };
+use chrono::{DateTime, Utc};
 use failure::Error;
+use parking_lot::RwLock;
+use std::{path::PathBuf, sync::Arc};
+
+mod writer;
 
 pub struct Builder {
     twitch: Twitch,
@@ -14,6 +20,10 @@ pub struct Builder {
     pub(crate) enabled: bool,
     pub(crate) emotes_enabled_stream: settings::Stream<bool>,
     pub(crate) emotes_enabled: bool,
+    pub(crate) path_stream: settings::OptionStream<PathBuf>,
+    rotation: Arc<RwLock<writer::Rotation>>,
+    retention: Arc<RwLock<utils::Duration>>,
+    writer: Option<writer::Writer>,
 }
 
 impl Builder {
@@ -30,9 +40,11 @@ impl Builder {
         let (emotes_enabled_stream, emotes_enabled) =
             settings.stream("emotes-enabled").or_default()?;
 
+        let (path_stream, path) = settings.stream("path").optional()?;
+
         message_log.enabled(enabled);
 
-        Ok(Self {
+        let mut builder = Self {
             twitch,
             message_log,
             cache_stream,
@@ -41,23 +53,44 @@ impl Builder {
             enabled,
             emotes_enabled_stream,
             emotes_enabled,
-        })
+            path_stream,
+            rotation: settings.var("rotation", writer::Rotation::Daily)?,
+            retention: settings.var("retention", utils::Duration::hours(24 * 30))?,
+            writer: None,
+        };
+
+        builder.path(path);
+        Ok(builder)
+    }
+
+    /// Set the directory to write chat logs to, if any.
+    pub fn path(&mut self, path: Option<PathBuf>) {
+        self.writer = path
+            .map(|path| writer::Writer::new(path, self.rotation.clone(), self.retention.clone()));
     }
 
     /// Construct a new chat log with the specified configuration.
     pub fn build(&self) -> Result<Option<ChatLog>, Error> {
-        if !self.enabled {
+        if !self.enabled && self.writer.is_none() {
             return Ok(None);
         }
 
-        let emotes = match (self.emotes_enabled, self.cache.as_ref()) {
+        // NB: emotes are also rendered for the writer, since the exported
+        // logs include them.
+        let emotes = match (
+            self.emotes_enabled || self.writer.is_some(),
+            self.cache.as_ref(),
+        ) {
             (true, Some(cache)) => Some(emotes::Emotes::new(cache.clone(), self.twitch.clone())?),
             _ => None,
         };
 
         Ok(Some(ChatLog {
             message_log: self.message_log.clone(),
+            enabled: self.enabled,
+            emotes_enabled: self.emotes_enabled,
             emotes,
+            writer: self.writer.clone(),
         }))
     }
 }
@@ -66,12 +99,29 @@ impl Builder {
 pub struct ChatLog {
     /// Log to add messages to.
     pub message_log: message_log::MessageLog,
+    /// If messages should be added to the log.
+    enabled: bool,
+    /// If rendered emotes should be added to the log.
+    emotes_enabled: bool,
     /// Handler of emotes.
     emotes: Option<emotes::Emotes>,
+    /// Writer of chat logs to disk.
+    writer: Option<writer::Writer>,
 }
 
 impl ChatLog {
-    pub async fn observe(&self, tags: &irc::Tags, channel: &Channel, name: &str, message: &str) {
+    /// Observe a message sent in chat.
+    ///
+    /// `stream_started_at` is when the current stream started, if the
+    /// streamer is live.
+    pub async fn observe(
+        &self,
+        tags: &irc::Tags,
+        channel: &Channel,
+        name: &str,
+        message: &str,
+        stream_started_at: Option<DateTime<Utc>>,
+    ) {
         let rendered = match self.emotes.as_ref() {
             Some(emotes) => match emotes.render(&tags, channel, name, message).await {
                 Ok(rendered) => Some(rendered),
@@ -83,6 +133,27 @@ impl ChatLog {
             None => None,
         };
 
+        if let Some(writer) = self.writer.as_ref() {
+            let record = writer::Record {
+                timestamp: Utc::now(),
+                channel: &channel.name,
+                name,
+                message,
+                tags,
+                rendered: rendered.as_ref(),
+            };
+
+            if let Err(e) = writer.write(&record, stream_started_at) {
+                log_err!(e, "failed to write chat log");
+            }
+        }
+
+        if !self.enabled {
+            return;
+        }
+
+        let rendered = if self.emotes_enabled { rendered } else { None };
+
         self.message_log.push_back(&tags, &name, message, rendered);
     }
 }
@@ -0,0 +1,415 @@
+//! Export of the chat log to disk.
+//!
+//! Every message is appended as a line of JSON to a file which covers either
+//! a single day or a single stream. When a new file is started, older files
+//! are compressed in the background and files older than the retention are
+//! removed.
+
+use crate::{emotes, irc, utils};
+use chrono::{DateTime, Utc};
+use failure::{bail, Error, ResultExt as _};
+use flate2::{write::GzEncoder, Compression};
+use parking_lot::{Mutex, RwLock};
+use std::{
+    fs,
+    io::{self, Write as _},
+    path::{Path, PathBuf},
+    sync::Arc,
+    thread,
+    time::SystemTime,
+};
+
+/// Extension of files being written to.
+const EXTENSION: &str = "jsonl";
+/// Extension of compressed files.
+const COMPRESSED_EXTENSION: &str = "jsonl.gz";
+/// Extension of files which have been moved aside to be compressed.
+const ROTATING_EXTENSION: &str = "jsonl.rotating";
+
+/// How chat log files are rotated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Rotation {
+    /// Start a new file every day.
+    #[serde(rename = "daily")]
+    Daily,
+    /// Start a new file for every stream, and every day while offline.
+    #[serde(rename = "stream")]
+    Stream,
+}
+
+/// A single message written to the chat log.
+#[derive(serde::Serialize)]
+pub struct Record<'a> {
+    pub timestamp: DateTime<Utc>,
+    pub channel: &'a str,
+    pub name: &'a str,
+    pub message: &'a str,
+    pub tags: &'a irc::Tags,
+    /// Badges and emote spans of the message, if emotes could be rendered.
+    pub rendered: Option<&'a emotes::Rendered>,
+}
+
+/// The file currently being written to.
+struct Current {
+    name: String,
+    file: fs::File,
+}
+
+struct Inner {
+    dir: PathBuf,
+    current: Option<Current>,
+}
+
+/// Writer of chat logs to a directory.
+#[derive(Clone)]
+pub struct Writer {
+    inner: Arc<Mutex<Inner>>,
+    /// Held while rotating, so that only one rotation runs at a time.
+    rotating: Arc<Mutex<()>>,
+    rotation: Arc<RwLock<Rotation>>,
+    retention: Arc<RwLock<utils::Duration>>,
+}
+
+impl Writer {
+    /// Construct a writer of chat logs into the given directory.
+    pub fn new(
+        dir: PathBuf,
+        rotation: Arc<RwLock<Rotation>>,
+        retention: Arc<RwLock<utils::Duration>>,
+    ) -> Self {
+        Writer {
+            inner: Arc::new(Mutex::new(Inner { dir, current: None })),
+            rotating: Arc::new(Mutex::new(())),
+            rotation,
+            retention,
+        }
+    }
+
+    /// Append a record to the current file, starting a new one if needed.
+    ///
+    /// `stream_started_at` is when the current stream started, if the
+    /// streamer is live.
+    pub fn write(
+        &self,
+        record: &Record<'_>,
+        stream_started_at: Option<DateTime<Utc>>,
+    ) -> Result<(), Error> {
+        let name = file_name(*self.rotation.read(), record.timestamp, stream_started_at);
+
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut inner = self.inner.lock();
+        let inner = &mut *inner;
+
+        let rotated = match inner.current.as_ref() {
+            Some(current) if current.name == name => false,
+            _ => {
+                // NB: the current file is replaced before rotating, so that a
+                // failing rotation doesn't prevent writes.
+                let file = open(&inner.dir, &name)?;
+                inner.current = Some(Current { name, file });
+                true
+            }
+        };
+
+        if let Some(current) = inner.current.as_mut() {
+            current.file.write_all(&line)?;
+        }
+
+        if rotated {
+            self.spawn_rotate(inner.dir.clone());
+        }
+
+        Ok(())
+    }
+
+    /// Compress and expire old files in the background.
+    ///
+    /// This runs on a dedicated thread, since compressing a large file would
+    /// otherwise block the runtime. Errors are logged, so that a file which
+    /// can't be rotated doesn't prevent the chat log from being written.
+    fn spawn_rotate(&self, dir: PathBuf) {
+        let inner = self.inner.clone();
+        let rotating = self.rotating.clone();
+        let retention = self.retention.read().clone();
+
+        let result = thread::Builder::new()
+            .name(String::from("chat-log-rotate"))
+            .spawn(move || {
+                let _guard = rotating.lock();
+
+                if let Err(e) = rotate(&dir, &inner, &retention, SystemTime::now()) {
+                    log_err!(e, "failed to rotate chat logs: {}", dir.display());
+                }
+            });
+
+        if let Err(e) = result {
+            log_err!(e, "failed to start chat log rotation");
+        }
+    }
+}
+
+/// The name of the file to write a message sent at `now` to.
+fn file_name(
+    rotation: Rotation,
+    now: DateTime<Utc>,
+    stream_started_at: Option<DateTime<Utc>>,
+) -> String {
+    match (rotation, stream_started_at) {
+        (Rotation::Stream, Some(started_at)) => format!(
+            "stream-{}.{}",
+            started_at.format("%Y-%m-%dT%H-%M-%S"),
+            EXTENSION
+        ),
+        _ => format!("chat-{}.{}", now.format("%Y-%m-%d"), EXTENSION),
+    }
+}
+
+/// Open the given file for appending, creating the directory if needed.
+fn open(dir: &Path, name: &str) -> Result<fs::File, Error> {
+    fs::create_dir_all(dir)
+        .with_context(|_| format!("failed to create directory: {}", dir.display()))?;
+
+    let path = dir.join(name);
+
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|_| format!("failed to open: {}", path.display()))?;
+
+    Ok(file)
+}
+
+/// Compress all files except the current one, and remove compressed files
+/// which are older than the retention.
+///
+/// A retention of zero means that files are kept forever. The age of files is
+/// measured relative to `now`.
+fn rotate(
+    dir: &Path,
+    inner: &Mutex<Inner>,
+    retention: &utils::Duration,
+    now: SystemTime,
+) -> Result<(), Error> {
+    for e in fs::read_dir(dir)? {
+        let e = e?;
+        let path = e.path();
+
+        let name = match path.file_name().and_then(|n| n.to_str()) {
+            Some(name) => name.to_string(),
+            None => continue,
+        };
+
+        if name.ends_with(&format!(".{}", COMPRESSED_EXTENSION)) {
+            if retention.is_empty() {
+                continue;
+            }
+
+            if let Err(e) = expire(&path, now, retention) {
+                log_err!(e, "failed to expire chat log: {}", path.display());
+            }
+
+            continue;
+        }
+
+        let path = if name.ends_with(&format!(".{}", EXTENSION)) {
+            // NB: move the file aside while holding the lock, so that if the
+            // writer reopens the same name it starts a fresh file instead of
+            // appending to one being compressed.
+            let inner = inner.lock();
+
+            if inner
+                .current
+                .as_ref()
+                .map(|c| c.name == name)
+                .unwrap_or(false)
+            {
+                continue;
+            }
+
+            let rotating = path.with_extension(ROTATING_EXTENSION);
+
+            if let Err(e) = fs::rename(&path, &rotating) {
+                log_err!(e, "failed to move aside chat log: {}", path.display());
+                continue;
+            }
+
+            rotating
+        } else if name.ends_with(&format!(".{}", ROTATING_EXTENSION)) {
+            // NB: left over from an earlier rotation which was interrupted.
+            path
+        } else {
+            continue;
+        };
+
+        log::info!("Compressing chat log: {}", path.display());
+
+        if let Err(e) = compress(&path) {
+            log_err!(e, "failed to compress: {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove the given compressed file if it's older than the retention.
+fn expire(path: &Path, now: SystemTime, retention: &utils::Duration) -> Result<(), Error> {
+    let age = now
+        .duration_since(fs::metadata(path)?.modified()?)
+        .unwrap_or_default();
+
+    if age > retention.as_std() {
+        log::info!("Removing expired chat log: {}", path.display());
+        fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+/// Compress the given file, removing the original.
+///
+/// Existing archives are never overwritten. If an archive with the same name
+/// exists, a numeric suffix is added to the new one.
+fn compress(path: &Path) -> Result<(), Error> {
+    let mut input = fs::File::open(path)?;
+    let output = create_archive(path)?;
+
+    let mut encoder = GzEncoder::new(output, Compression::default());
+    io::copy(&mut input, &mut encoder)?;
+    encoder.finish()?;
+
+    fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Create a new archive for the given file, which doesn't overwrite any
+/// existing one.
+fn create_archive(path: &Path) -> Result<fs::File, Error> {
+    // NB: `with_extension` only strips the last extension.
+    let stem = path.with_extension("").with_extension("");
+
+    for n in 0u32.. {
+        let archive = match n {
+            0 => stem.with_extension(COMPRESSED_EXTENSION),
+            n => stem.with_extension(format!("{}.{}", n, COMPRESSED_EXTENSION)),
+        };
+
+        match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&archive)
+        {
+            Ok(file) => return Ok(file),
+            Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => {
+                return Err(Error::from(e)
+                    .context(format!("failed to create: {}", archive.display()))
+                    .into())
+            }
+        }
+    }
+
+    bail!("no free archive name for: {}", path.display())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, file_name, open, rotate, Current, Inner, Rotation};
+    use crate::utils::Duration;
+    use chrono::{TimeZone as _, Utc};
+    use flate2::read::GzDecoder;
+    use parking_lot::Mutex;
+    use std::{
+        fs,
+        io::Read as _,
+        time::{self, SystemTime},
+    };
+
+    #[test]
+    fn test_file_name() {
+        let now = Utc.ymd(2019, 10, 24).and_hms(1, 30, 0);
+        let started_at = Utc.ymd(2019, 10, 23).and_hms(22, 15, 5);
+
+        assert_eq!(
+            "chat-2019-10-24.jsonl",
+            file_name(Rotation::Daily, now, None)
+        );
+        assert_eq!(
+            "chat-2019-10-24.jsonl",
+            file_name(Rotation::Daily, now, Some(started_at))
+        );
+        assert_eq!(
+            "chat-2019-10-24.jsonl",
+            file_name(Rotation::Stream, now, None)
+        );
+        assert_eq!(
+            "stream-2019-10-23T22-15-05.jsonl",
+            file_name(Rotation::Stream, now, Some(started_at))
+        );
+    }
+
+    #[test]
+    fn test_compress_keeps_existing_archive() -> Result<(), failure::Error> {
+        let dir = std::env::temp_dir().join(format!("oxidize-chat-log-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+
+        let path = dir.join("chat-2019-10-24.jsonl.rotating");
+
+        fs::write(&path, "first\n")?;
+        compress(&path)?;
+        fs::write(&path, "second\n")?;
+        compress(&path)?;
+
+        let read = |name: &str| -> Result<String, failure::Error> {
+            let mut out = String::new();
+            GzDecoder::new(fs::File::open(dir.join(name))?).read_to_string(&mut out)?;
+            Ok(out)
+        };
+
+        assert_eq!("first\n", read("chat-2019-10-24.jsonl.gz")?);
+        assert_eq!("second\n", read("chat-2019-10-24.1.jsonl.gz")?);
+        assert!(!path.exists());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_rotate() -> Result<(), failure::Error> {
+        let dir = std::env::temp_dir().join(format!("oxidize-chat-log-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir)?;
+
+        fs::write(dir.join("chat-2019-10-23.jsonl"), "old\n")?;
+        fs::write(dir.join("chat-2019-10-01.jsonl.gz"), "")?;
+
+        let inner = Mutex::new(Inner {
+            dir: dir.clone(),
+            current: Some(Current {
+                name: String::from("chat-2019-10-24.jsonl"),
+                file: open(&dir, "chat-2019-10-24.jsonl")?,
+            }),
+        });
+
+        let retention = Duration::seconds(24 * 3600);
+
+        // NB: nothing is old enough to expire yet.
+        rotate(&dir, &inner, &retention, SystemTime::now())?;
+
+        assert!(dir.join("chat-2019-10-24.jsonl").exists());
+        assert!(!dir.join("chat-2019-10-23.jsonl").exists());
+        assert!(dir.join("chat-2019-10-23.jsonl.gz").exists());
+        assert!(dir.join("chat-2019-10-01.jsonl.gz").exists());
+
+        let later = SystemTime::now() + time::Duration::from_secs(2 * 24 * 3600);
+        rotate(&dir, &inner, &retention, later)?;
+
+        assert!(dir.join("chat-2019-10-24.jsonl").exists());
+        assert!(!dir.join("chat-2019-10-23.jsonl.gz").exists());
+        assert!(!dir.join("chat-2019-10-01.jsonl.gz").exists());
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
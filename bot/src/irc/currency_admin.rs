@@ -1,19 +1,52 @@
 use crate::{
     auth::Scope,
-    command,
-    currency::{BalanceTransferError, Currency},
+    command::{self, Param, Spec, Sub},
+    currency::{BalanceTransferError, Currency, LedgerEntry, LedgerReason, RankBy, Ranked},
     db,
     injector::Injector,
     prelude::*,
     utils,
 };
+use chrono::{NaiveDateTime, Utc};
 use failure::Error;
 use parking_lot::RwLock;
 use std::sync::Arc;
 
-/// Handler for the !admin command.
+/// Number of entries shown by `!currency history`.
+const HISTORY_LIMIT: i64 = 5;
+/// Default number of users shown by `!currency top`.
+const DEFAULT_TOP: u32 = 5;
+/// Maximum number of users shown by `!currency top`.
+const MAX_TOP: u32 = 10;
+
+static SPEC: Spec = Spec {
+    subs: &[
+        Sub::new(
+            "give",
+            &[Param::required("user"), Param::required("amount")],
+        ),
+        Sub::scoped(
+            "boost",
+            Scope::CurrencyBoost,
+            &[Param::required("user"), Param::required("amount")],
+        ),
+        Sub::scoped(
+            "windfall",
+            Scope::CurrencyWindfall,
+            &[Param::required("amount")],
+        ),
+        Sub::scoped("show", Scope::CurrencyShow, &[Param::required("user")]),
+        Sub::new("history", &[Param::optional("user")]),
+        Sub::new("top", &[Param::optional("n")]),
+        Sub::new("watchtime", &[Param::optional("n")]),
+        Sub::new("rank", &[Param::optional("user")]),
+    ],
+};
+
+/// Handler for the !currency command.
 pub struct Handler {
     pub currency: Arc<RwLock<Option<Currency>>>,
+    pub audit_log: Arc<RwLock<Option<db::AuditLog>>>,
 }
 
 impl Handler {
@@ -39,41 +72,43 @@ impl command::Handler for Handler {
             }
         };
 
-        match ctx.next().as_ref().map(String::as_str) {
-            None => {
-                let user = match ctx.user.real() {
-                    Some(user) => user,
-                    None => {
-                        ctx.respond("Only real users can check their balance");
-                        return Ok(());
-                    }
-                };
+        if ctx.rest().trim().is_empty() {
+            let user = match ctx.user.real() {
+                Some(user) => user,
+                None => {
+                    ctx.respond("Only real users can check their balance");
+                    return Ok(());
+                }
+            };
 
-                let result = currency.balance_of(user.channel(), user.name()).await;
+            let result = currency.balance_of(user.channel(), user.name()).await;
 
-                match result {
-                    Ok(balance) => {
-                        let balance = balance.unwrap_or_default();
-                        let watch_time = utils::compact_duration(&balance.watch_time().as_std());
+            match result {
+                Ok(balance) => {
+                    let balance = balance.unwrap_or_default();
+                    let watch_time = utils::compact_duration(&balance.watch_time().as_std());
 
-                        user.respond(format!(
-                            "You have {balance} {name} [{watch_time}].",
-                            balance = balance.balance,
-                            name = currency.name,
-                            watch_time = watch_time,
-                        ));
-                    }
-                    Err(e) => {
-                        user.respond("Could not get balance, sorry :(");
-                        log_err!(e, "failed to get balance");
-                    }
+                    user.respond(format!(
+                        "You have {balance} {name} [{watch_time}].",
+                        balance = balance.balance,
+                        name = currency.name,
+                        watch_time = watch_time,
+                    ));
+                }
+                Err(e) => {
+                    user.respond("Could not get balance, sorry :(");
+                    log_err!(e, "failed to get balance");
                 }
             }
-            Some("show") => {
-                ctx.check_scope(Scope::CurrencyShow)?;
-                let to_show = ctx_try!(ctx.next_str("<user>"));
 
-                match currency.balance_of(ctx.channel(), to_show.as_str()).await {
+            return Ok(());
+        }
+
+        match ctx_try!(ctx.subcommand(&SPEC)?) {
+            "show" => {
+                let to_show = ctx_try!(ctx.arg::<command::User>());
+
+                match currency.balance_of(ctx.channel(), to_show.as_ref()).await {
                     Ok(balance) => {
                         let balance = balance.unwrap_or_default();
                         let watch_time = utils::compact_duration(&balance.watch_time().as_std());
@@ -92,9 +127,108 @@ impl command::Handler for Handler {
                     }
                 }
             }
-            Some("give") => {
-                let taker = db::user_id(&ctx_try!(ctx.next_str("<user> <amount>")));
-                let amount: i64 = ctx_try!(ctx.next_parse("<user> <amount>"));
+            "top" => leaderboard(&mut ctx, &currency, RankBy::Balance).await?,
+            "watchtime" => leaderboard(&mut ctx, &currency, RankBy::WatchTime).await?,
+            "rank" => {
+                let user = match ctx_try!(ctx.optional_arg::<command::User>()) {
+                    Some(command::User(user)) => user,
+                    None => match ctx.user.name() {
+                        Some(name) => name.to_string(),
+                        None => {
+                            ctx.respond("Only real users can check their rank");
+                            return Ok(());
+                        }
+                    },
+                };
+
+                let result = currency
+                    .rank_of(ctx.channel(), RankBy::Balance, &user)
+                    .await;
+
+                let rank = match result {
+                    Ok(Some(rank)) => rank,
+                    Ok(None) => {
+                        ctx.respond(format!("{} is not ranked.", user));
+                        return Ok(());
+                    }
+                    Err(e) => {
+                        ctx.respond("Could not get rank, sorry :(");
+                        log_err!(e, "failed to get rank");
+                        return Ok(());
+                    }
+                };
+
+                let mut response = format!(
+                    "{user} is ranked #{rank} of {total} by {currency}",
+                    user = user,
+                    rank = rank.rank,
+                    total = rank.total,
+                    currency = currency.name,
+                );
+
+                if currency.tracks_watch_time() {
+                    let result = currency
+                        .rank_of(ctx.channel(), RankBy::WatchTime, &user)
+                        .await;
+
+                    match result {
+                        Ok(Some(rank)) => {
+                            response.push_str(&format!(", and #{} by watch time", rank.rank));
+                        }
+                        Ok(None) => (),
+                        Err(e) => log_err!(e, "failed to get watch time rank"),
+                    }
+                }
+
+                ctx.respond(format!("{}.", response));
+            }
+            // Show the latest changes to the balance of a user.
+            "history" => {
+                let user = match ctx_try!(ctx.optional_arg::<command::User>()) {
+                    Some(command::User(user)) => {
+                        if !ctx.user.is(&user) && !ctx.user.has_scope(Scope::CurrencyShow) {
+                            ctx.respond("You are not allowed to see the history of other users.");
+                            return Ok(());
+                        }
+
+                        user
+                    }
+                    None => match ctx.user.name() {
+                        Some(name) => name.to_string(),
+                        None => {
+                            ctx.respond("Only real users can check their history");
+                            return Ok(());
+                        }
+                    },
+                };
+
+                let entries = currency.history(ctx.channel(), &user, HISTORY_LIMIT)?;
+
+                if entries.is_empty() {
+                    ctx.respond(format!(
+                        "No changes to the {currency} of {user}.",
+                        currency = currency.name,
+                        user = user
+                    ));
+                    return Ok(());
+                }
+
+                let now = Utc::now().naive_utc();
+
+                let entries = entries
+                    .iter()
+                    .map(|e| format_entry(e, now))
+                    .collect::<Vec<_>>();
+
+                ctx.respond(format!(
+                    "Latest changes for {user}: {entries}.",
+                    user = user,
+                    entries = entries.join(", ")
+                ));
+            }
+            "give" => {
+                let command::User(taker) = ctx_try!(ctx.arg());
+                let amount: i64 = ctx_try!(ctx.arg());
 
                 let user = match ctx.user.real() {
                     Some(user) => user,
@@ -124,6 +258,7 @@ impl command::Handler for Handler {
                         &taker,
                         amount,
                         user.is_streamer(),
+                        LedgerReason::Transfer,
                     )
                     .await;
 
@@ -152,11 +287,9 @@ impl command::Handler for Handler {
                     }
                 }
             }
-            Some("boost") => {
-                ctx.check_scope(Scope::CurrencyBoost)?;
-
-                let boosted_user = db::user_id(&ctx_try!(ctx.next_str("<user> <amount>")));
-                let amount: i64 = ctx_try!(ctx.next_parse("<user> <amount>"));
+            "boost" => {
+                let command::User(boosted_user) = ctx_try!(ctx.arg());
+                let amount: i64 = ctx_try!(ctx.arg());
 
                 if !ctx.user.is_streamer() && ctx.user.is(&boosted_user) {
                     ctx.respond("You gonna have to play by the rules (or ask another mod) :(");
@@ -165,14 +298,29 @@ impl command::Handler for Handler {
 
                 let user = ctx.user.clone();
                 let currency = currency.clone();
+                let audit_log = self.audit_log.read().clone();
 
                 ctx.spawn(async move {
                     let result = currency
-                        .balance_add(user.channel(), &boosted_user, amount)
+                        .balance_add(
+                            user.channel(),
+                            &boosted_user,
+                            amount,
+                            LedgerReason::Admin,
+                            user.name(),
+                        )
                         .await;
 
                     match result {
                         Ok(()) => {
+                            audit_balance_change(
+                                audit_log.as_ref(),
+                                &user,
+                                Some(boosted_user.as_str()),
+                                amount,
+                                &currency.name,
+                            );
+
                             if amount >= 0 {
                                 user.respond(format!(
                                     "Gave {user} {amount} {currency}!",
@@ -196,18 +344,33 @@ impl command::Handler for Handler {
                     }
                 });
             }
-            Some("windfall") => {
-                ctx.check_scope(Scope::CurrencyWindfall)?;
-
+            "windfall" => {
                 let user = ctx.user.clone();
-                let amount: i64 = ctx_try!(ctx.next_parse("<amount>"));
+                let amount: i64 = ctx_try!(ctx.arg());
                 let sender = ctx.sender.clone();
+                let audit_log = self.audit_log.read().clone();
 
                 ctx.spawn(async move {
-                    let result = currency.add_channel_all(user.channel(), amount, 0).await;
+                    let result = currency
+                        .add_channel_all(
+                            user.channel(),
+                            amount,
+                            0,
+                            LedgerReason::Admin,
+                            user.name(),
+                        )
+                        .await;
 
                     match result {
                         Ok(_) => {
+                            audit_balance_change(
+                                audit_log.as_ref(),
+                                &user,
+                                None,
+                                amount,
+                                &currency.name,
+                            );
+
                             if amount >= 0 {
                                 sender.privmsg(format!(
                                     "/me gave {amount} {currency} to EVERYONE!",
@@ -229,28 +392,122 @@ impl command::Handler for Handler {
                     }
                 });
             }
-            Some(..) => {
-                let mut alts = Vec::new();
+            _ => (),
+        }
 
-                alts.push("give");
+        Ok(())
+    }
+}
 
-                if ctx.user.has_scope(Scope::CurrencyBoost) {
-                    alts.push("boost");
-                }
+/// Respond with the top users, ranked by the given value.
+async fn leaderboard(
+    ctx: &mut command::Context<'_>,
+    currency: &Currency,
+    by: RankBy,
+) -> Result<(), Error> {
+    let what = match by {
+        RankBy::Balance => currency.name.as_str(),
+        RankBy::WatchTime => "watch time",
+    };
 
-                if ctx.user.has_scope(Scope::CurrencyWindfall) {
-                    alts.push("windfall");
-                }
+    let n = ctx_try!(ctx.optional_arg::<u32>())
+        .unwrap_or(DEFAULT_TOP)
+        .min(MAX_TOP);
 
-                if ctx.user.has_scope(Scope::CurrencyShow) {
-                    alts.push("show");
-                }
+    if by == RankBy::WatchTime && !currency.tracks_watch_time() {
+        ctx.respond(format!(
+            "Watch time isn't tracked for {currency}.",
+            currency = currency.name
+        ));
+        return Ok(());
+    }
 
-                ctx.respond(format!("Expected: {alts}", alts = alts.join(", ")));
-            }
+    let leaderboard = match currency.leaderboard(Some(ctx.channel()), by, n).await {
+        Ok(leaderboard) => leaderboard,
+        Err(e) => {
+            ctx.respond("Could not get leaderboard, sorry :(");
+            log_err!(e, "failed to get leaderboard");
+            return Ok(());
         }
+    };
 
-        Ok(())
+    if leaderboard.is_empty() {
+        ctx.respond(format!("Nobody has any {} yet.", what));
+        return Ok(());
+    }
+
+    let leaderboard = leaderboard
+        .iter()
+        .map(|r| format_ranked(r, by))
+        .collect::<Vec<_>>();
+
+    ctx.respond(format!("Top {}: {}.", what, leaderboard.join(", ")));
+    Ok(())
+}
+
+/// Format a single user on a leaderboard, like `#1 setbac (100)`.
+fn format_ranked(ranked: &Ranked, by: RankBy) -> String {
+    let value = match by {
+        RankBy::Balance => ranked.balance.to_string(),
+        RankBy::WatchTime => utils::compact_duration(&ranked.watch_time().as_std()),
+    };
+
+    format!("#{} {} ({})", ranked.rank, ranked.user, value)
+}
+
+/// Format a single ledger entry, like `-50 transfer to setbac (2h ago)`.
+fn format_entry(entry: &LedgerEntry, now: NaiveDateTime) -> String {
+    let mut out = format!("{:+} {}", entry.amount, entry.reason);
+
+    if let Some(counterparty) = entry.counterparty.as_ref() {
+        let preposition = match entry.reason.as_str() {
+            "transfer" | "gamble" | "duel" | "heist" if entry.amount < 0 => "to",
+            "transfer" | "gamble" | "duel" | "heist" | "swearjar" => "from",
+            _ => "by",
+        };
+
+        out.push_str(&format!(" {} {}", preposition, counterparty));
+    }
+
+    let ago = now
+        .signed_duration_since(entry.created_at)
+        .to_std()
+        .unwrap_or_default();
+
+    format!("{} ({} ago)", out, utils::compact_duration(&ago))
+}
+
+/// Record a balance change made by a user in the audit log.
+///
+/// A `target` of `None` means that the balance of everyone in the channel was
+/// changed. Failing to record is logged, but doesn't fail the change.
+fn audit_balance_change(
+    audit_log: Option<&db::AuditLog>,
+    user: &super::User,
+    target: Option<&str>,
+    amount: i64,
+    currency: &str,
+) {
+    let audit_log = match audit_log {
+        Some(audit_log) => audit_log,
+        None => return,
+    };
+
+    let reason = match target {
+        Some(_) => format!("{:+} {}", amount, currency),
+        None => format!("{:+} {} for everyone", amount, currency),
+    };
+
+    let result = audit_log.record(
+        user.channel(),
+        user.name(),
+        db::AuditAction::BalanceChanged,
+        target,
+        Some(&reason),
+    );
+
+    if let Err(e) = result {
+        log_err!(e, "failed to record balance change");
     }
 }
 
@@ -259,19 +516,26 @@ pub fn setup(
 ) -> Result<(impl Future<Output = Result<(), Error>>, Handler), Error> {
     let (currency_stream, currency) = injector.stream::<Currency>();
     let currency = Arc::new(RwLock::new(currency));
+    let (audit_log_stream, audit_log) = injector.stream::<db::AuditLog>();
+    let audit_log = Arc::new(RwLock::new(audit_log));
 
     let handler = Handler {
         currency: currency.clone(),
+        audit_log: audit_log.clone(),
     };
 
     let future = async move {
         let mut currency_stream = currency_stream.fuse();
+        let mut audit_log_stream = audit_log_stream.fuse();
 
         loop {
             futures::select! {
                 update = currency_stream.select_next_some() => {
                     *currency.write() = update;
                 }
+                update = audit_log_stream.select_next_some() => {
+                    *audit_log.write() = update;
+                }
             }
         }
     };
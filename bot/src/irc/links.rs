@@ -0,0 +1,265 @@
+//! Support for the URL whitelist.
+//!
+//! Moderators can permit users to post links which aren't whitelisted, and
+//! links behind known URL shorteners can be resolved so that the host they
+//! point to is the one checked against the whitelist. Resolved links are
+//! cached, since the same link tends to be posted many times.
+
+use crate::{prelude::*, settings};
+use chrono::{DateTime, Utc};
+use failure::Error;
+use hashbrown::{HashMap, HashSet};
+use parking_lot::{Mutex, RwLock};
+use reqwest::{header, Client, RedirectPolicy, Url};
+use std::{sync::Arc, time};
+
+/// Maximum number of redirects followed when resolving a shortened link.
+const MAX_REDIRECTS: usize = 5;
+/// Timeout of each request made when resolving a shortened link.
+const RESOLVE_TIMEOUT: time::Duration = time::Duration::from_secs(5);
+/// Timeout of expanding a link, across all redirects followed.
+const EXPAND_TIMEOUT: time::Duration = time::Duration::from_secs(3);
+/// How long an expanded link is cached for.
+const CACHE_TTL: time::Duration = time::Duration::from_secs(3600);
+/// Maximum number of expanded links cached.
+const CACHE_LIMIT: usize = 1024;
+
+/// A permit for a single user to post links.
+#[derive(Debug)]
+struct Permit {
+    channel: String,
+    user: String,
+    /// If the permit is used up by the first link.
+    once: bool,
+    expires_at: DateTime<Utc>,
+}
+
+/// Permits for users to post links which aren't whitelisted.
+#[derive(Debug, Clone, Default)]
+pub struct LinkPermits {
+    permits: Arc<RwLock<Vec<Permit>>>,
+}
+
+impl LinkPermits {
+    /// Permit a user to post links until the permit expires.
+    ///
+    /// If `once` is set, the permit is used up by the first link posted.
+    pub fn insert(&self, channel: &str, user: &str, once: bool, expires_at: DateTime<Utc>) {
+        let mut permits = self.permits.write();
+        permits.retain(|p| !(p.channel == channel && p.user == user));
+
+        permits.push(Permit {
+            channel: channel.to_string(),
+            user: user.to_string(),
+            once,
+            expires_at,
+        });
+    }
+
+    /// Use the permit of the given user to post a link, if they have one.
+    pub fn take(&self, channel: &str, user: &str, now: &DateTime<Utc>) -> bool {
+        let mut permits = self.permits.write();
+        permits.retain(|p| p.expires_at > *now);
+
+        let index = match permits
+            .iter()
+            .position(|p| p.channel == channel && p.user == user)
+        {
+            Some(index) => index,
+            None => return false,
+        };
+
+        if permits[index].once {
+            permits.swap_remove(index);
+        }
+
+        true
+    }
+}
+
+/// Resolves where a link redirects to.
+#[async_trait]
+pub trait Resolver: Send + Sync {
+    /// Resolve the location the given link redirects to, if any.
+    async fn redirect(&self, url: &Url) -> Result<Option<Url>, Error>;
+}
+
+/// Resolver which asks the shortener over HTTP.
+pub struct HttpResolver {
+    client: Client,
+}
+
+impl HttpResolver {
+    /// Construct a new HTTP resolver.
+    pub fn new() -> Result<Self, Error> {
+        let client = Client::builder()
+            .redirect(RedirectPolicy::none())
+            .timeout(RESOLVE_TIMEOUT)
+            .build()?;
+
+        Ok(HttpResolver { client })
+    }
+}
+
+#[async_trait]
+impl Resolver for HttpResolver {
+    async fn redirect(&self, url: &Url) -> Result<Option<Url>, Error> {
+        let res = self.client.head(url.clone()).send().await?;
+
+        if !res.status().is_redirection() {
+            return Ok(None);
+        }
+
+        let location = match res.headers().get(header::LOCATION) {
+            Some(location) => location.to_str()?,
+            None => return Ok(None),
+        };
+
+        Ok(Some(url.join(location)?))
+    }
+}
+
+/// Expands links from known URL shorteners.
+#[derive(Clone)]
+pub struct Shorteners {
+    enabled: Arc<RwLock<bool>>,
+    hosts: Arc<RwLock<HashSet<String>>>,
+    resolver: Arc<dyn Resolver>,
+    /// Expanded links, and when they were expanded.
+    cache: Arc<Mutex<HashMap<Url, (Url, time::Instant)>>>,
+}
+
+impl Shorteners {
+    /// Load shortener settings scoped to `chat/url-whitelist`.
+    pub fn new(settings: &settings::Settings, resolver: Arc<dyn Resolver>) -> Result<Self, Error> {
+        let default_hosts = vec![
+            String::from("bit.ly"),
+            String::from("buff.ly"),
+            String::from("cutt.ly"),
+            String::from("goo.gl"),
+            String::from("is.gd"),
+            String::from("ow.ly"),
+            String::from("rebrand.ly"),
+            String::from("t.co"),
+            String::from("tinyurl.com"),
+        ];
+
+        Ok(Shorteners {
+            enabled: settings.var("resolve-shorteners", false)?,
+            hosts: settings.var("shorteners", default_hosts.into_iter().collect())?,
+            resolver,
+            cache: Default::default(),
+        })
+    }
+
+    /// Test if the given link is from a known shortener.
+    fn is_shortener(&self, url: &Url) -> bool {
+        match url.host_str() {
+            Some(host) => self.hosts.read().contains(host),
+            None => false,
+        }
+    }
+
+    /// Get the final link without resolving anything.
+    ///
+    /// Returns `None` if the link is from a known shortener and hasn't been
+    /// expanded recently, in which case it has to be expanded with
+    /// [`Shorteners::expand`].
+    pub fn cached(&self, url: Url) -> Option<Url> {
+        if !*self.enabled.read() || !self.is_shortener(&url) {
+            return Some(url);
+        }
+
+        let cache = self.cache.lock();
+
+        match cache.get(&url) {
+            Some((expanded, at)) if at.elapsed() < CACHE_TTL => Some(expanded.clone()),
+            _ => None,
+        }
+    }
+
+    /// Expand the given link if it's from a known shortener, returning the
+    /// final link.
+    ///
+    /// Links which fail to resolve in time are returned as far as they got,
+    /// so that the shortener itself is checked against the whitelist.
+    pub async fn expand(&self, mut url: Url) -> Url {
+        if !*self.enabled.read() {
+            return url;
+        }
+
+        let original = url.clone();
+        let deadline = time::Instant::now() + EXPAND_TIMEOUT;
+        let mut complete = false;
+
+        for _ in 0..MAX_REDIRECTS {
+            if !self.is_shortener(&url) {
+                complete = true;
+                break;
+            }
+
+            let redirect = self.resolver.redirect(&url);
+            let delay = tokio::timer::delay(deadline).boxed();
+
+            let result = match future::select(redirect, delay).await {
+                future::Either::Left((result, _)) => result,
+                future::Either::Right(..) => {
+                    log::warn!("timed out resolving shortened link: {}", url);
+                    break;
+                }
+            };
+
+            url = match result {
+                Ok(Some(next)) => next,
+                Ok(None) => {
+                    complete = true;
+                    break;
+                }
+                Err(e) => {
+                    log_err!(e, "failed to resolve shortened link: {}", url);
+                    break;
+                }
+            };
+        }
+
+        // NB: links which failed to resolve are tried again next time.
+        if complete {
+            let now = time::Instant::now();
+            let mut cache = self.cache.lock();
+
+            if cache.len() >= CACHE_LIMIT {
+                cache.retain(|_, (_, at)| now.duration_since(*at) < CACHE_TTL);
+
+                if cache.len() >= CACHE_LIMIT {
+                    cache.clear();
+                }
+            }
+
+            cache.insert(original, (url.clone(), now));
+        }
+
+        url
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinkPermits;
+    use chrono::{Duration, Utc};
+
+    #[test]
+    fn test_link_permits() {
+        let permits = LinkPermits::default();
+        let now = Utc::now();
+
+        permits.insert("#setbac", "once", true, now + Duration::minutes(5));
+        permits.insert("#setbac", "window", false, now + Duration::minutes(5));
+
+        assert!(permits.take("#setbac", "once", &now));
+        assert!(!permits.take("#setbac", "once", &now));
+        assert!(!permits.take("#other", "window", &now));
+        assert!(permits.take("#setbac", "window", &now));
+        assert!(permits.take("#setbac", "window", &now));
+        assert!(!permits.take("#setbac", "window", &(now + Duration::minutes(5))));
+    }
+}
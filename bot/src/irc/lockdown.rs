@@ -0,0 +1,198 @@
+//! Lockdown mode, protecting chat against raids of new accounts.
+//!
+//! While in lockdown, messages from users who were first seen recently are
+//! filtered more strictly: they can't post links, shout in caps, or flood
+//! emotes. Lockdown is enabled manually through `!admin lockdown`, or
+//! automatically when a burst of users chat for the first time. It turns
+//! itself off again after a cooldown.
+
+use super::spam;
+use crate::{emotes, settings, utils};
+use chrono::NaiveDateTime;
+use failure::Error;
+use parking_lot::RwLock;
+use std::{collections::VecDeque, sync::Arc, time::Instant};
+
+/// Minimum number of letters in a message before it is tested for caps.
+const CAPS_MIN_LENGTH: usize = 5;
+
+/// A change in the state of lockdown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    /// Lockdown was enabled automatically.
+    Enabled,
+    /// Lockdown was disabled after the cooldown.
+    Disabled,
+}
+
+/// Lockdown state for a single channel.
+pub struct Lockdown {
+    settings: settings::Settings,
+    active: Arc<RwLock<bool>>,
+    cooldown: Arc<RwLock<utils::Duration>>,
+    new_user_age: Arc<RwLock<utils::Duration>>,
+    caps_max: Arc<RwLock<u32>>,
+    emotes_max: Arc<RwLock<u32>>,
+    auto_enabled: Arc<RwLock<bool>>,
+    auto_threshold: Arc<RwLock<u32>>,
+    auto_window: Arc<RwLock<utils::Duration>>,
+    /// When lockdown was first observed to be active.
+    activated_at: Option<Instant>,
+    /// When recent first-time chatters were seen.
+    new_users: VecDeque<Instant>,
+}
+
+impl Lockdown {
+    /// Load lockdown settings scoped to `chat/lockdown`.
+    pub fn new(settings: settings::Settings) -> Result<Lockdown, Error> {
+        let auto = settings.scoped("auto");
+
+        Ok(Lockdown {
+            active: settings.var("active", false)?,
+            cooldown: settings.var("cooldown", utils::Duration::seconds(10 * 60))?,
+            new_user_age: settings.var("new-user-age", utils::Duration::seconds(10 * 60))?,
+            caps_max: settings.var("caps-max%", 50)?,
+            emotes_max: settings.var("emotes-max", 3)?,
+            auto_enabled: auto.var("enabled", false)?,
+            auto_threshold: auto.var("threshold", 10)?,
+            auto_window: auto.var("window", utils::Duration::seconds(30))?,
+            settings,
+            activated_at: None,
+            new_users: VecDeque::new(),
+        })
+    }
+
+    /// Test if lockdown is active.
+    pub fn is_active(&self) -> bool {
+        *self.active.read()
+    }
+
+    /// The configured cooldown, after which lockdown is disabled.
+    pub fn cooldown(&self) -> utils::Duration {
+        self.cooldown.read().clone()
+    }
+
+    /// Update the state of lockdown, disabling it if the cooldown has expired.
+    pub fn update(&mut self, now: Instant) -> Result<Option<Transition>, Error> {
+        if !self.is_active() {
+            self.activated_at = None;
+            return Ok(None);
+        }
+
+        // NB: lockdown might have been enabled through settings, in which
+        // case the cooldown starts once we notice.
+        let activated_at = *self.activated_at.get_or_insert(now);
+        let cooldown = self.cooldown.read().clone();
+
+        if cooldown.is_empty() || now.duration_since(activated_at) < cooldown.as_std() {
+            return Ok(None);
+        }
+
+        self.set_active(false)?;
+        self.activated_at = None;
+        Ok(Some(Transition::Disabled))
+    }
+
+    /// Observe a user chatting for the first time, which might enable
+    /// lockdown automatically.
+    pub fn observe_new_user(&mut self, now: Instant) -> Result<Option<Transition>, Error> {
+        if !*self.auto_enabled.read() || self.is_active() {
+            self.new_users.clear();
+            return Ok(None);
+        }
+
+        let window = self.auto_window.read().as_std();
+        let threshold = *self.auto_threshold.read();
+
+        if !is_burst(&mut self.new_users, now, window, threshold) {
+            return Ok(None);
+        }
+
+        self.new_users.clear();
+        self.set_active(true)?;
+        self.activated_at = Some(now);
+        Ok(Some(Transition::Enabled))
+    }
+
+    /// Test if a user first seen at the given time counts as new.
+    ///
+    /// An age too large to be represented means users are always new.
+    pub fn is_new_user(&self, first_seen_at: NaiveDateTime, now: NaiveDateTime) -> bool {
+        match self.new_user_age.read().checked_as_chrono() {
+            Some(age) => now.signed_duration_since(first_seen_at) < age,
+            None => true,
+        }
+    }
+
+    /// Test a message from a new user against the strict lockdown filters,
+    /// returning the reason it isn't permitted.
+    pub fn test(&self, message: &str, emotes: &[emotes::Span]) -> Option<&'static str> {
+        if utils::Urls::new(message).next().is_some() {
+            return Some("no links during lockdown");
+        }
+
+        if emotes.len() > *self.emotes_max.read() as usize {
+            return Some("too many emotes during lockdown");
+        }
+
+        let text = spam::strip_emotes(message, emotes);
+
+        if let Some(p) = spam::caps_percentage(&text, CAPS_MIN_LENGTH) {
+            if p > *self.caps_max.read() {
+                return Some("too many capital letters during lockdown");
+            }
+        }
+
+        None
+    }
+
+    /// Store whether lockdown is active.
+    fn set_active(&self, active: bool) -> Result<(), Error> {
+        *self.active.write() = active;
+        self.settings.set("active", active)?;
+        Ok(())
+    }
+}
+
+/// Track a new user seen at `now`, and test if at least `threshold` new users
+/// have been seen within `window`.
+fn is_burst(
+    seen: &mut VecDeque<Instant>,
+    now: Instant,
+    window: std::time::Duration,
+    threshold: u32,
+) -> bool {
+    while let Some(first) = seen.front() {
+        if now.duration_since(*first) < window {
+            break;
+        }
+
+        seen.pop_front();
+    }
+
+    seen.push_back(now);
+    threshold > 0 && seen.len() >= threshold as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_burst;
+    use std::{
+        collections::VecDeque,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn test_is_burst() {
+        let start = Instant::now();
+        let at = |s| start + Duration::from_secs(s);
+        let window = Duration::from_secs(10);
+        let mut seen = VecDeque::new();
+
+        assert!(!is_burst(&mut seen, at(0), window, 3));
+        assert!(!is_burst(&mut seen, at(5), window, 3));
+        assert!(!is_burst(&mut seen, at(12), window, 3));
+        assert!(is_burst(&mut seen, at(14), window, 3));
+        assert!(!is_burst(&mut seen, at(14), window, 0));
+    }
+}
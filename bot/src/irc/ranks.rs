@@ -0,0 +1,358 @@
+//! Ranks reached by watching the stream.
+//!
+//! Ranks are configured under `rank/ranks` as a name and the watch time it
+//! takes to reach it. A rank can be bound to one of the rank roles (`@rank/1`
+//! through `@rank/5`), in which case users hold that role once they've reached
+//! the rank or any rank above it. Since the binding is part of the rank's
+//! configuration, adding or reordering ranks never moves a role to a different
+//! rank.
+//!
+//! There are only five rank roles, so at most five ranks can have grants, and
+//! every role can only be bound to a single rank.
+
+use crate::{
+    auth::Role,
+    currency::{Balance, Currency},
+    template::Template,
+    utils::Duration,
+};
+use failure::{bail, Error};
+use hashbrown::{HashMap, HashSet};
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// A rank which is reached after watching the stream for a while.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Rank {
+    pub name: String,
+    #[serde(rename = "watch-time")]
+    pub watch_time: Duration,
+    /// Rank role held by users who have reached this rank.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<Role>,
+}
+
+/// The rank reached by a user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reached {
+    /// Zero-based level of the rank, from the lowest rank and up.
+    pub level: usize,
+    pub rank: Rank,
+    /// The rank after this one, if any.
+    pub next: Option<Rank>,
+}
+
+#[derive(serde::Serialize)]
+struct Announcement<'a> {
+    name: &'a str,
+    rank: &'a str,
+    watch_time: &'a Duration,
+}
+
+#[derive(Default)]
+struct Inner {
+    enabled: bool,
+    /// Configured ranks, sorted by the watch time it takes to reach them.
+    ranks: Vec<Rank>,
+    /// Template used to announce that a user reached a new rank.
+    announcement: Option<Template>,
+    /// Last known watch time of every user, in seconds.
+    watch_time: HashMap<String, i64>,
+}
+
+/// Ranks of users in the streamer's channel.
+#[derive(Clone, Default)]
+pub struct Ranks {
+    inner: Arc<RwLock<Inner>>,
+}
+
+impl Ranks {
+    /// Test if ranks are enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.inner.read().enabled
+    }
+
+    /// Enable or disable ranks.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.inner.write().enabled = enabled;
+    }
+
+    /// Configure the available ranks.
+    ///
+    /// Roles which aren't rank roles are ignored, since that would let ranks
+    /// grant a role like `@moderator`. Ranks which bind the same role are
+    /// rejected, leaving the current ranks in place.
+    pub fn set_ranks(&self, mut ranks: Vec<Rank>) -> Result<(), Error> {
+        let mut bound = HashMap::new();
+
+        for rank in &mut ranks {
+            if let Some(role) = rank.role.filter(|r| !r.is_rank()) {
+                log::warn!(
+                    "ignoring role `{}` of rank `{}`, not a rank role",
+                    role,
+                    rank.name
+                );
+                rank.role = None;
+            }
+
+            if let Some(role) = rank.role {
+                if let Some(other) = bound.insert(role, rank.name.clone()) {
+                    bail!(
+                        "role `{}` is bound to both rank `{}` and rank `{}`",
+                        role,
+                        other,
+                        rank.name
+                    );
+                }
+            }
+        }
+
+        ranks.sort_by(|a, b| a.watch_time.cmp(&b.watch_time));
+        self.inner.write().ranks = ranks;
+        Ok(())
+    }
+
+    /// Set the template used to announce rank ups, or `None` to not announce
+    /// them.
+    pub fn set_announcement(&self, announcement: Option<Template>) {
+        self.inner.write().announcement = announcement;
+    }
+
+    /// Get the lowest rank, if any ranks are configured.
+    pub fn first(&self) -> Option<Rank> {
+        self.inner.read().ranks.first().cloned()
+    }
+
+    /// Get the rank reached with the given watch time, in seconds.
+    pub fn rank_for(&self, watch_time: i64) -> Option<Reached> {
+        reached(&self.inner.read().ranks, watch_time)
+    }
+
+    /// Get the roles given to the user by their rank, which are the roles of
+    /// their rank and every rank below it.
+    ///
+    /// Users whose watch time isn't known yet don't have any rank roles.
+    pub fn roles(&self, user: &str) -> impl Iterator<Item = Role> {
+        let inner = self.inner.read();
+
+        let level = match inner.watch_time.get(user) {
+            Some(watch_time) if inner.enabled => {
+                reached(&inner.ranks, *watch_time).map(|r| r.level)
+            }
+            _ => None,
+        };
+
+        let count = level.map(|l| l + 1).unwrap_or_default();
+
+        inner
+            .ranks
+            .iter()
+            .take(count)
+            .flat_map(|r| r.role)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+
+    /// Record the current watch time of a user, without announcing any rank
+    /// they might have reached.
+    pub fn observe(&self, user: &str, watch_time: i64) {
+        self.inner
+            .write()
+            .watch_time
+            .insert(user.to_string(), watch_time);
+    }
+
+    /// Record the watch time of every balance in the given channel.
+    pub fn observe_all(&self, channel: &str, balances: impl IntoIterator<Item = Balance>) {
+        let channel = channel.trim_start_matches('#');
+        let mut inner = self.inner.write();
+
+        // NB: backends without channels export balances with an empty channel.
+        for balance in balances {
+            let other = balance.channel.trim_start_matches('#');
+
+            if other == channel || other.is_empty() {
+                inner.watch_time.insert(balance.user, balance.watch_time);
+            }
+        }
+    }
+
+    /// Add watch time to users who have been rewarded for watching, and build
+    /// announcements for every user who reached a new rank.
+    ///
+    /// The watch time of users who aren't known yet is looked up from the
+    /// currency instead.
+    pub async fn reward(
+        &self,
+        currency: &Currency,
+        channel: &str,
+        users: HashSet<String>,
+        seconds: i64,
+    ) -> Result<Vec<String>, Error> {
+        if !self.is_enabled() || !currency.tracks_watch_time() {
+            return Ok(Vec::new());
+        }
+
+        let mut unknown = Vec::new();
+        let mut announcements = Vec::new();
+
+        {
+            let mut inner = self.inner.write();
+            let inner = &mut *inner;
+
+            for user in users {
+                let watch_time = match inner.watch_time.get_mut(&user) {
+                    Some(watch_time) => watch_time,
+                    None => {
+                        unknown.push(user);
+                        continue;
+                    }
+                };
+
+                let before = reached(&inner.ranks, *watch_time);
+                *watch_time += seconds;
+                let after = reached(&inner.ranks, *watch_time);
+
+                let after = match after {
+                    Some(after) if before.map(|b| b.level) != Some(after.level) => after,
+                    _ => continue,
+                };
+
+                let template = match inner.announcement.as_ref() {
+                    Some(template) => template,
+                    None => continue,
+                };
+
+                let watch_time = Duration::seconds(*watch_time as u64);
+
+                announcements.push(template.render_to_string(Announcement {
+                    name: &user,
+                    rank: &after.rank.name,
+                    watch_time: &watch_time,
+                })?);
+            }
+        }
+
+        for user in unknown {
+            if let Some(balance) = currency.balance_of(channel, &user).await? {
+                self.observe(&user, balance.watch_time);
+            }
+        }
+
+        Ok(announcements)
+    }
+}
+
+/// Find the rank reached with the given watch time among the given ranks,
+/// which are sorted by watch time.
+fn reached(ranks: &[Rank], watch_time: i64) -> Option<Reached> {
+    let watch_time = watch_time.max(0) as u64;
+
+    let level = ranks
+        .iter()
+        .take_while(|r| r.watch_time.num_seconds() <= watch_time)
+        .count()
+        .checked_sub(1)?;
+
+    Some(Reached {
+        level,
+        rank: ranks[level].clone(),
+        next: ranks.get(level + 1).cloned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reached, Rank, Ranks};
+    use crate::{auth::Role, utils::Duration};
+
+    fn rank(name: &str, hours: u64, role: Option<Role>) -> Rank {
+        Rank {
+            name: name.to_string(),
+            watch_time: Duration::hours(hours),
+            role,
+        }
+    }
+
+    #[test]
+    fn test_reached() {
+        let ranks = vec![rank("regular", 10, None), rank("veteran", 100, None)];
+
+        assert_eq!(None, reached(&ranks, 0));
+        assert_eq!(None, reached(&ranks, 10 * 3600 - 1));
+
+        let regular = reached(&ranks, 10 * 3600).expect("regular");
+        assert_eq!(0, regular.level);
+        assert_eq!("regular", regular.rank.name);
+        assert_eq!(Some(rank("veteran", 100, None)), regular.next);
+
+        let veteran = reached(&ranks, 1000 * 3600).expect("veteran");
+        assert_eq!(1, veteran.level);
+        assert_eq!(None, veteran.next);
+
+        assert_eq!(None, reached(&[], 1000 * 3600));
+    }
+
+    #[test]
+    fn test_roles() -> Result<(), failure::Error> {
+        let ranks = Ranks::default();
+        ranks.set_ranks(vec![
+            rank("veteran", 100, Some(Role::Rank2)),
+            rank("regular", 10, Some(Role::Rank1)),
+        ])?;
+        ranks.observe("setbac", 150 * 3600);
+        ranks.observe("regular", 20 * 3600);
+        ranks.observe("tiny", 3600);
+
+        assert_eq!(0, ranks.roles("setbac").count());
+
+        ranks.set_enabled(true);
+
+        assert_eq!(
+            vec![Role::Rank1, Role::Rank2],
+            ranks.roles("setbac").collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Role::Rank1],
+            ranks.roles("regular").collect::<Vec<_>>()
+        );
+        assert_eq!(0, ranks.roles("tiny").count());
+        assert_eq!(0, ranks.roles("unknown").count());
+        Ok(())
+    }
+
+    #[test]
+    fn test_roles_are_bound_to_ranks() -> Result<(), failure::Error> {
+        let ranks = Ranks::default();
+        ranks.set_enabled(true);
+        ranks.observe("setbac", 20 * 3600);
+
+        ranks.set_ranks(vec![rank("regular", 10, Some(Role::Rank1))])?;
+        assert_eq!(vec![Role::Rank1], ranks.roles("setbac").collect::<Vec<_>>());
+
+        // A new lower rank without a role doesn't shift the existing binding.
+        ranks.set_ranks(vec![
+            rank("newbie", 1, None),
+            rank("regular", 10, Some(Role::Rank1)),
+        ])?;
+        assert_eq!(vec![Role::Rank1], ranks.roles("setbac").collect::<Vec<_>>());
+
+        // A role bound to more than one rank is rejected.
+        assert!(ranks
+            .set_ranks(vec![
+                rank("regular", 10, Some(Role::Rank1)),
+                rank("veteran", 100, Some(Role::Rank1)),
+            ])
+            .is_err());
+        assert_eq!(vec![Role::Rank1], ranks.roles("setbac").collect::<Vec<_>>());
+
+        // Ranks beyond the number of rank roles simply don't grant any role.
+        ranks.set_ranks((0..10).map(|h| rank("rank", h, None)).collect())?;
+        assert_eq!(0, ranks.roles("setbac").count());
+
+        // Only rank roles can be bound to ranks.
+        ranks.set_ranks(vec![rank("regular", 10, Some(Role::Moderator))])?;
+        assert_eq!(0, ranks.roles("setbac").count());
+        Ok(())
+    }
+}
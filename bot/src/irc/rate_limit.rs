@@ -0,0 +1,212 @@
+//! Per-user message rate limits in chat.
+//!
+//! Every role has its own limit on the number of messages a user may send
+//! within a window, configured under `chat/rate-limit/<role>/`. Users are held
+//! to the most permissive limit among their roles, and users with an exempt
+//! role aren't limited at all.
+
+use super::spam;
+use crate::{auth::Role, settings, utils};
+use failure::Error;
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use std::{collections::VecDeque, sync::Arc, time::Instant};
+
+/// Number of tracked users after which users who haven't chatted recently are
+/// forgotten.
+const SENT_PRUNE_THRESHOLD: usize = 1024;
+
+/// The limit for a single role.
+struct Limit {
+    role: Role,
+    /// Number of messages permitted within the window, `0` meaning no limit.
+    messages: Arc<RwLock<u32>>,
+    window: Arc<RwLock<utils::Duration>>,
+}
+
+/// Message rate limits for a single channel.
+pub struct RateLimit {
+    enabled: Arc<RwLock<bool>>,
+    exempt: Arc<RwLock<Vec<Role>>>,
+    action: Arc<RwLock<spam::Action>>,
+    timeout: Arc<RwLock<utils::Duration>>,
+    limits: Vec<Limit>,
+    /// When recent messages were sent by each user.
+    sent: HashMap<String, VecDeque<Instant>>,
+}
+
+impl RateLimit {
+    /// Load rate limits from settings scoped to `chat/rate-limit`.
+    pub fn new(settings: &settings::Settings) -> Result<RateLimit, Error> {
+        let mut limits = Vec::new();
+
+        // NB: rank roles don't have limits of their own, since users are
+        // held to the most permissive limit among their roles.
+        for role in Role::list().into_iter().filter(|r| !r.is_rank()) {
+            let (messages, window) = default_limit(role);
+            let settings = settings.scoped(role.to_string().trim_start_matches('@'));
+
+            limits.push(Limit {
+                role,
+                messages: settings.var("messages", messages)?,
+                window: settings.var("window", window)?,
+            });
+        }
+
+        Ok(RateLimit {
+            enabled: settings.var("enabled", false)?,
+            exempt: settings.var("exempt", vec![Role::Streamer, Role::Moderator, Role::Vip])?,
+            action: settings.var("action", spam::Action::Delete)?,
+            timeout: settings.var("timeout", utils::Duration::seconds(60))?,
+            limits,
+            sent: HashMap::new(),
+        })
+    }
+
+    /// Track a message sent by the given user at `now`, and test if they have
+    /// exceeded their rate limit.
+    pub fn test(&mut self, name: &str, roles: &[Role], now: Instant) -> Option<spam::Verdict> {
+        if !*self.enabled.read() {
+            return None;
+        }
+
+        {
+            let exempt = self.exempt.read();
+
+            if roles.iter().any(|r| exempt.contains(r)) {
+                return None;
+            }
+        }
+
+        let limits = self
+            .limits
+            .iter()
+            .filter(|l| roles.contains(&l.role))
+            .map(|l| (*l.messages.read(), l.window.read().clone()));
+
+        let (messages, window) = most_permissive(limits)?;
+        let window = window.as_std();
+
+        if self.sent.len() > SENT_PRUNE_THRESHOLD {
+            self.sent.retain(|_, sent| match sent.back() {
+                Some(last) => now.duration_since(*last) < window,
+                None => false,
+            });
+        }
+
+        let sent = self.sent.entry(name.to_string()).or_default();
+
+        if !is_limited(sent, now, window, messages) {
+            return None;
+        }
+
+        Some(spam::Verdict {
+            reason: "sending messages too quickly",
+            action: *self.action.read(),
+            timeout: self.timeout.read().clone(),
+        })
+    }
+}
+
+/// The default limit of the given role.
+///
+/// Subscribers are permitted more messages than everyone else, and the roles
+/// which are exempt by default have no limit.
+fn default_limit(role: Role) -> (u32, utils::Duration) {
+    let messages = match role {
+        Role::Subscriber => 8,
+        Role::Everyone => 5,
+        _ => 0,
+    };
+
+    (messages, utils::Duration::seconds(10))
+}
+
+/// Pick the most permissive of the given limits, which is the one permitting
+/// the highest rate of messages.
+///
+/// Returns `None` if any of the limits is unlimited.
+fn most_permissive(
+    limits: impl Iterator<Item = (u32, utils::Duration)>,
+) -> Option<(u32, utils::Duration)> {
+    let mut best = None::<(u32, utils::Duration)>;
+
+    for (messages, window) in limits {
+        if messages == 0 || window.is_empty() {
+            return None;
+        }
+
+        best = match best {
+            Some((m, w))
+                if u64::from(m) * window.num_seconds() >= u64::from(messages) * w.num_seconds() =>
+            {
+                Some((m, w))
+            }
+            _ => Some((messages, window)),
+        };
+    }
+
+    best
+}
+
+/// Track a message sent at `now`, and test if more than `messages` messages
+/// have been sent within `window`.
+fn is_limited(
+    sent: &mut VecDeque<Instant>,
+    now: Instant,
+    window: std::time::Duration,
+    messages: u32,
+) -> bool {
+    while let Some(first) = sent.front() {
+        if now.duration_since(*first) < window {
+            break;
+        }
+
+        sent.pop_front();
+    }
+
+    sent.push_back(now);
+    sent.len() > messages as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_limited, most_permissive};
+    use crate::utils;
+    use std::{
+        collections::VecDeque,
+        time::{Duration, Instant},
+    };
+
+    #[test]
+    fn test_is_limited() {
+        let start = Instant::now();
+        let at = |s| start + Duration::from_secs(s);
+        let window = Duration::from_secs(10);
+        let mut sent = VecDeque::new();
+
+        assert!(!is_limited(&mut sent, at(0), window, 2));
+        assert!(!is_limited(&mut sent, at(1), window, 2));
+        assert!(is_limited(&mut sent, at(2), window, 2));
+        assert!(!is_limited(&mut sent, at(12), window, 2));
+    }
+
+    #[test]
+    fn test_most_permissive() {
+        let limit = |m, s| (m, utils::Duration::seconds(s));
+
+        assert_eq!(None, most_permissive(vec![].into_iter()));
+        assert_eq!(
+            None,
+            most_permissive(vec![limit(5, 10), limit(0, 10)].into_iter())
+        );
+        assert_eq!(
+            Some(limit(8, 10)),
+            most_permissive(vec![limit(8, 10), limit(5, 10)].into_iter())
+        );
+        assert_eq!(
+            Some(limit(5, 5)),
+            most_permissive(vec![limit(8, 20), limit(5, 5)].into_iter())
+        );
+    }
+}
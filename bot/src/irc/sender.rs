@@ -26,7 +26,6 @@ impl Default for Type {
 }
 
 struct Inner {
-    target: String,
     sender: client::Sender,
     limiter: LeakyBucket,
     nightbot_limiter: LeakyBucket,
@@ -36,6 +35,7 @@ struct Inner {
 #[derive(Clone)]
 pub struct Sender {
     ty: Arc<RwLock<Type>>,
+    target: Arc<String>,
     inner: Arc<Inner>,
 }
 
@@ -64,8 +64,8 @@ impl Sender {
 
         Ok(Sender {
             ty,
+            target: Arc::new(target),
             inner: Arc::new(Inner {
-                target,
                 sender,
                 limiter,
                 nightbot_limiter,
@@ -74,9 +74,21 @@ impl Sender {
         })
     }
 
+    /// Construct a sender for another channel.
+    ///
+    /// The new sender shares the connection and rate limits of this sender,
+    /// but always sends messages directly to chat.
+    pub fn for_channel(&self, target: String) -> Sender {
+        Sender {
+            ty: Arc::new(RwLock::new(Type::Chat)),
+            target: Arc::new(target),
+            inner: self.inner.clone(),
+        }
+    }
+
     /// Get the channel this sender is associated with.
     pub fn channel(&self) -> &str {
-        self.inner.target.as_str()
+        self.target.as_str()
     }
 
     /// Delete the given message by id.
@@ -84,6 +96,42 @@ impl Sender {
         self.privmsg_immediate(format!("/delete {}", id));
     }
 
+    /// Time out the given user, with a reason.
+    pub fn timeout(&self, user: &str, duration: time::Duration, reason: &str) {
+        self.privmsg_immediate(format!(
+            "/timeout {} {} {}",
+            user,
+            duration.as_secs(),
+            reason
+        ));
+    }
+
+    /// Ban the given user, with a reason.
+    pub fn ban(&self, user: &str, reason: &str) {
+        self.privmsg_immediate(format!("/ban {} {}", user, reason));
+    }
+
+    /// Time out the given user, with a reason.
+    ///
+    /// Unlike [`Sender::timeout`], this is rate limited like chat messages so
+    /// that sending many timeouts at once doesn't exceed the limits of Twitch.
+    pub fn timeout_limited(&self, user: &str, duration: time::Duration, reason: &str) {
+        self.send(Command::PRIVMSG(
+            self.channel().to_string(),
+            format!("/timeout {} {} {}", user, duration.as_secs(), reason),
+        ));
+    }
+
+    /// Ban the given user, with a reason.
+    ///
+    /// Unlike [`Sender::ban`], this is rate limited like chat messages.
+    pub fn ban_limited(&self, user: &str, reason: &str) {
+        self.send(Command::PRIVMSG(
+            self.channel().to_string(),
+            format!("/ban {} {}", user, reason),
+        ));
+    }
+
     /// Get list of mods.
     pub fn mods(&self) {
         self.privmsg_immediate("/mods");
@@ -127,14 +175,14 @@ impl Sender {
                 return;
             }
             Type::Chat => {
-                self.send(Command::PRIVMSG(self.inner.target.clone(), f.to_string()));
+                self.send(Command::PRIVMSG(self.channel().to_string(), f.to_string()));
             }
         }
     }
 
     /// Send a PRIVMSG without rate limiting.
     pub fn privmsg_immediate(&self, f: impl fmt::Display) {
-        self.send_immediate(Command::PRIVMSG(self.inner.target.clone(), f.to_string()))
+        self.send_immediate(Command::PRIVMSG(self.channel().to_string(), f.to_string()))
     }
 
     /// Send a capability request.
@@ -0,0 +1,317 @@
+//! Heuristics for detecting spam in chat, beyond URL and bad-word filtering.
+//!
+//! Each filter is configured under `chat/spam/<filter>/`, with its own
+//! thresholds, the roles which are exempt from it, and the action to take
+//! when it matches.
+
+use crate::{auth::Role, emotes, settings, utils};
+use failure::Error;
+use hashbrown::HashMap;
+use parking_lot::RwLock;
+use std::{sync::Arc, time::Instant};
+
+/// Number of tracked users after which users who haven't repeated themselves
+/// recently are forgotten.
+const RECENT_PRUNE_THRESHOLD: usize = 1024;
+
+/// Action to take when a filter matches a message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Action {
+    /// Delete the message.
+    #[serde(rename = "delete")]
+    Delete,
+    /// Delete the message and tell the user why.
+    #[serde(rename = "warn")]
+    Warn,
+    /// Time out the user.
+    #[serde(rename = "timeout")]
+    Timeout,
+    /// Delete the message and give the user a strike, escalating the action
+    /// taken with every strike.
+    #[serde(rename = "strike")]
+    Strike,
+}
+
+/// A filter which matched a message, and what to do about it.
+#[derive(Debug, Clone)]
+pub struct Verdict {
+    /// Why the message matched, like `too many emotes`.
+    pub reason: &'static str,
+    /// The action to take.
+    pub action: Action,
+    /// How long to time out the user for, if the action is a timeout.
+    pub timeout: utils::Duration,
+}
+
+/// Settings common to all filters.
+struct Filter {
+    reason: &'static str,
+    enabled: Arc<RwLock<bool>>,
+    exempt: Arc<RwLock<Vec<Role>>>,
+    action: Arc<RwLock<Action>>,
+    timeout: Arc<RwLock<utils::Duration>>,
+}
+
+impl Filter {
+    /// Load a filter from its scoped settings.
+    fn new(settings: &settings::Settings, reason: &'static str) -> Result<Filter, Error> {
+        Ok(Filter {
+            reason,
+            enabled: settings.var("enabled", false)?,
+            exempt: settings.var("exempt", vec![Role::Streamer, Role::Moderator])?,
+            action: settings.var("action", Action::Delete)?,
+            timeout: settings.var("timeout", utils::Duration::seconds(60))?,
+        })
+    }
+
+    /// Test if the filter applies to a user with the given roles.
+    fn applies(&self, roles: &[Role]) -> bool {
+        if !*self.enabled.read() {
+            return false;
+        }
+
+        let exempt = self.exempt.read();
+        !roles.iter().any(|r| exempt.contains(r))
+    }
+
+    /// Build the verdict for a message matched by this filter.
+    fn verdict(&self) -> Verdict {
+        Verdict {
+            reason: self.reason,
+            action: *self.action.read(),
+            timeout: self.timeout.read().clone(),
+        }
+    }
+}
+
+/// The last message seen from a user.
+struct Recent {
+    /// The normalized message.
+    message: String,
+    /// How many times in a row the message has been seen.
+    count: u32,
+    /// When the message was first seen.
+    first: Instant,
+}
+
+/// Spam filters for a single channel.
+pub struct Spam {
+    caps: Filter,
+    caps_max: Arc<RwLock<u32>>,
+    caps_min_length: Arc<RwLock<u32>>,
+    symbols: Filter,
+    symbols_max: Arc<RwLock<u32>>,
+    symbols_min_length: Arc<RwLock<u32>>,
+    length: Filter,
+    length_max: Arc<RwLock<u32>>,
+    repeat: Filter,
+    repeat_limit: Arc<RwLock<u32>>,
+    repeat_window: Arc<RwLock<utils::Duration>>,
+    emotes: Filter,
+    emotes_max: Arc<RwLock<u32>>,
+    /// The last message seen from each user, used to detect repeats.
+    recent: HashMap<String, Recent>,
+}
+
+impl Spam {
+    /// Load the spam filters from settings scoped to `chat/spam`.
+    pub fn new(settings: &settings::Settings) -> Result<Spam, Error> {
+        let caps = settings.scoped("caps");
+        let symbols = settings.scoped("symbols");
+        let length = settings.scoped("length");
+        let repeat = settings.scoped("repeat");
+        let emotes = settings.scoped("emotes");
+
+        Ok(Spam {
+            caps: Filter::new(&caps, "too many capital letters")?,
+            caps_max: caps.var("max%", 70)?,
+            caps_min_length: caps.var("min-length", 10)?,
+            symbols: Filter::new(&symbols, "too many symbols")?,
+            symbols_max: symbols.var("max%", 50)?,
+            symbols_min_length: symbols.var("min-length", 10)?,
+            length: Filter::new(&length, "message too long")?,
+            length_max: length.var("max", 400)?,
+            repeat: Filter::new(&repeat, "repeated message")?,
+            repeat_limit: repeat.var("limit", 3)?,
+            repeat_window: repeat.var("window", utils::Duration::seconds(30))?,
+            emotes: Filter::new(&emotes, "too many emotes")?,
+            emotes_max: emotes.var("max", 10)?,
+            recent: HashMap::new(),
+        })
+    }
+
+    /// Test the message from the given user against all filters.
+    ///
+    /// `emotes` are the Twitch emotes in the message, which are not counted as
+    /// caps or symbols.
+    pub fn test(
+        &mut self,
+        name: &str,
+        roles: &[Role],
+        message: &str,
+        emotes: &[emotes::Span],
+    ) -> Option<Verdict> {
+        if self.length.applies(roles) && message.chars().count() > *self.length_max.read() as usize
+        {
+            return Some(self.length.verdict());
+        }
+
+        if self.emotes.applies(roles) && emotes.len() > *self.emotes_max.read() as usize {
+            return Some(self.emotes.verdict());
+        }
+
+        let text = strip_emotes(message, emotes);
+
+        if self.caps.applies(roles) {
+            let min = *self.caps_min_length.read() as usize;
+
+            if let Some(p) = caps_percentage(&text, min) {
+                if p > *self.caps_max.read() {
+                    return Some(self.caps.verdict());
+                }
+            }
+        }
+
+        if self.symbols.applies(roles) {
+            let min = *self.symbols_min_length.read() as usize;
+
+            if let Some(p) = symbols_percentage(&text, min) {
+                if p > *self.symbols_max.read() {
+                    return Some(self.symbols.verdict());
+                }
+            }
+        }
+
+        if self.repeat.applies(roles) && self.is_repeat(name, message, Instant::now()) {
+            return Some(self.repeat.verdict());
+        }
+
+        None
+    }
+
+    /// Track the message from the given user, and test if they have repeated
+    /// it more times than permitted within the window.
+    fn is_repeat(&mut self, name: &str, message: &str, now: Instant) -> bool {
+        let window = self.repeat_window.read().as_std();
+        let limit = *self.repeat_limit.read();
+
+        if self.recent.len() > RECENT_PRUNE_THRESHOLD {
+            self.recent
+                .retain(|_, r| now.duration_since(r.first) < window);
+        }
+
+        let message = message.trim().to_lowercase();
+
+        if let Some(recent) = self.recent.get_mut(name) {
+            if recent.message == message && now.duration_since(recent.first) < window {
+                recent.count += 1;
+                return recent.count > limit;
+            }
+        }
+
+        self.recent.insert(
+            name.to_string(),
+            Recent {
+                message,
+                count: 1,
+                first: now,
+            },
+        );
+
+        false
+    }
+}
+
+/// Get all characters in the message which are not part of an emote.
+pub(super) fn strip_emotes(message: &str, emotes: &[emotes::Span]) -> Vec<char> {
+    message
+        .chars()
+        .enumerate()
+        .filter(|(i, _)| !emotes.iter().any(|e| e.start <= *i && *i <= e.end))
+        .map(|(_, c)| c)
+        .collect()
+}
+
+/// Percentage of letters which are uppercase, if there are at least `min`
+/// letters.
+pub(super) fn caps_percentage(text: &[char], min: usize) -> Option<u32> {
+    let letters = text.iter().filter(|c| c.is_alphabetic()).count();
+
+    if letters == 0 || letters < min {
+        return None;
+    }
+
+    let upper = text.iter().filter(|c| c.is_uppercase()).count();
+    Some((upper * 100 / letters) as u32)
+}
+
+/// Percentage of non-whitespace characters which are symbols, if there are
+/// at least `min` of them.
+fn symbols_percentage(text: &[char], min: usize) -> Option<u32> {
+    let total = text.iter().filter(|c| !c.is_whitespace()).count();
+
+    if total == 0 || total < min {
+        return None;
+    }
+
+    let symbols = text.iter().filter(|c| is_symbol(**c)).count();
+    Some((symbols * 100 / total) as u32)
+}
+
+/// Test if the character is a symbol, or a combining mark commonly used to
+/// stack characters on top of each other.
+fn is_symbol(c: char) -> bool {
+    match c {
+        '\u{0300}'..='\u{036f}'
+        | '\u{1ab0}'..='\u{1aff}'
+        | '\u{1dc0}'..='\u{1dff}'
+        | '\u{20d0}'..='\u{20ff}'
+        | '\u{fe20}'..='\u{fe2f}' => true,
+        c => !c.is_alphanumeric() && !c.is_whitespace(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{caps_percentage, strip_emotes, symbols_percentage};
+    use crate::emotes::Span;
+
+    #[test]
+    fn test_caps_percentage() {
+        let text = |s: &str| s.chars().collect::<Vec<_>>();
+
+        assert_eq!(Some(100), caps_percentage(&text("HELLO THERE"), 10));
+        assert_eq!(Some(50), caps_percentage(&text("HELLO there"), 10));
+        assert_eq!(None, caps_percentage(&text("HELLO"), 10));
+        assert_eq!(None, caps_percentage(&text("!!!"), 0));
+    }
+
+    #[test]
+    fn test_symbols_percentage() {
+        let text = |s: &str| s.chars().collect::<Vec<_>>();
+
+        assert_eq!(Some(0), symbols_percentage(&text("hello there"), 10));
+        assert_eq!(Some(50), symbols_percentage(&text("hello ☺☺☺☺☺"), 10));
+        assert_eq!(
+            Some(50),
+            symbols_percentage(&text("h\u{0300}e\u{0301}l\u{0302}l\u{0303}o\u{0304}"), 10)
+        );
+        assert_eq!(None, symbols_percentage(&text("!!!"), 10));
+    }
+
+    #[test]
+    fn test_strip_emotes() {
+        let emotes = [Span {
+            id: 25,
+            start: 6,
+            end: 10,
+        }];
+
+        assert_eq!(
+            "hello  there",
+            strip_emotes("hello Kappa there", &emotes)
+                .into_iter()
+                .collect::<String>()
+        );
+    }
+}
@@ -0,0 +1,163 @@
+//! Escalating moderation actions for filtered messages.
+//!
+//! Every filtered message gives the user a strike. The first strike is a
+//! warning, followed by timeouts which grow longer with every strike, and
+//! finally a ban. Strikes decay over time, so that users who behave are
+//! eventually forgiven.
+
+use crate::{settings, template::Template, utils};
+use failure::Error;
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// The longest timeout Twitch allows, two weeks.
+const MAX_TIMEOUT: u64 = 1_209_600;
+
+/// The action taken for a given number of strikes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Step {
+    /// Warn the user.
+    Warn,
+    /// Time out the user for the given duration.
+    Timeout(utils::Duration),
+    /// Ban the user.
+    Ban,
+}
+
+#[derive(serde::Serialize)]
+struct Vars<'a> {
+    name: &'a str,
+    reason: &'a str,
+    strikes: u32,
+    duration: Option<String>,
+}
+
+/// Settings for how strikes escalate.
+pub struct Escalation {
+    enabled: Arc<RwLock<bool>>,
+    decay: Arc<RwLock<utils::Duration>>,
+    timeout: Arc<RwLock<utils::Duration>>,
+    timeout_multiplier: Arc<RwLock<u32>>,
+    ban_at: Arc<RwLock<u32>>,
+    warn_message: Arc<RwLock<Template>>,
+    timeout_message: Arc<RwLock<Template>>,
+    ban_message: Arc<RwLock<Template>>,
+}
+
+impl Escalation {
+    /// Load escalation settings scoped to `chat/strikes`.
+    pub fn new(settings: &settings::Settings) -> Result<Escalation, Error> {
+        let warn_message =
+            Template::compile("{{name}} -> Please don't do that ({{reason}}), this is a warning.")?;
+        let timeout_message =
+            Template::compile("{{name}} -> Timed out for {{duration}} ({{reason}}).")?;
+        let ban_message = Template::compile("{{name}} has been banned ({{reason}}).")?;
+
+        Ok(Escalation {
+            enabled: settings.var("enabled", false)?,
+            decay: settings.var("decay", utils::Duration::hours(24))?,
+            timeout: settings.var("timeout", utils::Duration::seconds(600))?,
+            timeout_multiplier: settings.var("timeout-multiplier", 6)?,
+            ban_at: settings.var("ban-at", 5)?,
+            warn_message: settings.var("warn-message", warn_message)?,
+            timeout_message: settings.var("timeout-message", timeout_message)?,
+            ban_message: settings.var("ban-message", ban_message)?,
+        })
+    }
+
+    /// Test if strikes are enabled.
+    pub fn is_enabled(&self) -> bool {
+        *self.enabled.read()
+    }
+
+    /// How long it takes for a single strike to decay.
+    pub fn decay(&self) -> utils::Duration {
+        self.decay.read().clone()
+    }
+
+    /// The step to take for a user with the given number of strikes.
+    pub fn step(&self, strikes: u32) -> Step {
+        step(
+            strikes,
+            &*self.timeout.read(),
+            *self.timeout_multiplier.read(),
+            *self.ban_at.read(),
+        )
+    }
+
+    /// Render the response to the given step.
+    pub fn render(
+        &self,
+        step: &Step,
+        name: &str,
+        reason: &str,
+        strikes: u32,
+    ) -> Result<String, Error> {
+        let (template, duration) = match step {
+            Step::Warn => (&self.warn_message, None),
+            Step::Timeout(duration) => (
+                &self.timeout_message,
+                Some(utils::compact_duration(&duration.as_std())),
+            ),
+            Step::Ban => (&self.ban_message, None),
+        };
+
+        template.read().render_to_string(&Vars {
+            name,
+            reason,
+            strikes,
+            duration,
+        })
+    }
+}
+
+/// The step to take for the given number of strikes.
+///
+/// Timeouts start at `timeout` on the second strike and are multiplied by
+/// `multiplier` for every strike after that, up to the longest timeout Twitch
+/// allows. A `ban_at` of zero means users are never banned.
+fn step(strikes: u32, timeout: &utils::Duration, multiplier: u32, ban_at: u32) -> Step {
+    if strikes <= 1 {
+        return Step::Warn;
+    }
+
+    if ban_at > 0 && strikes >= ban_at {
+        return Step::Ban;
+    }
+
+    let seconds = timeout.as_std().as_secs();
+    let factor = u64::from(multiplier.max(1)).saturating_pow(strikes - 2);
+    let seconds = seconds.saturating_mul(factor).min(MAX_TIMEOUT);
+    Step::Timeout(utils::Duration::seconds(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{step, Step};
+    use crate::utils::Duration;
+
+    #[test]
+    fn test_step() {
+        let timeout = Duration::seconds(60);
+
+        assert_eq!(Step::Warn, step(1, &timeout, 5, 4));
+        assert_eq!(
+            Step::Timeout(Duration::seconds(60)),
+            step(2, &timeout, 5, 4)
+        );
+        assert_eq!(
+            Step::Timeout(Duration::seconds(300)),
+            step(3, &timeout, 5, 4)
+        );
+        assert_eq!(Step::Ban, step(4, &timeout, 5, 4));
+        assert_eq!(Step::Ban, step(10, &timeout, 5, 4));
+        assert_eq!(
+            Step::Timeout(Duration::seconds(60)),
+            step(10, &timeout, 0, 0)
+        );
+        assert_eq!(
+            Step::Timeout(Duration::seconds(1_209_600)),
+            step(100, &timeout, 6, 0)
+        );
+    }
+}
@@ -0,0 +1,752 @@
+//! Harness for testing the chat handler against an in-process fake of the
+//! Twitch chat server.
+//!
+//! The fake server accepts a single connection from a regular IRC client and
+//! answers the way Twitch does for the handful of messages the bot depends on.
+//! Information that would otherwise come from the Twitch API, like the
+//! streamer, the channel and subscribers, is served by a mocked API which
+//! answers with fixtures.
+
+use super::{
+    currency_admin, links, Handler, LinkPermits, Ranks, Sender, User, TWITCH_COMMANDS_CAP,
+    TWITCH_MEMBERSHIP_CAP, TWITCH_TAGS_CAP,
+};
+use crate::{
+    api::{self, twitch},
+    auth::{self, Auth},
+    bus,
+    currency::{Currency, CurrencyBuilder},
+    db, idle,
+    injector::Injector,
+    module, oauth2,
+    prelude::*,
+    settings, stream_info, utils,
+};
+use failure::{bail, format_err, Error};
+use hashbrown::{HashMap, HashSet};
+use irc::{
+    client::{self, Client},
+    proto::{
+        command::{CapSubCommand, Command},
+        message::Message,
+    },
+};
+use leaky_bucket::LeakyBuckets;
+use parking_lot::{Mutex, RwLock};
+use std::{
+    io::{self, BufRead as _, Read as _, Write as _},
+    net,
+    path::Path,
+    sync::Arc,
+    thread, time,
+};
+
+/// How long to wait for the bot to send something before giving up.
+const TIMEOUT: time::Duration = time::Duration::from_secs(5);
+
+/// Run the given test future to completion.
+pub fn run<F>(future: F) -> Result<(), Error>
+where
+    F: Future<Output = Result<(), Error>>,
+{
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(future)
+}
+
+/// Set up a fake chat server, and a bot connected to the given channel.
+pub async fn setup(channel: &str) -> Result<(Fixture, Chat), Error> {
+    let listener = net::TcpListener::bind("127.0.0.1:0")?;
+    let addr = listener.local_addr()?;
+
+    let config = client::data::config::Config {
+        nickname: Some(String::from("bot")),
+        server: Some(addr.ip().to_string()),
+        port: Some(addr.port()),
+        use_ssl: false,
+        ..client::data::config::Config::default()
+    };
+
+    let mut client = Client::from_config(config).await?;
+
+    // NB: the connection is already established, so this doesn't block.
+    let (stream, _) = listener.accept()?;
+    let server = Server::start(stream)?;
+
+    client.identify()?;
+
+    let outgoing = client
+        .outgoing()
+        .ok_or_else(|| format_err!("missing outgoing future for irc client"))?;
+
+    tokio::spawn(async move {
+        let _ = outgoing.await;
+    });
+
+    let buckets = LeakyBuckets::new();
+
+    let sender = Sender::new(
+        Arc::new(RwLock::new(super::sender::Type::Chat)),
+        format!("#{}", channel),
+        client.sender(),
+        Default::default(),
+        &buckets,
+    )?;
+
+    tokio::spawn(async move {
+        if let Err(e) = buckets.coordinate().await {
+            log::error!("leaky bucket coordinator errored: {}", e);
+        }
+    });
+
+    let stream = client.stream()?.map(|m| m.map_err(Error::from)).boxed();
+
+    let db = db::Database::open(Path::new(":memory:"))?;
+    let injector = Injector::new();
+
+    let (force_refresh, _) = mpsc::unbounded();
+    let (bad_links_tx, bad_links) = mpsc::unbounded();
+    let (shutdown, _) = utils::Shutdown::new();
+
+    let token = oauth2::SyncToken::new("test", force_refresh);
+    token.update(serde_json::from_value(serde_json::json!({
+        "id": "twitch-bot",
+        "title": "Twitch Bot",
+        "description": "",
+        "hash": "",
+        "token": {
+            "client_id": "test",
+            "flow_id": "twitch",
+            "access_token": "test",
+            "refreshed_at": "2019-10-01T00:00:00Z",
+            "expires_in": null,
+            "scopes": [],
+        },
+    }))?);
+
+    let api = MockTwitch::start()?;
+    api.insert(
+        "/helix/users",
+        serde_json::json!({ "data": [user(channel)] }),
+    );
+    api.insert(
+        &format!("/kraken/channels/{}-id", channel),
+        self::channel(channel),
+    );
+    api.insert("/helix/subscriptions", serde_json::json!({ "data": [] }));
+    api.insert("/helix/streams", serde_json::json!({ "data": [] }));
+
+    let twitch = api.twitch(token.clone())?;
+
+    let streamer = twitch
+        .user_by_login(channel)
+        .await?
+        .ok_or_else(|| format_err!("no such user: {}", channel))?;
+    let streamer = Arc::new(twitch::User::from(streamer));
+    let channel = Arc::new(twitch.channel_by_id(&streamer.id).await?);
+
+    let mut currency = CurrencyBuilder::new(twitch.clone(), Default::default());
+    currency.enabled = true;
+    currency.name = Some(Arc::new(String::from("thingies")));
+    currency.db = Some(db.clone());
+    currency.ledger = Some(db::Ledger::load(db.clone())?);
+
+    let currency = currency
+        .build()
+        .ok_or_else(|| format_err!("failed to build currency"))?;
+
+    let fixture = Fixture {
+        streamer: streamer.clone(),
+        channel,
+        twitch,
+        api,
+        currency,
+        sender,
+        commands: db::Commands::load(db.clone())?,
+        aliases: db::Aliases::load(db.clone())?,
+        bad_words: db::Words::load(db.clone())?,
+        strikes: db::Strikes::load(db.clone())?,
+        audit_log: db::AuditLog::load(db.clone())?,
+        messages: db::Messages::load(db.clone())?,
+        seen_users: db::SeenUsers::load(db.clone())?,
+        link_permits: LinkPermits::default(),
+        ranks: Ranks::default(),
+        ban_list: db::BanList::load(db.clone())?,
+        redirects: Arc::new(Redirects::default()),
+        auth: Auth::new(db.clone(), auth::Schema::load_static()?)?,
+        settings: settings::Settings::new(db.clone(), settings::Schema::load_static()?),
+        global_bus: Arc::new(bus::Bus::new()),
+        shutdown,
+        idle: idle::Idle::new(Arc::new(RwLock::new(5))),
+        token,
+        stream_info: stream_info::StreamInfo {
+            user: streamer,
+            data: Default::default(),
+        },
+        injector,
+        bad_links: bad_links_tx,
+    };
+
+    let chat = Chat {
+        server,
+        _client: client,
+        stream,
+        sync: 0,
+        bad_links,
+    };
+
+    Ok((fixture, chat))
+}
+
+/// A user, like it would be returned from the Twitch API.
+pub fn user(name: &str) -> serde_json::Value {
+    serde_json::json!({
+        "id": format!("{}-id", name),
+        "login": name,
+        "display_name": name,
+        "type": "",
+        "broadcaster_type": "",
+        "description": "",
+        "profile_image_url": "",
+        "offline_image_url": "",
+        "view_count": 0,
+    })
+}
+
+/// A channel, like it would be returned from the Twitch API.
+pub fn channel(name: &str) -> serde_json::Value {
+    serde_json::json!({
+        "mature": false,
+        "status": "Testing",
+        "_id": format!("{}-id", name),
+        "name": name,
+        "created_at": "2019-10-01T00:00:00Z",
+        "updated_at": "2019-10-01T00:00:00Z",
+        "partner": false,
+        "url": format!("https://twitch.tv/{}", name),
+        "views": 0,
+        "followers": 0,
+    })
+}
+
+/// A subscription to the given channel, like it would be returned from the
+/// Twitch API.
+pub fn subscription(channel: &str, name: &str) -> serde_json::Value {
+    serde_json::json!({
+        "broadcaster_id": format!("{}-id", channel),
+        "broadcaster_name": channel,
+        "is_gift": false,
+        "tier": "1000",
+        "plan_name": "Channel Subscription",
+        "user_id": format!("{}-id", name),
+        "user_name": name,
+    })
+}
+
+/// Everything a chat handler borrows during a test.
+pub struct Fixture {
+    pub streamer: Arc<twitch::User>,
+    pub channel: Arc<twitch::Channel>,
+    /// Twitch API integration, talking to the mocked API.
+    pub twitch: api::Twitch,
+    pub api: MockTwitch,
+    /// The builtin currency of the channel.
+    pub currency: Currency,
+    pub sender: Sender,
+    pub commands: db::Commands,
+    pub aliases: db::Aliases,
+    pub bad_words: db::Words,
+    pub strikes: db::Strikes,
+    pub audit_log: db::AuditLog,
+    pub messages: db::Messages,
+    pub seen_users: db::SeenUsers,
+    pub link_permits: LinkPermits,
+    pub ranks: Ranks,
+    pub ban_list: db::BanList,
+    pub redirects: Arc<Redirects>,
+    pub auth: Auth,
+    pub settings: settings::Settings,
+    global_bus: Arc<bus::Bus<bus::Global>>,
+    shutdown: utils::Shutdown,
+    idle: idle::Idle,
+    token: oauth2::SyncToken,
+    stream_info: stream_info::StreamInfo,
+    pub injector: Injector,
+    bad_links: mpsc::UnboundedSender<User>,
+}
+
+impl Fixture {
+    /// Refresh the subscribers of the channel from the mocked API.
+    pub async fn refresh_subs(&self) {
+        self.stream_info
+            .refresh_subs(&self.twitch, &self.streamer)
+            .await;
+    }
+
+    /// Construct a handler for the channel, with bad words, the URL
+    /// whitelist and chat history enabled.
+    pub fn handler(&self) -> Result<Handler<'_>, Error> {
+        let (_, currency_handler) = currency_admin::setup(&self.injector)?;
+
+        Ok(Handler {
+            streamer: self.streamer.clone(),
+            sender: self.sender.clone(),
+            moderators: Default::default(),
+            vips: Default::default(),
+            whitelisted_hosts: Default::default(),
+            commands: Some(self.commands.clone()),
+            bad_words: &self.bad_words,
+            global_bus: &self.global_bus,
+            aliases: Some(self.aliases.clone()),
+            api_url: None,
+            moderator_cooldown: Default::default(),
+            handlers: module::Handlers::default(),
+            shutdown: &self.shutdown,
+            idle: &self.idle,
+            rate_limit: super::rate_limit::RateLimit::new(
+                &self.settings.scoped("chat/rate-limit"),
+            )?,
+            token: &self.token,
+            handler_shutdown: false,
+            stream_info: &self.stream_info,
+            auth: &self.auth,
+            scope_cooldowns: self.auth.scope_cooldowns(),
+            currency_handler,
+            bad_words_enabled: Arc::new(RwLock::new(true)),
+            url_whitelist_enabled: Arc::new(RwLock::new(true)),
+            spam: super::spam::Spam::new(&self.settings.scoped("chat/spam"))?,
+            escalation: super::strikes::Escalation::new(&self.settings.scoped("chat/strikes"))?,
+            strikes: Some(self.strikes.clone()),
+            audit_log: Some(self.audit_log.clone()),
+            history: Some(self.messages.clone()),
+            history_enabled: Arc::new(RwLock::new(true)),
+            lockdown: super::lockdown::Lockdown::new(self.settings.scoped("chat/lockdown"))?,
+            seen_users: Some(self.seen_users.clone()),
+            link_permits: Some(self.link_permits.clone()),
+            ranks: Some(self.ranks.clone()),
+            ban_list: Some(self.ban_list.clone()),
+            ban_list_enabled: self.settings.var("chat/ban-list/enabled", false)?,
+            ban_list_on_join: self.settings.var("chat/ban-list/on-join", true)?,
+            ban_list_applied: Default::default(),
+            shorteners: links::Shorteners::new(
+                &self.settings.scoped("chat/url-whitelist"),
+                self.redirects.clone(),
+            )?,
+            bad_links: self.bad_links.clone(),
+            message_hooks: Default::default(),
+            chat_log: None,
+            channel: self.channel.clone(),
+            currency: Some(self.currency.clone()),
+            counters: None,
+            command_stats: None,
+            last_invoked: Default::default(),
+        })
+    }
+
+    /// Hook the given module into the handler, like the bot does on startup.
+    ///
+    /// The currency of the channel is injected first. Background futures set
+    /// up by the module are not driven, so settings have to be set before the
+    /// module is hooked.
+    pub async fn hook<'a>(
+        &'a self,
+        handler: &mut Handler<'a>,
+        module: &dyn module::Module,
+    ) -> Result<(), Error> {
+        self.injector.update(self.currency.clone());
+
+        let mut futures = utils::Futures::default();
+        let command_bus = Arc::new(bus::Bus::new());
+
+        module
+            .hook(module::HookContext {
+                injector: &self.injector,
+                handlers: &mut handler.handlers,
+                futures: &mut futures,
+                stream_info: &self.stream_info,
+                idle: &self.idle,
+                twitch: &self.twitch,
+                streamer_twitch: &self.twitch,
+                sender: &self.sender,
+                settings: &self.settings,
+                auth: &self.auth,
+                command_bus: &command_bus,
+                global_bus: &self.global_bus,
+            })
+            .await
+    }
+}
+
+/// Resolver of shortened links, answering from a fixed set of redirects
+/// instead of asking the shortener.
+#[derive(Default)]
+pub struct Redirects {
+    redirects: Mutex<HashMap<String, String>>,
+    /// Links which never finish resolving.
+    hanging: Mutex<HashSet<String>>,
+}
+
+impl Redirects {
+    /// Make the given link redirect to another one.
+    pub fn insert(&self, from: &str, to: &str) {
+        self.redirects
+            .lock()
+            .insert(from.to_string(), to.to_string());
+    }
+
+    /// Make the given link never finish resolving.
+    pub fn hang(&self, url: &str) {
+        self.hanging.lock().insert(url.to_string());
+    }
+}
+
+#[async_trait]
+impl links::Resolver for Redirects {
+    async fn redirect(&self, url: &reqwest::Url) -> Result<Option<reqwest::Url>, Error> {
+        let hanging = self.hanging.lock().contains(url.as_str());
+
+        if hanging {
+            future::pending::<()>().await;
+        }
+
+        match self.redirects.lock().get(url.as_str()) {
+            Some(to) => Ok(Some(reqwest::Url::parse(to)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+/// A mocked Twitch API, answering every request to a path with a fixed JSON
+/// response.
+pub struct MockTwitch {
+    url: reqwest::Url,
+    responses: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+}
+
+impl MockTwitch {
+    /// Start serving the mocked API on a background thread.
+    fn start() -> Result<MockTwitch, Error> {
+        let listener = net::TcpListener::bind("127.0.0.1:0")?;
+        let url = reqwest::Url::parse(&format!("http://{}/", listener.local_addr()?))?;
+        let responses = Arc::new(Mutex::new(HashMap::new()));
+
+        let thread_responses = responses.clone();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream.and_then(|s| serve_api(s, &thread_responses));
+
+                if let Err(e) = result {
+                    log::error!("mocked twitch api errored: {}", e);
+                }
+            }
+        });
+
+        Ok(MockTwitch { url, responses })
+    }
+
+    /// Respond to requests for the given path, ignoring the query.
+    pub fn insert(&self, path: &str, response: serde_json::Value) {
+        self.responses.lock().insert(path.to_string(), response);
+    }
+
+    /// Construct an API integration which talks to the mocked API.
+    pub fn twitch(&self, token: oauth2::SyncToken) -> Result<api::Twitch, Error> {
+        api::Twitch::with_base_url(token, self.url.clone())
+    }
+}
+
+/// Serve a single HTTP request to the mocked API.
+fn serve_api(
+    stream: net::TcpStream,
+    responses: &Mutex<HashMap<String, serde_json::Value>>,
+) -> io::Result<()> {
+    let mut reader = io::BufReader::new(stream.try_clone()?);
+
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+
+    let mut content_length = 0;
+
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+
+        let header = header.trim();
+
+        if header.is_empty() {
+            break;
+        }
+
+        let mut parts = header.splitn(2, ':');
+
+        if let (Some(name), Some(value)) = (parts.next(), parts.next()) {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or_default();
+            }
+        }
+    }
+
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body)?;
+
+    let path = request
+        .split_whitespace()
+        .nth(1)
+        .and_then(|p| p.split('?').next())
+        .unwrap_or("/");
+
+    let (status, body) = match responses.lock().get(path) {
+        Some(response) => ("200 OK", response.to_string()),
+        None => ("404 Not Found", String::from("{}")),
+    };
+
+    let mut stream = stream;
+
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+
+    stream.flush()
+}
+
+/// The connection between the fake server and the bot.
+pub struct Chat {
+    server: Server,
+    /// NB: kept alive for the duration of the test.
+    _client: Client,
+    stream: stream::BoxStream<'static, Result<Message, Error>>,
+    /// Counter used to generate unique sync points.
+    sync: usize,
+    /// Messages with bad links found in the background.
+    bad_links: mpsc::UnboundedReceiver<User>,
+}
+
+impl Chat {
+    /// Request the Twitch capabilities the same way the bot does when it
+    /// connects, and process the acknowledgements.
+    pub async fn connect(&mut self, handler: &mut Handler<'_>) -> Result<(), Error> {
+        handler.sender.cap_req(TWITCH_TAGS_CAP);
+        handler.sender.cap_req(TWITCH_COMMANDS_CAP);
+        handler.sender.cap_req(TWITCH_MEMBERSHIP_CAP);
+
+        let mut requested = 0;
+
+        while requested < 3 {
+            if let Command::CAP(_, CapSubCommand::REQ, ..) = self.server.recv().await?.command {
+                requested += 1;
+            }
+        }
+
+        self.sync(handler).await
+    }
+
+    /// Push a raw line from the chat server, and wait until the handler has
+    /// processed it.
+    pub async fn push(&mut self, handler: &mut Handler<'_>, line: &str) -> Result<(), Error> {
+        self.server.send(line)?;
+        self.sync(handler).await
+    }
+
+    /// Process everything sent to the bot up until now.
+    ///
+    /// This works by sending a PING with a unique token, and processing
+    /// messages until the same PING is seen by the bot.
+    pub async fn sync(&mut self, handler: &mut Handler<'_>) -> Result<(), Error> {
+        self.sync += 1;
+        let token = format!("sync-{}", self.sync);
+        self.server.send(&format!("PING {}", token))?;
+
+        while let Some(m) = self.stream.next().await {
+            let m = m?;
+
+            if let Command::PING(ref server, _) = m.command {
+                if *server == token {
+                    return Ok(());
+                }
+            }
+
+            handler.handle(m).await?;
+        }
+
+        bail!("connection closed before sync point `{}`", token);
+    }
+
+    /// Wait for the next message found to have a bad link in the background,
+    /// and let the handler moderate it like the bot does.
+    pub async fn bad_link(&mut self, handler: &mut Handler<'_>) -> Result<(), Error> {
+        let user = self
+            .bad_links
+            .next()
+            .await
+            .ok_or_else(|| format_err!("no more bad links"))?;
+
+        handler.moderate_bad_link(&user)
+    }
+
+    /// Wait for the next PRIVMSG sent by the bot, returning the channel and
+    /// the message.
+    pub async fn privmsg(&mut self) -> Result<(String, String), Error> {
+        loop {
+            if let Command::PRIVMSG(channel, message) = self.server.recv().await?.command {
+                return Ok((channel, message));
+            }
+        }
+    }
+
+    /// Wait for the next message received by the bot as a consequence of
+    /// something it sent, like the CLEARCHAT following a `/ban`.
+    pub async fn echo(&mut self) -> Result<Message, Error> {
+        self.server.echo().await
+    }
+}
+
+/// A fake Twitch chat server, serving a single connection.
+struct Server {
+    /// The write half of the connection.
+    writer: Arc<Mutex<net::TcpStream>>,
+    /// Messages received from the bot.
+    received: mpsc::UnboundedReceiver<Message>,
+    /// Replies sent to the bot in response to its chat commands.
+    echoed: mpsc::UnboundedReceiver<Message>,
+}
+
+impl Server {
+    /// Start serving the given connection on a background thread.
+    fn start(stream: net::TcpStream) -> Result<Server, Error> {
+        let writer = Arc::new(Mutex::new(stream.try_clone()?));
+        let (received_tx, received) = mpsc::unbounded();
+        let (echoed_tx, echoed) = mpsc::unbounded();
+
+        let thread_writer = writer.clone();
+
+        thread::spawn(move || {
+            if let Err(e) = serve(stream, thread_writer, received_tx, echoed_tx) {
+                log::error!("fake chat server errored: {}", e);
+            }
+        });
+
+        Ok(Server {
+            writer,
+            received,
+            echoed,
+        })
+    }
+
+    /// Send a raw line to the bot.
+    fn send(&self, line: &str) -> Result<(), Error> {
+        write_line(&self.writer, line)?;
+        Ok(())
+    }
+
+    /// Wait for the next message received from the bot.
+    async fn recv(&mut self) -> Result<Message, Error> {
+        timeout(self.received.next()).await
+    }
+
+    /// Wait for the next reply echoed back to the bot.
+    async fn echo(&mut self) -> Result<Message, Error> {
+        timeout(self.echoed.next()).await
+    }
+}
+
+/// Wait for the given message, or time out.
+async fn timeout<F>(future: F) -> Result<Message, Error>
+where
+    F: Future<Output = Option<Message>> + Unpin,
+{
+    let delay = tokio::timer::delay(time::Instant::now() + TIMEOUT).boxed();
+
+    match future::select(future, delay).await {
+        future::Either::Left((Some(m), _)) => Ok(m),
+        future::Either::Left((None, _)) => bail!("connection to bot closed"),
+        future::Either::Right(..) => bail!("timed out waiting for the bot"),
+    }
+}
+
+/// Serve the connection until it is closed.
+fn serve(
+    stream: net::TcpStream,
+    writer: Arc<Mutex<net::TcpStream>>,
+    received: mpsc::UnboundedSender<Message>,
+    echoed: mpsc::UnboundedSender<Message>,
+) -> Result<(), Error> {
+    for line in io::BufReader::new(stream).lines() {
+        let line = line?;
+
+        let m = str::parse::<Message>(&line)
+            .map_err(|e| format_err!("bad message from bot `{}`: {}", line, e))?;
+
+        // NB: reply before the message is made visible to the test, so that
+        // any sync point requested afterwards is ordered after the reply.
+        if let Some(reply) = reply(&m) {
+            write_line(&writer, &reply)?;
+        }
+
+        if let Some(echo) = echo(&m) {
+            write_line(&writer, &echo)?;
+
+            let echo = str::parse::<Message>(&echo)
+                .map_err(|e| format_err!("bad echo `{}`: {}", echo, e))?;
+
+            let _ = echoed.unbounded_send(echo);
+        }
+
+        if received.unbounded_send(m).is_err() {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reply to the protocol messages the bot depends on, like Twitch does.
+fn reply(m: &Message) -> Option<String> {
+    match m.command {
+        Command::NICK(ref nick) => Some(format!(":tmi.twitch.tv 001 {} :Welcome, GLHF!", nick)),
+        Command::CAP(_, CapSubCommand::REQ, ref a, ref b) => {
+            let cap = a.as_ref().or(b.as_ref())?;
+            Some(format!(":tmi.twitch.tv CAP * ACK :{}", cap))
+        }
+        Command::PING(ref server, _) => {
+            Some(format!(":tmi.twitch.tv PONG tmi.twitch.tv :{}", server))
+        }
+        _ => None,
+    }
+}
+
+/// Echo chat commands sent by the bot as the messages Twitch would broadcast
+/// in response.
+fn echo(m: &Message) -> Option<String> {
+    let (channel, message) = match m.command {
+        Command::PRIVMSG(ref channel, ref message) => (channel, message),
+        _ => return None,
+    };
+
+    let mut it = message.split_whitespace();
+
+    match it.next()? {
+        "/delete" => Some(format!(
+            "@login=;target-msg-id={} :tmi.twitch.tv CLEARMSG {} :deleted",
+            it.next()?,
+            channel
+        )),
+        "/ban" | "/timeout" => Some(format!(
+            ":tmi.twitch.tv CLEARCHAT {} :{}",
+            channel,
+            it.next()?
+        )),
+        "/clear" => Some(format!(":tmi.twitch.tv CLEARCHAT {}", channel)),
+        _ => None,
+    }
+}
+
+/// Write a single line to the connection.
+fn write_line(writer: &Mutex<net::TcpStream>, line: &str) -> io::Result<()> {
+    let mut writer = writer.lock();
+    write!(writer, "{}\r\n", line)?;
+    writer.flush()
+}
@@ -28,6 +28,7 @@ pub mod oauth2;
 mod panic_logger;
 pub mod player;
 pub mod prelude;
+pub mod script;
 pub mod settings;
 mod song_file;
 mod spotify_id;
@@ -424,11 +424,32 @@ async fn try_main(
     injector.update(db::Aliases::load(db.clone())?);
     injector.update(db::Promotions::load(db.clone())?);
     injector.update(db::Themes::load(db.clone())?);
+    injector.update(db::Scripts::load(db.clone())?);
+    injector.update(db::Schedules::load(db.clone())?);
+    injector.update(db::Quotes::load(db.clone())?);
+    injector.update(db::Counters::load(db.clone())?);
+
+    let command_stats = db::CommandStats::load(db.clone())?;
+    injector.update(command_stats.clone());
+
+    injector.update(db::Strikes::load(db.clone())?);
+    injector.update(db::AuditLog::load(db.clone())?);
+    injector.update(db::SeenUsers::load(db.clone())?);
+    injector.update(irc::LinkPermits::default());
+    injector.update(irc::Ranks::default());
+    injector.update(db::BanList::load(db.clone())?);
+
+    let ledger = db::Ledger::load(db.clone())?;
+    injector.update(ledger.clone());
+
+    let messages = db::Messages::load(db.clone())?;
+    injector.update(messages.clone());
 
     let message_bus = Arc::new(bus::Bus::new());
     let global_bus = Arc::new(bus::Bus::new());
     let youtube_bus = Arc::new(bus::Bus::new());
     let global_channel = Arc::new(RwLock::new(None));
+    let global_channels = Arc::new(RwLock::new(Vec::new()));
     let command_bus = Arc::new(bus::Bus::new());
 
     futures.push(
@@ -444,6 +465,31 @@ async fn try_main(
             .boxed()
             .instrument(trace_span!(target: "futures", "system-loop",)),
     );
+    futures.push(
+        history_loop(settings.scoped("chat/history"), messages)
+            .boxed()
+            .instrument(trace_span!(target: "futures", "history-loop",)),
+    );
+
+    let retention = settings.var("currency/ledger/retention", utils::Duration::hours(24 * 90))?;
+
+    futures.push(
+        retention_loop("currency ledger", retention, move |before| {
+            ledger.prune(before)
+        })
+        .boxed()
+        .instrument(trace_span!(target: "futures", "ledger-retention-loop",)),
+    );
+
+    let retention = settings.var("command-stats/retention", utils::Duration::hours(24 * 365))?;
+
+    futures.push(
+        retention_loop("command stats", retention, move |before| {
+            command_stats.prune(before)
+        })
+        .boxed()
+        .instrument(trace_span!(target: "futures", "command-stats-retention-loop",)),
+    );
 
     let storage = storage::Storage::open(&root.join("storage"))?;
     injector.update(storage.cache()?);
@@ -470,6 +516,7 @@ async fn try_main(
         db.clone(),
         auth.clone(),
         global_channel.clone(),
+        global_channels.clone(),
         latest.clone(),
     )
     .await?;
@@ -678,6 +725,16 @@ async fn try_main(
     modules.push(Box::new(module::poll::Module));
     modules.push(Box::new(module::weather::Module));
     modules.push(Box::new(module::help::Module));
+    modules.push(Box::new(module::script::Module));
+    modules.push(Box::new(module::schedule::Module));
+    modules.push(Box::new(module::quote::Module));
+    modules.push(Box::new(module::counter::Module));
+    modules.push(Box::new(module::strikes::Module));
+    modules.push(Box::new(module::audit::Module));
+    modules.push(Box::new(module::permit::Module));
+    modules.push(Box::new(module::ban_list::Module));
+    modules.push(Box::new(module::games::Module));
+    modules.push(Box::new(module::rank::Module));
 
     let (stream_state_tx, stream_state_rx) = mpsc::channel(64);
 
@@ -697,6 +754,7 @@ async fn try_main(
         settings,
         auth,
         global_channel,
+        global_channels,
         injector: injector.clone(),
         stream_state_tx,
         message_log,
@@ -806,3 +864,83 @@ async fn system_loop(settings: settings::Settings, system: sys::System) -> Resul
 
     bail!("run-on-startup stream ended");
 }
+
+/// Periodically prune messages older than the configured number of days from
+/// the chat history.
+async fn history_loop(settings: settings::Settings, messages: db::Messages) -> Result<(), Error> {
+    let days = settings.var("days", 7u32)?;
+    let mut interval = tokio::timer::Interval::new_interval(time::Duration::from_secs(60 * 60));
+
+    while let Some(_) = interval.next().await {
+        let days = *days.read();
+
+        // NB: zero days means that history is kept forever.
+        if days == 0 {
+            continue;
+        }
+
+        let before = chrono::Utc::now()
+            .naive_utc()
+            .checked_sub_signed(chrono::Duration::days(i64::from(days)));
+
+        // NB: history older than the earliest representable date is kept
+        // forever.
+        let before = match before {
+            Some(before) => before,
+            None => continue,
+        };
+
+        match messages.prune(before) {
+            Ok(pruned) if pruned > 0 => {
+                log::info!("Pruned {} message(s) from the chat history", pruned);
+            }
+            Ok(..) => (),
+            Err(e) => oxidize::log_err!(e, "failed to prune chat history"),
+        }
+    }
+
+    bail!("history interval ended");
+}
+
+/// Periodically prune whatever was created longer ago than the retention,
+/// using `prune` to remove everything created before a point in time.
+///
+/// A retention of zero means that everything is kept forever.
+async fn retention_loop<F>(
+    what: &'static str,
+    retention: Arc<RwLock<utils::Duration>>,
+    prune: F,
+) -> Result<(), Error>
+where
+    F: Fn(chrono::NaiveDateTime) -> Result<usize, Error>,
+{
+    let mut interval = tokio::timer::Interval::new_interval(time::Duration::from_secs(60 * 60));
+
+    while let Some(_) = interval.next().await {
+        let retention = retention.read().clone();
+
+        if retention.is_empty() {
+            continue;
+        }
+
+        // NB: a retention reaching before the earliest representable date
+        // keeps everything.
+        let before = match retention
+            .checked_as_chrono()
+            .and_then(|r| chrono::Utc::now().naive_utc().checked_sub_signed(r))
+        {
+            Some(before) => before,
+            None => continue,
+        };
+
+        match prune(before) {
+            Ok(pruned) if pruned > 0 => {
+                log::info!("Pruned {} entries from the {}", pruned, what);
+            }
+            Ok(..) => (),
+            Err(e) => oxidize::log_err!(e, "failed to prune the {}", what),
+        }
+    }
+
+    bail!("{} retention interval ended", what);
+}
@@ -1,32 +1,52 @@
-use crate::{api, command, idle, injector, irc, settings, stream_info, utils};
+use crate::{api, bus, command, idle, injector, irc, settings, stream_info, utils};
 use hashbrown::HashMap;
+use std::sync::Arc;
 
 #[macro_use]
 mod macros;
 pub mod admin;
 pub mod after_stream;
 pub mod alias_admin;
+pub mod audit;
 pub mod auth;
+pub mod ban_list;
 pub mod clip;
 pub mod command_admin;
 pub mod countdown;
+pub mod counter;
 pub mod eight_ball;
+pub mod games;
 pub mod gtav;
 pub mod help;
 pub mod misc;
+pub mod permit;
 pub mod poll;
 pub mod promotions;
+pub mod quote;
+pub mod rank;
+pub mod schedule;
+pub mod script;
 pub mod song;
 pub mod speedrun;
+pub mod strikes;
 pub mod swearjar;
 pub mod theme_admin;
 pub mod time;
 pub mod water;
 pub mod weather;
 
+/// A source of command handlers which are resolved when they are invoked.
+///
+/// Used for handlers which can be added and removed at runtime, like scripts.
+pub trait DynamicHandlers: Send {
+    /// Lookup a handler for the given command in the given channel.
+    fn lookup(&self, channel: &str, command: &str) -> Option<Box<dyn command::Handler + Send>>;
+}
+
 #[derive(Default)]
 pub struct Handlers<'a> {
     handlers: HashMap<String, Box<dyn command::Handler + Send + 'a>>,
+    dynamic: Vec<Box<dyn DynamicHandlers + 'a>>,
 }
 
 impl<'a> Handlers<'a> {
@@ -40,6 +60,30 @@ impl<'a> Handlers<'a> {
     pub fn get_mut(&mut self, command: &str) -> Option<&mut (dyn command::Handler + Send + 'a)> {
         self.handlers.get_mut(command).map(|command| &mut **command)
     }
+
+    /// Insert a source of dynamic handlers.
+    pub fn insert_dynamic(&mut self, dynamic: impl DynamicHandlers + 'a) {
+        self.dynamic.push(Box::new(dynamic));
+    }
+
+    /// Lookup a dynamic handler for the given command.
+    ///
+    /// Statically registered handlers always take precedence, so this returns
+    /// `None` if one exists for the given command.
+    pub fn get_dynamic(
+        &self,
+        channel: &str,
+        command: &str,
+    ) -> Option<Box<dyn command::Handler + Send>> {
+        if self.handlers.contains_key(command) {
+            return None;
+        }
+
+        self.dynamic
+            .iter()
+            .flat_map(|dynamic| dynamic.lookup(channel, command))
+            .next()
+    }
 }
 
 /// Context for a hook.
@@ -54,6 +98,8 @@ pub struct HookContext<'a: 'b, 'b> {
     pub sender: &'a irc::Sender,
     pub settings: &'a settings::Settings,
     pub auth: &'a crate::auth::Auth,
+    pub command_bus: &'b Arc<bus::Bus<bus::Command>>,
+    pub global_bus: &'b Arc<bus::Bus<bus::Global>>,
 }
 
 #[async_trait::async_trait]
@@ -61,6 +107,15 @@ pub trait Module: 'static + Send + Sync {
     /// Type of the module as a string to help with diagnostics.
     fn ty(&self) -> &'static str;
 
+    /// If the module should also be hooked for channels other than the
+    /// streamer's own channel.
+    ///
+    /// Modules which act on behalf of the streamer, like changing the title of
+    /// the stream or controlling the player, should not.
+    fn multi_channel(&self) -> bool {
+        false
+    }
+
     /// Set up command handlers for this module.
     async fn hook(&self, _: HookContext<'_, '_>) -> Result<(), failure::Error>;
 }
@@ -1,8 +1,46 @@
-use crate::{auth, command, db, module, prelude::*, settings};
+use crate::{
+    auth,
+    command::{self, Choice as _, Param, Spec, Sub, Toggle},
+    db, module,
+    prelude::*,
+    settings,
+};
+use chrono::Utc;
 use failure::Error;
 use parking_lot::RwLock;
 use std::sync::Arc;
 
+/// Default number of days covered by `!admin stats`.
+const DEFAULT_STATS_DAYS: u32 = 7;
+/// Number of commands listed by `!admin stats`.
+const STATS_TOP_COMMANDS: usize = 5;
+/// Setting controlling whether lockdown is active.
+const LOCKDOWN_ACTIVE: &str = "chat/lockdown/active";
+
+static SPEC: Spec = Spec {
+    subs: &[
+        Sub::new("refresh-mods", &[]),
+        Sub::new("refresh-vips", &[]),
+        Sub::new("refresh", &[]),
+        Sub::new("version", &[]),
+        Sub::new("stats", &[Param::optional("days")]),
+        Sub::new(
+            "lockdown",
+            &[Param::optional("mode").choices(Toggle::CHOICES)],
+        ),
+        Sub::new("shutdown", &[]),
+        Sub::new("restart", &[]),
+        Sub::new("push", &[Param::required("key"), Param::rest("value")]),
+        Sub::new("delete", &[Param::required("key"), Param::rest("value")]),
+        Sub::new("enable-group", &[Param::required("group")]),
+        Sub::new("disable-group", &[Param::required("group")]),
+        Sub::new(
+            "settings",
+            &[Param::required("key"), Param::optional_rest("value")],
+        ),
+    ],
+};
+
 /// Handler for the !admin command.
 pub struct Handler<'a> {
     settings: &'a settings::Settings,
@@ -10,9 +48,30 @@ pub struct Handler<'a> {
     commands: Arc<RwLock<Option<db::Commands>>>,
     promotions: Arc<RwLock<Option<db::Promotions>>>,
     themes: Arc<RwLock<Option<db::Themes>>>,
+    command_stats: Arc<RwLock<Option<db::CommandStats>>>,
+    audit_log: Arc<RwLock<Option<db::AuditLog>>>,
 }
 
 impl Handler<'_> {
+    /// Record an action taken by the user in the audit log, if available.
+    ///
+    /// Failures are only logged, since the action has already been taken.
+    fn audit(
+        &self,
+        ctx: &command::Context<'_>,
+        action: db::AuditAction,
+        target: Option<&str>,
+        reason: Option<&str>,
+    ) {
+        if let Some(audit_log) = self.audit_log.read().as_ref() {
+            let result = audit_log.record(ctx.channel(), ctx.user.name(), action, target, reason);
+
+            if let Err(e) = result {
+                log_err!(e, "failed to record {} in the audit log", action);
+            }
+        }
+    }
+
     /// List settings by prefix.
     fn list_settings_by_prefix(&self, ctx: command::Context<'_>, key: &str) -> Result<(), Error> {
         let mut results = Vec::new();
@@ -55,6 +114,59 @@ impl Handler<'_> {
 
         return Ok(());
     }
+
+    /// Summarize command usage over the given number of days.
+    fn stats(&self, ctx: command::Context<'_>, days: u32) -> Result<(), Error> {
+        let command_stats = match self.command_stats.read().clone() {
+            Some(command_stats) => command_stats,
+            None => {
+                ctx.respond("Command stats are not available");
+                return Ok(());
+            }
+        };
+
+        let days = days.min(db::MAX_REPORT_DAYS);
+        let since = Utc::now().naive_utc() - chrono::Duration::days(i64::from(days));
+        let report = command_stats.report(ctx.channel(), since)?;
+
+        if report.counts.total == 0 {
+            ctx.respond(format!("No commands used in the last {} day(s)", days));
+            return Ok(());
+        }
+
+        let top = report
+            .commands
+            .iter()
+            .take(STATS_TOP_COMMANDS)
+            .map(|u| format!("{} ({})", u.command, u.counts.total))
+            .collect::<Vec<_>>();
+
+        let mut response = format!(
+            "Last {days} day(s): {total} commands ({denied} denied, {cooldown} in cooldown, {error} failed). Most used: {top}.",
+            days = days,
+            total = report.counts.total,
+            denied = report.counts.denied,
+            cooldown = report.counts.cooldown,
+            error = report.counts.error,
+            top = top.join(", "),
+        );
+
+        let latest = report
+            .sessions
+            .iter()
+            .rev()
+            .find(|s| s.started_at.is_some());
+
+        if let Some(session) = latest {
+            response = format!(
+                "{} Latest stream: {} commands.",
+                response, session.counts.total
+            );
+        }
+
+        ctx.respond(response);
+        Ok(())
+    }
 }
 
 #[async_trait]
@@ -64,24 +176,57 @@ impl<'a> command::Handler for Handler<'a> {
     }
 
     async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), failure::Error> {
-        match ctx.next().as_ref().map(String::as_str) {
-            Some("refresh-mods") => {
+        match ctx_try!(ctx.subcommand(&SPEC)?) {
+            "refresh-mods" => {
                 ctx.privmsg("/mods");
+                self.audit(&ctx, db::AuditAction::RefreshMods, None, None);
                 ctx.respond("Refreshed information on mods");
             }
-            Some("refresh-vips") => {
+            "refresh-vips" => {
                 ctx.privmsg("/vips");
+                self.audit(&ctx, db::AuditAction::RefreshVips, None, None);
                 ctx.respond("Refreshed information on vips");
             }
-            Some("refresh") => {
+            "refresh" => {
                 ctx.privmsg("/mods");
                 ctx.privmsg("/vips");
+                self.audit(&ctx, db::AuditAction::RefreshMods, None, None);
+                self.audit(&ctx, db::AuditAction::RefreshVips, None, None);
                 ctx.respond("Refreshed information on mods and vips");
             }
-            Some("version") => {
+            "version" => {
                 ctx.respond(format!("OxidizeBot Version {}", crate::VERSION));
             }
-            Some("shutdown") | Some("restart") => {
+            "stats" => {
+                let days = ctx_try!(ctx.optional_arg()).unwrap_or(DEFAULT_STATS_DAYS);
+                return self.stats(ctx, days);
+            }
+            // Toggle lockdown, filtering new chatters more strictly.
+            "lockdown" => {
+                let active = match ctx_try!(ctx.optional_arg::<Toggle>()) {
+                    Some(Toggle::On) => true,
+                    Some(Toggle::Off) => false,
+                    None => !self
+                        .settings
+                        .get::<bool>(LOCKDOWN_ACTIVE)?
+                        .unwrap_or_default(),
+                };
+
+                self.settings.set(LOCKDOWN_ACTIVE, active)?;
+                self.audit(
+                    &ctx,
+                    db::AuditAction::SettingChanged,
+                    Some(LOCKDOWN_ACTIVE),
+                    Some(&format!("set to {}", active)),
+                );
+
+                if active {
+                    ctx.respond("Lockdown enabled, new chatters are filtered more strictly.");
+                } else {
+                    ctx.respond("Lockdown disabled.");
+                }
+            }
+            "shutdown" | "restart" => {
                 if ctx.shutdown.shutdown() {
                     ctx.respond("Restarting...");
                 } else {
@@ -89,13 +234,13 @@ impl<'a> command::Handler for Handler<'a> {
                 }
             }
             // Insert a value into a setting.
-            Some("push") => {
+            "push" => {
                 let key = match key(&mut ctx) {
                     Some(key) => key,
                     None => return Ok(()),
                 };
 
-                let value = match self.value_in_set(&ctx, &key) {
+                let value = match self.value_in_set(&mut ctx, &key) {
                     Some(ty) => ty,
                     None => return Ok(()),
                 };
@@ -105,18 +250,25 @@ impl<'a> command::Handler for Handler<'a> {
                     .get::<Vec<serde_json::Value>>(&key)?
                     .unwrap_or_default();
 
+                let reason = format!("added {}", value);
                 values.push(value);
                 self.settings.set(&key, values)?;
+                self.audit(
+                    &ctx,
+                    db::AuditAction::SettingChanged,
+                    Some(&key),
+                    Some(&reason),
+                );
                 ctx.respond(format!("Updated the {} setting", key));
             }
             // Delete a value from a setting.
-            Some("delete") => {
+            "delete" => {
                 let key = match key(&mut ctx) {
                     Some(key) => key,
                     None => return Ok(()),
                 };
 
-                let value = match self.value_in_set(&ctx, &key) {
+                let value = match self.value_in_set(&mut ctx, &key) {
                     Some(ty) => ty,
                     None => return Ok(()),
                 };
@@ -128,16 +280,16 @@ impl<'a> command::Handler for Handler<'a> {
 
                 values.retain(|v| v != &value);
                 self.settings.set(&key, values)?;
+                self.audit(
+                    &ctx,
+                    db::AuditAction::SettingChanged,
+                    Some(&key),
+                    Some(&format!("removed {}", value)),
+                );
                 ctx.respond(format!("Updated the {} setting", key));
             }
-            Some("enable-group") => {
-                let group = match ctx.next() {
-                    Some(group) => group,
-                    None => {
-                        ctx.respond("Expected <group> to enable");
-                        return Ok(());
-                    }
-                };
+            "enable-group" => {
+                let group: String = ctx_try!(ctx.arg());
 
                 if let Some(aliases) = self.aliases.read().as_ref() {
                     aliases.enable_group(ctx.channel(), &group)?;
@@ -155,16 +307,11 @@ impl<'a> command::Handler for Handler<'a> {
                     themes.enable_group(ctx.channel(), &group)?;
                 }
 
+                self.audit(&ctx, db::AuditAction::GroupEnabled, Some(&group), None);
                 ctx.respond(format!("Enabled group {}", group));
             }
-            Some("disable-group") => {
-                let group = match ctx.next() {
-                    Some(group) => group,
-                    None => {
-                        ctx.respond("Expected <group> to disable");
-                        return Ok(());
-                    }
-                };
+            "disable-group" => {
+                let group: String = ctx_try!(ctx.arg());
 
                 if let Some(aliases) = self.aliases.read().as_ref() {
                     aliases.disable_group(ctx.channel(), &group)?;
@@ -182,10 +329,11 @@ impl<'a> command::Handler for Handler<'a> {
                     themes.disable_group(ctx.channel(), &group)?;
                 }
 
+                self.audit(&ctx, db::AuditAction::GroupDisabled, Some(&group), None);
                 ctx.respond(format!("Disabled group {}", group));
             }
             // Get or set settings.
-            Some("settings") => {
+            "settings" => {
                 let key = match key(&mut ctx) {
                     Some(key) => key,
                     None => return Ok(()),
@@ -240,21 +388,26 @@ impl<'a> command::Handler for Handler<'a> {
                         }
 
                         let value_string = serde_json::to_string(&value)?;
+
+                        // NB: don't leak secret values into the audit log.
+                        let reason = if schema.secret {
+                            String::from("set to *secret*")
+                        } else {
+                            format!("set to {}", value_string)
+                        };
+
                         self.settings.set_json(&key, value)?;
+                        self.audit(
+                            &ctx,
+                            db::AuditAction::SettingChanged,
+                            Some(&key),
+                            Some(&reason),
+                        );
                         ctx.respond(format!("Updated setting {} = {}", key, value_string));
                     }
                 }
             }
-            _ => {
-                ctx.respond(
-                    "Expected one of: \
-                     refresh-mods, \
-                     refresh-vips, \
-                     version, \
-                     shutdown, \
-                     setting.",
-                );
-            }
+            _ => (),
         }
 
         Ok(())
@@ -263,7 +416,11 @@ impl<'a> command::Handler for Handler<'a> {
 
 impl<'a> Handler<'a> {
     /// Get a value that corresponds with the given set.
-    fn value_in_set(&mut self, ctx: &command::Context<'_>, key: &str) -> Option<serde_json::Value> {
+    fn value_in_set(
+        &mut self,
+        ctx: &mut command::Context<'_>,
+        key: &str,
+    ) -> Option<serde_json::Value> {
         let schema = match self.settings.lookup(key) {
             Some(schema) => schema,
             None => {
@@ -283,7 +440,7 @@ impl<'a> Handler<'a> {
             }
         };
 
-        let value = match ty.parse_as_json(ctx.rest()) {
+        let value = match ty.parse_as_json(ctx.rest_arg()?) {
             Ok(value) => value,
             Err(e) => {
                 ctx.respond(format!("Value is not a valid {} type: {}", ty, e));
@@ -297,13 +454,7 @@ impl<'a> Handler<'a> {
 
 /// Extract a settings key from the context.
 fn key(ctx: &mut command::Context<'_>) -> Option<String> {
-    let key = match ctx.next() {
-        Some(key) => key,
-        None => {
-            ctx.respond("Expected <key>");
-            return None;
-        }
-    };
+    let key: String = ctx.arg()?;
 
     if key.starts_with("secrets/") {
         ctx.respond("Cannot access secrets through chat!");
@@ -338,6 +489,8 @@ impl super::Module for Module {
                 commands: injector.var()?,
                 promotions: injector.var()?,
                 themes: injector.var()?,
+                command_stats: injector.var()?,
+                audit_log: injector.var()?,
             },
         );
 
@@ -51,8 +51,29 @@ impl command::Handler for Handler {
 
                 ctx.respond("Edited pattern for alias.");
             }
+            Some("cooldown") => {
+                command_restriction!(ctx, aliases, "alias", AliasEdit, cooldown, "cooldown");
+            }
+            Some("user-cooldown") => {
+                command_restriction!(
+                    ctx,
+                    aliases,
+                    "alias",
+                    AliasEdit,
+                    user_cooldown,
+                    "user cooldown"
+                );
+            }
+            Some("scope") => {
+                command_restriction!(ctx, aliases, "alias", AliasEdit, scope, "scope");
+            }
+            Some("price") => {
+                command_restriction!(ctx, aliases, "alias", AliasEdit, price, "price");
+            }
             None | Some(..) => {
-                ctx.respond("Expected: show, list, edit, delete, enable, disable, or group.");
+                ctx.respond(
+                    "Expected: show, list, edit, delete, enable, disable, group, cooldown, user-cooldown, scope, or price.",
+                );
             }
         }
 
@@ -68,6 +89,10 @@ impl super::Module for Module {
         "alias"
     }
 
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
     async fn hook(
         &self,
         module::HookContext {
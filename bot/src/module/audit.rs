@@ -0,0 +1,123 @@
+use crate::{
+    auth,
+    command::{self, Param, Spec, Sub},
+    db, module,
+    prelude::*,
+    utils,
+};
+use chrono::Utc;
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// Default number of entries shown by `!audit last`.
+const DEFAULT_LAST: u32 = 5;
+/// Maximum number of entries shown by `!audit last`.
+const MAX_LAST: u32 = 10;
+
+static SPEC: Spec = Spec {
+    subs: &[Sub::new("last", &[Param::optional("n")])],
+};
+
+/// Handler for the `!audit` command.
+pub struct Audit {
+    audit_log: Arc<RwLock<Option<db::AuditLog>>>,
+}
+
+#[async_trait]
+impl command::Handler for Audit {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Audit)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), failure::Error> {
+        let audit_log = match self.audit_log.read().clone() {
+            Some(audit_log) => audit_log,
+            None => return Ok(()),
+        };
+
+        match ctx_try!(ctx.subcommand(&SPEC)?) {
+            "last" => {
+                let n = ctx_try!(ctx.optional_arg())
+                    .unwrap_or(DEFAULT_LAST)
+                    .min(MAX_LAST);
+
+                let page =
+                    audit_log.list(ctx.channel(), &db::AuditFilter::default(), 0, i64::from(n))?;
+
+                if page.entries.is_empty() {
+                    ctx.respond("The audit log is empty.");
+                    return Ok(());
+                }
+
+                let now = Utc::now().naive_utc();
+
+                let entries = page
+                    .entries
+                    .iter()
+                    .map(|e| describe(e, now))
+                    .collect::<Vec<_>>();
+
+                ctx.respond(format!("{}.", entries.join("; ")));
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// Describe a single entry, like `5m ago: setbac timeout foo (spam)`.
+fn describe(entry: &db::AuditEntry, now: chrono::NaiveDateTime) -> String {
+    let ago = now
+        .signed_duration_since(entry.logged_at)
+        .to_std()
+        .unwrap_or_default();
+
+    let mut out = format!(
+        "{} ago: {} {}",
+        utils::compact_duration(&ago),
+        entry.actor.as_ref().map(String::as_str).unwrap_or("bot"),
+        entry.action,
+    );
+
+    if let Some(target) = entry.target.as_ref() {
+        out.push(' ');
+        out.push_str(target);
+    }
+
+    if let Some(reason) = entry.reason.as_ref() {
+        out.push_str(&format!(" ({})", reason));
+    }
+
+    out
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "audit"
+    }
+
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector, handlers, ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), failure::Error> {
+        handlers.insert(
+            "audit",
+            Audit {
+                audit_log: injector.var()?,
+            },
+        );
+
+        Ok(())
+    }
+}
@@ -1,10 +1,13 @@
-use crate::{auth, command, module, prelude::*, utils::Duration};
+use crate::{auth, command, db, module, prelude::*, utils::Duration};
 use chrono::Utc;
 use failure::Error;
+use parking_lot::RwLock;
+use std::sync::Arc;
 
 /// Handler for the !auth command.
 pub struct Handler<'a> {
     auth: &'a auth::Auth,
+    audit_log: Arc<RwLock<Option<db::AuditLog>>>,
 }
 
 #[async_trait]
@@ -87,6 +90,20 @@ impl<'a> command::Handler for Handler<'a> {
                 let now = Utc::now();
                 let expires_at = now + duration.as_chrono();
 
+                if let Some(audit_log) = self.audit_log.read().as_ref() {
+                    let result = audit_log.record(
+                        ctx.channel(),
+                        ctx.user.name(),
+                        db::AuditAction::Permit,
+                        Some(&principal.to_string()),
+                        Some(&format!("{} for {}", scope, duration)),
+                    );
+
+                    if let Err(e) = result {
+                        log_err!(e, "failed to record permit in the audit log");
+                    }
+                }
+
                 ctx.respond(format!(
                     "Gave: {scope} to {principal} for {duration}",
                     duration = duration,
@@ -121,9 +138,20 @@ impl super::Module for Module {
 
     async fn hook(
         &self,
-        module::HookContext { handlers, auth, .. }: module::HookContext<'_, '_>,
+        module::HookContext {
+            injector,
+            handlers,
+            auth,
+            ..
+        }: module::HookContext<'_, '_>,
     ) -> Result<(), Error> {
-        handlers.insert("auth", Handler { auth });
+        handlers.insert(
+            "auth",
+            Handler {
+                auth,
+                audit_log: injector.var()?,
+            },
+        );
         Ok(())
     }
 }
@@ -0,0 +1,259 @@
+use crate::{
+    auth,
+    command::{self, Param, Spec, Sub},
+    db, module,
+    prelude::*,
+    utils,
+};
+use failure::{Error, ResultExt as _};
+use hashbrown::HashSet;
+use parking_lot::RwLock;
+use std::{
+    fs,
+    sync::Arc,
+    time::{self, Instant},
+};
+use tokio::timer::Interval;
+
+/// Shortest interval at which subscribed ban lists are read.
+const MIN_REFRESH_INTERVAL: time::Duration = time::Duration::from_secs(60);
+
+static SPEC: Spec = Spec {
+    subs: &[
+        Sub::new(
+            "ban",
+            &[Param::required("user"), Param::optional_rest("reason")],
+        ),
+        Sub::new(
+            "timeout",
+            &[
+                Param::required("user"),
+                Param::required("duration"),
+                Param::optional_rest("reason"),
+            ],
+        ),
+        Sub::new("remove", &[Param::required("user")]),
+        Sub::new("import", &[Param::required("url")]),
+    ],
+};
+
+/// Handler for the `!banlist` command.
+pub struct Handler {
+    ban_list: Arc<RwLock<Option<db::BanList>>>,
+    client: reqwest::Client,
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::BanList)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), Error> {
+        let ban_list = match self.ban_list.read().clone() {
+            Some(ban_list) => ban_list,
+            None => return Ok(()),
+        };
+
+        match ctx_try!(ctx.subcommand(&SPEC)?) {
+            "ban" => {
+                let user: command::User = ctx_try!(ctx.arg());
+                let reason = reason(ctx.rest());
+
+                ban_list.insert(&[db::BanEntry {
+                    user: user.as_ref().to_string(),
+                    action: db::BanAction::Ban,
+                    reason,
+                }])?;
+
+                ctx.respond(format!("Added {} to the ban list.", user));
+            }
+            "timeout" => {
+                let user: command::User = ctx_try!(ctx.arg());
+                let duration: utils::Duration = ctx_try!(ctx.arg());
+                let reason = reason(ctx.rest());
+
+                ban_list.insert(&[db::BanEntry {
+                    user: user.as_ref().to_string(),
+                    action: db::BanAction::Timeout(duration.clone()),
+                    reason,
+                }])?;
+
+                ctx.respond(format!(
+                    "Added {} to the ban list, they will be timed out for {}.",
+                    user, duration
+                ));
+            }
+            "remove" => {
+                let user: command::User = ctx_try!(ctx.arg());
+
+                if ban_list.delete(user.as_ref())? {
+                    ctx.respond(format!("Removed {} from the ban list.", user));
+                } else {
+                    ctx.respond(format!("{} is not on the ban list.", user));
+                }
+            }
+            "import" => {
+                let source: String = ctx_try!(ctx.arg());
+
+                // NB: files can only be imported through the web API, or
+                // subscribed to in the settings.
+                if !is_url(&source) {
+                    ctx.respond("Ban lists can only be imported from http or https URLs.");
+                    return Ok(());
+                }
+
+                let entries = match fetch(&self.client, &source).await {
+                    Ok(entries) => entries,
+                    Err(e) => {
+                        ctx.respond(format!("Failed to import ban list: {}", e));
+                        log_err!(e, "failed to import ban list: {}", source);
+                        return Ok(());
+                    }
+                };
+
+                ban_list.insert(&entries)?;
+                ctx.respond(format!("Imported {} users to the ban list.", entries.len()));
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+/// Treat an empty reason as no reason.
+fn reason(rest: &str) -> Option<String> {
+    match rest.trim() {
+        "" => None,
+        reason => Some(reason.to_string()),
+    }
+}
+
+/// Test if the given source is an http or https URL.
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Read a ban list from the given file or URL.
+///
+/// The format is guessed from the extension of the source.
+async fn fetch(client: &reqwest::Client, source: &str) -> Result<Vec<db::BanEntry>, Error> {
+    let data = if is_url(source) {
+        let res = client.get(source).send().await?.error_for_status()?;
+        res.text().await?
+    } else {
+        fs::read_to_string(source).with_context(|_| format!("failed to read: {}", source))?
+    };
+
+    db::BanListFormat::from_path(source).parse(&data)
+}
+
+/// Synchronize the ban list with all subscribed sources.
+///
+/// Entries from sources which are no longer subscribed to are removed.
+async fn sync(
+    client: &reqwest::Client,
+    ban_list: &db::BanList,
+    sources: &HashSet<String>,
+) -> Result<(), Error> {
+    let sources = sources.iter().cloned().collect::<Vec<_>>();
+    ban_list.retain_sources(&sources)?;
+
+    for source in &sources {
+        match fetch(client, source).await {
+            Ok(entries) => {
+                log::info!(
+                    "Synchronized {} users from ban list: {}",
+                    entries.len(),
+                    source
+                );
+                ban_list.sync(source, &entries)?;
+            }
+            // NB: keep the entries from the last successful sync.
+            Err(e) => log_err!(e, "failed to synchronize ban list: {}", source),
+        }
+    }
+
+    Ok(())
+}
+
+/// Construct an interval which ticks right away, and then every refresh.
+fn new_interval(refresh_interval: &utils::Duration) -> Interval {
+    let period = refresh_interval.as_std().max(MIN_REFRESH_INTERVAL);
+    Interval::new(Instant::now(), period)
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "ban-list"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector,
+            handlers,
+            futures,
+            settings,
+            ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), Error> {
+        let settings = settings.scoped("chat/ban-list");
+        let client = reqwest::Client::new();
+
+        handlers.insert(
+            "banlist",
+            Handler {
+                ban_list: injector.var()?,
+                client: client.clone(),
+            },
+        );
+
+        let (mut sources_stream, mut sources) =
+            settings.stream::<HashSet<String>>("sources").or_default()?;
+
+        let (mut refresh_interval_stream, mut refresh_interval) = settings
+            .stream("refresh-interval")
+            .or_with(utils::Duration::hours(1))?;
+
+        let (mut ban_list_stream, mut ban_list) = injector.stream::<db::BanList>();
+        let mut interval = new_interval(&refresh_interval);
+
+        let future = async move {
+            loop {
+                futures::select! {
+                    update = ban_list_stream.select_next_some() => {
+                        ban_list = update;
+                    }
+                    update = sources_stream.select_next_some() => {
+                        sources = update;
+                        // NB: synchronize new subscriptions right away.
+                        interval = new_interval(&refresh_interval);
+                    }
+                    update = refresh_interval_stream.select_next_some() => {
+                        refresh_interval = update;
+                        interval = new_interval(&refresh_interval);
+                    }
+                    _ = interval.select_next_some() => {
+                        let ban_list = match ban_list.as_ref() {
+                            Some(ban_list) => ban_list,
+                            None => continue,
+                        };
+
+                        if let Err(e) = sync(&client, ban_list, &sources).await {
+                            log_err!(e, "failed to synchronize ban list");
+                        }
+                    }
+                }
+            }
+        };
+
+        futures.push(future.boxed());
+        Ok(())
+    }
+}
@@ -58,8 +58,29 @@ impl command::Handler for Handler {
 
                 ctx.respond("Edited pattern for command.");
             }
+            Some("cooldown") => {
+                command_restriction!(ctx, commands, "command", CommandEdit, cooldown, "cooldown");
+            }
+            Some("user-cooldown") => {
+                command_restriction!(
+                    ctx,
+                    commands,
+                    "command",
+                    CommandEdit,
+                    user_cooldown,
+                    "user cooldown"
+                );
+            }
+            Some("scope") => {
+                command_restriction!(ctx, commands, "command", CommandEdit, scope, "scope");
+            }
+            Some("price") => {
+                command_restriction!(ctx, commands, "command", CommandEdit, price, "price");
+            }
             None | Some(..) => {
-                ctx.respond("Expected: show, list, edit, delete, enable, disable, or group.");
+                ctx.respond(
+                    "Expected: show, list, edit, delete, enable, disable, group, cooldown, user-cooldown, scope, or price.",
+                );
             }
         }
 
@@ -75,6 +96,10 @@ impl super::Module for Module {
         "command"
     }
 
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
     async fn hook(
         &self,
         module::HookContext {
@@ -0,0 +1,180 @@
+use crate::{auth, bus, command, db, module, prelude::*};
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// Handler for the `!counter` command.
+pub struct Counter {
+    enabled: Arc<RwLock<bool>>,
+    counters: Arc<RwLock<Option<db::Counters>>>,
+    global_bus: Arc<bus::Bus<bus::Global>>,
+}
+
+impl Counter {
+    /// Broadcast the current state of a counter.
+    fn broadcast(&self, channel: &str, name: &str, count: Option<i64>) {
+        self.global_bus.send(bus::Global::Counter {
+            channel: channel.to_string(),
+            name: name.to_string(),
+            count,
+        });
+    }
+}
+
+#[async_trait]
+impl command::Handler for Counter {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Counter)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), failure::Error> {
+        if !*self.enabled.read() {
+            return Ok(());
+        }
+
+        let counters = match self.counters.read().clone() {
+            Some(counters) => counters,
+            None => return Ok(()),
+        };
+
+        match ctx.next().as_ref().map(String::as_str) {
+            Some("list") => {
+                let names = counters
+                    .list(ctx.channel())
+                    .into_iter()
+                    .map(|c| format!("{} = {}", c.key.name, c.count))
+                    .collect::<Vec<_>>();
+
+                if names.is_empty() {
+                    ctx.respond("There are no counters.");
+                } else {
+                    ctx.respond(format!("{}.", names.join(", ")));
+                }
+            }
+            Some("create") => {
+                ctx.check_scope(auth::Scope::CounterEdit)?;
+
+                let name = ctx_try!(ctx.next_str("<name>"));
+
+                if !is_valid_name(&name) {
+                    ctx.respond(
+                        "Counter names may only contain letters, numbers, and underscores.",
+                    );
+                    return Ok(());
+                }
+
+                if !counters.create(ctx.channel(), &name)? {
+                    ctx.respond(format!("Counter `{}` already exists.", name));
+                    return Ok(());
+                }
+
+                self.broadcast(ctx.channel(), &name, Some(0));
+                ctx.respond(format!("Created counter `{}`.", name));
+            }
+            Some(command @ "increment") | Some(command @ "decrement") => {
+                ctx.check_scope(auth::Scope::CounterEdit)?;
+
+                let name = ctx_try!(ctx.next_str("<name> [amount]"));
+                let amount = ctx_try!(ctx.next_parse_optional::<i64>()).unwrap_or(1);
+
+                let amount = match command {
+                    "decrement" => amount.saturating_neg(),
+                    _ => amount,
+                };
+
+                let count = match counters.add(ctx.channel(), &name, amount)? {
+                    Some(count) => count,
+                    None => {
+                        ctx.respond(format!("No counter named `{}`.", name));
+                        return Ok(());
+                    }
+                };
+
+                self.broadcast(ctx.channel(), &name, Some(count));
+                ctx.respond(format!("{} = {}", name, count));
+            }
+            Some(command @ "set") | Some(command @ "reset") => {
+                ctx.check_scope(auth::Scope::CounterEdit)?;
+
+                let name = ctx_try!(ctx.next_str("<name>"));
+
+                let count = match command {
+                    "set" => ctx_try!(ctx.next_parse::<i64, _>("<name> <count>")),
+                    _ => 0,
+                };
+
+                if !counters.set(ctx.channel(), &name, count)? {
+                    ctx.respond(format!("No counter named `{}`.", name));
+                    return Ok(());
+                }
+
+                self.broadcast(ctx.channel(), &name, Some(count));
+                ctx.respond(format!("{} = {}", name, count));
+            }
+            Some("delete") => {
+                ctx.check_scope(auth::Scope::CounterEdit)?;
+
+                let name = ctx_try!(ctx.next_str("<name>"));
+
+                if !counters.delete(ctx.channel(), &name)? {
+                    ctx.respond(format!("No counter named `{}`.", name));
+                    return Ok(());
+                }
+
+                self.broadcast(ctx.channel(), &name, None);
+                ctx.respond(format!("Deleted counter `{}`.", name));
+            }
+            Some(name) => match counters.get(ctx.channel(), name) {
+                Some(count) => ctx.respond(format!("{} = {}", name, count)),
+                None => ctx.respond(format!("No counter named `{}`.", name)),
+            },
+            None => {
+                ctx.respond(
+                    "Expected: <name>, list, create, increment, decrement, set, reset, or delete.",
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Test if the counter name can be used as a template variable.
+fn is_valid_name(name: &str) -> bool {
+    name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "counter"
+    }
+
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector,
+            handlers,
+            settings,
+            global_bus,
+            ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), failure::Error> {
+        handlers.insert(
+            "counter",
+            Counter {
+                enabled: settings.var("counter/enabled", false)?,
+                counters: injector.var()?,
+                global_bus: global_bus.clone(),
+            },
+        );
+
+        Ok(())
+    }
+}
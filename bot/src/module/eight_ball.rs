@@ -69,6 +69,10 @@ impl super::Module for Module {
         "8ball"
     }
 
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
     /// Set up command handlers for this module.
     async fn hook(
         &self,
@@ -0,0 +1,1178 @@
+//! Chance games to spend currency on: `!gamble`, `!duel` and `!heist`.
+//!
+//! The streamer acts as the house. Lost wagers are paid to the streamer and
+//! winnings are paid by the streamer, so currency only ever moves between
+//! users.
+
+use crate::{
+    auth::Scope,
+    command,
+    currency::{BalanceTransferError, Currency, LedgerReason},
+    db, module,
+    prelude::*,
+    settings,
+    utils::{self, Cooldown, Duration},
+};
+use failure::{bail, format_err, Error};
+use hashbrown::HashMap;
+use parking_lot::{Mutex, RwLock};
+use rand::SeedableRng as _;
+use std::{str::FromStr, sync::Arc, time};
+
+/// Usage of a wager argument.
+const WAGER_USAGE: &str = "<amount|all|percentage%>";
+
+/// Random number generator shared by all games.
+///
+/// Games only roll through the functions in this module, which take any
+/// `rand::Rng`. A generator can be provided through the injector, otherwise
+/// one seeded from entropy is used.
+pub type Rng = Arc<Mutex<Box<dyn rand::RngCore + Send>>>;
+
+/// An amount to wager, like `100`, `all` or `50%`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wager {
+    /// A fixed amount.
+    Amount(i64),
+    /// The whole balance.
+    All,
+    /// A percentage of the balance, between 1 and 100.
+    Percent(u32),
+}
+
+impl Wager {
+    /// Resolve the wager into an amount given the current balance.
+    pub fn amount(self, balance: i64) -> i64 {
+        match self {
+            Wager::Amount(amount) => amount,
+            Wager::All => balance.max(0),
+            // NB: the percentage is at most 100, so the amount fits.
+            Wager::Percent(percent) => {
+                (i128::from(balance.max(0)) * i128::from(percent) / 100) as i64
+            }
+        }
+    }
+}
+
+impl FromStr for Wager {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("all") {
+            return Ok(Wager::All);
+        }
+
+        if s.ends_with('%') {
+            let percent = str::parse::<u32>(&s[..s.len() - 1])
+                .map_err(|_| format_err!("expected a percentage, like `50%`"))?;
+
+            if percent == 0 || percent > 100 {
+                bail!("percentage must be between 1% and 100%");
+            }
+
+            return Ok(Wager::Percent(percent));
+        }
+
+        let amount = str::parse::<i64>(s)
+            .map_err(|_| format_err!("expected an amount, `all` or a percentage"))?;
+        Ok(Wager::Amount(amount))
+    }
+}
+
+/// Limits on how much can be wagered.
+struct Limits {
+    min: Arc<RwLock<i64>>,
+    /// Maximum amount, `0` meaning no limit.
+    max: Arc<RwLock<i64>>,
+}
+
+impl Limits {
+    /// Load limits from the given game settings.
+    fn new(settings: &settings::Settings) -> Result<Self, Error> {
+        Ok(Limits {
+            min: settings.var("min-amount", 1)?,
+            max: settings.var("max-amount", 0)?,
+        })
+    }
+
+    /// Check that the amount can be wagered with the given balance, returning
+    /// the reason why not otherwise.
+    fn check(&self, amount: i64, balance: i64, currency: &str) -> Result<(), String> {
+        check_amount(
+            amount,
+            balance,
+            *self.min.read(),
+            *self.max.read(),
+            currency,
+        )
+    }
+}
+
+/// Cooldowns which are tracked separately for each user.
+struct UserCooldowns {
+    cooldown: Arc<RwLock<Duration>>,
+    last: HashMap<String, time::Instant>,
+}
+
+impl UserCooldowns {
+    fn new(cooldown: Arc<RwLock<Duration>>) -> Self {
+        UserCooldowns {
+            cooldown,
+            last: HashMap::new(),
+        }
+    }
+
+    /// Test how much time remains until the given user may play again.
+    fn check(&mut self, user: &str, now: time::Instant) -> Option<time::Duration> {
+        let cooldown = self.cooldown.read().as_std();
+        self.last
+            .retain(|_, last| now.duration_since(*last) < cooldown);
+
+        let last = self.last.get(user)?;
+        Some(cooldown - now.duration_since(*last))
+    }
+
+    /// Start the cooldown for the given user.
+    fn poke(&mut self, user: &str, now: time::Instant) {
+        self.last.insert(user.to_string(), now);
+    }
+}
+
+/// Handler for the `!gamble` command.
+pub struct Gamble {
+    enabled: Arc<RwLock<bool>>,
+    chance: Arc<RwLock<u32>>,
+    payout: Arc<RwLock<u32>>,
+    limits: Limits,
+    cooldowns: UserCooldowns,
+    currency: Arc<RwLock<Option<Currency>>>,
+    rng: Rng,
+}
+
+#[async_trait]
+impl command::Handler for Gamble {
+    fn scope(&self) -> Option<Scope> {
+        Some(Scope::Gamble)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), Error> {
+        if !*self.enabled.read() {
+            return Ok(());
+        }
+
+        let currency = match self.currency.read().as_ref() {
+            Some(currency) => currency.clone(),
+            None => {
+                ctx.respond("No currency configured for stream, sorry :(");
+                return Ok(());
+            }
+        };
+
+        let user = match ctx.user.name() {
+            Some(name) => name.to_string(),
+            None => {
+                ctx.respond("Only real users can gamble");
+                return Ok(());
+            }
+        };
+
+        let streamer = ctx.user.streamer().name.clone();
+
+        if ctx.user.is(&streamer) {
+            ctx.respond("The house doesn't gamble!");
+            return Ok(());
+        }
+
+        let wager: Wager = ctx_try!(ctx.next_parse(WAGER_USAGE));
+        let now = time::Instant::now();
+
+        if let Some(remaining) = self.cooldowns.check(&user, now) {
+            ctx.respond(format!(
+                "You can gamble again in {}.",
+                utils::compact_duration(&remaining)
+            ));
+            return Ok(());
+        }
+
+        let balance = balance_of(&currency, ctx.channel(), &user).await?;
+        let amount = wager.amount(balance);
+
+        if let Err(reason) = self.limits.check(amount, balance, &currency.name) {
+            ctx.respond(reason);
+            return Ok(());
+        }
+
+        self.cooldowns.poke(&user, now);
+
+        let won = roll(&mut *self.rng.lock(), *self.chance.read());
+        let winnings = profit(amount, *self.payout.read());
+
+        let result = if won {
+            currency
+                .balance_transfer(
+                    ctx.channel(),
+                    &streamer,
+                    &user,
+                    winnings,
+                    true,
+                    LedgerReason::Gamble,
+                )
+                .await
+        } else {
+            currency
+                .balance_transfer(
+                    ctx.channel(),
+                    &user,
+                    &streamer,
+                    amount,
+                    false,
+                    LedgerReason::Gamble,
+                )
+                .await
+        };
+
+        match result {
+            Ok(()) if won => {
+                ctx.respond(format!(
+                    "You gambled {amount} and won {winnings} {currency} PogChamp",
+                    amount = amount,
+                    winnings = winnings,
+                    currency = currency.name,
+                ));
+            }
+            Ok(()) => {
+                ctx.respond(format!(
+                    "You gambled {amount} {currency} and lost it all LUL",
+                    amount = amount,
+                    currency = currency.name,
+                ));
+            }
+            Err(BalanceTransferError::NoBalance) => {
+                ctx.respond(format!(
+                    "Not enough {currency} to gamble {amount}",
+                    currency = currency.name,
+                    amount = amount,
+                ));
+            }
+            Err(BalanceTransferError::Other(e)) => {
+                ctx.respond("Failed to gamble, sorry :(");
+                log_err!(e, "failed to settle gamble");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A duel waiting to be accepted.
+struct Challenge {
+    challenger: String,
+    amount: i64,
+    expires_at: time::Instant,
+}
+
+/// Handler for the `!duel` command.
+pub struct Duel {
+    enabled: Arc<RwLock<bool>>,
+    chance: Arc<RwLock<u32>>,
+    timeout: Arc<RwLock<Duration>>,
+    limits: Limits,
+    cooldowns: UserCooldowns,
+    currency: Arc<RwLock<Option<Currency>>>,
+    rng: Rng,
+    /// Pending challenges, by the user who was challenged.
+    challenges: HashMap<String, Challenge>,
+}
+
+#[async_trait]
+impl command::Handler for Duel {
+    fn scope(&self) -> Option<Scope> {
+        Some(Scope::Duel)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), Error> {
+        if !*self.enabled.read() {
+            return Ok(());
+        }
+
+        let currency = match self.currency.read().as_ref() {
+            Some(currency) => currency.clone(),
+            None => {
+                ctx.respond("No currency configured for stream, sorry :(");
+                return Ok(());
+            }
+        };
+
+        let user = match ctx.user.name() {
+            Some(name) => name.to_string(),
+            None => {
+                ctx.respond("Only real users can duel");
+                return Ok(());
+            }
+        };
+
+        let now = time::Instant::now();
+        self.challenges.retain(|_, c| c.expires_at > now);
+
+        let first = match ctx.next() {
+            Some(first) => first,
+            None => {
+                ctx.respond(format!(
+                    "Expected: <user> {}, accept, or decline.",
+                    WAGER_USAGE
+                ));
+                return Ok(());
+            }
+        };
+
+        match first.as_str() {
+            "accept" => {
+                let challenge = match self.challenges.remove(&user) {
+                    Some(challenge) => challenge,
+                    None => {
+                        ctx.respond("Nobody has challenged you to a duel.");
+                        return Ok(());
+                    }
+                };
+
+                let balance = balance_of(&currency, ctx.channel(), &user).await?;
+
+                if balance < challenge.amount {
+                    ctx.respond(format!(
+                        "You need {amount} {currency} to accept the duel from {challenger}.",
+                        amount = challenge.amount,
+                        currency = currency.name,
+                        challenger = challenge.challenger,
+                    ));
+                    return Ok(());
+                }
+
+                let challenger_won = roll(&mut *self.rng.lock(), *self.chance.read());
+
+                let (winner, loser) = if challenger_won {
+                    (challenge.challenger.as_str(), user.as_str())
+                } else {
+                    (user.as_str(), challenge.challenger.as_str())
+                };
+
+                let result = currency
+                    .balance_transfer(
+                        ctx.channel(),
+                        loser,
+                        winner,
+                        challenge.amount,
+                        false,
+                        LedgerReason::Duel,
+                    )
+                    .await;
+
+                match result {
+                    Ok(()) => {
+                        ctx.privmsg(format!(
+                            "{winner} won the duel against {loser} and takes {amount} {currency}!",
+                            winner = winner,
+                            loser = loser,
+                            amount = challenge.amount,
+                            currency = currency.name,
+                        ));
+                    }
+                    Err(BalanceTransferError::NoBalance) => {
+                        ctx.privmsg(format!(
+                            "{winner} won the duel against {loser}, but {loser} can't pay up. The duel is off!",
+                            winner = winner,
+                            loser = loser,
+                        ));
+                    }
+                    Err(BalanceTransferError::Other(e)) => {
+                        ctx.respond("Failed to settle the duel, sorry :(");
+                        log_err!(e, "failed to settle duel");
+                    }
+                }
+            }
+            "decline" => match self.challenges.remove(&user) {
+                Some(challenge) => {
+                    ctx.privmsg(format!(
+                        "{user} declined the duel from {challenger}.",
+                        user = user,
+                        challenger = challenge.challenger,
+                    ));
+                }
+                None => {
+                    ctx.respond("Nobody has challenged you to a duel.");
+                }
+            },
+            target => {
+                let target = db::user_id(target);
+                let wager: Wager = ctx_try!(ctx.next_parse(WAGER_USAGE));
+
+                if target.is_empty() {
+                    ctx.respond(format!("Expected: <user> {}", WAGER_USAGE));
+                    return Ok(());
+                }
+
+                if ctx.user.is(&target) {
+                    ctx.respond("Dueling... yourself? But WHY?");
+                    return Ok(());
+                }
+
+                if let Some(remaining) = self.cooldowns.check(&user, now) {
+                    ctx.respond(format!(
+                        "You can duel again in {}.",
+                        utils::compact_duration(&remaining)
+                    ));
+                    return Ok(());
+                }
+
+                if self.challenges.contains_key(&target) {
+                    ctx.respond(format!("{} already has a pending duel.", target));
+                    return Ok(());
+                }
+
+                let balance = balance_of(&currency, ctx.channel(), &user).await?;
+                let amount = wager.amount(balance);
+
+                if let Err(reason) = self.limits.check(amount, balance, &currency.name) {
+                    ctx.respond(reason);
+                    return Ok(());
+                }
+
+                self.cooldowns.poke(&user, now);
+
+                let timeout = self.timeout.read().clone();
+
+                self.challenges.insert(
+                    target.clone(),
+                    Challenge {
+                        challenger: user.clone(),
+                        amount,
+                        expires_at: now + timeout.as_std(),
+                    },
+                );
+
+                ctx.privmsg(format!(
+                    "{target}, {user} challenges you to a duel for {amount} {currency}! \
+                     Type !duel accept or !duel decline within {timeout}.",
+                    target = target,
+                    user = user,
+                    amount = amount,
+                    currency = currency.name,
+                    timeout = timeout,
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Handler for the `!heist` command.
+pub struct Heist {
+    enabled: Arc<RwLock<bool>>,
+    chance: Arc<RwLock<u32>>,
+    payout: Arc<RwLock<u32>>,
+    window: Arc<RwLock<Duration>>,
+    limits: Limits,
+    cooldown: Arc<RwLock<Cooldown>>,
+    currency: Arc<RwLock<Option<Currency>>>,
+    rng: Rng,
+    /// Participants and their stakes in the heist being planned, if any.
+    active: Arc<Mutex<Option<Vec<(String, i64)>>>>,
+}
+
+#[async_trait]
+impl command::Handler for Heist {
+    fn scope(&self) -> Option<Scope> {
+        Some(Scope::Heist)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), Error> {
+        if !*self.enabled.read() {
+            return Ok(());
+        }
+
+        let currency = match self.currency.read().as_ref() {
+            Some(currency) => currency.clone(),
+            None => {
+                ctx.respond("No currency configured for stream, sorry :(");
+                return Ok(());
+            }
+        };
+
+        let user = match ctx.user.name() {
+            Some(name) => name.to_string(),
+            None => {
+                ctx.respond("Only real users can join a heist");
+                return Ok(());
+            }
+        };
+
+        let streamer = ctx.user.streamer().name.clone();
+
+        if ctx.user.is(&streamer) {
+            ctx.respond("The house doesn't join heists!");
+            return Ok(());
+        }
+
+        let wager: Wager = ctx_try!(ctx.next_parse(WAGER_USAGE));
+
+        match self.active.lock().as_ref() {
+            Some(participants) => {
+                if participants.iter().any(|(p, _)| *p == user) {
+                    ctx.respond("You are already in on the heist.");
+                    return Ok(());
+                }
+            }
+            None => {
+                if let Some(remaining) = self.cooldown.write().check(time::Instant::now()) {
+                    ctx.respond(format!(
+                        "The crew is laying low, the next heist can start in {}.",
+                        utils::compact_duration(&remaining)
+                    ));
+                    return Ok(());
+                }
+            }
+        }
+
+        let balance = balance_of(&currency, ctx.channel(), &user).await?;
+        let amount = wager.amount(balance);
+
+        if let Err(reason) = self.limits.check(amount, balance, &currency.name) {
+            ctx.respond(reason);
+            return Ok(());
+        }
+
+        // NB: the stake is paid up front, and paid back with the winnings if
+        // the heist succeeds.
+        let result = currency
+            .balance_transfer(
+                ctx.channel(),
+                &user,
+                &streamer,
+                amount,
+                false,
+                LedgerReason::Heist,
+            )
+            .await;
+
+        match result {
+            Ok(()) => (),
+            Err(BalanceTransferError::NoBalance) => {
+                ctx.respond(format!(
+                    "Not enough {currency} to join the heist with {amount}",
+                    currency = currency.name,
+                    amount = amount,
+                ));
+                return Ok(());
+            }
+            Err(BalanceTransferError::Other(e)) => {
+                ctx.respond("Failed to join the heist, sorry :(");
+                log_err!(e, "failed to pay heist stake");
+                return Ok(());
+            }
+        }
+
+        let started = {
+            let mut active = self.active.lock();
+
+            match active.as_mut() {
+                Some(participants) => {
+                    participants.push((user.clone(), amount));
+                    false
+                }
+                None => {
+                    *active = Some(vec![(user.clone(), amount)]);
+                    true
+                }
+            }
+        };
+
+        if !started {
+            ctx.respond(format!(
+                "You joined the heist with {amount} {currency}.",
+                amount = amount,
+                currency = currency.name,
+            ));
+            return Ok(());
+        }
+
+        let window = self.window.read().clone();
+
+        ctx.privmsg(format!(
+            "{user} is planning a heist! Type !heist {usage} within {window} to join the crew.",
+            user = user,
+            usage = WAGER_USAGE,
+            window = window,
+        ));
+
+        let chance = *self.chance.read();
+        let payout = *self.payout.read();
+        let active = self.active.clone();
+        let cooldown = self.cooldown.clone();
+        let rng = self.rng.clone();
+        let sender = ctx.user.sender().clone();
+
+        let future = async move {
+            tokio::timer::delay(time::Instant::now() + window.as_std()).await;
+
+            let participants = active.lock().take().unwrap_or_default();
+            cooldown.write().poke(time::Instant::now());
+
+            let outcomes = resolve_heist(&mut *rng.lock(), participants, chance, payout);
+
+            let mut escaped = Vec::new();
+            let mut caught = Vec::new();
+
+            for outcome in outcomes {
+                let winnings = match outcome.winnings {
+                    Some(winnings) => winnings,
+                    None => {
+                        caught.push(outcome.user);
+                        continue;
+                    }
+                };
+
+                let result = currency
+                    .balance_transfer(
+                        sender.channel(),
+                        &streamer,
+                        &outcome.user,
+                        winnings,
+                        true,
+                        LedgerReason::Heist,
+                    )
+                    .await;
+
+                if let Err(e) = result {
+                    log_err!(e, "failed to pay heist winnings to {}", outcome.user);
+                    continue;
+                }
+
+                escaped.push(format!("{} (+{})", outcome.user, winnings - outcome.stake));
+            }
+
+            let message = match (escaped.is_empty(), caught.is_empty()) {
+                (true, _) => String::from("The heist failed and the whole crew got caught LUL"),
+                (false, true) => format!(
+                    "The heist was a success! The whole crew made it out: {}.",
+                    escaped.join(", ")
+                ),
+                (false, false) => format!(
+                    "The heist is over! Made it out: {}. Got caught: {}.",
+                    escaped.join(", "),
+                    caught.join(", ")
+                ),
+            };
+
+            sender.privmsg(message);
+            Ok(())
+        };
+
+        ctx.spawn_result("heist", future);
+        Ok(())
+    }
+}
+
+/// The outcome of a heist for a single participant.
+#[derive(Debug, PartialEq, Eq)]
+struct Outcome {
+    user: String,
+    stake: i64,
+    /// What the participant is paid back if they made it out, including their
+    /// stake.
+    winnings: Option<i64>,
+}
+
+/// Roll the outcome of a heist for every participant, each of which makes it
+/// out with the given chance in percent.
+fn resolve_heist(
+    rng: &mut impl rand::Rng,
+    participants: Vec<(String, i64)>,
+    chance: u32,
+    payout: u32,
+) -> Vec<Outcome> {
+    participants
+        .into_iter()
+        .map(|(user, stake)| {
+            let winnings = if roll(rng, chance) {
+                Some(stake.saturating_add(profit(stake, payout)))
+            } else {
+                None
+            };
+
+            Outcome {
+                user,
+                stake,
+                winnings,
+            }
+        })
+        .collect()
+}
+
+/// Roll for something which happens with the given chance in percent.
+fn roll(rng: &mut impl rand::Rng, chance: u32) -> bool {
+    rng.gen_ratio(chance.min(100), 100)
+}
+
+/// The profit made on a winning wager, with the payout in percent of the
+/// amount.
+///
+/// Profits which don't fit in a balance are capped.
+fn profit(amount: i64, payout: u32) -> i64 {
+    let profit = i128::from(amount) * i128::from(payout) / 100;
+    profit
+        .max(i128::from(i64::min_value()))
+        .min(i128::from(i64::max_value())) as i64
+}
+
+/// Check that the amount can be wagered, where a `max` of `0` means no limit.
+fn check_amount(
+    amount: i64,
+    balance: i64,
+    min: i64,
+    max: i64,
+    currency: &str,
+) -> Result<(), String> {
+    if amount <= 0 || amount < min {
+        return Err(format!(
+            "You need to wager at least {} {}.",
+            min.max(1),
+            currency
+        ));
+    }
+
+    if max > 0 && amount > max {
+        return Err(format!("You can wager at most {} {}.", max, currency));
+    }
+
+    if amount > balance {
+        return Err(format!(
+            "Not enough {}, you only have {}.",
+            currency, balance
+        ));
+    }
+
+    Ok(())
+}
+
+/// Get the balance of the given user.
+async fn balance_of(currency: &Currency, channel: &str, user: &str) -> Result<i64, Error> {
+    let balance = currency.balance_of(channel, user).await?;
+    Ok(balance.map(|b| b.balance).unwrap_or_default())
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "games"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            injector,
+            futures,
+            settings,
+            ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), Error> {
+        let currency = injector.var()?;
+
+        // NB: a generator is only injected to make games deterministic in
+        // tests.
+        let rng: Rng = match injector.get::<Rng>() {
+            Some(rng) => rng,
+            None => Arc::new(Mutex::new(Box::new(rand::rngs::StdRng::from_entropy()))),
+        };
+
+        let gamble = settings.scoped("gamble");
+
+        handlers.insert(
+            "gamble",
+            Gamble {
+                enabled: gamble.var("enabled", false)?,
+                chance: gamble.var("chance%", 45)?,
+                payout: gamble.var("payout%", 100)?,
+                limits: Limits::new(&gamble)?,
+                cooldowns: UserCooldowns::new(gamble.var("cooldown", Duration::seconds(30))?),
+                currency: currency.clone(),
+                rng: rng.clone(),
+            },
+        );
+
+        let duel = settings.scoped("duel");
+
+        handlers.insert(
+            "duel",
+            Duel {
+                enabled: duel.var("enabled", false)?,
+                chance: duel.var("chance%", 50)?,
+                timeout: duel.var("timeout", Duration::seconds(60))?,
+                limits: Limits::new(&duel)?,
+                cooldowns: UserCooldowns::new(duel.var("cooldown", Duration::seconds(30))?),
+                currency: currency.clone(),
+                rng: rng.clone(),
+                challenges: HashMap::new(),
+            },
+        );
+
+        let heist = settings.scoped("heist");
+
+        let (mut cooldown_stream, cooldown) = heist
+            .stream("cooldown")
+            .or_with(Duration::seconds(60 * 10))?;
+
+        let cooldown = Arc::new(RwLock::new(Cooldown::from_duration(cooldown)));
+
+        handlers.insert(
+            "heist",
+            Heist {
+                enabled: heist.var("enabled", false)?,
+                chance: heist.var("chance%", 50)?,
+                payout: heist.var("payout%", 100)?,
+                window: heist.var("window", Duration::seconds(60))?,
+                limits: Limits::new(&heist)?,
+                cooldown: cooldown.clone(),
+                currency,
+                rng,
+                active: Arc::new(Mutex::new(None)),
+            },
+        );
+
+        let future = async move {
+            loop {
+                futures::select! {
+                    update = cooldown_stream.select_next_some() => {
+                        cooldown.write().cooldown = update;
+                    }
+                }
+            }
+        };
+
+        futures.push(future.boxed());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        balance_of, check_amount, profit, resolve_heist, roll, Module, Outcome, Rng, Wager,
+    };
+    use crate::{currency::LedgerReason, irc::testing, utils::Duration};
+    use parking_lot::Mutex;
+    use rand::rngs::mock::StepRng;
+    use std::sync::Arc;
+
+    /// Rolls which always succeed, unless the chance is zero.
+    fn lucky() -> StepRng {
+        StepRng::new(0, 0)
+    }
+
+    /// Rolls which always fail, unless the chance is a hundred percent.
+    fn unlucky() -> StepRng {
+        StepRng::new(u64::max_value(), 0)
+    }
+
+    /// Make all games roll with the given generator.
+    fn inject(fixture: &testing::Fixture, rng: StepRng) {
+        let rng: Rng = Arc::new(Mutex::new(Box::new(rng)));
+        fixture.injector.update(rng);
+    }
+
+    /// A chat line with a message from the given user.
+    fn line(user: &str, message: &str) -> String {
+        format!(
+            ":{user}!{user}@{user}.tmi.twitch.tv PRIVMSG #setbac :{message}",
+            user = user,
+            message = message
+        )
+    }
+
+    /// Give the user some currency.
+    async fn give(
+        fixture: &testing::Fixture,
+        user: &str,
+        amount: i64,
+    ) -> Result<(), failure::Error> {
+        fixture
+            .currency
+            .balance_add("#setbac", user, amount, LedgerReason::Admin, None)
+            .await
+    }
+
+    /// Get the balance of the user.
+    async fn balance(fixture: &testing::Fixture, user: &str) -> Result<i64, failure::Error> {
+        balance_of(&fixture.currency, "#setbac", user).await
+    }
+
+    /// A message sent by the bot to the channel.
+    fn said(message: &str) -> (String, String) {
+        (String::from("#setbac"), String::from(message))
+    }
+
+    #[test]
+    fn test_gamble_win() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("gamble/enabled", true)?;
+            inject(&fixture, lucky());
+
+            let mut handler = fixture.handler()?;
+            fixture.hook(&mut handler, &Module).await?;
+            give(&fixture, "someuser", 100).await?;
+
+            chat.push(&mut handler, &line("someuser", "!gamble 50"))
+                .await?;
+
+            assert_eq!(
+                said("someuser -> You gambled 50 and won 50 thingies PogChamp"),
+                chat.privmsg().await?
+            );
+            assert_eq!(150, balance(&fixture, "someuser").await?);
+            assert_eq!(-50, balance(&fixture, "setbac").await?);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_gamble_lose() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("gamble/enabled", true)?;
+            inject(&fixture, unlucky());
+
+            let mut handler = fixture.handler()?;
+            fixture.hook(&mut handler, &Module).await?;
+            give(&fixture, "someuser", 100).await?;
+
+            chat.push(&mut handler, &line("someuser", "!gamble 50%"))
+                .await?;
+
+            assert_eq!(
+                said("someuser -> You gambled 50 thingies and lost it all LUL"),
+                chat.privmsg().await?
+            );
+            assert_eq!(50, balance(&fixture, "someuser").await?);
+            assert_eq!(50, balance(&fixture, "setbac").await?);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_duel_accept() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("duel/enabled", true)?;
+            inject(&fixture, lucky());
+
+            let mut handler = fixture.handler()?;
+            fixture.hook(&mut handler, &Module).await?;
+            give(&fixture, "someuser", 100).await?;
+            give(&fixture, "otheruser", 100).await?;
+
+            chat.push(&mut handler, &line("someuser", "!duel @OtherUser 50"))
+                .await?;
+
+            assert_eq!(
+                said(
+                    "otheruser, someuser challenges you to a duel for 50 thingies! \
+                     Type !duel accept or !duel decline within 1m."
+                ),
+                chat.privmsg().await?
+            );
+
+            chat.push(&mut handler, &line("otheruser", "!duel accept"))
+                .await?;
+
+            assert_eq!(
+                said("someuser won the duel against otheruser and takes 50 thingies!"),
+                chat.privmsg().await?
+            );
+            assert_eq!(150, balance(&fixture, "someuser").await?);
+            assert_eq!(50, balance(&fixture, "otheruser").await?);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_duel_decline() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("duel/enabled", true)?;
+            inject(&fixture, lucky());
+
+            let mut handler = fixture.handler()?;
+            fixture.hook(&mut handler, &Module).await?;
+            give(&fixture, "someuser", 100).await?;
+            give(&fixture, "otheruser", 100).await?;
+
+            chat.push(&mut handler, &line("someuser", "!duel otheruser 50"))
+                .await?;
+            chat.privmsg().await?;
+
+            chat.push(&mut handler, &line("otheruser", "!duel decline"))
+                .await?;
+
+            assert_eq!(
+                said("otheruser declined the duel from someuser."),
+                chat.privmsg().await?
+            );
+
+            chat.push(&mut handler, &line("otheruser", "!duel accept"))
+                .await?;
+
+            assert_eq!(
+                said("otheruser -> Nobody has challenged you to a duel."),
+                chat.privmsg().await?
+            );
+            assert_eq!(100, balance(&fixture, "someuser").await?);
+            assert_eq!(100, balance(&fixture, "otheruser").await?);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_duel_timeout() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("duel/enabled", true)?;
+            fixture.settings.set("duel/timeout", Duration::seconds(0))?;
+            inject(&fixture, lucky());
+
+            let mut handler = fixture.handler()?;
+            fixture.hook(&mut handler, &Module).await?;
+            give(&fixture, "someuser", 100).await?;
+            give(&fixture, "otheruser", 100).await?;
+
+            chat.push(&mut handler, &line("someuser", "!duel otheruser 50"))
+                .await?;
+            chat.privmsg().await?;
+
+            chat.push(&mut handler, &line("otheruser", "!duel accept"))
+                .await?;
+
+            assert_eq!(
+                said("otheruser -> Nobody has challenged you to a duel."),
+                chat.privmsg().await?
+            );
+            assert_eq!(100, balance(&fixture, "someuser").await?);
+            assert_eq!(100, balance(&fixture, "otheruser").await?);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_heist_payout() -> Result<(), failure::Error> {
+        testing::run(async {
+            let (fixture, mut chat) = testing::setup("setbac").await?;
+            fixture.settings.set("heist/enabled", true)?;
+            fixture.settings.set("heist/window", Duration::seconds(0))?;
+            inject(&fixture, lucky());
+
+            let mut handler = fixture.handler()?;
+            fixture.hook(&mut handler, &Module).await?;
+            give(&fixture, "someuser", 100).await?;
+
+            chat.push(&mut handler, &line("someuser", "!heist 50"))
+                .await?;
+
+            assert_eq!(
+                said(
+                    "someuser is planning a heist! \
+                     Type !heist <amount|all|percentage%> within 0s to join the crew."
+                ),
+                chat.privmsg().await?
+            );
+            assert_eq!(
+                said("The heist was a success! The whole crew made it out: someuser (+50)."),
+                chat.privmsg().await?
+            );
+            assert_eq!(150, balance(&fixture, "someuser").await?);
+            assert_eq!(-50, balance(&fixture, "setbac").await?);
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_wager() {
+        assert_eq!(Wager::Amount(100), str::parse::<Wager>("100").unwrap());
+        assert_eq!(Wager::All, str::parse::<Wager>("ALL").unwrap());
+        assert_eq!(Wager::Percent(50), str::parse::<Wager>("50%").unwrap());
+        assert!(str::parse::<Wager>("0%").is_err());
+        assert!(str::parse::<Wager>("101%").is_err());
+        assert!(str::parse::<Wager>("lots").is_err());
+
+        assert_eq!(100, Wager::Amount(100).amount(50));
+        assert_eq!(50, Wager::All.amount(50));
+        assert_eq!(0, Wager::All.amount(-10));
+        assert_eq!(25, Wager::Percent(50).amount(51));
+        assert_eq!(
+            i64::max_value(),
+            Wager::Percent(100).amount(i64::max_value())
+        );
+    }
+
+    #[test]
+    fn test_profit() {
+        assert_eq!(150, profit(100, 150));
+        assert_eq!(i64::max_value(), profit(i64::max_value(), u32::max_value()));
+        assert_eq!(
+            vec![Some(i64::max_value())],
+            resolve_heist(
+                &mut lucky(),
+                vec![(String::from("a"), i64::max_value())],
+                100,
+                100
+            )
+            .into_iter()
+            .map(|o| o.winnings)
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_check_amount() {
+        assert!(check_amount(10, 100, 1, 0, "thalers").is_ok());
+        assert!(check_amount(0, 100, 1, 0, "thalers").is_err());
+        assert!(check_amount(5, 100, 10, 0, "thalers").is_err());
+        assert!(check_amount(50, 100, 1, 20, "thalers").is_err());
+        assert!(check_amount(200, 100, 1, 0, "thalers").is_err());
+    }
+
+    #[test]
+    fn test_roll() {
+        assert!(roll(&mut lucky(), 1));
+        assert!(!roll(&mut lucky(), 0));
+        assert!(!roll(&mut unlucky(), 99));
+        assert!(roll(&mut unlucky(), 100));
+    }
+
+    #[test]
+    fn test_resolve_heist() {
+        let participants = vec![(String::from("a"), 100), (String::from("b"), 30)];
+
+        assert_eq!(
+            vec![
+                Outcome {
+                    user: String::from("a"),
+                    stake: 100,
+                    winnings: Some(250),
+                },
+                Outcome {
+                    user: String::from("b"),
+                    stake: 30,
+                    winnings: Some(75),
+                },
+            ],
+            resolve_heist(&mut lucky(), participants.clone(), 50, 150)
+        );
+
+        assert!(resolve_heist(&mut unlucky(), participants, 50, 150)
+            .into_iter()
+            .all(|o| o.winnings.is_none()));
+    }
+}
@@ -1002,7 +1002,13 @@ impl command::Handler for Handler {
                 }
 
                 currency
-                    .balance_add(user.channel(), real.name(), -(cost as i64))
+                    .balance_add(
+                        user.channel(),
+                        real.name(),
+                        -(cost as i64),
+                        currency::LedgerReason::Gtav,
+                        None,
+                    )
                     .await?;
             }
 
@@ -47,6 +47,10 @@ impl super::Module for Module {
         "help"
     }
 
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
     async fn hook(
         &self,
         module::HookContext {
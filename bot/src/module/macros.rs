@@ -200,6 +200,51 @@ macro_rules! command_show {
     }};
 }
 
+/// Helper macro for constructing a command which edits a single restriction.
+macro_rules! command_restriction {
+    ($ctx:expr, $db:expr, $what:expr, $edit_scope:ident, $field:ident, $usage:expr) => {{
+        $ctx.check_scope(crate::auth::Scope::$edit_scope)?;
+
+        let name = ctx_try!($ctx.next_str(concat!("<name> [", $usage, "]")));
+
+        let thing = match $db.get_any($ctx.channel(), &name)? {
+            Some(thing) => thing,
+            None => {
+                $ctx.respond(format!("No {} named `{}`.", $what, name));
+                return Ok(());
+            }
+        };
+
+        let mut restrictions = thing.restrictions.clone();
+
+        restrictions.$field = match $ctx.rest().trim() {
+            "" => None,
+            value => match str::parse(value) {
+                Ok(value) => Some(value),
+                Err(e) => {
+                    $ctx.respond(format!("Bad {} `{}`: {}", $usage, value, e));
+                    return Ok(());
+                }
+            },
+        };
+
+        let value = restrictions.$field.as_ref().map(|v| v.to_string());
+
+        if !$db.edit_restrictions($ctx.channel(), &name, restrictions)? {
+            $ctx.respond(format!("No {} named `{}`.", $what, name));
+            return Ok(());
+        }
+
+        match value {
+            Some(value) => $ctx.respond(format!(
+                "Set {} for {} `{}` to {}",
+                $usage, $what, name, value
+            )),
+            None => $ctx.respond(format!("Cleared {} for {} `{}`", $usage, $what, name)),
+        }
+    }};
+}
+
 macro_rules! command_base {
     ($ctx:expr, $db:expr, $what:expr, $edit_scope:ident) => {{
         let arg = $ctx.next();
@@ -0,0 +1,108 @@
+use crate::{auth, command, db, irc, module, prelude::*, utils};
+use chrono::Utc;
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// How long a permit for a single link is valid for.
+const ONCE_EXPIRY: u64 = 5 * 60;
+
+/// Handler for the `!permit` command.
+pub struct Permit {
+    link_permits: Arc<RwLock<Option<irc::LinkPermits>>>,
+    audit_log: Arc<RwLock<Option<db::AuditLog>>>,
+}
+
+#[async_trait]
+impl command::Handler for Permit {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Permit)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), failure::Error> {
+        let link_permits = match self.link_permits.read().clone() {
+            Some(link_permits) => link_permits,
+            None => return Ok(()),
+        };
+
+        let user: command::User = ctx_try!(ctx.arg());
+        let duration = ctx_try!(ctx.optional_arg::<utils::Duration>());
+
+        let (once, expires_in, reason) = match duration {
+            Some(duration) => (false, duration.clone(), format!("links for {}", duration)),
+            None => (
+                true,
+                utils::Duration::seconds(ONCE_EXPIRY),
+                String::from("one link"),
+            ),
+        };
+
+        let expires_at = match expires_in
+            .checked_as_chrono()
+            .and_then(|d| Utc::now().checked_add_signed(d))
+        {
+            Some(expires_at) => expires_at,
+            None => {
+                ctx.respond(format!("Can't permit links for as long as {}.", expires_in));
+                return Ok(());
+            }
+        };
+
+        link_permits.insert(ctx.channel(), user.as_ref(), once, expires_at);
+
+        if let Some(audit_log) = self.audit_log.read().as_ref() {
+            let result = audit_log.record(
+                ctx.channel(),
+                ctx.user.name(),
+                db::AuditAction::Permit,
+                Some(user.as_ref()),
+                Some(&reason),
+            );
+
+            if let Err(e) = result {
+                log_err!(e, "failed to record permit in the audit log");
+            }
+        }
+
+        if once {
+            ctx.respond(format!(
+                "{} may post one link within the next {}.",
+                user, expires_in
+            ));
+        } else {
+            ctx.respond(format!("{} may post links for {}.", user, expires_in));
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "permit"
+    }
+
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector, handlers, ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), failure::Error> {
+        handlers.insert(
+            "permit",
+            Permit {
+                link_permits: injector.var()?,
+                audit_log: injector.var()?,
+            },
+        );
+
+        Ok(())
+    }
+}
@@ -176,6 +176,10 @@ impl super::Module for Module {
         "poll"
     }
 
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
     /// Set up command handlers for this module.
     async fn hook(
         &self,
@@ -1,5 +1,6 @@
 use crate::{auth, command, db, irc, module, prelude::*, utils};
 use chrono::Utc;
+use hashbrown::HashMap;
 use parking_lot::RwLock;
 use std::sync::Arc;
 
@@ -50,6 +51,10 @@ impl super::Module for Module {
         "promotions"
     }
 
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
     async fn hook(
         &self,
         module::HookContext {
@@ -78,6 +83,7 @@ impl super::Module for Module {
         );
 
         let (mut promotions_stream, mut promotions) = injector.stream::<db::Promotions>();
+        let counters = injector.var::<db::Counters>()?;
         let sender = sender.clone();
         let mut interval = tokio::timer::Interval::new_interval(frequency.as_std());
         let idle = idle.clone();
@@ -108,10 +114,11 @@ impl super::Module for Module {
                             log::trace!("channel is too idle to send a promotion");
                         } else {
                             let promotions = promotions.clone();
+                            let counters = counters.read().clone();
                             let sender = sender.clone();
 
                             tokio::spawn(async move {
-                                if let Err(e) = promote(promotions, sender) {
+                                if let Err(e) = promote(promotions, counters, sender) {
                                     log::error!("failed to send promotion: {}", e);
                                 }
                             });
@@ -127,11 +134,20 @@ impl super::Module for Module {
 }
 
 /// Run the next promotion.
-fn promote(promotions: db::Promotions, sender: irc::Sender) -> Result<(), failure::Error> {
+fn promote(
+    promotions: db::Promotions,
+    counters: Option<db::Counters>,
+    sender: irc::Sender,
+) -> Result<(), failure::Error> {
     let channel = sender.channel();
 
     if let Some(p) = pick(promotions.list(channel)) {
-        let text = p.render(&PromoData { channel })?;
+        let counters = counters
+            .as_ref()
+            .map(|c| c.vars(channel))
+            .unwrap_or_default();
+
+        let text = p.render(&PromoData { channel, counters })?;
         promotions.bump_promoted_at(&*p)?;
         sender.privmsg(text);
     }
@@ -142,6 +158,7 @@ fn promote(promotions: db::Promotions, sender: irc::Sender) -> Result<(), failur
 #[derive(Debug, serde::Serialize)]
 struct PromoData<'a> {
     channel: &'a str,
+    counters: HashMap<String, i64>,
 }
 
 /// Pick the best promo.
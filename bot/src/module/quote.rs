@@ -0,0 +1,180 @@
+use crate::{auth, command, db, module, prelude::*, stream_info};
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// The most quotes listed when searching.
+const SEARCH_LIMIT: usize = 10;
+
+/// Handler for the `!quote` command.
+pub struct Quote {
+    enabled: Arc<RwLock<bool>>,
+    quotes: Arc<RwLock<Option<db::Quotes>>>,
+    stream_info: stream_info::StreamInfo,
+}
+
+#[async_trait]
+impl command::Handler for Quote {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Quote)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), failure::Error> {
+        if !*self.enabled.read() {
+            return Ok(());
+        }
+
+        let quotes = match self.quotes.read().clone() {
+            Some(quotes) => quotes,
+            None => return Ok(()),
+        };
+
+        match ctx.next().as_ref().map(String::as_str) {
+            Some("add") => {
+                ctx.check_scope(auth::Scope::QuoteEdit)?;
+
+                let user = match ctx.user.real() {
+                    Some(user) => user,
+                    None => {
+                        ctx.respond("Only real users can add quotes");
+                        return Ok(());
+                    }
+                };
+
+                let text = ctx.rest().trim();
+
+                if text.is_empty() {
+                    ctx.respond("Expected: !quote add <text>");
+                    return Ok(());
+                }
+
+                let game = self.stream_info.data.read().game.clone();
+
+                let id = quotes.push(
+                    ctx.channel(),
+                    user.name(),
+                    game.as_ref().map(String::as_str),
+                    text,
+                )?;
+
+                ctx.respond(format!("Added quote #{}.", id));
+            }
+            Some("random") => match quotes.random(ctx.channel())? {
+                Some(quote) => ctx.respond(Display(&quote)),
+                None => ctx.respond("There are no quotes yet."),
+            },
+            Some("search") => {
+                let term = ctx.rest().trim();
+
+                if term.is_empty() {
+                    ctx.respond("Expected: !quote search <term>");
+                    return Ok(());
+                }
+
+                // NB: one more than is listed, to tell if there are more.
+                let found = quotes.search(ctx.channel(), term, SEARCH_LIMIT as i64 + 1)?;
+
+                match found.as_slice() {
+                    [] => ctx.respond(format!("No quotes matching `{}`.", term)),
+                    [quote] => ctx.respond(Display(quote)),
+                    found => {
+                        let ids = found
+                            .iter()
+                            .take(SEARCH_LIMIT)
+                            .map(|q| format!("#{}", q.id))
+                            .collect::<Vec<_>>();
+
+                        if found.len() > SEARCH_LIMIT {
+                            ctx.respond(format!(
+                                "Matching quotes: {} .. and more.",
+                                ids.join(", ")
+                            ));
+                        } else {
+                            ctx.respond(format!("Matching quotes: {}.", ids.join(", ")));
+                        }
+                    }
+                }
+            }
+            Some("delete") => {
+                ctx.check_scope(auth::Scope::QuoteEdit)?;
+
+                let id = ctx_try!(ctx.next_parse::<i32, _>("<id>"));
+
+                if quotes.delete(ctx.channel(), id)? {
+                    ctx.respond(format!("Deleted quote #{}.", id));
+                } else {
+                    ctx.respond(format!("No quote with id #{}.", id));
+                }
+            }
+            Some(id) => {
+                let id = match str::parse::<i32>(id.trim_start_matches('#')) {
+                    Ok(id) => id,
+                    Err(_) => {
+                        ctx.respond("Expected: <id>, random, search, add, or delete.");
+                        return Ok(());
+                    }
+                };
+
+                match quotes.get(ctx.channel(), id)? {
+                    Some(quote) => ctx.respond(Display(&quote)),
+                    None => ctx.respond(format!("No quote with id #{}.", id)),
+                }
+            }
+            None => {
+                ctx.respond("Expected: <id>, random, search, add, or delete.");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Helper to display a quote in chat.
+struct Display<'a>(&'a db::Quote);
+
+impl std::fmt::Display for Display<'_> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let quote = self.0;
+        let date = quote.added_at.format("%Y-%m-%d");
+
+        match quote.game.as_ref() {
+            Some(game) => write!(fmt, "#{}: {} ({}, {})", quote.id, quote.text, game, date),
+            None => write!(fmt, "#{}: {} ({})", quote.id, quote.text, date),
+        }
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "quote"
+    }
+
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector,
+            handlers,
+            settings,
+            stream_info,
+            ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), failure::Error> {
+        handlers.insert(
+            "quote",
+            Quote {
+                enabled: settings.var("quote/enabled", false)?,
+                quotes: injector.var()?,
+                stream_info: stream_info.clone(),
+            },
+        );
+
+        Ok(())
+    }
+}
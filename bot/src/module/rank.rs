@@ -0,0 +1,250 @@
+use crate::{
+    auth, command, currency::Currency, irc, module, prelude::*, template::Template, utils::Duration,
+};
+use failure::Error;
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// Handler for the `!rank` command.
+pub struct Rank {
+    ranks: Arc<RwLock<Option<irc::Ranks>>>,
+    currency: Arc<RwLock<Option<Currency>>>,
+}
+
+#[async_trait]
+impl command::Handler for Rank {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Rank)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), Error> {
+        let ranks = match self.ranks.read().clone() {
+            Some(ranks) if ranks.is_enabled() => ranks,
+            _ => return Ok(()),
+        };
+
+        let currency = match self.currency.read().clone() {
+            Some(currency) if currency.tracks_watch_time() => currency,
+            _ => {
+                ctx.respond("Ranks need a stream currency which keeps track of watch time.");
+                return Ok(());
+            }
+        };
+
+        let user = match ctx_try!(ctx.optional_arg::<command::User>()) {
+            Some(command::User(user)) => user,
+            None => match ctx.user.name() {
+                Some(name) => name.to_string(),
+                None => {
+                    ctx.respond("Only real users can check their rank");
+                    return Ok(());
+                }
+            },
+        };
+
+        let result = currency.balance_of(ctx.channel(), &user).await;
+
+        let watch_time = match result {
+            Ok(balance) => balance.map(|b| b.watch_time).unwrap_or_default(),
+            Err(e) => {
+                ctx.respond("Could not get watch time, sorry :(");
+                log_err!(e, "failed to get watch time");
+                return Ok(());
+            }
+        };
+
+        ranks.observe(&user, watch_time);
+        let watched = Duration::seconds(watch_time.max(0) as u64);
+
+        let reached = match ranks.rank_for(watch_time) {
+            Some(reached) => reached,
+            None => {
+                match ranks.first() {
+                    Some(first) => ctx.respond(format!(
+                        "{user} hasn't reached a rank yet, {left} left until {next}.",
+                        user = user,
+                        left = first.watch_time.saturating_sub(watched.clone()),
+                        next = first.name,
+                    )),
+                    None => ctx.respond("No ranks have been configured."),
+                }
+
+                return Ok(());
+            }
+        };
+
+        match reached.next {
+            Some(next) => ctx.respond(format!(
+                "{user} has reached {rank} after watching for {watched}, {left} left until {next}.",
+                user = user,
+                rank = reached.rank.name,
+                watched = watched,
+                left = next.watch_time.saturating_sub(watched.clone()),
+                next = next.name,
+            )),
+            None => ctx.respond(format!(
+                "{user} has reached {rank} after watching for {watched}, the highest rank!",
+                user = user,
+                rank = reached.rank.name,
+                watched = watched,
+            )),
+        }
+
+        Ok(())
+    }
+}
+
+/// Configuration of ranks, kept in sync with settings.
+struct Config {
+    enabled: bool,
+    ranks: Vec<irc::Rank>,
+    announce: bool,
+    announcement: Template,
+}
+
+impl Config {
+    /// Apply the configuration to the given ranks.
+    fn apply(&self, ranks: &irc::Ranks) {
+        ranks.set_enabled(self.enabled);
+
+        if let Err(e) = ranks.set_ranks(self.ranks.clone()) {
+            log_err!(e, "ignoring ranks in `rank/ranks`");
+        }
+
+        if self.announce {
+            ranks.set_announcement(Some(self.announcement.clone()));
+        } else {
+            ranks.set_announcement(None);
+        }
+    }
+}
+
+/// Record the watch time of every user with a balance in the given channel,
+/// so that they hold their rank roles before they've been rewarded.
+async fn observe_all(
+    ranks: Option<irc::Ranks>,
+    currency: Option<Currency>,
+    channel: String,
+) -> Result<(), Error> {
+    let (ranks, currency) = match (ranks, currency) {
+        (Some(ranks), Some(currency)) => (ranks, currency),
+        _ => return Ok(()),
+    };
+
+    if !ranks.is_enabled() || !currency.tracks_watch_time() {
+        return Ok(());
+    }
+
+    let balances = currency.export_balances().await?;
+    ranks.observe_all(&channel, balances);
+    Ok(())
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "rank"
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            handlers,
+            injector,
+            futures,
+            settings,
+            sender,
+            ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), Error> {
+        let settings = settings.scoped("rank");
+        let channel = sender.channel().to_string();
+
+        let default_announcement = Template::compile(
+            "Congratulations {{name}}, you have reached {{rank}} after watching for {{watch_time}}!",
+        )?;
+
+        let (mut enabled_stream, enabled) = settings.stream("enabled").or_default()?;
+        let (mut list_stream, list) = settings.stream("ranks").or_default()?;
+        let (mut announce_stream, announce) = settings.stream("announce").or_with(true)?;
+        let (mut announcement_stream, announcement) = settings
+            .stream("announcement")
+            .or_with(default_announcement)?;
+
+        let (mut ranks_stream, mut ranks) = injector.stream::<irc::Ranks>();
+        let (mut currency_stream, mut currency) = injector.stream::<Currency>();
+
+        handlers.insert(
+            "rank",
+            Rank {
+                ranks: injector.var()?,
+                currency: injector.var()?,
+            },
+        );
+
+        let mut config = Config {
+            enabled,
+            ranks: list,
+            announce,
+            announcement,
+        };
+
+        if let Some(ranks) = ranks.as_ref() {
+            config.apply(ranks);
+        }
+
+        let future = async move {
+            if let Err(e) = observe_all(ranks.clone(), currency.clone(), channel.clone()).await {
+                log_err!(e, "failed to look up watch time for ranks");
+            }
+
+            loop {
+                let observe = futures::select! {
+                    update = enabled_stream.select_next_some() => {
+                        config.enabled = update;
+                        true
+                    }
+                    update = list_stream.select_next_some() => {
+                        config.ranks = update;
+                        false
+                    }
+                    update = announce_stream.select_next_some() => {
+                        config.announce = update;
+                        false
+                    }
+                    update = announcement_stream.select_next_some() => {
+                        config.announcement = update;
+                        false
+                    }
+                    update = ranks_stream.select_next_some() => {
+                        ranks = update;
+                        true
+                    }
+                    update = currency_stream.select_next_some() => {
+                        currency = update;
+                        true
+                    }
+                };
+
+                if let Some(ranks) = ranks.as_ref() {
+                    config.apply(ranks);
+                }
+
+                if observe {
+                    let result =
+                        observe_all(ranks.clone(), currency.clone(), channel.clone()).await;
+
+                    if let Err(e) = result {
+                        log_err!(e, "failed to look up watch time for ranks");
+                    }
+                }
+            }
+        };
+
+        futures.push(future.boxed());
+        Ok(())
+    }
+}
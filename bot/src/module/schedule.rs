@@ -0,0 +1,241 @@
+use crate::{auth, bus, command, db, irc, module, prelude::*, settings, stream_info};
+use chrono::Utc;
+use parking_lot::RwLock;
+use std::{sync::Arc, time};
+
+/// How often schedules are checked.
+const TICK: time::Duration = time::Duration::from_secs(5);
+
+pub struct Handler {
+    enabled: Arc<RwLock<bool>>,
+    schedules: Arc<RwLock<Option<db::Schedules>>>,
+    settings: settings::Settings,
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Schedule)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), failure::Error> {
+        if !*self.enabled.read() {
+            return Ok(());
+        }
+
+        let schedules = match self.schedules.read().clone() {
+            Some(schedules) => schedules,
+            None => return Ok(()),
+        };
+
+        let next = command_base!(ctx, schedules, "schedule", ScheduleEdit);
+
+        match next.as_ref().map(String::as_str) {
+            Some("edit") => {
+                ctx.check_scope(auth::Scope::ScheduleEdit)?;
+
+                let name = ctx_try!(ctx.next_str("<name> <trigger> <action..>"));
+                let trigger = ctx_try!(ctx.next_parse("<name> <trigger> <action..>"));
+                let action = ctx_try!(ctx.rest_parse("<name> <trigger> <action..>"));
+
+                if let Err(e) = action.validate(&self.settings) {
+                    ctx.respond(format!("Bad action: {}", e));
+                    return Ok(());
+                }
+
+                match action {
+                    // NB: raw commands run with the permissions of the bot.
+                    db::Action::Raw(..) => {
+                        ctx.check_scope(auth::Scope::ScheduleEditRaw)?;
+                    }
+                    db::Action::Setting { ref key, .. } => {
+                        let scope = self.settings.lookup(key).and_then(|s| s.scope.clone());
+
+                        if let Some(scope) = scope {
+                            if !ctx.user.has_scope(scope) {
+                                ctx.respond(
+                                    "You are not permitted to modify that setting, sorry :(",
+                                );
+                                return Ok(());
+                            }
+                        }
+                    }
+                    db::Action::Message(..) => (),
+                }
+
+                schedules.edit(ctx.channel(), &name, trigger, action)?;
+                ctx.respond("Edited schedule.");
+            }
+            Some("only-live") => {
+                ctx.check_scope(auth::Scope::ScheduleEdit)?;
+
+                let name = ctx_try!(ctx.next_str("<name> <true|false>"));
+                let only_live = ctx_try!(ctx.next_parse("<name> <true|false>"));
+
+                if !schedules.edit_only_live(ctx.channel(), &name, only_live)? {
+                    ctx.respond(format!("No such schedule: `{}`", name));
+                    return Ok(());
+                }
+
+                if only_live {
+                    ctx.respond(format!("Schedule `{}` will only run while live.", name));
+                } else {
+                    ctx.respond(format!("Schedule `{}` will always run.", name));
+                }
+            }
+            None | Some(..) => {
+                ctx.respond(
+                    "Expected: show, list, edit, only-live, delete, enable, disable, or group.",
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "schedule"
+    }
+
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector,
+            handlers,
+            futures,
+            sender,
+            settings,
+            stream_info,
+            command_bus,
+            ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), failure::Error> {
+        let enabled = settings.var("schedule/enabled", false)?;
+
+        handlers.insert(
+            "schedule",
+            Handler {
+                enabled: enabled.clone(),
+                schedules: injector.var()?,
+                settings: settings.clone(),
+            },
+        );
+
+        let (mut schedules_stream, mut schedules) = injector.stream::<db::Schedules>();
+        let mut interval = tokio::timer::Interval::new_interval(TICK);
+
+        let runner = Runner {
+            sender: sender.clone(),
+            settings: settings.clone(),
+            stream_info: stream_info.clone(),
+            command_bus: command_bus.clone(),
+            started_at: Utc::now(),
+        };
+
+        let future = async move {
+            loop {
+                futures::select! {
+                    update = schedules_stream.select_next_some() => {
+                        schedules = update;
+                    }
+                    _ = interval.select_next_some() => {
+                        if !*enabled.read() {
+                            continue;
+                        }
+
+                        let schedules = match schedules.as_ref() {
+                            Some(schedules) => schedules,
+                            None => continue,
+                        };
+
+                        if let Err(e) = runner.tick(schedules) {
+                            log::error!("failed to run schedules: {}", e);
+                        }
+                    }
+                }
+            }
+        };
+
+        futures.push(future.boxed());
+        Ok(())
+    }
+}
+
+/// Runs schedules which are due.
+struct Runner {
+    sender: irc::Sender,
+    settings: settings::Settings,
+    stream_info: stream_info::StreamInfo,
+    command_bus: Arc<bus::Bus<bus::Command>>,
+    /// When the runner was started, used as the base for schedules which have
+    /// never run.
+    started_at: chrono::DateTime<Utc>,
+}
+
+impl Runner {
+    /// Run all schedules which are due.
+    fn tick(&self, schedules: &db::Schedules) -> Result<(), failure::Error> {
+        let now = Utc::now();
+        let is_live = self.stream_info.data.read().stream.is_some();
+
+        for schedule in schedules.list(self.sender.channel()) {
+            match schedule.next_run(&self.started_at) {
+                Some(next_run) if next_run <= now => (),
+                _ => continue,
+            }
+
+            // NB: schedules which are due while the stream is offline are
+            // skipped, not deferred until the stream goes live.
+            schedules.bump_ran_at(&*schedule, &now)?;
+
+            if schedule.only_live && !is_live {
+                log::trace!(
+                    "skipping schedule `{}` since stream is not live",
+                    schedule.key.name
+                );
+                continue;
+            }
+
+            if let Err(e) = self.run(&schedule.action) {
+                log::error!("failed to run schedule `{}`: {}", schedule.key.name, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run the given action.
+    fn run(&self, action: &db::Action) -> Result<(), failure::Error> {
+        match *action {
+            db::Action::Raw(ref command) => {
+                self.command_bus.send(bus::Command::Raw {
+                    command: command.to_string(),
+                    channel: Some(self.sender.channel().to_string()),
+                });
+            }
+            db::Action::Message(ref message) => {
+                self.sender.privmsg(message);
+            }
+            db::Action::Setting { ref key, ref value } => {
+                let schema = match self.settings.lookup(key) {
+                    Some(schema) => schema,
+                    None => failure::bail!("no setting named `{}`", key),
+                };
+
+                let value = schema.ty.parse_as_json(value)?;
+                self.settings.set_json(key, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
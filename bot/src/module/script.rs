@@ -0,0 +1,326 @@
+use crate::{auth, command, currency, db, irc, module, prelude::*, script, settings, utils};
+use parking_lot::RwLock;
+use std::{sync::Arc, time::Instant};
+
+/// The maximum number of messages a single run of a script may send.
+const MAX_MESSAGES: usize = 5;
+
+/// Handler for the `!script` command.
+pub struct Handler {
+    enabled: Arc<RwLock<bool>>,
+    scripts: Arc<RwLock<Option<db::Scripts>>>,
+}
+
+#[async_trait]
+impl command::Handler for Handler {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Script)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), failure::Error> {
+        if !*self.enabled.read() {
+            return Ok(());
+        }
+
+        let scripts = match self.scripts.read().clone() {
+            Some(scripts) => scripts,
+            None => return Ok(()),
+        };
+
+        let next = command_base!(ctx, scripts, "script", ScriptEdit);
+
+        match next.as_ref().map(String::as_str) {
+            Some("edit") => {
+                ctx.check_scope(auth::Scope::ScriptEdit)?;
+
+                let name = ctx_try!(ctx.next_str("<name> <source>"));
+
+                let source = match ctx.rest().trim() {
+                    "" => {
+                        ctx.respond("Expected <name> <source>");
+                        return Ok(());
+                    }
+                    source => source,
+                };
+
+                let program = match script::Program::parse(source) {
+                    Ok(program) => program,
+                    Err(e) => {
+                        ctx.respond(format!("Bad script: {}", e));
+                        return Ok(());
+                    }
+                };
+
+                scripts.edit(ctx.channel(), &name, source, program)?;
+                ctx.respond("Edited script.");
+            }
+            None | Some(..) => {
+                ctx.respond("Expected: show, list, edit, delete, enable, disable, or group.");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Resolves commands into the scripts registered for them.
+struct ScriptHandlers {
+    enabled: Arc<RwLock<bool>>,
+    scripts: Arc<RwLock<Option<db::Scripts>>>,
+    currency: Arc<RwLock<Option<currency::Currency>>>,
+    settings: settings::Settings,
+    max_operations: Arc<RwLock<u64>>,
+    timeout: Arc<RwLock<utils::Duration>>,
+}
+
+impl module::DynamicHandlers for ScriptHandlers {
+    fn lookup(&self, channel: &str, command: &str) -> Option<Box<dyn command::Handler + Send>> {
+        if !*self.enabled.read() {
+            return None;
+        }
+
+        let script = self.scripts.read().as_ref()?.get(channel, command)?;
+
+        Some(Box::new(ScriptHandler {
+            script,
+            currency: self.currency.read().clone(),
+            settings: self.settings.clone(),
+            max_operations: *self.max_operations.read(),
+            timeout: self.timeout.read().as_std(),
+        }))
+    }
+}
+
+/// Handler running a single script.
+struct ScriptHandler {
+    script: Arc<db::Script>,
+    currency: Option<currency::Currency>,
+    settings: settings::Settings,
+    max_operations: u64,
+    timeout: std::time::Duration,
+}
+
+#[async_trait]
+impl command::Handler for ScriptHandler {
+    async fn handle(&mut self, ctx: command::Context<'_>) -> Result<(), failure::Error> {
+        let name = self.script.key.name.clone();
+        let program = self.script.program.clone();
+
+        let limits = script::Limits {
+            max_operations: self.max_operations,
+            deadline: Instant::now() + self.timeout,
+        };
+
+        let mut host = Host {
+            user: ctx.user.clone(),
+            rest: ctx.rest().to_string(),
+            currency: self.currency.clone(),
+            settings: self.settings.clone(),
+            messages: 0,
+        };
+
+        // NB: scripts run in the background to avoid stalling the handler
+        // while they are waiting for the host.
+        ctx.spawn(async move {
+            let result = {
+                let run = script::run(&*program, &mut host, limits).boxed();
+                let timeout = tokio::timer::delay(limits.deadline);
+
+                match future::select(run, timeout).await {
+                    future::Either::Left((result, _)) => result,
+                    future::Either::Right(..) => Err(script::Error::Timeout),
+                }
+            };
+
+            if let Err(e) = result {
+                log::warn!("script `{}` failed: {}", name, e);
+                host.user.respond(format!("Script failed: {}", e));
+            }
+        });
+
+        Ok(())
+    }
+}
+
+/// The restricted view of the bot that scripts have access to.
+struct Host {
+    user: irc::User,
+    /// Arguments which have not yet been consumed by the script.
+    rest: String,
+    currency: Option<currency::Currency>,
+    settings: settings::Settings,
+    /// Number of messages sent so far.
+    messages: usize,
+}
+
+impl Host {
+    /// Account for a single message being sent.
+    fn message(&mut self) -> Result<(), script::Error> {
+        if self.messages >= MAX_MESSAGES {
+            return Err(script::Error::Host(failure::format_err!(
+                "scripts may only send {} messages",
+                MAX_MESSAGES
+            )));
+        }
+
+        self.messages += 1;
+        Ok(())
+    }
+
+    /// Access the currency, if configured.
+    fn currency(&self) -> Result<&currency::Currency, script::Error> {
+        match self.currency.as_ref() {
+            Some(currency) => Ok(currency),
+            None => Err(script::Error::Host(failure::format_err!(
+                "no currency configured"
+            ))),
+        }
+    }
+
+    /// Read a setting. Secret settings are not accessible.
+    fn setting(&self, key: &str) -> Result<script::Value, script::Error> {
+        let setting = self
+            .settings
+            .setting::<serde_json::Value>(key)
+            .map_err(|e| script::Error::Host(e.into()))?;
+
+        let value = match setting {
+            Some(ref setting) if !setting.schema.secret => setting.value.clone(),
+            _ => None,
+        };
+
+        let value = match value {
+            None | Some(serde_json::Value::Null) => script::Value::Nil,
+            Some(serde_json::Value::Bool(b)) => script::Value::Bool(b),
+            Some(serde_json::Value::String(s)) => script::Value::String(s),
+            Some(serde_json::Value::Number(ref n)) if n.is_i64() => n.as_i64().into(),
+            Some(other) => script::Value::String(other.to_string()),
+        };
+
+        Ok(value)
+    }
+}
+
+#[async_trait]
+impl script::Host for Host {
+    async fn call(
+        &mut self,
+        name: &str,
+        args: Vec<script::Value>,
+    ) -> Result<script::Value, script::Error> {
+        use script::Value;
+
+        let value = match (name, args.as_slice()) {
+            ("respond", [m]) => {
+                self.message()?;
+                self.user.respond(m);
+                Value::Nil
+            }
+            ("privmsg", [m]) => {
+                self.message()?;
+                self.user.sender().privmsg(m);
+                Value::Nil
+            }
+            ("next", []) => {
+                let mut it = utils::Words::new(&self.rest);
+                let next = it.next();
+                let rest = it.rest().to_string();
+                self.rest = rest;
+                Value::from(next)
+            }
+            ("rest", []) => Value::from(self.rest.trim()),
+            ("user", []) => Value::from(self.user.name()),
+            ("display_name", []) => Value::from(self.user.display_name()),
+            ("channel", []) => Value::from(self.user.channel()),
+            ("currency", []) => Value::from(self.currency.as_ref().map(|c| c.name.to_string())),
+            ("balance", [Value::String(user)]) => {
+                let balance = self
+                    .currency()?
+                    .balance_of(self.user.channel(), &db::user_id(user))
+                    .await
+                    .map_err(script::Error::Host)?;
+
+                Value::Int(balance.map(|b| b.balance).unwrap_or_default())
+            }
+            ("balance_add", [Value::String(user), Value::Int(amount)]) => {
+                self.currency()?
+                    .balance_add(
+                        self.user.channel(),
+                        &db::user_id(user),
+                        *amount,
+                        currency::LedgerReason::Script,
+                        None,
+                    )
+                    .await
+                    .map_err(script::Error::Host)?;
+
+                Value::Nil
+            }
+            ("setting", [Value::String(key)]) => self.setting(key)?,
+            ("respond", _)
+            | ("privmsg", _)
+            | ("next", _)
+            | ("rest", _)
+            | ("user", _)
+            | ("display_name", _)
+            | ("channel", _)
+            | ("currency", _)
+            | ("balance", _)
+            | ("balance_add", _)
+            | ("setting", _) => {
+                return Err(script::Error::BadArguments(
+                    name.to_string(),
+                    script::describe_arguments(&args),
+                ));
+            }
+            _ => return Err(script::Error::MissingFunction(name.to_string())),
+        };
+
+        Ok(value)
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "script"
+    }
+
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector,
+            handlers,
+            settings,
+            ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), failure::Error> {
+        let enabled = settings.var("script/enabled", false)?;
+        let scripts = injector.var()?;
+
+        handlers.insert(
+            "script",
+            Handler {
+                enabled: enabled.clone(),
+                scripts: scripts.clone(),
+            },
+        );
+
+        handlers.insert_dynamic(ScriptHandlers {
+            enabled,
+            scripts,
+            currency: injector.var()?,
+            settings: settings.clone(),
+            max_operations: settings.var("script/max-operations", 10_000)?,
+            timeout: settings.var("script/timeout", utils::Duration::seconds(5))?,
+        });
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     auth::Scope,
-    command,
-    currency::Currency,
+    command::{self, Param, Spec, Sub},
+    currency::{Currency, LedgerReason},
     irc, module, player,
     player::{AddTrackError, Event, Item, PlayThemeError, Player},
     prelude::*,
@@ -16,6 +16,41 @@ use std::sync::Arc;
 
 const EXAMPLE_SEARCH: &'static str = "queen we will rock you";
 
+static SPEC: Spec = Spec {
+    subs: &[
+        Sub::scoped("theme", Scope::SongTheme, &[Param::required("name")]),
+        Sub::scoped(
+            "promote",
+            Scope::SongEditQueue,
+            &[Param::required("position")],
+        ),
+        Sub::scoped(
+            "close",
+            Scope::SongEditQueue,
+            &[Param::optional_rest("reason")],
+        ),
+        Sub::scoped("open", Scope::SongEditQueue, &[]),
+        Sub::scoped("purge", Scope::SongEditQueue, &[]),
+        Sub::new("volume", &[Param::optional("volume")]),
+        Sub::scoped("skip", Scope::SongPlaybackControl, &[]),
+        Sub::scoped("toggle", Scope::SongPlaybackControl, &[]),
+        Sub::scoped("play", Scope::SongPlaybackControl, &[]),
+        Sub::scoped("pause", Scope::SongPlaybackControl, &[]),
+        Sub::new("list", &[Param::optional("limit")]),
+        Sub::new("current", &[]),
+        Sub::new("when", &[Param::optional("user")]),
+        Sub::new(
+            "delete",
+            &[
+                Param::required("last|mine|position"),
+                Param::optional("user"),
+            ],
+        ),
+        Sub::new("request", &[Param::rest("query")]),
+        Sub::new("length", &[]),
+    ],
+};
+
 /// Handler for the `!song` command.
 pub struct Handler {
     enabled: Arc<RwLock<bool>>,
@@ -264,7 +299,13 @@ impl Handler {
             };
 
             match currency
-                .balance_add(user.channel(), user.name(), request_reward as i64)
+                .balance_add(
+                    user.channel(),
+                    user.name(),
+                    request_reward as i64,
+                    LedgerReason::SongRequest,
+                    None,
+                )
                 .await
             {
                 Ok(()) => {
@@ -341,10 +382,9 @@ impl command::Handler for Handler {
             }
         };
 
-        match ctx.next().as_ref().map(String::as_str) {
-            Some("theme") => {
-                ctx.check_scope(Scope::SongTheme)?;
-                let name = ctx_try!(ctx.next_str("<name>")).to_string();
+        match ctx_try!(ctx.subcommand(&SPEC)?) {
+            "theme" => {
+                let name: String = ctx_try!(ctx.arg());
 
                 let player = player.clone();
                 let user = ctx.user.clone();
@@ -370,13 +410,8 @@ impl command::Handler for Handler {
                     }
                 });
             }
-            Some("promote") => {
-                ctx.check_scope(Scope::SongEditQueue)?;
-
-                let index = match ctx.next().and_then(|n| parse_queue_position(&ctx.user, &n)) {
-                    Some(index) => index,
-                    None => return Ok(()),
-                };
+            "promote" => {
+                let Position(index) = ctx_try!(ctx.arg());
 
                 if let Some(item) = player.promote_song(ctx.user.name(), index) {
                     ctx.respond(format!("Promoted song to head of queue: {}", item.what()));
@@ -384,22 +419,18 @@ impl command::Handler for Handler {
                     ctx.respond("No such song to promote");
                 }
             }
-            Some("close") => {
-                ctx.check_scope(Scope::SongEditQueue)?;
-
+            "close" => {
                 player.close(match ctx.rest() {
                     "" => None,
                     other => Some(other.to_string()),
                 });
                 ctx.respond("Closed player from further requests.");
             }
-            Some("open") => {
-                ctx.check_scope(Scope::SongEditQueue)?;
-
+            "open" => {
                 player.open();
                 ctx.respond("Opened player for requests.");
             }
-            Some("list") => {
+            "list" => {
                 if let Some(api_url) = ctx.api_url {
                     ctx.respond(format!(
                         "You can find the queue at {}/player/{}",
@@ -411,12 +442,9 @@ impl command::Handler for Handler {
 
                 let mut limit = 3usize;
 
-                if let Some(n) = ctx.next() {
+                if let Some(n) = ctx_try!(ctx.optional_arg()) {
                     ctx.check_scope(Scope::SongListLimit)?;
-
-                    if let Ok(n) = str::parse(&n) {
-                        limit = n;
-                    }
+                    limit = n;
                 }
 
                 let items = player.list();
@@ -428,7 +456,7 @@ impl command::Handler for Handler {
 
                 display_songs(&ctx.user, has_more, items.iter().take(limit).cloned());
             }
-            Some("current") => match player.current() {
+            "current" => match player.current() {
                 Some(current) => {
                     let elapsed = utils::digital_duration(&current.elapsed());
                     let duration = utils::digital_duration(&current.duration());
@@ -456,18 +484,16 @@ impl command::Handler for Handler {
                     ctx.respond("No song :(");
                 }
             },
-            Some("purge") => {
-                ctx.check_scope(Scope::SongEditQueue)?;
-
+            "purge" => {
                 player.purge()?;
                 ctx.respond("Song queue purged.");
             }
             // print when your next song will play.
-            Some("when") => {
-                let user = ctx.next();
+            "when" => {
+                let user = ctx_try!(ctx.optional_arg::<command::User>());
 
                 let (your, user) = match &user {
-                    Some(user) => (false, user.as_str()),
+                    Some(user) => (false, user.as_ref()),
                     None => {
                         let user = match ctx.user.real() {
                             Some(user) => user,
@@ -519,11 +545,10 @@ impl command::Handler for Handler {
                     }
                 }
             }
-            Some("delete") => {
-                let removed = match ctx.next().as_ref().map(String::as_str) {
-                    Some("last") => match ctx.next() {
-                        Some(last_user) => {
-                            let last_user = last_user.to_lowercase();
+            "delete" => {
+                let removed = match ctx_try!(ctx.arg()) {
+                    Delete::Last => match ctx_try!(ctx.optional_arg()) {
+                        Some(command::User(last_user)) => {
                             ctx.check_scope(Scope::SongEditQueue)?;
                             player.remove_last_by_user(&last_user)?
                         }
@@ -532,7 +557,7 @@ impl command::Handler for Handler {
                             player.remove_last()?
                         }
                     },
-                    Some("mine") => {
+                    Delete::Mine => {
                         let user = match ctx.user.real() {
                             Some(user) => user,
                             None => {
@@ -543,20 +568,10 @@ impl command::Handler for Handler {
 
                         player.remove_last_by_user(user.name())?
                     }
-                    Some(n) => {
+                    Delete::Position(n) => {
                         ctx.check_scope(Scope::SongEditQueue)?;
-
-                        let n = match parse_queue_position(&ctx.user, n) {
-                            Some(n) => n,
-                            None => return Ok(()),
-                        };
-
                         player.remove_at(n)?
                     }
-                    None => {
-                        ctx.respond(format!("Expected: last, last <user>, or mine"));
-                        return Ok(());
-                    }
                 };
 
                 match removed {
@@ -564,32 +579,12 @@ impl command::Handler for Handler {
                     Some(item) => ctx.respond(format!("Removed: {}!", item.what())),
                 }
             }
-            Some("volume") => {
-                match ctx.next().as_ref().map(String::as_str) {
+            "volume" => {
+                match ctx_try!(ctx.optional_arg()) {
                     // setting volume
-                    Some(other) => {
+                    Some(volume) => {
                         ctx.check_scope(Scope::SongVolume)?;
 
-                        let (diff, argument) = match other.chars().next() {
-                            Some('+') => (Some(true), &other[1..]),
-                            Some('-') => (Some(false), &other[1..]),
-                            _ => (None, other),
-                        };
-
-                        let argument = match str::parse::<u32>(argument) {
-                            Ok(argument) => argument,
-                            Err(_) => {
-                                ctx.respond("expected whole number argument");
-                                return Ok(());
-                            }
-                        };
-
-                        let volume = match diff {
-                            Some(true) => player::ModifyVolume::Increase(argument),
-                            Some(false) => player::ModifyVolume::Decrease(argument),
-                            None => player::ModifyVolume::Set(argument),
-                        };
-
                         match player.volume(volume)? {
                             Some(volume) => {
                                 ctx.respond(format!("Updated volume to {}.", volume));
@@ -610,26 +605,22 @@ impl command::Handler for Handler {
                     },
                 }
             }
-            Some("skip") => {
-                ctx.check_scope(Scope::SongPlaybackControl)?;
+            "skip" => {
                 player.skip()?;
             }
-            Some("request") => {
+            "request" => {
                 self.handle_request(ctx, player)?;
             }
-            Some("toggle") => {
-                ctx.check_scope(Scope::SongPlaybackControl)?;
+            "toggle" => {
                 player.toggle()?;
             }
-            Some("play") => {
-                ctx.check_scope(Scope::SongPlaybackControl)?;
+            "play" => {
                 player.play()?;
             }
-            Some("pause") => {
-                ctx.check_scope(Scope::SongPlaybackControl)?;
+            "pause" => {
                 player.pause()?;
             }
-            Some("length") => {
+            "length" => {
                 let (count, duration) = player.length();
 
                 match count {
@@ -647,39 +638,7 @@ impl command::Handler for Handler {
                     }
                 }
             }
-            _ => {
-                let mut alts = Vec::new();
-
-                if ctx.user.has_scope(Scope::SongTheme) {
-                    alts.push("theme");
-                }
-
-                if ctx.user.has_scope(Scope::SongEditQueue) {
-                    alts.push("promote");
-                    alts.push("close");
-                    alts.push("open");
-                    alts.push("purge");
-                }
-
-                if ctx.user.has_scope(Scope::SongVolume) {
-                    alts.push("volume");
-                }
-
-                if ctx.user.has_scope(Scope::SongPlaybackControl) {
-                    alts.push("skip");
-                    alts.push("toggle");
-                    alts.push("play");
-                    alts.push("pause");
-                }
-
-                alts.push("list");
-                alts.push("current");
-                alts.push("when");
-                alts.push("delete");
-                alts.push("request");
-                alts.push("length");
-                ctx.respond(format!("Expected argument: {}.", alts.join(", ")));
-            }
+            _ => (),
         }
 
         Ok(())
@@ -791,21 +750,66 @@ impl Constraint {
     }
 }
 
-/// Parse a queue position.
-fn parse_queue_position(user: &irc::User, n: &str) -> Option<usize> {
-    match str::parse::<usize>(n) {
-        Ok(0) => {
-            user.respond("Can't mess with the current song :(");
-            return None;
+/// A position in the queue, starting at 1 for the song after the current one.
+struct Position(usize);
+
+impl command::Arg for Position {
+    fn expected() -> &'static str {
+        "a position in the queue, starting at 1"
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match str::parse::<usize>(s).ok()? {
+            0 => None,
+            n => Some(Position(n - 1)),
         }
-        Ok(n) => Some(n.saturating_sub(1)),
-        Err(_) => {
-            user.respond("Expected whole number argument");
-            return None;
+    }
+}
+
+/// Which song to delete.
+enum Delete {
+    /// The last song, optionally by a specific user.
+    Last,
+    /// The last song requested by the current user.
+    Mine,
+    /// The song at the given position.
+    Position(usize),
+}
+
+impl command::Arg for Delete {
+    fn expected() -> &'static str {
+        "`last`, `mine`, or a position in the queue"
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "last" => Some(Delete::Last),
+            "mine" => Some(Delete::Mine),
+            other => {
+                <Position as command::Arg>::parse(other).map(|Position(n)| Delete::Position(n))
+            }
         }
     }
 }
 
+impl command::Arg for player::ModifyVolume {
+    fn expected() -> &'static str {
+        "a volume, like `50`, `+10`, or `-10`"
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        if s.starts_with('+') {
+            return Some(player::ModifyVolume::Increase(str::parse(&s[1..]).ok()?));
+        }
+
+        if s.starts_with('-') {
+            return Some(player::ModifyVolume::Decrease(str::parse(&s[1..]).ok()?));
+        }
+
+        Some(player::ModifyVolume::Set(str::parse(s).ok()?))
+    }
+}
+
 /// Display the collection of songs.
 fn display_songs(
     user: &irc::User,
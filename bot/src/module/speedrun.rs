@@ -742,6 +742,10 @@ impl super::Module for Module {
         "8ball"
     }
 
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
     /// Set up command handlers for this module.
     async fn hook(
         &self,
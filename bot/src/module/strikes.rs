@@ -0,0 +1,114 @@
+use crate::{
+    auth,
+    command::{self, Param, Spec, Sub},
+    db, module,
+    prelude::*,
+    utils,
+};
+use chrono::Utc;
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+static SPEC: Spec = Spec {
+    subs: &[
+        Sub::new("show", &[Param::required("user")]),
+        Sub::new("clear", &[Param::required("user")]),
+    ],
+};
+
+/// Handler for the `!strikes` command.
+pub struct Strikes {
+    strikes: Arc<RwLock<Option<db::Strikes>>>,
+    decay: Arc<RwLock<utils::Duration>>,
+}
+
+#[async_trait]
+impl command::Handler for Strikes {
+    fn scope(&self) -> Option<auth::Scope> {
+        Some(auth::Scope::Strikes)
+    }
+
+    async fn handle(&mut self, mut ctx: command::Context<'_>) -> Result<(), failure::Error> {
+        let strikes = match self.strikes.read().clone() {
+            Some(strikes) => strikes,
+            None => return Ok(()),
+        };
+
+        match ctx_try!(ctx.subcommand(&SPEC)?) {
+            "show" => {
+                let user: command::User = ctx_try!(ctx.arg());
+                let decay = self.decay.read().clone();
+
+                let strike = match strikes.get(ctx.channel(), user.as_ref(), &decay)? {
+                    Some(strike) => strike,
+                    None => {
+                        ctx.respond(format!("{} doesn't have any strikes.", user));
+                        return Ok(());
+                    }
+                };
+
+                let ago = Utc::now()
+                    .naive_utc()
+                    .signed_duration_since(strike.struck_at)
+                    .to_std()
+                    .unwrap_or_default();
+
+                ctx.respond(format!(
+                    "{} has {} strike(s), the last one given {} ago.",
+                    user,
+                    strike.count,
+                    utils::compact_duration(&ago),
+                ));
+            }
+            "clear" => {
+                let user: command::User = ctx_try!(ctx.arg());
+
+                if strikes.clear(ctx.channel(), user.as_ref())? {
+                    ctx.respond(format!("Cleared the strikes of {}.", user));
+                } else {
+                    ctx.respond(format!("{} doesn't have any strikes.", user));
+                }
+            }
+            _ => (),
+        }
+
+        Ok(())
+    }
+}
+
+pub struct Module;
+
+#[async_trait]
+impl super::Module for Module {
+    fn ty(&self) -> &'static str {
+        "strikes"
+    }
+
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
+    /// Set up command handlers for this module.
+    async fn hook(
+        &self,
+        module::HookContext {
+            injector,
+            handlers,
+            settings,
+            ..
+        }: module::HookContext<'_, '_>,
+    ) -> Result<(), failure::Error> {
+        handlers.insert(
+            "strikes",
+            Strikes {
+                strikes: injector.var()?,
+                // NB: same settings as the escalation used by the chat handler.
+                decay: settings
+                    .scoped("chat/strikes")
+                    .var("decay", utils::Duration::hours(24))?,
+            },
+        );
+
+        Ok(())
+    }
+}
@@ -2,7 +2,7 @@ use crate::{
     api,
     auth::Scope,
     command,
-    currency::Currency,
+    currency::{Currency, LedgerReason},
     module,
     prelude::*,
     utils::{Cooldown, Duration},
@@ -63,11 +63,24 @@ impl<'a> command::Handler for Handler<'a> {
             let total_reward = reward * u.len() as i64;
 
             currency
-                .balance_add(user.channel(), &user.streamer().name, -total_reward)
+                .balance_add(
+                    user.channel(),
+                    &user.streamer().name,
+                    -total_reward,
+                    LedgerReason::Swearjar,
+                    None,
+                )
                 .await?;
 
             currency
-                .balances_increment(user.channel(), u, reward, 0)
+                .balances_increment(
+                    user.channel(),
+                    u,
+                    reward,
+                    0,
+                    LedgerReason::Swearjar,
+                    Some(&user.streamer().name),
+                )
                 .await?;
 
             user.sender().privmsg(format!(
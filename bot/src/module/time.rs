@@ -96,6 +96,10 @@ impl super::Module for Module {
         "time"
     }
 
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
     /// Set up command handlers for this module.
     async fn hook(
         &self,
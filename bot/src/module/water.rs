@@ -1,4 +1,10 @@
-use crate::{auth, command, currency::Currency, module, prelude::*, stream_info, utils};
+use crate::{
+    auth, command,
+    currency::{Currency, LedgerReason},
+    module,
+    prelude::*,
+    stream_info, utils,
+};
 use chrono::{DateTime, Utc};
 use failure::Error;
 use parking_lot::RwLock;
@@ -97,7 +103,13 @@ impl command::Handler for Handler {
                 ));
 
                 if let Err(e) = currency
-                    .balance_add(ctx.channel(), &reward.user, -reward.amount)
+                    .balance_add(
+                        ctx.channel(),
+                        &reward.user,
+                        -reward.amount,
+                        LedgerReason::Water,
+                        ctx.user.name(),
+                    )
                     .await
                 {
                     log::error!("failed to undo water from database: {}", e);
@@ -138,7 +150,13 @@ impl command::Handler for Handler {
                 ));
 
                 if let Err(e) = currency
-                    .balance_add(ctx.channel(), user.name(), amount)
+                    .balance_add(
+                        ctx.channel(),
+                        user.name(),
+                        amount,
+                        LedgerReason::Water,
+                        None,
+                    )
                     .await
                 {
                     log::error!("failed to appply water balance: {}", e);
@@ -153,6 +153,10 @@ impl super::Module for Module {
         "weather"
     }
 
+    fn multi_channel(&self) -> bool {
+        true
+    }
+
     /// Set up command handlers for this module.
     async fn hook(
         &self,
@@ -0,0 +1,539 @@
+//! A small, sandboxed scripting language for custom command handlers.
+//!
+//! Scripts are parsed once into a [Program], which can then be run any number
+//! of times against a [Host] providing the functions that the script is allowed
+//! to call. Every run is bounded by [Limits], so a misbehaving script can't
+//! stall the bot.
+//!
+//! ```text
+//! let amount = int(next());
+//!
+//! if amount == nil || amount <= 0 {
+//!     respond("Expected a positive amount");
+//!     return;
+//! }
+//!
+//! respond("You have " + str(balance(user()) * amount) + " " + currency());
+//! ```
+
+use crate::prelude::*;
+use hashbrown::HashMap;
+use std::{fmt, time::Instant};
+
+mod parser;
+
+use self::parser::{BinOp, Expr, Stmt, UnOp};
+pub use self::parser::{Position, Program};
+
+/// The maximum length of any string produced by a script.
+pub const MAX_STRING_LENGTH: usize = 4096;
+
+#[derive(Debug, err_derive::Error)]
+pub enum Error {
+    /// The script failed to parse.
+    #[error(display = "{}: {}", _0, _1)]
+    Syntax(Position, String),
+    /// Tried to access a variable which doesn't exist.
+    #[error(display = "no such variable `{}`", _0)]
+    MissingVariable(String),
+    /// Tried to call a function which doesn't exist.
+    #[error(display = "no such function `{}`", _0)]
+    MissingFunction(String),
+    /// Function called with bad arguments.
+    #[error(display = "bad arguments to `{}`: {}", _0, _1)]
+    BadArguments(String, String),
+    /// Operation applied to values of unsupported types.
+    #[error(display = "type error: {}", _0)]
+    Type(String),
+    /// Integer operation overflowed.
+    #[error(display = "integer overflow")]
+    Overflow,
+    /// Division by zero.
+    #[error(display = "division by zero")]
+    DivideByZero,
+    /// A string grew beyond `MAX_STRING_LENGTH`.
+    #[error(display = "string too long")]
+    StringTooLong,
+    /// The script ran for too many operations.
+    #[error(display = "operation limit exceeded")]
+    OperationLimit,
+    /// The script ran for too long.
+    #[error(display = "script timed out")]
+    Timeout,
+    /// Error raised by the host.
+    #[error(display = "{}", _0)]
+    Host(failure::Error),
+}
+
+/// A value in a script.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    String(String),
+}
+
+impl Value {
+    /// Test if the value is considered true in a condition.
+    pub fn is_truthy(&self) -> bool {
+        match *self {
+            Value::Nil => false,
+            Value::Bool(b) => b,
+            Value::Int(n) => n != 0,
+            Value::String(ref s) => !s.is_empty(),
+        }
+    }
+
+    /// Get the name of the type of the value.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::Nil => "nil",
+            Value::Bool(..) => "bool",
+            Value::Int(..) => "int",
+            Value::String(..) => "string",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Value::Nil => fmt.write_str("nil"),
+            Value::Bool(b) => b.fmt(fmt),
+            Value::Int(n) => n.fmt(fmt),
+            Value::String(ref s) => s.fmt(fmt),
+        }
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Self {
+        Value::Bool(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Self {
+        Value::Int(value)
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Self {
+        Value::String(value)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl<T> From<Option<T>> for Value
+where
+    Value: From<T>,
+{
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => Value::from(value),
+            None => Value::Nil,
+        }
+    }
+}
+
+/// Limits imposed on a single run of a script.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    /// The maximum number of operations the script may perform.
+    pub max_operations: u64,
+    /// The point in time at which the script is aborted.
+    pub deadline: Instant,
+}
+
+/// The environment a script runs in.
+///
+/// All functions which are not built into the language are resolved through
+/// the host.
+#[async_trait]
+pub trait Host: Send {
+    /// Call the host function `name` with the given arguments.
+    async fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, Error>;
+}
+
+/// Run the given program against a host.
+pub async fn run<H>(program: &Program, host: &mut H, limits: Limits) -> Result<(), Error>
+where
+    H: Host,
+{
+    let mut vm = Vm {
+        host,
+        limits,
+        operations: 0,
+        vars: HashMap::new(),
+    };
+
+    vm.block(&program.statements).await?;
+    Ok(())
+}
+
+/// Control flow out of a block.
+enum Flow {
+    Continue,
+    Return,
+}
+
+struct Vm<'a, H> {
+    host: &'a mut H,
+    limits: Limits,
+    operations: u64,
+    vars: HashMap<String, Value>,
+}
+
+impl<'a, H> Vm<'a, H>
+where
+    H: Host,
+{
+    /// Account for a single operation.
+    fn tick(&mut self) -> Result<(), Error> {
+        self.operations += 1;
+
+        if self.operations > self.limits.max_operations {
+            return Err(Error::OperationLimit);
+        }
+
+        if Instant::now() >= self.limits.deadline {
+            return Err(Error::Timeout);
+        }
+
+        Ok(())
+    }
+
+    fn block<'b>(
+        &'b mut self,
+        statements: &'b [Stmt],
+    ) -> future::BoxFuture<'b, Result<Flow, Error>> {
+        async move {
+            for stmt in statements {
+                if let Flow::Return = self.stmt(stmt).await? {
+                    return Ok(Flow::Return);
+                }
+            }
+
+            Ok(Flow::Continue)
+        }
+        .boxed()
+    }
+
+    async fn stmt(&mut self, stmt: &Stmt) -> Result<Flow, Error> {
+        self.tick()?;
+
+        match stmt {
+            Stmt::Let(name, expr) => {
+                let value = self.expr(expr).await?;
+                self.vars.insert(name.clone(), value);
+            }
+            Stmt::Assign(name, expr) => {
+                let value = self.expr(expr).await?;
+
+                match self.vars.get_mut(name) {
+                    Some(var) => *var = value,
+                    None => return Err(Error::MissingVariable(name.clone())),
+                }
+            }
+            Stmt::If(branches, fallback) => {
+                for (cond, block) in branches {
+                    if self.expr(cond).await?.is_truthy() {
+                        return self.block(block).await;
+                    }
+                }
+
+                if let Some(block) = fallback {
+                    return self.block(block).await;
+                }
+            }
+            Stmt::While(cond, block) => {
+                while self.expr(cond).await?.is_truthy() {
+                    if let Flow::Return = self.block(block).await? {
+                        return Ok(Flow::Return);
+                    }
+                }
+            }
+            Stmt::Return => return Ok(Flow::Return),
+            Stmt::Expr(expr) => {
+                self.expr(expr).await?;
+            }
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    fn expr<'b>(&'b mut self, expr: &'b Expr) -> future::BoxFuture<'b, Result<Value, Error>> {
+        async move {
+            self.tick()?;
+
+            let value = match expr {
+                Expr::Nil => Value::Nil,
+                Expr::Bool(b) => Value::Bool(*b),
+                Expr::Int(n) => Value::Int(*n),
+                Expr::String(s) => Value::String(s.clone()),
+                Expr::Var(name) => match self.vars.get(name) {
+                    Some(value) => value.clone(),
+                    None => return Err(Error::MissingVariable(name.clone())),
+                },
+                Expr::Unary(op, expr) => {
+                    let value = self.expr(expr).await?;
+
+                    match (op, value) {
+                        (UnOp::Not, value) => Value::Bool(!value.is_truthy()),
+                        (UnOp::Neg, Value::Int(n)) => {
+                            Value::Int(n.checked_neg().ok_or(Error::Overflow)?)
+                        }
+                        (UnOp::Neg, value) => {
+                            return Err(Error::Type(format!(
+                                "cannot negate {}",
+                                value.type_name()
+                            )));
+                        }
+                    }
+                }
+                Expr::Binary(BinOp::And, lhs, rhs) => {
+                    let value =
+                        self.expr(lhs).await?.is_truthy() && self.expr(rhs).await?.is_truthy();
+                    Value::Bool(value)
+                }
+                Expr::Binary(BinOp::Or, lhs, rhs) => {
+                    let value =
+                        self.expr(lhs).await?.is_truthy() || self.expr(rhs).await?.is_truthy();
+                    Value::Bool(value)
+                }
+                Expr::Binary(op, lhs, rhs) => {
+                    let lhs = self.expr(lhs).await?;
+                    let rhs = self.expr(rhs).await?;
+                    binary(*op, lhs, rhs)?
+                }
+                Expr::Call(name, args) => {
+                    let mut values = Vec::with_capacity(args.len());
+
+                    for arg in args {
+                        values.push(self.expr(arg).await?);
+                    }
+
+                    match builtin(name, values)? {
+                        Ok(value) => value,
+                        Err(values) => self.host.call(name, values).await?,
+                    }
+                }
+            };
+
+            if let Value::String(s) = &value {
+                if s.len() > MAX_STRING_LENGTH {
+                    return Err(Error::StringTooLong);
+                }
+            }
+
+            Ok(value)
+        }
+        .boxed()
+    }
+}
+
+/// Apply a binary operator.
+fn binary(op: BinOp, lhs: Value, rhs: Value) -> Result<Value, Error> {
+    let value = match (op, lhs, rhs) {
+        (BinOp::Eq, lhs, rhs) => Value::Bool(lhs == rhs),
+        (BinOp::Neq, lhs, rhs) => Value::Bool(lhs != rhs),
+        (BinOp::Add, Value::Int(a), Value::Int(b)) => {
+            Value::Int(a.checked_add(b).ok_or(Error::Overflow)?)
+        }
+        (BinOp::Add, Value::String(a), b) => concat(a, b)?,
+        (BinOp::Add, a, Value::String(b)) => concat(a.to_string(), Value::String(b))?,
+        (BinOp::Sub, Value::Int(a), Value::Int(b)) => {
+            Value::Int(a.checked_sub(b).ok_or(Error::Overflow)?)
+        }
+        (BinOp::Mul, Value::Int(a), Value::Int(b)) => {
+            Value::Int(a.checked_mul(b).ok_or(Error::Overflow)?)
+        }
+        (BinOp::Div, Value::Int(_), Value::Int(0)) | (BinOp::Rem, Value::Int(_), Value::Int(0)) => {
+            return Err(Error::DivideByZero);
+        }
+        (BinOp::Div, Value::Int(a), Value::Int(b)) => {
+            Value::Int(a.checked_div(b).ok_or(Error::Overflow)?)
+        }
+        (BinOp::Rem, Value::Int(a), Value::Int(b)) => {
+            Value::Int(a.checked_rem(b).ok_or(Error::Overflow)?)
+        }
+        (BinOp::Lt, Value::Int(a), Value::Int(b)) => Value::Bool(a < b),
+        (BinOp::Lte, Value::Int(a), Value::Int(b)) => Value::Bool(a <= b),
+        (BinOp::Gt, Value::Int(a), Value::Int(b)) => Value::Bool(a > b),
+        (BinOp::Gte, Value::Int(a), Value::Int(b)) => Value::Bool(a >= b),
+        (BinOp::Lt, Value::String(a), Value::String(b)) => Value::Bool(a < b),
+        (BinOp::Lte, Value::String(a), Value::String(b)) => Value::Bool(a <= b),
+        (BinOp::Gt, Value::String(a), Value::String(b)) => Value::Bool(a > b),
+        (BinOp::Gte, Value::String(a), Value::String(b)) => Value::Bool(a >= b),
+        (op, lhs, rhs) => {
+            return Err(Error::Type(format!(
+                "unsupported operation {:?} between {} and {}",
+                op,
+                lhs.type_name(),
+                rhs.type_name()
+            )));
+        }
+    };
+
+    Ok(value)
+}
+
+/// Concatenate a string with any other value.
+fn concat(mut a: String, b: Value) -> Result<Value, Error> {
+    use std::fmt::Write as _;
+
+    write!(a, "{}", b).map_err(|e| Error::Host(e.into()))?;
+
+    if a.len() > MAX_STRING_LENGTH {
+        return Err(Error::StringTooLong);
+    }
+
+    Ok(Value::String(a))
+}
+
+/// Call one of the functions built into the language.
+///
+/// Gives back the arguments if the function is not a builtin, so that the call
+/// can be forwarded to the host.
+fn builtin(name: &str, args: Vec<Value>) -> Result<Result<Value, Vec<Value>>, Error> {
+    let value = match (name, args.as_slice()) {
+        ("str", [value]) => match value {
+            Value::Nil => Value::Nil,
+            value => Value::String(value.to_string()),
+        },
+        ("int", [Value::Int(n)]) => Value::Int(*n),
+        ("int", [Value::String(s)]) => s.trim().parse::<i64>().ok().into(),
+        ("int", [_]) => Value::Nil,
+        ("len", [Value::String(s)]) => Value::Int(s.chars().count() as i64),
+        ("lower", [Value::String(s)]) => Value::String(s.to_lowercase()),
+        ("upper", [Value::String(s)]) => Value::String(s.to_uppercase()),
+        ("random", [Value::Int(low), Value::Int(high)]) => {
+            use rand::Rng as _;
+
+            if low >= high {
+                return Err(Error::BadArguments(
+                    name.to_string(),
+                    String::from("expected low < high"),
+                ));
+            }
+
+            Value::Int(rand::thread_rng().gen_range(*low, *high))
+        }
+        ("str", _) | ("int", _) | ("len", _) | ("lower", _) | ("upper", _) | ("random", _) => {
+            return Err(Error::BadArguments(
+                name.to_string(),
+                describe_arguments(&args),
+            ));
+        }
+        _ => return Ok(Err(args)),
+    };
+
+    Ok(Ok(value))
+}
+
+/// Describe the types of the given arguments.
+pub fn describe_arguments(args: &[Value]) -> String {
+    let types = args.iter().map(Value::type_name).collect::<Vec<_>>();
+    format!("got ({})", types.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Error, Host, Limits, Program, Value};
+    use std::time::{Duration, Instant};
+
+    struct Recorder {
+        responses: Vec<String>,
+    }
+
+    #[async_trait::async_trait]
+    impl Host for Recorder {
+        async fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, Error> {
+            match (name, args.as_slice()) {
+                ("respond", [value]) => {
+                    self.responses.push(value.to_string());
+                    Ok(Value::Nil)
+                }
+                _ => Err(Error::MissingFunction(name.to_string())),
+            }
+        }
+    }
+
+    fn run(source: &str, max_operations: u64) -> Result<Vec<String>, Error> {
+        let program = Program::parse(source)?;
+        let mut host = Recorder {
+            responses: Vec::new(),
+        };
+
+        let limits = Limits {
+            max_operations,
+            deadline: Instant::now() + Duration::from_secs(10),
+        };
+
+        futures::executor::block_on(super::run(&program, &mut host, limits))?;
+        Ok(host.responses)
+    }
+
+    #[test]
+    pub fn test_run() -> Result<(), Error> {
+        assert!(match run("missing = 1;", 1000) {
+            Err(Error::MissingVariable(ref name)) => name == "missing",
+            _ => false,
+        });
+
+        let source = r#"
+            // sum up some numbers.
+            let n = 0;
+            let sum = 0;
+
+            while n < 5 {
+                n = n + 1;
+                sum = sum + n * 2;
+            }
+
+            if sum > 100 {
+                respond("big");
+            } else if sum == 30 && !false {
+                respond("sum: " + sum);
+                return;
+            }
+
+            respond("unreachable");
+        "#;
+
+        assert_eq!(vec![String::from("sum: 30")], run(source, 1000)?);
+        Ok(())
+    }
+
+    #[test]
+    pub fn test_limits() {
+        assert!(match run("while true { }", 1000) {
+            Err(Error::OperationLimit) => true,
+            _ => false,
+        });
+
+        assert!(match run("let a = 1 / 0;", 1000) {
+            Err(Error::DivideByZero) => true,
+            _ => false,
+        });
+
+        assert!(
+            match run("let s = \"xxxx\"; while true { s = s + s; }", 1000) {
+                Err(Error::StringTooLong) => true,
+                _ => false,
+            }
+        );
+
+        assert!(match Program::parse("let a = ;") {
+            Err(Error::Syntax(pos, _)) => pos.line == 1 && pos.column == 9,
+            _ => false,
+        });
+    }
+}
@@ -0,0 +1,578 @@
+//! Lexer and parser for scripts.
+
+use super::Error;
+use std::fmt;
+
+/// A position in the source of a script.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(fmt, "{}:{}", self.line, self.column)
+    }
+}
+
+/// A parsed script, ready to run.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub(super) statements: Vec<Stmt>,
+}
+
+impl Program {
+    /// Parse the given source into a program.
+    pub fn parse(source: &str) -> Result<Program, Error> {
+        let tokens = Lexer::new(source).tokenize()?;
+
+        let mut parser = Parser { tokens, pos: 0 };
+
+        let mut statements = Vec::new();
+
+        while !parser.is_eof() {
+            statements.push(parser.statement()?);
+        }
+
+        Ok(Program { statements })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum Stmt {
+    /// `let <name> = <expr>;`
+    Let(String, Expr),
+    /// `<name> = <expr>;`
+    Assign(String, Expr),
+    /// `if <expr> { .. } else if <expr> { .. } else { .. }`
+    If(Vec<(Expr, Vec<Stmt>)>, Option<Vec<Stmt>>),
+    /// `while <expr> { .. }`
+    While(Expr, Vec<Stmt>),
+    /// `return;`
+    Return,
+    /// `<expr>;`
+    Expr(Expr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum UnOp {
+    Not,
+    Neg,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum Expr {
+    Nil,
+    Bool(bool),
+    Int(i64),
+    String(String),
+    Var(String),
+    Unary(UnOp, Box<Expr>),
+    Binary(BinOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    Ident(String),
+    Int(i64),
+    String(String),
+    Let,
+    If,
+    Else,
+    While,
+    Return,
+    True,
+    False,
+    Nil,
+    OpenParen,
+    CloseParen,
+    OpenBrace,
+    CloseBrace,
+    Comma,
+    SemiColon,
+    Assign,
+    Eq,
+    Neq,
+    Lt,
+    Lte,
+    Gt,
+    Gte,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    Bang,
+    And,
+    Or,
+    Eof,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Kind::Ident(ident) => return write!(fmt, "`{}`", ident),
+            Kind::Int(n) => return write!(fmt, "`{}`", n),
+            Kind::String(s) => return write!(fmt, "{:?}", s),
+            Kind::Let => "`let`",
+            Kind::If => "`if`",
+            Kind::Else => "`else`",
+            Kind::While => "`while`",
+            Kind::Return => "`return`",
+            Kind::True => "`true`",
+            Kind::False => "`false`",
+            Kind::Nil => "`nil`",
+            Kind::OpenParen => "`(`",
+            Kind::CloseParen => "`)`",
+            Kind::OpenBrace => "`{`",
+            Kind::CloseBrace => "`}`",
+            Kind::Comma => "`,`",
+            Kind::SemiColon => "`;`",
+            Kind::Assign => "`=`",
+            Kind::Eq => "`==`",
+            Kind::Neq => "`!=`",
+            Kind::Lt => "`<`",
+            Kind::Lte => "`<=`",
+            Kind::Gt => "`>`",
+            Kind::Gte => "`>=`",
+            Kind::Plus => "`+`",
+            Kind::Minus => "`-`",
+            Kind::Star => "`*`",
+            Kind::Slash => "`/`",
+            Kind::Percent => "`%`",
+            Kind::Bang => "`!`",
+            Kind::And => "`&&`",
+            Kind::Or => "`||`",
+            Kind::Eof => "end of script",
+        };
+
+        fmt.write_str(s)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: Kind,
+    pos: Position,
+}
+
+struct Lexer<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    pos: Position,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            chars: source.chars().peekable(),
+            pos: Position { line: 1, column: 1 },
+        }
+    }
+
+    /// Take the next character, keeping track of the current position.
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+
+        if c == '\n' {
+            self.pos.line += 1;
+            self.pos.column = 1;
+        } else {
+            self.pos.column += 1;
+        }
+
+        Some(c)
+    }
+
+    /// Take the next character if it matches the expected one.
+    fn eat(&mut self, expected: char) -> bool {
+        if self.chars.peek() == Some(&expected) {
+            self.bump();
+            return true;
+        }
+
+        false
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Token>, Error> {
+        let mut tokens = Vec::new();
+
+        loop {
+            let pos = self.pos;
+
+            let c = match self.bump() {
+                Some(c) => c,
+                None => {
+                    tokens.push(Token {
+                        kind: Kind::Eof,
+                        pos,
+                    });
+
+                    return Ok(tokens);
+                }
+            };
+
+            let kind = match c {
+                c if c.is_whitespace() => continue,
+                '/' if self.eat('/') => {
+                    while let Some(c) = self.bump() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+
+                    continue;
+                }
+                '(' => Kind::OpenParen,
+                ')' => Kind::CloseParen,
+                '{' => Kind::OpenBrace,
+                '}' => Kind::CloseBrace,
+                ',' => Kind::Comma,
+                ';' => Kind::SemiColon,
+                '+' => Kind::Plus,
+                '-' => Kind::Minus,
+                '*' => Kind::Star,
+                '/' => Kind::Slash,
+                '%' => Kind::Percent,
+                '=' if self.eat('=') => Kind::Eq,
+                '=' => Kind::Assign,
+                '!' if self.eat('=') => Kind::Neq,
+                '!' => Kind::Bang,
+                '<' if self.eat('=') => Kind::Lte,
+                '<' => Kind::Lt,
+                '>' if self.eat('=') => Kind::Gte,
+                '>' => Kind::Gt,
+                '&' if self.eat('&') => Kind::And,
+                '|' if self.eat('|') => Kind::Or,
+                '"' => Kind::String(self.string(pos)?),
+                c if c.is_ascii_digit() => {
+                    let mut n = c.to_digit(10).unwrap_or_default() as i64;
+
+                    while let Some(d) = self.chars.peek().and_then(|c| c.to_digit(10)) {
+                        self.bump();
+
+                        n = n
+                            .checked_mul(10)
+                            .and_then(|n| n.checked_add(d as i64))
+                            .ok_or_else(|| {
+                                Error::Syntax(pos, String::from("number literal too large"))
+                            })?;
+                    }
+
+                    Kind::Int(n)
+                }
+                c if c.is_alphabetic() || c == '_' => {
+                    let mut ident = c.to_string();
+
+                    while let Some(&c) = self.chars.peek() {
+                        if !c.is_alphanumeric() && c != '_' {
+                            break;
+                        }
+
+                        ident.push(c);
+                        self.bump();
+                    }
+
+                    match ident.as_str() {
+                        "let" => Kind::Let,
+                        "if" => Kind::If,
+                        "else" => Kind::Else,
+                        "while" => Kind::While,
+                        "return" => Kind::Return,
+                        "true" => Kind::True,
+                        "false" => Kind::False,
+                        "nil" => Kind::Nil,
+                        _ => Kind::Ident(ident),
+                    }
+                }
+                c => {
+                    return Err(Error::Syntax(pos, format!("unexpected character `{}`", c)));
+                }
+            };
+
+            tokens.push(Token { kind, pos });
+        }
+    }
+
+    /// Parse the rest of a string literal.
+    fn string(&mut self, pos: Position) -> Result<String, Error> {
+        let mut s = String::new();
+
+        loop {
+            let c = match self.bump() {
+                Some(c) => c,
+                None => return Err(Error::Syntax(pos, String::from("unterminated string"))),
+            };
+
+            match c {
+                '"' => return Ok(s),
+                '\\' => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('\\') => s.push('\\'),
+                    Some('"') => s.push('"'),
+                    Some(c) => {
+                        return Err(Error::Syntax(
+                            self.pos,
+                            format!("unsupported escape sequence `\\{}`", c),
+                        ))
+                    }
+                    None => return Err(Error::Syntax(pos, String::from("unterminated string"))),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        // NB: the last token is always `Eof`.
+        &self.tokens[usize::min(self.pos, self.tokens.len() - 1)]
+    }
+
+    fn is_eof(&self) -> bool {
+        self.peek().kind == Kind::Eof
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek().clone();
+
+        if token.kind != Kind::Eof {
+            self.pos += 1;
+        }
+
+        token
+    }
+
+    /// Take the next token if it is of the given kind.
+    fn eat(&mut self, kind: &Kind) -> bool {
+        if self.peek().kind == *kind {
+            self.next();
+            return true;
+        }
+
+        false
+    }
+
+    fn expect(&mut self, kind: Kind) -> Result<(), Error> {
+        let token = self.next();
+
+        if token.kind != kind {
+            return Err(Error::Syntax(
+                token.pos,
+                format!("expected {} but got {}", kind, token.kind),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn ident(&mut self) -> Result<String, Error> {
+        let token = self.next();
+
+        match token.kind {
+            Kind::Ident(ident) => Ok(ident),
+            kind => Err(Error::Syntax(
+                token.pos,
+                format!("expected identifier but got {}", kind),
+            )),
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Stmt>, Error> {
+        self.expect(Kind::OpenBrace)?;
+
+        let mut statements = Vec::new();
+
+        while !self.eat(&Kind::CloseBrace) {
+            if self.is_eof() {
+                let pos = self.peek().pos;
+                return Err(Error::Syntax(pos, String::from("expected `}`")));
+            }
+
+            statements.push(self.statement()?);
+        }
+
+        Ok(statements)
+    }
+
+    fn statement(&mut self) -> Result<Stmt, Error> {
+        let stmt = match self.peek().kind {
+            Kind::Let => {
+                self.next();
+                let name = self.ident()?;
+                self.expect(Kind::Assign)?;
+                let expr = self.expr()?;
+                Stmt::Let(name, expr)
+            }
+            Kind::If => {
+                self.next();
+
+                let mut branches = Vec::new();
+                let mut fallback = None;
+
+                loop {
+                    let cond = self.expr()?;
+                    let block = self.block()?;
+                    branches.push((cond, block));
+
+                    if !self.eat(&Kind::Else) {
+                        break;
+                    }
+
+                    if !self.eat(&Kind::If) {
+                        fallback = Some(self.block()?);
+                        break;
+                    }
+                }
+
+                return Ok(Stmt::If(branches, fallback));
+            }
+            Kind::While => {
+                self.next();
+                let cond = self.expr()?;
+                let block = self.block()?;
+                return Ok(Stmt::While(cond, block));
+            }
+            Kind::Return => {
+                self.next();
+                Stmt::Return
+            }
+            Kind::Ident(..)
+                if self.tokens.get(self.pos + 1).map(|t| &t.kind) == Some(&Kind::Assign) =>
+            {
+                let name = self.ident()?;
+                self.expect(Kind::Assign)?;
+                let expr = self.expr()?;
+                Stmt::Assign(name, expr)
+            }
+            _ => Stmt::Expr(self.expr()?),
+        };
+
+        self.expect(Kind::SemiColon)?;
+        Ok(stmt)
+    }
+
+    fn expr(&mut self) -> Result<Expr, Error> {
+        self.binary(0)
+    }
+
+    /// Parse a binary expression using precedence climbing.
+    fn binary(&mut self, min: u8) -> Result<Expr, Error> {
+        let mut lhs = self.unary()?;
+
+        loop {
+            let op = match self.peek().kind {
+                Kind::Or => BinOp::Or,
+                Kind::And => BinOp::And,
+                Kind::Eq => BinOp::Eq,
+                Kind::Neq => BinOp::Neq,
+                Kind::Lt => BinOp::Lt,
+                Kind::Lte => BinOp::Lte,
+                Kind::Gt => BinOp::Gt,
+                Kind::Gte => BinOp::Gte,
+                Kind::Plus => BinOp::Add,
+                Kind::Minus => BinOp::Sub,
+                Kind::Star => BinOp::Mul,
+                Kind::Slash => BinOp::Div,
+                Kind::Percent => BinOp::Rem,
+                _ => return Ok(lhs),
+            };
+
+            let precedence = match op {
+                BinOp::Or => 1,
+                BinOp::And => 2,
+                BinOp::Eq | BinOp::Neq => 3,
+                BinOp::Lt | BinOp::Lte | BinOp::Gt | BinOp::Gte => 4,
+                BinOp::Add | BinOp::Sub => 5,
+                BinOp::Mul | BinOp::Div | BinOp::Rem => 6,
+            };
+
+            if precedence <= min {
+                return Ok(lhs);
+            }
+
+            self.next();
+            let rhs = self.binary(precedence)?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expr, Error> {
+        if self.eat(&Kind::Bang) {
+            return Ok(Expr::Unary(UnOp::Not, Box::new(self.unary()?)));
+        }
+
+        if self.eat(&Kind::Minus) {
+            return Ok(Expr::Unary(UnOp::Neg, Box::new(self.unary()?)));
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, Error> {
+        let token = self.next();
+
+        let expr = match token.kind {
+            Kind::Nil => Expr::Nil,
+            Kind::True => Expr::Bool(true),
+            Kind::False => Expr::Bool(false),
+            Kind::Int(n) => Expr::Int(n),
+            Kind::String(s) => Expr::String(s),
+            Kind::OpenParen => {
+                let expr = self.expr()?;
+                self.expect(Kind::CloseParen)?;
+                expr
+            }
+            Kind::Ident(name) => {
+                if !self.eat(&Kind::OpenParen) {
+                    return Ok(Expr::Var(name));
+                }
+
+                let mut args = Vec::new();
+
+                while !self.eat(&Kind::CloseParen) {
+                    if !args.is_empty() {
+                        self.expect(Kind::Comma)?;
+                    }
+
+                    args.push(self.expr()?);
+                }
+
+                Expr::Call(name, args)
+            }
+            kind => {
+                return Err(Error::Syntax(
+                    token.pos,
+                    format!("expected expression but got {}", kind),
+                ));
+            }
+        };
+
+        Ok(expr)
+    }
+}
@@ -9,6 +9,9 @@ use parking_lot::{Mutex, RwLock};
 use std::{borrow::Cow, error, fmt, marker, pin::Pin, sync::Arc};
 
 const SEPARATOR: char = '/';
+/// Prefix under which settings for additional channels are stored, like
+/// `channels/<channel>/song/enabled`.
+pub const CHANNELS_PREFIX: &str = "channels";
 
 type EventSender = mpsc::UnboundedSender<Event<serde_json::Value>>;
 type Subscriptions = Arc<RwLock<HashMap<String, Vec<EventSender>>>>;
@@ -233,12 +236,19 @@ impl Schema {
 
     /// Lookup the given type by key.
     pub fn lookup(&self, key: &str) -> Option<SchemaType> {
-        self.types.get(key).cloned()
+        self.get(key).cloned()
+    }
+
+    /// Get the type of the given key.
+    ///
+    /// Channel-scoped keys use the same schema as their unscoped counterpart.
+    fn get(&self, key: &str) -> Option<&SchemaType> {
+        self.types.get(schema_key(key))
     }
 
     /// Test if schema contains the given key.
     pub fn contains(&self, key: &str) -> bool {
-        self.types.contains_key(key)
+        self.types.contains_key(schema_key(key))
     }
 }
 
@@ -390,7 +400,7 @@ impl Settings {
     /// Lookup the given schema.
     pub fn lookup(&self, key: &str) -> Option<&SchemaType> {
         let key = self.key(key);
-        self.inner.schema.types.get(key.as_ref())
+        self.inner.schema.get(key.as_ref())
     }
 
     /// Get a setting by prefix.
@@ -398,10 +408,13 @@ impl Settings {
         use self::db::schema::settings::dsl;
 
         let prefix = self.key(prefix);
+        let schema_prefix = schema_key(prefix.as_ref());
+        // NB: the channel scope of the prefix, if any.
+        let channel_scope = &prefix[..(prefix.len() - schema_prefix.len())];
 
         let c = self.inner.db.pool.lock();
 
-        let prefix = match self.inner.prefixes.get(prefix.as_ref()) {
+        let prefix = match self.inner.prefixes.get(schema_prefix) {
             Some(prefix) => prefix,
             None => return Ok(Vec::default()),
         };
@@ -421,7 +434,9 @@ impl Settings {
                 None => continue,
             };
 
-            let value = match values.get(key) {
+            let key = format!("{}{}", channel_scope, key);
+
+            let value = match values.get(&key) {
                 Some(value) => serde_json::from_str(value)?,
                 None if schema.ty.optional => serde_json::Value::Null,
                 None => continue,
@@ -429,7 +444,7 @@ impl Settings {
 
             settings.push(Setting {
                 schema: schema.clone(),
-                key: key.to_string(),
+                key,
                 value,
             });
         }
@@ -446,7 +461,7 @@ impl Settings {
     {
         let key = self.key(key);
 
-        let schema = match self.inner.schema.types.get(key.as_ref()) {
+        let schema = match self.inner.schema.get(key.as_ref()) {
             Some(schema) => schema,
             None => return Ok(None),
         };
@@ -836,6 +851,19 @@ impl Settings {
     }
 }
 
+/// Get the schema key for the given key, stripping any channel scope.
+///
+/// For example, `channels/setbac/song/enabled` has the schema key
+/// `song/enabled`.
+fn schema_key(key: &str) -> &str {
+    let mut it = key.splitn(3, SEPARATOR);
+
+    match (it.next(), it.next(), it.next()) {
+        (Some(CHANNELS_PREFIX), Some(_), Some(rest)) => rest,
+        _ => key,
+    }
+}
+
 #[must_use = "Must consume to drive decide how to handle stream"]
 pub struct StreamBuilder<'a, T> {
     settings: &'a Settings,
@@ -3,6 +3,7 @@ use parking_lot::Mutex;
 use percent_encoding::PercentDecode;
 use std::{borrow, fmt, mem, sync::Arc, time};
 
+mod cron;
 mod duration;
 
 pub type Futures<'a> =
@@ -24,6 +25,7 @@ impl<'a> Driver<'a> for Vec<future::BoxFuture<'a, Result<(), failure::Error>>> {
     }
 }
 
+pub use self::cron::Cron;
 pub use self::duration::Duration;
 
 pub struct Urls<'a> {
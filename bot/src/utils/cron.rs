@@ -0,0 +1,239 @@
+use chrono::{DateTime, Datelike as _, Duration, TimeZone as _, Timelike as _, Utc};
+use std::fmt;
+
+/// The maximum number of steps to take when searching for the next matching
+/// time. Enough to cover impossible expressions like `0 0 31 2 *` without
+/// spinning forever.
+const MAX_STEPS: usize = 100_000;
+
+/// A standard five-field cron expression, evaluated in UTC.
+///
+/// Fields are: minute, hour, day of month, month, and day of week.
+/// Each field supports `*`, single values, ranges (`a-b`), lists (`a,b`), and
+/// steps (`*/n` or `a-b/n`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    source: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    /// If day of month is unrestricted (starts with `*`).
+    any_day_of_month: bool,
+    /// If day of week is unrestricted (starts with `*`).
+    any_day_of_week: bool,
+}
+
+impl Cron {
+    /// Find the next time after `after` that matches the expression.
+    pub fn next_after(&self, after: &DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = Utc.ymd(after.year(), after.month(), after.day()).and_hms(
+            after.hour(),
+            after.minute(),
+            0,
+        ) + Duration::minutes(1);
+
+        for _ in 0..MAX_STEPS {
+            if !is_set(self.months, t.month()) {
+                let (year, month) = match t.month() {
+                    12 => (t.year() + 1, 1),
+                    month => (t.year(), month + 1),
+                };
+
+                t = Utc.ymd(year, month, 1).and_hms(0, 0, 0);
+                continue;
+            }
+
+            if !self.matches_day(&t) {
+                t = Utc.ymd(t.year(), t.month(), t.day()).and_hms(0, 0, 0) + Duration::days(1);
+                continue;
+            }
+
+            if !is_set(self.hours, t.hour()) {
+                t = Utc
+                    .ymd(t.year(), t.month(), t.day())
+                    .and_hms(t.hour(), 0, 0)
+                    + Duration::hours(1);
+                continue;
+            }
+
+            if !is_set(self.minutes, t.minute()) {
+                t = t + Duration::minutes(1);
+                continue;
+            }
+
+            return Some(t);
+        }
+
+        None
+    }
+
+    /// Test if the day of the given time matches.
+    ///
+    /// Like in traditional cron, if both day of month and day of week are
+    /// restricted, matching either is sufficient.
+    fn matches_day(&self, t: &DateTime<Utc>) -> bool {
+        let dom = is_set(self.days_of_month, t.day());
+        let dow = is_set(self.days_of_week, t.weekday().num_days_from_sunday());
+
+        match (self.any_day_of_month, self.any_day_of_week) {
+            (false, false) => dom || dow,
+            _ => dom && dow,
+        }
+    }
+}
+
+impl std::str::FromStr for Cron {
+    type Err = failure::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+
+        let (minutes, hours, days_of_month, months, days_of_week) = match *fields.as_slice() {
+            [a, b, c, d, e] => (a, b, c, d, e),
+            _ => failure::bail!("expected five fields, but got {}", fields.len()),
+        };
+
+        let mut days_of_week = parse_field(days_of_week, 0, 7)?;
+
+        // NB: both 0 and 7 are sunday.
+        if is_set(days_of_week, 7) {
+            days_of_week |= 1;
+        }
+
+        Ok(Cron {
+            source: fields.join(" "),
+            minutes: parse_field(minutes, 0, 59)?,
+            hours: parse_field(hours, 0, 23)?,
+            days_of_month: parse_field(days_of_month, 1, 31)?,
+            months: parse_field(months, 1, 12)?,
+            days_of_week,
+            // NB: like in traditional cron, steps like `*/2` also count as
+            // unrestricted.
+            any_day_of_month: fields[2].starts_with('*'),
+            any_day_of_week: fields[4].starts_with('*'),
+        })
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(fmt)
+    }
+}
+
+/// Test if the given value is set in the bitset.
+fn is_set(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Parse a single field into a bitset of matching values.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, failure::Error> {
+    let mut set = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(i) => (&part[..i], str::parse::<u32>(&part[(i + 1)..])?),
+            None => (part, 1),
+        };
+
+        if step == 0 {
+            failure::bail!("bad step in `{}`", part);
+        }
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.find('-') {
+                Some(i) => (
+                    str::parse::<u32>(&range[..i])?,
+                    str::parse::<u32>(&range[(i + 1)..])?,
+                ),
+                None => {
+                    let value = str::parse::<u32>(range)?;
+                    (value, value)
+                }
+            },
+        };
+
+        if start < min || end > max || start > end {
+            failure::bail!("`{}` is out of range {}-{}", part, min, max);
+        }
+
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Cron;
+    use chrono::{TimeZone as _, Utc};
+
+    #[test]
+    pub fn test_parse_cron() {
+        assert!(str::parse::<Cron>("* * * * *").is_ok());
+        assert!(str::parse::<Cron>("*/5 1-5,10 * 1 0-6").is_ok());
+        assert!(str::parse::<Cron>("* * * *").is_err());
+        assert!(str::parse::<Cron>("60 * * * *").is_err());
+        assert!(str::parse::<Cron>("*/0 * * * *").is_err());
+        assert!(str::parse::<Cron>("5-1 * * * *").is_err());
+    }
+
+    #[test]
+    pub fn test_next_after() {
+        let at = Utc.ymd(2019, 10, 14).and_hms(12, 34, 56);
+
+        let cron = str::parse::<Cron>("* * * * *").unwrap();
+        assert_eq!(
+            Some(Utc.ymd(2019, 10, 14).and_hms(12, 35, 0)),
+            cron.next_after(&at)
+        );
+
+        let cron = str::parse::<Cron>("*/15 * * * *").unwrap();
+        assert_eq!(
+            Some(Utc.ymd(2019, 10, 14).and_hms(12, 45, 0)),
+            cron.next_after(&at)
+        );
+
+        let cron = str::parse::<Cron>("0 9 * * *").unwrap();
+        assert_eq!(
+            Some(Utc.ymd(2019, 10, 15).and_hms(9, 0, 0)),
+            cron.next_after(&at)
+        );
+
+        // 2019-10-14 is a monday, so next sunday is the 20th.
+        let cron = str::parse::<Cron>("30 18 * * 7").unwrap();
+        assert_eq!(
+            Some(Utc.ymd(2019, 10, 20).and_hms(18, 30, 0)),
+            cron.next_after(&at)
+        );
+
+        let cron = str::parse::<Cron>("0 0 1 1 *").unwrap();
+        assert_eq!(
+            Some(Utc.ymd(2020, 1, 1).and_hms(0, 0, 0)),
+            cron.next_after(&at)
+        );
+
+        let cron = str::parse::<Cron>("0 0 31 2 *").unwrap();
+        assert_eq!(None, cron.next_after(&at));
+
+        // NB: a step in one day field still requires both to match, so this
+        // is the next monday on an odd day of the month.
+        let cron = str::parse::<Cron>("0 0 */2 * 1").unwrap();
+        assert_eq!(
+            Some(Utc.ymd(2019, 10, 21).and_hms(0, 0, 0)),
+            cron.next_after(&at)
+        );
+
+        // The next 13th which is on a sunday, tuesday, thursday or saturday.
+        let cron = str::parse::<Cron>("0 0 13 * */2").unwrap();
+        assert_eq!(
+            Some(Utc.ymd(2020, 2, 13).and_hms(0, 0, 0)),
+            cron.next_after(&at)
+        );
+    }
+}
@@ -35,6 +35,17 @@ impl Duration {
         chrono::Duration::seconds(self.0 as i64)
     }
 
+    /// Convert into a chrono duration, or `None` if the duration is too large
+    /// to be represented by chrono.
+    pub fn checked_as_chrono(&self) -> Option<chrono::Duration> {
+        // NB: chrono durations are limited to `i64::max_value()` milliseconds.
+        if self.0 > (i64::max_value() / 1000) as u64 {
+            return None;
+        }
+
+        Some(chrono::Duration::seconds(self.0 as i64))
+    }
+
     /// Subtract another duration from this duration.
     ///
     /// This will saturate on overflows.
@@ -189,6 +200,18 @@ mod tests {
         );
     }
 
+    #[test]
+    pub fn test_checked_as_chrono() {
+        assert_eq!(
+            Some(chrono::Duration::seconds(60)),
+            Duration::seconds(60).checked_as_chrono()
+        );
+        assert_eq!(
+            None,
+            Duration::seconds(u64::max_value()).checked_as_chrono()
+        );
+    }
+
     #[test]
     pub fn test_format_duration() {
         assert_eq!("0s", Duration::default().to_string());
@@ -1,8 +1,16 @@
 use self::assets::Asset;
 use crate::{
-    api, api::setbac::ConnectionMeta, auth, bus, currency::Currency, db, injector, message_log,
-    player, prelude::*, template, track_id::TrackId, utils,
+    api,
+    api::setbac::ConnectionMeta,
+    auth, bus,
+    currency::{Currency, RankBy},
+    db, injector, message_log, player,
+    prelude::*,
+    template,
+    track_id::TrackId,
+    utils,
 };
+use chrono::{DateTime, Utc};
 use failure::bail;
 use hashbrown::HashMap;
 use parking_lot::{MappedRwLockReadGuard, RwLock, RwLockReadGuard};
@@ -64,6 +72,8 @@ struct AudioDevice {
 #[derive(serde::Serialize)]
 pub struct Current {
     channel: Option<String>,
+    /// All channels the bot is in, including the streamer's channel.
+    channels: Vec<String>,
 }
 
 #[derive(Debug, Clone, serde::Deserialize)]
@@ -132,11 +142,30 @@ impl Aliases {
                 }
             });
 
-        return list.or(delete).or(edit).or(edit_disabled).boxed();
+        let edit_restrictions = warp::post2()
+            .and(path!("aliases" / Fragment / Fragment / "restrictions").and(path::end()))
+            .and(body::json())
+            .and_then({
+                let api = api.clone();
+                move |channel: Fragment, name: Fragment, body: db::Restrictions| {
+                    let api = api.clone();
+                    async move {
+                        api.edit_restrictions(channel.as_str(), name.as_str(), body)
+                            .map_err(warp::reject::custom)
+                    }
+                }
+            });
+
+        return list
+            .or(delete)
+            .or(edit)
+            .or(edit_disabled)
+            .or(edit_restrictions)
+            .boxed();
 
         #[derive(serde::Deserialize)]
         pub struct PutAlias {
-            template: template::Template,
+            template: db::AliasTemplate,
         }
     }
 
@@ -159,7 +188,7 @@ impl Aliases {
         &self,
         channel: &str,
         name: &str,
-        template: template::Template,
+        template: db::AliasTemplate,
     ) -> Result<impl warp::Reply, failure::Error> {
         self.aliases()?.edit(channel, name, template)?;
         Ok(warp::reply::json(&EMPTY))
@@ -181,6 +210,18 @@ impl Aliases {
         Ok(warp::reply::json(&EMPTY))
     }
 
+    /// Set the restrictions of the given alias.
+    fn edit_restrictions(
+        &self,
+        channel: &str,
+        name: &str,
+        restrictions: db::Restrictions,
+    ) -> Result<impl warp::Reply, failure::Error> {
+        self.aliases()?
+            .edit_restrictions(channel, name, restrictions)?;
+        Ok(warp::reply::json(&EMPTY))
+    }
+
     /// Delete the given alias by key.
     fn delete(&self, channel: &str, name: &str) -> Result<impl warp::Reply, failure::Error> {
         self.aliases()?.delete(channel, name)?;
@@ -250,7 +291,26 @@ impl Commands {
                 }
             });
 
-        return list.or(delete).or(edit).or(edit_disabled).boxed();
+        let edit_restrictions = warp::post2()
+            .and(path!("commands" / Fragment / Fragment / "restrictions").and(path::end()))
+            .and(body::json())
+            .and_then({
+                let api = api.clone();
+                move |channel: Fragment, name: Fragment, body: db::Restrictions| {
+                    let api = api.clone();
+                    async move {
+                        api.edit_restrictions(channel.as_str(), name.as_str(), body)
+                            .map_err(warp::reject::custom)
+                    }
+                }
+            });
+
+        return list
+            .or(delete)
+            .or(edit)
+            .or(edit_disabled)
+            .or(edit_restrictions)
+            .boxed();
 
         #[derive(serde::Deserialize)]
         pub struct PutCommand {
@@ -299,6 +359,18 @@ impl Commands {
         Ok(warp::reply::json(&EMPTY))
     }
 
+    /// Set the restrictions of the given command.
+    fn edit_restrictions(
+        &self,
+        channel: &str,
+        name: &str,
+        restrictions: db::Restrictions,
+    ) -> Result<impl warp::Reply, failure::Error> {
+        self.commands()?
+            .edit_restrictions(channel, name, restrictions)?;
+        Ok(warp::reply::json(&EMPTY))
+    }
+
     /// Delete the given command by key.
     fn delete(&self, channel: &str, name: &str) -> Result<impl warp::Reply, failure::Error> {
         self.commands()?.delete(channel, name)?;
@@ -434,6 +506,176 @@ impl Promotions {
     }
 }
 
+/// Schedules endpoint.
+#[derive(Clone)]
+struct Schedules {
+    schedules: Arc<RwLock<Option<db::Schedules>>>,
+    settings: Arc<RwLock<Option<crate::settings::Settings>>>,
+}
+
+impl Schedules {
+    fn route(
+        schedules: Arc<RwLock<Option<db::Schedules>>>,
+        settings: Arc<RwLock<Option<crate::settings::Settings>>>,
+    ) -> filters::BoxedFilter<(impl warp::Reply,)> {
+        let api = Schedules {
+            schedules,
+            settings,
+        };
+
+        let list = warp::get2()
+            .and(path!("schedules" / Fragment).and(path::end()))
+            .and_then({
+                let api = api.clone();
+                move |channel: Fragment| {
+                    let api = api.clone();
+                    async move { api.list(channel.as_str()).map_err(warp::reject::custom) }
+                }
+            });
+
+        let delete = warp::delete2()
+            .and(path!("schedules" / Fragment / Fragment).and(path::end()))
+            .and_then({
+                let api = api.clone();
+                move |channel: Fragment, name: Fragment| {
+                    let api = api.clone();
+
+                    async move {
+                        api.delete(channel.as_str(), name.as_str())
+                            .map_err(warp::reject::custom)
+                    }
+                }
+            });
+
+        let edit = warp::put2()
+            .and(path!("schedules" / Fragment / Fragment).and(path::end()))
+            .and(body::json())
+            .and_then({
+                let api = api.clone();
+                move |channel: Fragment, name: Fragment, body: PutSchedule| {
+                    let api = api.clone();
+
+                    async move {
+                        api.edit(channel.as_str(), name.as_str(), body.trigger, body.action)
+                            .map_err(warp::reject::custom)
+                    }
+                }
+            });
+
+        let edit_disabled = warp::post2()
+            .and(path!("schedules" / Fragment / Fragment / "disabled").and(path::end()))
+            .and(body::json())
+            .and_then({
+                let api = api.clone();
+                move |channel: Fragment, name: Fragment, body: DisabledBody| {
+                    let api = api.clone();
+
+                    async move {
+                        api.edit_disabled(channel.as_str(), name.as_str(), body.disabled)
+                            .map_err(warp::reject::custom)
+                    }
+                }
+            });
+
+        let edit_only_live = warp::post2()
+            .and(path!("schedules" / Fragment / Fragment / "only-live").and(path::end()))
+            .and(body::json())
+            .and_then({
+                let api = api.clone();
+                move |channel: Fragment, name: Fragment, body: OnlyLiveBody| {
+                    let api = api.clone();
+
+                    async move {
+                        api.edit_only_live(channel.as_str(), name.as_str(), body.only_live)
+                            .map_err(warp::reject::custom)
+                    }
+                }
+            });
+
+        return list
+            .or(delete)
+            .or(edit)
+            .or(edit_disabled)
+            .or(edit_only_live)
+            .boxed();
+
+        #[derive(serde::Deserialize)]
+        pub struct PutSchedule {
+            trigger: db::Trigger,
+            action: db::Action,
+        }
+
+        #[derive(serde::Deserialize)]
+        pub struct OnlyLiveBody {
+            only_live: bool,
+        }
+    }
+
+    /// Access underlying schedules abstraction.
+    fn schedules(&self) -> Result<MappedRwLockReadGuard<'_, db::Schedules>, failure::Error> {
+        match RwLockReadGuard::try_map(self.schedules.read(), |c| c.as_ref()) {
+            Ok(out) => Ok(out),
+            Err(_) => bail!("schedules not configured"),
+        }
+    }
+
+    /// Get the list of all schedules.
+    fn list(&self, channel: &str) -> Result<impl warp::Reply, failure::Error> {
+        let schedules = self.schedules()?.list_all(channel)?;
+        Ok(warp::reply::json(&schedules))
+    }
+
+    /// Edit the given schedule by key.
+    fn edit(
+        &self,
+        channel: &str,
+        name: &str,
+        trigger: db::Trigger,
+        action: db::Action,
+    ) -> Result<impl warp::Reply, failure::Error> {
+        match self.settings.read().as_ref() {
+            Some(settings) => action.validate(settings)?,
+            None => bail!("settings not configured"),
+        }
+
+        self.schedules()?.edit(channel, name, trigger, action)?;
+        Ok(warp::reply::json(&EMPTY))
+    }
+
+    /// Set the given schedule's disabled status.
+    fn edit_disabled(
+        &self,
+        channel: &str,
+        name: &str,
+        disabled: bool,
+    ) -> Result<impl warp::Reply, failure::Error> {
+        if disabled {
+            self.schedules()?.disable(channel, name)?;
+        } else {
+            self.schedules()?.enable(channel, name)?;
+        }
+
+        Ok(warp::reply::json(&EMPTY))
+    }
+
+    /// Set if the given schedule only runs while the stream is live.
+    fn edit_only_live(
+        &self,
+        channel: &str,
+        name: &str,
+        only_live: bool,
+    ) -> Result<impl warp::Reply, failure::Error> {
+        self.schedules()?.edit_only_live(channel, name, only_live)?;
+        Ok(warp::reply::json(&EMPTY))
+    }
+
+    /// Delete the given schedule by key.
+    fn delete(&self, channel: &str, name: &str) -> Result<impl warp::Reply, failure::Error> {
+        self.schedules()?.delete(channel, name)?;
+        Ok(warp::reply::json(&EMPTY))
+    }
+}
+
 /// Themes endpoint.
 #[derive(Clone)]
 struct Themes(Arc<RwLock<Option<db::Themes>>>);
@@ -752,11 +994,108 @@ impl Auth {
 struct Api {
     player: Arc<RwLock<Option<player::Player>>>,
     after_streams: Arc<RwLock<Option<db::AfterStreams>>>,
+    quotes: Arc<RwLock<Option<db::Quotes>>>,
+    command_stats: Arc<RwLock<Option<db::CommandStats>>>,
+    audit_log: Arc<RwLock<Option<db::AuditLog>>>,
+    messages: Arc<RwLock<Option<db::Messages>>>,
     db: db::Database,
     currency: Arc<RwLock<Option<Currency>>>,
+    ledger: Arc<RwLock<Option<db::Ledger>>>,
+    ban_list: Arc<RwLock<Option<db::BanList>>>,
     latest: Arc<RwLock<Option<api::github::Release>>>,
 }
 
+#[derive(serde::Deserialize)]
+pub struct CommandStatsQuery {
+    /// Number of days to cover.
+    #[serde(default)]
+    days: Option<u32>,
+}
+
+/// Default number of audit log entries per page.
+const DEFAULT_AUDIT_LIMIT: u32 = 50;
+/// Maximum number of audit log entries per page.
+const MAX_AUDIT_LIMIT: u32 = 200;
+
+#[derive(serde::Deserialize)]
+pub struct AuditQuery {
+    /// The page to get, starting at zero.
+    #[serde(default)]
+    page: Option<u32>,
+    /// Number of entries per page.
+    #[serde(default)]
+    limit: Option<u32>,
+    /// Only include actions taken by the given user.
+    #[serde(default)]
+    actor: Option<String>,
+    /// Only include actions of the given kind.
+    #[serde(default)]
+    action: Option<db::AuditAction>,
+    /// Only include actions taken against the given target.
+    #[serde(default)]
+    target: Option<String>,
+}
+
+/// Default number of messages returned by a search.
+const DEFAULT_MESSAGES_LIMIT: u32 = 100;
+/// Maximum number of messages returned by a search.
+const MAX_MESSAGES_LIMIT: u32 = 1000;
+
+/// Default number of ledger entries returned for a user.
+const DEFAULT_HISTORY_LIMIT: u32 = 50;
+/// Maximum number of ledger entries returned for a user.
+const MAX_HISTORY_LIMIT: u32 = 500;
+
+/// Default number of users on a leaderboard.
+const DEFAULT_LEADERBOARD_LIMIT: u32 = 10;
+/// Maximum number of users on a leaderboard.
+const MAX_LEADERBOARD_LIMIT: u32 = 100;
+
+#[derive(serde::Deserialize)]
+pub struct LeaderboardQuery {
+    /// Only rank users in the given channel.
+    #[serde(default)]
+    channel: Option<String>,
+    /// What to rank users by, `balance` or `watch-time`.
+    #[serde(default)]
+    by: RankBy,
+    /// Number of users to return.
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct BalanceHistoryQuery {
+    /// Only include changes to the balance in the given channel.
+    #[serde(default)]
+    channel: Option<String>,
+    /// Maximum number of entries to return.
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct MessagesQuery {
+    /// Only include messages in the given channel.
+    #[serde(default)]
+    channel: Option<String>,
+    /// Only include messages sent by the given user.
+    #[serde(default)]
+    user: Option<String>,
+    /// Full-text search of the messages.
+    #[serde(default)]
+    q: Option<String>,
+    /// Only include messages sent since the given point in time.
+    #[serde(default)]
+    since: Option<DateTime<Utc>>,
+    /// Include deleted messages.
+    #[serde(default)]
+    deleted: bool,
+    /// Maximum number of messages to return.
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
 #[derive(Clone, serde::Serialize, serde::Deserialize)]
 pub struct Balance {
     name: String,
@@ -858,6 +1197,148 @@ impl Api {
         Ok(warp::reply::json(&EMPTY))
     }
 
+    /// Access underlying quotes abstraction.
+    fn quotes(&self) -> Result<MappedRwLockReadGuard<'_, db::Quotes>, failure::Error> {
+        match RwLockReadGuard::try_map(self.quotes.read(), |c| c.as_ref()) {
+            Ok(out) => Ok(out),
+            Err(_) => bail!("quotes not configured"),
+        }
+    }
+
+    /// Get the list of quotes in the given channel.
+    fn get_quotes(&self, channel: &str) -> Result<impl warp::Reply, failure::Error> {
+        let quotes = self.quotes()?.list(channel)?;
+        Ok(warp::reply::json(&quotes))
+    }
+
+    /// Delete the given quote.
+    fn delete_quote(&self, channel: &str, id: i32) -> Result<impl warp::Reply, failure::Error> {
+        self.quotes()?.delete(channel, id)?;
+        Ok(warp::reply::json(&EMPTY))
+    }
+
+    /// Access underlying command stats abstraction.
+    fn command_stats(&self) -> Result<MappedRwLockReadGuard<'_, db::CommandStats>, failure::Error> {
+        match RwLockReadGuard::try_map(self.command_stats.read(), |c| c.as_ref()) {
+            Ok(out) => Ok(out),
+            Err(_) => bail!("command stats not configured"),
+        }
+    }
+
+    /// Get command usage in the given channel, aggregated by day and by stream.
+    fn get_command_stats(
+        &self,
+        channel: &str,
+        query: CommandStatsQuery,
+    ) -> Result<impl warp::Reply, failure::Error> {
+        let days = query.days.unwrap_or(30).min(db::MAX_REPORT_DAYS);
+        let since = Utc::now().naive_utc() - chrono::Duration::days(i64::from(days));
+        let report = self.command_stats()?.report(channel, since)?;
+        Ok(warp::reply::json(&report))
+    }
+
+    /// Access underlying audit log abstraction.
+    fn audit_log(&self) -> Result<MappedRwLockReadGuard<'_, db::AuditLog>, failure::Error> {
+        match RwLockReadGuard::try_map(self.audit_log.read(), |c| c.as_ref()) {
+            Ok(out) => Ok(out),
+            Err(_) => bail!("audit log not configured"),
+        }
+    }
+
+    /// Get a page of the audit log in the given channel, most recent first.
+    fn get_audit_log(
+        &self,
+        channel: &str,
+        query: AuditQuery,
+    ) -> Result<impl warp::Reply, failure::Error> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_AUDIT_LIMIT)
+            .min(MAX_AUDIT_LIMIT);
+        let offset = i64::from(query.page.unwrap_or_default()) * i64::from(limit);
+
+        let filter = db::AuditFilter {
+            actor: query.actor.as_ref().map(String::as_str),
+            action: query.action,
+            target: query.target.as_ref().map(String::as_str),
+        };
+
+        let page = self
+            .audit_log()?
+            .list(channel, &filter, offset, i64::from(limit))?;
+        Ok(warp::reply::json(&page))
+    }
+
+    /// Access underlying chat history abstraction.
+    fn messages(&self) -> Result<MappedRwLockReadGuard<'_, db::Messages>, failure::Error> {
+        match RwLockReadGuard::try_map(self.messages.read(), |c| c.as_ref()) {
+            Ok(out) => Ok(out),
+            Err(_) => bail!("chat history not configured"),
+        }
+    }
+
+    /// Search the chat history, most recent messages first.
+    fn get_messages(&self, query: MessagesQuery) -> Result<impl warp::Reply, failure::Error> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_MESSAGES_LIMIT)
+            .min(MAX_MESSAGES_LIMIT);
+
+        // NB: channels are stored with their leading `#`.
+        let channel = query
+            .channel
+            .as_ref()
+            .map(|c| format!("#{}", c.trim_start_matches('#').to_lowercase()));
+
+        let user = query.user.as_ref().map(|u| db::user_id(u));
+
+        let filter = db::MessageFilter {
+            channel: channel.as_ref().map(String::as_str),
+            user: user.as_ref().map(String::as_str),
+            q: query.q.as_ref().map(String::as_str),
+            since: query.since.map(|since| since.naive_utc()),
+            deleted: query.deleted,
+        };
+
+        let messages = self.messages()?.search(&filter, limit)?;
+        Ok(warp::reply::json(&messages))
+    }
+
+    /// Access underlying currency ledger abstraction.
+    fn ledger(&self) -> Result<MappedRwLockReadGuard<'_, db::Ledger>, failure::Error> {
+        match RwLockReadGuard::try_map(self.ledger.read(), |c| c.as_ref()) {
+            Ok(out) => Ok(out),
+            Err(_) => bail!("currency ledger not configured"),
+        }
+    }
+
+    /// Get the latest changes to the balance of the given user, most recent
+    /// first.
+    fn get_balance_history(
+        &self,
+        user: &str,
+        query: BalanceHistoryQuery,
+    ) -> Result<impl warp::Reply, failure::Error> {
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_HISTORY_LIMIT)
+            .min(MAX_HISTORY_LIMIT);
+
+        // NB: channels are stored with their leading `#`.
+        let channel = query
+            .channel
+            .as_ref()
+            .map(|c| format!("#{}", c.trim_start_matches('#').to_lowercase()));
+
+        let entries = self.ledger()?.history(
+            channel.as_ref().map(String::as_str),
+            &db::user_id(user),
+            i64::from(limit),
+        )?;
+
+        Ok(warp::reply::json(&entries))
+    }
+
     /// Import balances.
     async fn import_balances(
         self,
@@ -885,6 +1366,62 @@ impl Api {
         Ok(warp::reply::json(&balances))
     }
 
+    /// Get the top users of the currency.
+    async fn get_leaderboard(self, query: LeaderboardQuery) -> Result<impl warp::Reply, Error> {
+        let currency = self.currency.read().as_ref().cloned();
+
+        let currency = match currency {
+            Some(currency) => currency,
+            None => return Err(Error::NotFound),
+        };
+
+        if query.by == RankBy::WatchTime && !currency.tracks_watch_time() {
+            return Err(Error::BadRequest);
+        }
+
+        let limit = query
+            .limit
+            .unwrap_or(DEFAULT_LEADERBOARD_LIMIT)
+            .min(MAX_LEADERBOARD_LIMIT);
+
+        let leaderboard = currency
+            .leaderboard(query.channel.as_ref().map(String::as_str), query.by, limit)
+            .await?;
+
+        Ok(warp::reply::json(&leaderboard))
+    }
+
+    /// Import users into the ban list.
+    fn import_ban_list(&self, entries: Vec<db::BanEntry>) -> Result<impl warp::Reply, Error> {
+        let ban_list = self.ban_list.read().as_ref().cloned();
+
+        let ban_list = match ban_list {
+            Some(ban_list) => ban_list,
+            None => return Err(Error::NotFound),
+        };
+
+        let entries = entries
+            .into_iter()
+            .map(|e| db::BanEntry {
+                user: db::user_id(&e.user),
+                ..e
+            })
+            .collect::<Vec<_>>();
+
+        ban_list.insert(&entries)?;
+        Ok(warp::reply::json(&EMPTY))
+    }
+
+    /// Export the ban list.
+    fn export_ban_list(&self) -> Result<impl warp::Reply, Error> {
+        let entries = match self.ban_list.read().as_ref() {
+            Some(ban_list) => ban_list.list(),
+            None => return Err(Error::NotFound),
+        };
+
+        Ok(warp::reply::json(&entries))
+    }
+
     /// Get version information.
     fn version(&self) -> Result<impl warp::Reply, Error> {
         let info = Version {
@@ -942,6 +1479,7 @@ pub async fn setup(
     db: db::Database,
     auth: auth::Auth,
     channel: Arc<RwLock<Option<String>>>,
+    channels: Arc<RwLock<Vec<String>>>,
     latest: Arc<RwLock<Option<api::github::Release>>>,
 ) -> Result<(Server, impl Future<Output = ()>), failure::Error> {
     let addr: SocketAddr = str::parse(&format!("0.0.0.0:12345"))?;
@@ -952,8 +1490,14 @@ pub async fn setup(
     let api = Api {
         player: player.clone(),
         after_streams: injector.var()?,
+        quotes: injector.var()?,
+        command_stats: injector.var()?,
+        audit_log: injector.var()?,
+        messages: injector.var()?,
         db,
         currency: injector.var()?,
+        ledger: injector.var()?,
+        ban_list: injector.var()?,
         latest,
     };
 
@@ -1014,6 +1558,111 @@ pub async fn setup(
             }))
             .boxed();
 
+        let route = route
+            .or(warp::delete2()
+                .and(path!("quotes" / Fragment / i32).and(path::end()))
+                .and_then({
+                    let api = api.clone();
+                    move |channel: Fragment, id| {
+                        let api = api.clone();
+                        async move {
+                            api.delete_quote(channel.as_str(), id)
+                                .map_err(warp::reject::custom)
+                        }
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get2()
+                .and(path!("quotes" / Fragment).and(path::end()))
+                .and_then({
+                    let api = api.clone();
+                    move |channel: Fragment| {
+                        let api = api.clone();
+                        async move {
+                            api.get_quotes(channel.as_str())
+                                .map_err(warp::reject::custom)
+                        }
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get2()
+                .and(
+                    path!("stats" / "commands" / Fragment)
+                        .and(warp::query::<CommandStatsQuery>())
+                        .and(path::end()),
+                )
+                .and_then({
+                    let api = api.clone();
+                    move |channel: Fragment, query: CommandStatsQuery| {
+                        let api = api.clone();
+                        async move {
+                            api.get_command_stats(channel.as_str(), query)
+                                .map_err(warp::reject::custom)
+                        }
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get2()
+                .and(
+                    path!("audit" / Fragment)
+                        .and(warp::query::<AuditQuery>())
+                        .and(path::end()),
+                )
+                .and_then({
+                    let api = api.clone();
+                    move |channel: Fragment, query: AuditQuery| {
+                        let api = api.clone();
+                        async move {
+                            api.get_audit_log(channel.as_str(), query)
+                                .map_err(warp::reject::custom)
+                        }
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get2()
+                .and(
+                    warp::path("messages")
+                        .and(warp::query::<MessagesQuery>())
+                        .and(path::end()),
+                )
+                .and_then({
+                    let api = api.clone();
+                    move |query: MessagesQuery| {
+                        let api = api.clone();
+                        async move { api.get_messages(query).map_err(warp::reject::custom) }
+                    }
+                }))
+            .boxed();
+
+        // NB: needs to come before the other balances routes, since those
+        // match any path starting with `balances`.
+        let route = route
+            .or(warp::get2()
+                .and(
+                    path!("balances" / Fragment / "history")
+                        .and(warp::query::<BalanceHistoryQuery>())
+                        .and(path::end()),
+                )
+                .and_then({
+                    let api = api.clone();
+                    move |user: Fragment, query: BalanceHistoryQuery| {
+                        let api = api.clone();
+                        async move {
+                            api.get_balance_history(user.as_str(), query)
+                                .map_err(warp::reject::custom)
+                        }
+                    }
+                }))
+            .boxed();
+
         let route = route
             .or(warp::put2()
                 .and(warp::path("balances"))
@@ -1049,6 +1698,49 @@ pub async fn setup(
             }))
             .boxed();
 
+        let route = route
+            .or(warp::get2()
+                .and(
+                    warp::path("leaderboard")
+                        .and(warp::query::<LeaderboardQuery>())
+                        .and(path::end()),
+                )
+                .and_then({
+                    let api = api.clone();
+                    move |query: LeaderboardQuery| {
+                        let api = api.clone();
+                        async move {
+                            api.get_leaderboard(query)
+                                .await
+                                .map_err(warp::reject::custom)
+                        }
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::put2()
+                .and(warp::path("ban-list"))
+                .and(body::json())
+                .and_then({
+                    let api = api.clone();
+                    move |entries: Vec<db::BanEntry>| {
+                        let api = api.clone();
+                        async move { api.import_ban_list(entries).map_err(warp::reject::custom) }
+                    }
+                }))
+            .boxed();
+
+        let route = route
+            .or(warp::get2().and(warp::path("ban-list")).and_then({
+                let api = api.clone();
+                move || {
+                    let api = api.clone();
+                    async move { api.export_ban_list().map_err(warp::reject::custom) }
+                }
+            }))
+            .boxed();
+
         let route = route.or(warp::path("auth")
             .and(Auth::route(
                 auth,
@@ -1059,6 +1751,7 @@ pub async fn setup(
         let route = route.or(Aliases::route(injector.var()?));
         let route = route.or(Commands::route(injector.var()?));
         let route = route.or(Promotions::route(injector.var()?));
+        let route = route.or(Schedules::route(injector.var()?, injector.var()?));
         let route = route.or(Themes::route(injector.var()?));
         let route = route.or(Settings::route(injector.var()?));
         let route = route.or(Cache::route(injector.var()?));
@@ -1069,6 +1762,7 @@ pub async fn setup(
             .or(
                 warp::get2().and(path!("current").and(path::end()).and_then(move || {
                     let channel = channel.clone();
+                    let channels = channels.clone();
 
                     async move {
                         let channel = channel.read();
@@ -1078,7 +1772,8 @@ pub async fn setup(
                             None => None,
                         };
 
-                        let current = Current { channel };
+                        let channels = channels.read().clone();
+                        let current = Current { channel, channels };
 
                         Ok::<_, warp::Rejection>(warp::reply::json(&current))
                     }
@@ -1264,11 +1959,9 @@ where
             move |ws: warp::ws::Ws2| {
                 let bus = bus.clone();
 
-                ws.on_upgrade(move |websocket: warp::filters::ws::WebSocket| {
-                    async {
-                        if let Err(e) = send_bus_forward(bus, websocket).await {
-                            log::error!("websocket error: {}", e);
-                        }
+                ws.on_upgrade(move |websocket: warp::filters::ws::WebSocket| async {
+                    if let Err(e) = send_bus_forward(bus, websocket).await {
+                        log::error!("websocket error: {}", e);
                     }
                 })
             }
@@ -5,6 +5,9 @@ use warp::{filters, path, Filter as _};
 #[derive(serde::Deserialize)]
 struct CommandQuery {
     command: String,
+    /// The channel to run the command in, defaults to the streamer's channel.
+    #[serde(default)]
+    channel: Option<String>,
 }
 
 /// Chat endpoint.
@@ -50,6 +53,7 @@ impl Chat {
     fn command(&self, query: CommandQuery) -> Result<impl warp::Reply, failure::Error> {
         self.bus.send(bus::Command::Raw {
             command: query.command,
+            channel: query.channel,
         });
 
         Ok(warp::reply::json(&EMPTY))